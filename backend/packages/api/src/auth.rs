@@ -0,0 +1,193 @@
+//! API key checks guarding the API and its administrative endpoints
+
+use std::{
+    fmt,
+    sync::Arc,
+};
+
+use axum::http::{
+    header,
+    HeaderMap,
+};
+
+use crate::errors::{
+    ApiError,
+    ApiResult,
+};
+
+/// Header carrying the admin API key
+pub const ADMIN_KEY_HEADER: &str = "x-admin-key";
+
+/// Path of the liveness check, which stays reachable without the API key
+pub const HEALTH_PATH: &str = "/health";
+
+/// Secret API key; its `Debug` output is redacted so it never reaches logs
+#[derive(Clone, PartialEq, Eq)]
+pub struct ApiKey(Arc<str>);
+
+impl ApiKey {
+    /// Create a key, treating an empty or blank value as no key at all
+    pub fn new(key: &str) -> Option<Self> {
+        let key = key.trim();
+        (!key.is_empty()).then(|| Self(Arc::from(key)))
+    }
+
+    /// Compare against a presented key in time independent of where they
+    /// differ
+    pub fn matches(&self, candidate: &str) -> bool {
+        let expected = self.0.as_bytes();
+        let candidate = candidate.as_bytes();
+        expected.len() == candidate.len()
+            && expected
+                .iter()
+                .zip(candidate)
+                .fold(0_u8, |difference, (left, right)| {
+                    difference | (left ^ right)
+                })
+                == 0
+    }
+}
+
+impl fmt::Debug for ApiKey {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("ApiKey(<redacted>)")
+    }
+}
+
+/// Check that a request presents the admin key in `x-admin-key`
+///
+/// # Errors
+/// Returns `ApiError::Unauthorized` if no admin key is configured, or the
+/// request lacks the header or presents a different key
+pub fn require_admin(admin_key: Option<&ApiKey>, headers: &HeaderMap) -> ApiResult<()> {
+    let Some(admin_key) = admin_key else {
+        return Err(ApiError::unauthorized(
+            "administrative endpoints are disabled; set ADMIN_API_KEY to enable them",
+        ));
+    };
+    match headers
+        .get(ADMIN_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(presented) if admin_key.matches(presented) => Ok(()),
+        Some(_) => Err(ApiError::unauthorized("invalid admin API key")),
+        None => Err(ApiError::unauthorized(&format!(
+            "missing {ADMIN_KEY_HEADER} header"
+        ))),
+    }
+}
+
+/// Whether `path` may be requested without the API key
+pub fn is_public_path(path: &str) -> bool {
+    path.strip_prefix(HEALTH_PATH)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Token of an `Authorization: Bearer <token>` header
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
+}
+
+/// Check that a request presents the API key as a bearer token. Without a
+/// configured key every request is allowed.
+///
+/// # Errors
+/// Returns `ApiError::Unauthorized` if a key is configured and the request
+/// lacks a bearer token or presents a different one
+pub fn require_api_key(api_key: Option<&ApiKey>, headers: &HeaderMap) -> ApiResult<()> {
+    let Some(api_key) = api_key else {
+        return Ok(());
+    };
+    match bearer_token(headers) {
+        Some(token) if api_key.matches(token) => Ok(()),
+        Some(_) => Err(ApiError::unauthorized("invalid API key")),
+        None => Err(ApiError::unauthorized(
+            "missing Authorization: Bearer <API key> header",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn headers_with_key(key: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(ADMIN_KEY_HEADER, HeaderValue::from_static(key));
+        headers
+    }
+
+    #[test]
+    fn test_api_key_rejects_blank_values() {
+        assert!(ApiKey::new("").is_none());
+        assert!(ApiKey::new("   ").is_none());
+        assert_eq!(ApiKey::new(" secret "), ApiKey::new("secret"));
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_api_key_matches() {
+        let key = ApiKey::new("secret").expect("non-empty key");
+        assert!(key.matches("secret"));
+        assert!(!key.matches("secreT"));
+        assert!(!key.matches("secret2"));
+        assert!(!key.matches(""));
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_api_key_debug_is_redacted() {
+        let key = ApiKey::new("secret").expect("non-empty key");
+        assert!(!format!("{key:?}").contains("secret"));
+    }
+
+    fn headers_with_authorization(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn test_is_public_path() {
+        assert!(is_public_path("/health"));
+        assert!(is_public_path("/health/ready"));
+        assert!(!is_public_path("/healthz"));
+        assert!(!is_public_path("/api/sensors"));
+    }
+
+    #[test]
+    fn test_require_api_key() {
+        let key = ApiKey::new("secret");
+
+        for accepted in ["Bearer secret", "bearer secret"] {
+            let headers = headers_with_authorization(accepted);
+            assert!(
+                require_api_key(key.as_ref(), &headers).is_ok(),
+                "{accepted}"
+            );
+        }
+        for rejected in ["Bearer guess", "Basic secret", "secret"] {
+            let headers = headers_with_authorization(rejected);
+            assert!(
+                require_api_key(key.as_ref(), &headers).is_err(),
+                "{rejected}"
+            );
+        }
+        assert!(require_api_key(key.as_ref(), &HeaderMap::new()).is_err());
+        assert!(require_api_key(None, &HeaderMap::new()).is_ok());
+    }
+
+    #[test]
+    fn test_require_admin() {
+        let key = ApiKey::new("secret");
+
+        assert!(require_admin(key.as_ref(), &headers_with_key("secret")).is_ok());
+        assert!(require_admin(key.as_ref(), &headers_with_key("guess")).is_err());
+        assert!(require_admin(key.as_ref(), &HeaderMap::new()).is_err());
+        assert!(require_admin(None, &headers_with_key("secret")).is_err());
+    }
+}
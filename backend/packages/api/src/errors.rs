@@ -10,12 +10,18 @@ use axum::{
     },
     Json,
 };
+use postgres_store::QueryTimeout;
 use serde::{
     Deserialize,
     Serialize,
 };
 use sqlx;
 
+use crate::utils::{
+    MAX_ALIAS_LEN,
+    MAX_GROUP_NAME_LEN,
+};
+
 /// API Error Response structure
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ApiErrorResponse {
@@ -54,6 +60,14 @@ pub enum ApiError {
     Internal { message: String },
     /// Bad request with custom message
     BadRequest { message: String },
+    /// Request conflicts with one already being processed
+    Conflict { message: String },
+    /// Request lacks valid credentials
+    Unauthorized { message: String },
+    /// Client exceeded its request rate
+    RateLimited { retry_after_seconds: u64 },
+    /// Database query exceeded the configured timeout
+    Timeout { operation: String },
 }
 
 impl fmt::Display for ApiError {
@@ -96,9 +110,11 @@ impl fmt::Display for ApiError {
             ApiError::Internal { message } => {
                 write!(formatter, "Internal server error: {message}")
             }
-            ApiError::BadRequest { message } => {
-                write!(formatter, "Bad request: {message}")
-            }
+            ApiError::BadRequest { message } => write!(formatter, "Bad request: {message}"),
+            ApiError::Conflict { message } => write!(formatter, "Conflict: {message}"),
+            ApiError::Unauthorized { message } => write!(formatter, "Unauthorized: {message}"),
+            ApiError::RateLimited { .. } => write!(formatter, "Too many requests"),
+            ApiError::Timeout { operation } => write!(formatter, "Timed out during {operation}"),
         }
     }
 }
@@ -112,7 +128,11 @@ impl ApiError {
             | ApiError::InvalidDateFormat { .. }
             | ApiError::InvalidDateRange { .. }
             | ApiError::BadRequest { .. } => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized { .. } => StatusCode::UNAUTHORIZED,
             ApiError::NotFound { .. } => StatusCode::NOT_FOUND,
+            ApiError::Conflict { .. } => StatusCode::CONFLICT,
+            ApiError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Timeout { .. } => StatusCode::GATEWAY_TIMEOUT,
             ApiError::DatabaseError { .. } | ApiError::Internal { .. } => {
                 StatusCode::INTERNAL_SERVER_ERROR
             }
@@ -127,7 +147,11 @@ impl ApiError {
             ApiError::InvalidDateFormat { .. } => "INVALID_DATE_FORMAT",
             ApiError::InvalidDateRange { .. } => "INVALID_DATE_RANGE",
             ApiError::BadRequest { .. } => "BAD_REQUEST",
+            ApiError::Unauthorized { .. } => "UNAUTHORIZED",
             ApiError::NotFound { .. } => "NOT_FOUND",
+            ApiError::Conflict { .. } => "CONFLICT",
+            ApiError::RateLimited { .. } => "RATE_LIMITED",
+            ApiError::Timeout { .. } => "TIMEOUT",
             ApiError::DatabaseError { .. } => "DATABASE_ERROR",
             ApiError::Internal { .. } => "INTERNAL_ERROR",
         }
@@ -145,13 +169,22 @@ impl ApiError {
                 expected_format, ..
             } => Some(format!("Expected format: {expected_format}")),
             ApiError::InvalidDateRange { reason } => Some(reason.clone()),
-            ApiError::BadRequest { .. } | ApiError::NotFound { .. } => None,
+            ApiError::BadRequest { .. }
+            | ApiError::NotFound { .. }
+            | ApiError::Conflict { .. }
+            | ApiError::Unauthorized { .. } => None,
             ApiError::DatabaseError { .. } => Some(
                 "Please try again later or contact support if the problem persists".to_string(),
             ),
             ApiError::Internal { .. } => {
                 Some("An unexpected error occurred. Please try again later".to_string())
             }
+            ApiError::Timeout { .. } => {
+                Some("The query took too long. Try a shorter time range".to_string())
+            }
+            ApiError::RateLimited {
+                retry_after_seconds,
+            } => Some(format!("Retry after {retry_after_seconds} seconds")),
         }
     }
 
@@ -200,6 +233,20 @@ impl ApiError {
         }
     }
 
+    pub fn unknown_parameter(parameter: &str, known: &[&str]) -> Self {
+        Self::InvalidParameter {
+            parameter: parameter.to_string(),
+            value: "unknown parameter".to_string(),
+            expected: format!("one of: {}", known.join(", ")),
+        }
+    }
+
+    pub fn missing_parameter(parameter: &str) -> Self {
+        Self::BadRequest {
+            message: format!("Missing required parameter '{parameter}'"),
+        }
+    }
+
     pub fn invalid_date(date: &str) -> Self {
         Self::InvalidDateFormat {
             date: date.to_string(),
@@ -227,6 +274,50 @@ impl ApiError {
         }
     }
 
+    pub fn gateway_readings_not_found(mac: &str) -> Self {
+        Self::NotFound {
+            resource: "Gateway readings".to_string(),
+            identifier: mac.to_string(),
+        }
+    }
+
+    pub fn threshold_not_found(id: i64) -> Self {
+        Self::NotFound {
+            resource: "Threshold".to_string(),
+            identifier: id.to_string(),
+        }
+    }
+
+    pub fn group_not_found(name: &str) -> Self {
+        Self::NotFound {
+            resource: "Sensor group".to_string(),
+            identifier: name.to_string(),
+        }
+    }
+
+    pub fn alias_not_found(mac: &str) -> Self {
+        Self::NotFound {
+            resource: "Sensor alias".to_string(),
+            identifier: mac.to_string(),
+        }
+    }
+
+    pub fn invalid_alias(alias: &str) -> Self {
+        Self::InvalidParameter {
+            parameter: "alias".to_string(),
+            value: alias.to_string(),
+            expected: format!("1 to {MAX_ALIAS_LEN} characters"),
+        }
+    }
+
+    pub fn invalid_group_name(name: &str) -> Self {
+        Self::InvalidParameter {
+            parameter: "group".to_string(),
+            value: name.to_string(),
+            expected: format!("1 to {MAX_GROUP_NAME_LEN} letters, digits, '-' or '_'"),
+        }
+    }
+
     pub fn database_error(operation: &str, details: &str) -> Self {
         Self::DatabaseError {
             operation: operation.to_string(),
@@ -234,6 +325,18 @@ impl ApiError {
         }
     }
 
+    /// Map a store failure, reporting query timeouts as 504 and anything else
+    /// as a database error
+    pub fn store_error(operation: &str, error: &anyhow::Error) -> Self {
+        if error.downcast_ref::<QueryTimeout>().is_some() {
+            Self::Timeout {
+                operation: operation.to_string(),
+            }
+        } else {
+            Self::database_error(operation, &error.to_string())
+        }
+    }
+
     pub fn internal_error(message: &str) -> Self {
         Self::Internal {
             message: message.to_string(),
@@ -245,6 +348,24 @@ impl ApiError {
             message: message.to_string(),
         }
     }
+
+    pub fn conflict(message: &str) -> Self {
+        Self::Conflict {
+            message: message.to_string(),
+        }
+    }
+
+    pub const fn rate_limited(retry_after_seconds: u64) -> Self {
+        Self::RateLimited {
+            retry_after_seconds,
+        }
+    }
+
+    pub fn unauthorized(message: &str) -> Self {
+        Self::Unauthorized {
+            message: message.to_string(),
+        }
+    }
 }
 
 /// Convert database errors to API errors
@@ -0,0 +1,362 @@
+//! File exports of raw sensor readings
+
+use std::sync::Arc;
+
+use arrow_array::{
+    ArrayRef,
+    Float64Array,
+    Int64Array,
+    RecordBatch,
+    StringArray,
+    TimestampMicrosecondArray,
+};
+use arrow_schema::{
+    ArrowError,
+    DataType,
+    Field,
+    Schema,
+    SchemaRef,
+    TimeUnit,
+};
+use chrono::SecondsFormat;
+use chrono_tz::Tz;
+use futures::{
+    Stream,
+    StreamExt,
+    TryStreamExt,
+};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::Compression,
+    file::properties::WriterProperties,
+};
+use postgres_store::Event;
+
+/// File format of an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Parquet,
+}
+
+impl ExportFormat {
+    /// Parse a `format` parameter, case-insensitively
+    pub fn parse(format: &str) -> Option<Self> {
+        match format.to_ascii_lowercase().as_str() {
+            "parquet" => Some(Self::Parquet),
+            _ => None,
+        }
+    }
+
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Parquet => "parquet",
+        }
+    }
+}
+
+/// Content type of Parquet exports
+pub const PARQUET_CONTENT_TYPE: &str = "application/vnd.apache.parquet";
+
+/// Readings per Parquet row group; also the most the export buffers at once
+pub const PARQUET_ROW_GROUP_SIZE: usize = 10_000;
+
+/// Content type of CSV exports
+pub const CSV_CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+
+/// Reads a numeric field of a reading
+type Accessor<T> = fn(&Event) -> T;
+/// Borrows a text field of a reading
+type TextField = for<'a> fn(&'a Event) -> &'a str;
+
+/// How an export column reads its value from a reading
+#[derive(Clone, Copy)]
+enum Column {
+    Text(TextField),
+    Float(Accessor<f64>),
+    Integer(Accessor<i64>),
+    Timestamp,
+}
+
+/// Column header and how to read its value
+type ExportColumn = (&'static str, Column);
+
+/// Columns of every export, one per `Event` field, in file order. Both the
+/// CSV and the Parquet writer are derived from this list.
+const EXPORT_COLUMNS: &[ExportColumn] = &[
+    (
+        "sensor_mac",
+        Column::Text(|event| event.sensor_mac.as_str()),
+    ),
+    (
+        "gateway_mac",
+        Column::Text(|event| event.gateway_mac.as_str()),
+    ),
+    ("temperature", Column::Float(|event| event.temperature)),
+    ("humidity", Column::Float(|event| event.humidity)),
+    ("pressure", Column::Float(|event| event.pressure)),
+    ("battery", Column::Integer(|event| event.battery)),
+    ("tx_power", Column::Integer(|event| event.tx_power)),
+    (
+        "movement_counter",
+        Column::Integer(|event| event.movement_counter),
+    ),
+    (
+        "measurement_sequence_number",
+        Column::Integer(|event| event.measurement_sequence_number),
+    ),
+    ("acceleration", Column::Float(|event| event.acceleration)),
+    (
+        "acceleration_x",
+        Column::Integer(|event| event.acceleration_x),
+    ),
+    (
+        "acceleration_y",
+        Column::Integer(|event| event.acceleration_y),
+    ),
+    (
+        "acceleration_z",
+        Column::Integer(|event| event.acceleration_z),
+    ),
+    ("rssi", Column::Integer(|event| event.rssi)),
+    ("timestamp", Column::Timestamp),
+];
+
+/// Header row of CSV exports, naming the columns of [`event_schema`]
+pub fn csv_header() -> String {
+    let names: Vec<&str> = EXPORT_COLUMNS.iter().map(|(name, _)| *name).collect();
+    format!("{}\n", names.join(","))
+}
+
+/// Arrow schema of an exported reading, one column per `Event` field, with
+/// timestamps annotated with `timezone`
+pub fn event_schema(timezone: Tz) -> SchemaRef {
+    let fields: Vec<Field> = EXPORT_COLUMNS
+        .iter()
+        .map(|(name, column)| {
+            let data_type = match column {
+                Column::Text(_) => DataType::Utf8,
+                Column::Float(_) => DataType::Float64,
+                Column::Integer(_) => DataType::Int64,
+                Column::Timestamp => {
+                    DataType::Timestamp(TimeUnit::Microsecond, Some(timezone.name().into()))
+                }
+            };
+            Field::new(*name, data_type, false)
+        })
+        .collect();
+    Arc::new(Schema::new(fields))
+}
+
+/// Convert a chunk of readings into a record batch of [`event_schema`]
+fn record_batch(events: &[Event], timezone: Tz) -> Result<RecordBatch, ArrowError> {
+    let arrays = EXPORT_COLUMNS
+        .iter()
+        .map(|(_, column)| -> ArrayRef {
+            match *column {
+                Column::Text(field) => {
+                    Arc::new(StringArray::from_iter_values(events.iter().map(field)))
+                }
+                Column::Float(field) => {
+                    Arc::new(Float64Array::from_iter_values(events.iter().map(field)))
+                }
+                Column::Integer(field) => {
+                    Arc::new(Int64Array::from_iter_values(events.iter().map(field)))
+                }
+                Column::Timestamp => Arc::new(
+                    TimestampMicrosecondArray::from_iter_values(
+                        events
+                            .iter()
+                            .map(|event| event.timestamp.timestamp_micros()),
+                    )
+                    .with_timezone(timezone.name()),
+                ),
+            }
+        })
+        .collect();
+
+    RecordBatch::try_new(event_schema(timezone), arrays)
+}
+
+/// Encode a stream of readings as a Parquet file, emitting the bytes of each
+/// row group as soon as it is complete and the footer at the end.
+///
+/// Parquet offsets are tracked by the writer itself, so the encoded bytes can
+/// be handed out and dropped between row groups.
+pub fn parquet_stream<S>(
+    events: S,
+    timezone: Tz,
+) -> impl Stream<Item = anyhow::Result<Vec<u8>>> + Send
+where
+    S: Stream<Item = anyhow::Result<Event>> + Send,
+{
+    async_stream::try_stream! {
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_max_row_group_size(PARQUET_ROW_GROUP_SIZE)
+            .build();
+        let mut writer = ArrowWriter::try_new(Vec::new(), event_schema(timezone), Some(properties))?;
+        let mut chunk = Vec::with_capacity(PARQUET_ROW_GROUP_SIZE);
+
+        futures::pin_mut!(events);
+        while let Some(event) = events.try_next().await? {
+            chunk.push(event);
+            if chunk.len() >= PARQUET_ROW_GROUP_SIZE {
+                writer.write(&record_batch(&chunk, timezone)?)?;
+                writer.flush()?;
+                chunk.clear();
+                yield std::mem::take(writer.inner_mut());
+            }
+        }
+
+        if !chunk.is_empty() {
+            writer.write(&record_batch(&chunk, timezone)?)?;
+        }
+        yield writer.into_inner()?;
+    }
+}
+
+/// One CSV line of a reading, in the column order of [`csv_header`], with
+/// its timestamp in `timezone`.
+///
+/// MACs and numbers never contain separators or quotes, so no field needs
+/// escaping.
+fn csv_row(event: &Event, timezone: Tz) -> String {
+    let values: Vec<String> = EXPORT_COLUMNS
+        .iter()
+        .map(|(_, column)| match *column {
+            Column::Text(field) => field(event).to_string(),
+            Column::Float(field) => field(event).to_string(),
+            Column::Integer(field) => field(event).to_string(),
+            Column::Timestamp => event
+                .timestamp
+                .with_timezone(&timezone)
+                .to_rfc3339_opts(SecondsFormat::Micros, true),
+        })
+        .collect();
+    format!("{}\n", values.join(","))
+}
+
+/// Encode a stream of readings as CSV, emitting the header and then each
+/// reading as its own chunk
+pub fn csv_stream<S>(events: S, timezone: Tz) -> impl Stream<Item = anyhow::Result<String>> + Send
+where
+    S: Stream<Item = anyhow::Result<Event>> + Send,
+{
+    futures::stream::once(async { Ok(csv_header()) })
+        .chain(events.map_ok(move |event| csv_row(&event, timezone)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_format_parse() {
+        assert_eq!(ExportFormat::parse("parquet"), Some(ExportFormat::Parquet));
+        assert_eq!(ExportFormat::parse("Parquet"), Some(ExportFormat::Parquet));
+        assert_eq!(ExportFormat::parse("xlsx"), None);
+        assert_eq!(ExportFormat::Parquet.extension(), "parquet");
+    }
+
+    #[test]
+    fn test_record_batch_matches_schema() {
+        let event = Event::new_with_current_time(
+            "AA:BB:CC:DD:EE:01".to_string(),
+            "FF:FF:FF:FF:FF:01".to_string(),
+            22.5,
+            65.0,
+            1013.25,
+            3000,
+            4,
+            10,
+            1,
+            1.0,
+            -16,
+            -20,
+            1044,
+            -40,
+        );
+
+        let batch = record_batch(&[event.clone(), event], Tz::Europe__Helsinki);
+
+        assert!(matches!(batch, Ok(ref batch) if batch.num_rows() == 2));
+        assert!(
+            matches!(batch, Ok(ref batch) if batch.schema() == event_schema(Tz::Europe__Helsinki))
+        );
+    }
+
+    #[test]
+    fn test_csv_header_matches_schema() {
+        let schema = event_schema(Tz::UTC);
+        let columns: Vec<&str> = schema
+            .fields()
+            .iter()
+            .map(|field| field.name().as_str())
+            .collect();
+
+        assert_eq!(
+            csv_header().trim_end().split(',').collect::<Vec<_>>(),
+            columns
+        );
+    }
+
+    #[test]
+    fn test_export_columns_cover_event_fields() {
+        let event = Event::new_with_current_time(
+            "AA:BB:CC:DD:EE:01".to_string(),
+            "FF:FF:FF:FF:FF:01".to_string(),
+            22.5,
+            65.0,
+            1013.25,
+            3000,
+            4,
+            10,
+            1,
+            1.0,
+            -16,
+            -20,
+            1044,
+            -40,
+        );
+        let serialized = serde_json::to_value(&event).unwrap_or_default();
+        let mut fields: Vec<&str> = serialized
+            .as_object()
+            .map(|object| object.keys().map(String::as_str).collect())
+            .unwrap_or_default();
+        let mut columns: Vec<&str> = EXPORT_COLUMNS.iter().map(|(name, _)| *name).collect();
+
+        fields.sort_unstable();
+        columns.sort_unstable();
+        assert_eq!(columns, fields);
+    }
+
+    #[test]
+    fn test_csv_row() {
+        let mut event = Event::new_with_current_time(
+            "AA:BB:CC:DD:EE:01".to_string(),
+            "FF:FF:FF:FF:FF:01".to_string(),
+            22.5,
+            65.0,
+            1013.25,
+            3000,
+            4,
+            10,
+            1,
+            1.0,
+            -16,
+            -20,
+            1044,
+            -40,
+        );
+        event.timestamp = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap_or_default();
+
+        assert_eq!(
+            csv_row(&event, Tz::UTC),
+            "AA:BB:CC:DD:EE:01,FF:FF:FF:FF:FF:01,22.5,65,1013.25,3000,4,10,1,1,-16,-20,1044,-40,\
+             2023-11-14T22:13:20.000000Z\n"
+        );
+        assert!(
+            csv_row(&event, Tz::Europe__Helsinki).ends_with(",2023-11-15T00:13:20.000000+02:00\n")
+        );
+    }
+}
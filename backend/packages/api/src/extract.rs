@@ -0,0 +1,197 @@
+//! Request extractors shared by the API handlers
+
+use axum::{
+    extract::{
+        FromRequestParts,
+        Query,
+    },
+    http::{
+        request::Parts,
+        Uri,
+    },
+};
+use chrono_tz::Tz;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    errors::{
+        ApiError,
+        ApiResult,
+    },
+    state::AppState,
+    utils::parse_timezone,
+};
+
+/// Query parameter that disables unknown-parameter rejection when `false`
+pub const STRICT_PARAM: &str = "strict";
+
+/// Query parameter selecting the timezone of output timestamps
+pub const TZ_PARAM: &str = "tz";
+
+/// Parameters every endpoint accepts in addition to its own
+const GLOBAL_PARAMS: &[&str] = &[STRICT_PARAM, TZ_PARAM];
+
+/// Query structures that know the parameter names they accept
+pub trait KnownParams {
+    const FIELDS: &'static [&'static str];
+}
+
+/// Query extractor that rejects parameters the endpoint does not understand
+///
+/// A typo such as `?limt=100` would otherwise be ignored silently and the
+/// request served with defaults. Clients that need to send extra parameters
+/// can opt out with `?strict=false`.
+#[derive(Debug)]
+pub struct StrictQuery<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for StrictQuery<T>
+where
+    T: DeserializeOwned + KnownParams,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Query(pairs) = Query::<Vec<(String, String)>>::try_from_uri(&parts.uri)
+            .map_err(|rejection| ApiError::bad_request(&rejection.body_text()))?;
+
+        let mut strict = true;
+        for (name, value) in &pairs {
+            if name == STRICT_PARAM {
+                strict = match value.as_str() {
+                    "true" => true,
+                    "false" => false,
+                    _ => {
+                        return Err(ApiError::InvalidParameter {
+                            parameter: STRICT_PARAM.to_string(),
+                            value: value.clone(),
+                            expected: "true or false".to_string(),
+                        })
+                    }
+                };
+            }
+        }
+
+        if strict {
+            if let Some((name, _)) = pairs.iter().find(|(name, _)| {
+                !GLOBAL_PARAMS.contains(&name.as_str()) && !T::FIELDS.contains(&name.as_str())
+            }) {
+                return Err(ApiError::unknown_parameter(name, T::FIELDS));
+            }
+        }
+
+        let Query(value) = Query::<T>::try_from_uri(&parts.uri)
+            .map_err(|rejection| ApiError::bad_request(&rejection.body_text()))?;
+
+        Ok(Self(value))
+    }
+}
+
+/// Timezone named by a request's `?tz=` parameter, if any
+///
+/// # Errors
+/// Returns `ApiError::InvalidParameter` if the zone is unknown
+pub fn requested_timezone(uri: &Uri) -> ApiResult<Option<Tz>> {
+    let Ok(Query(pairs)) = Query::<Vec<(String, String)>>::try_from_uri(uri) else {
+        // Malformed query strings are reported by the handler's extractor
+        return Ok(None);
+    };
+
+    pairs
+        .iter()
+        .find(|(name, _)| name == TZ_PARAM)
+        .map(|(_, value)| {
+            parse_timezone(value).ok_or_else(|| ApiError::InvalidParameter {
+                parameter: TZ_PARAM.to_string(),
+                value: value.clone(),
+                expected: "IANA timezone name (e.g., Europe/Helsinki)".to_string(),
+            })
+        })
+        .transpose()
+}
+
+/// Timezone a request works in: `?tz=` when given, otherwise the configured
+/// default timezone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestTimezone(pub Tz);
+
+impl FromRequestParts<AppState> for RequestTimezone {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(Self(
+            requested_timezone(&parts.uri)?.unwrap_or(state.default_timezone),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Request;
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Params {
+        limit: Option<i64>,
+    }
+
+    impl KnownParams for Params {
+        const FIELDS: &'static [&'static str] = &["limit"];
+    }
+
+    #[allow(clippy::expect_used)]
+    async fn extract(uri: &str) -> Result<Params, ApiError> {
+        let (mut parts, ()) = Request::get(uri)
+            .body(())
+            .expect("valid request")
+            .into_parts();
+        StrictQuery::<Params>::from_request_parts(&mut parts, &())
+            .await
+            .map(|StrictQuery(params)| params)
+    }
+
+    #[tokio::test]
+    #[allow(clippy::expect_used)]
+    async fn test_known_params_accepted() {
+        let params = extract("/?limit=5").await.expect("known param");
+        assert_eq!(params.limit, Some(5));
+
+        let params = extract("/").await.expect("no params");
+        assert_eq!(params.limit, None);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_param_rejected() {
+        let error = extract("/?limt=5").await;
+        assert!(matches!(
+            error,
+            Err(ApiError::InvalidParameter { ref parameter, .. }) if parameter == "limt"
+        ));
+    }
+
+    #[tokio::test]
+    #[allow(clippy::expect_used)]
+    async fn test_strict_false_allows_unknown() {
+        let params = extract("/?limt=5&limit=7&strict=false")
+            .await
+            .expect("strict=false");
+        assert_eq!(params.limit, Some(7));
+
+        assert!(extract("/?limit=7&strict=true").await.is_ok());
+        assert!(extract("/?limit=7&strict=maybe").await.is_err());
+        assert!(extract("/?limit=7&tz=Europe/Helsinki").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_malformed_value_is_bad_request() {
+        assert!(matches!(
+            extract("/?limit=abc").await,
+            Err(ApiError::BadRequest { .. })
+        ));
+    }
+}
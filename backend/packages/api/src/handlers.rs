@@ -1,68 +1,532 @@
 //! HTTP request handlers for the API
 
+use std::collections::BTreeMap;
+
 use axum::{
+    body::Body,
     extract::{
+        ws::{
+            Message,
+            WebSocket,
+            WebSocketUpgrade,
+        },
         Path,
-        Query,
         State,
     },
-    response::Json,
+    http::{
+        header,
+        HeaderMap,
+        HeaderValue,
+        StatusCode,
+    },
+    response::{
+        IntoResponse,
+        Json,
+        Response,
+    },
 };
 use chrono::{
+    DateTime,
     Duration,
     Utc,
 };
+use chrono_tz::Tz;
+use futures::StreamExt;
 use postgres_store::{
+    AlertComparator,
+    AlertHit,
+    AlertRule,
+    BatteryProjection,
     Event,
+    GatewayInfo,
+    GatewayStats,
+    GrowthStatistics,
+    HistoryCursor,
+    ImportSummary,
+    LatestPerMetric,
+    Metric,
+    MetricTrends,
+    Order,
+    PageCursor,
+    SensorAlias,
+    SensorCard,
+    SensorCorrelation,
+    SensorDedup,
+    SensorGroup,
+    SensorHealthMetrics,
+    SensorStats,
+    SensorThreshold,
     StorageEstimate,
     StorageStats,
+    ThresholdMetric,
     TimeBucketedData,
+    TimeInterval,
+    VibrationAlert,
+    DEFAULT_HISTORY_LIMIT,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use tokio::sync::broadcast::{
+    self,
+    error::RecvError,
 };
 
 use crate::{
+    auth::require_admin,
     errors::{
         ApiError,
         ApiResult,
     },
+    export::{
+        csv_stream,
+        parquet_stream,
+        ExportFormat,
+        CSV_CONTENT_TYPE,
+        PARQUET_CONTENT_TYPE,
+    },
+    extract::{
+        RequestTimezone,
+        StrictQuery,
+    },
+    metrics::{
+        PoolUsage,
+        METRICS_CONTENT_TYPE,
+    },
+    middleware::localize_value,
     queries::{
+        ActiveSensorsQuery,
+        BatteryProjectionQuery,
+        CompareQuery,
+        CorrelationQuery,
+        DaysQuery,
+        ExportQuery,
+        GatewayHistoryQuery,
         HistoricalQuery,
+        HistoryCsvQuery,
+        HoursQuery,
+        LatestReadingsQuery,
+        LiveQuery,
         StorageEstimateQuery,
+        TemperatureTrendQuery,
+        ThresholdDeleteQuery,
         TimeBucketQuery,
+        TimeRangeQuery,
+        TrendsQuery,
+        UnitsQuery,
     },
     state::AppState,
     utils::{
+        bucket_in_units,
+        clamp_max_points,
+        is_valid_group_name,
         is_valid_mac_format,
+        normalize_alias,
+        normalize_mac,
         parse_datetime,
         parse_interval,
+        parse_mac_list,
+        parse_metric,
+        parse_order,
+        parse_range_preset,
+        parse_sensor_dedup,
+        parse_units,
+        preset_range,
         sanitize_mac_for_logging,
         validate_limit,
+        TimeRange,
+        UnitSystem,
+        RANGE_PRESETS,
     },
 };
 
-/// Health check endpoint
+/// Content type for newline-delimited JSON responses
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Response header carrying the cursor of the next page of sensor history
+pub const NEXT_CURSOR_HEADER: &str = "x-next-cursor";
+
+/// Response header carrying the cursor that pages sensor history in the
+/// opposite direction
+pub const PREV_CURSOR_HEADER: &str = "x-prev-cursor";
+
+/// Liveness probe: succeeds whenever the process is up and serving
 pub async fn health_check() -> &'static str {
     "OK"
 }
 
-/// Get all active sensors
+/// How long the readiness probe waits for each dependency to answer
+const READINESS_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// State of one dependency checked by the readiness probe
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DependencyStatus {
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl DependencyStatus {
+    fn from_check(result: anyhow::Result<()>) -> Self {
+        match result {
+            Ok(()) => Self {
+                status: "ok".to_string(),
+                error: None,
+            },
+            Err(error) => Self {
+                status: "unavailable".to_string(),
+                error: Some(error.to_string()),
+            },
+        }
+    }
+
+    pub const fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Readiness of the server and each dependency it needs, by name
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadinessReport {
+    pub ready: bool,
+    pub dependencies: BTreeMap<String, DependencyStatus>,
+}
+
+/// Readiness probe: unlike [`health_check`], only succeeds while every
+/// dependency answers within `READINESS_TIMEOUT`
+///
+/// The database is the only dependency the API server has; it does not use
+/// Redis. Responds with 503 and the same report when any dependency is down.
+pub async fn readiness_check(State(state): State<AppState>) -> Response {
+    let dependencies = BTreeMap::from([(
+        "database".to_string(),
+        DependencyStatus::from_check(state.store.ping(READINESS_TIMEOUT).await),
+    )]);
+    let ready = dependencies.values().all(DependencyStatus::is_ok);
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        Json(ReadinessReport {
+            ready,
+            dependencies,
+        }),
+    )
+        .into_response()
+}
+
+/// Prometheus metrics of the API server
+pub async fn get_metrics(State(state): State<AppState>) -> Response {
+    let size = usize::try_from(state.store.pool.size()).unwrap_or(usize::MAX);
+    let idle = state.store.pool.num_idle();
+    let pool = PoolUsage {
+        active: size.saturating_sub(idle),
+        idle,
+    };
+
+    (
+        [(header::CONTENT_TYPE, METRICS_CONTENT_TYPE)],
+        state.metrics.render(pool),
+    )
+        .into_response()
+}
+
+/// List the MACs of all sensors with stored readings, without fetching
+/// their readings
 ///
 /// # Errors
 /// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
 pub async fn get_sensors(State(state): State<AppState>) -> ApiResult<Json<Vec<String>>> {
-    match state.store.get_sensors().await {
+    match state.store.list_sensor_macs().await {
         Ok(sensors) => {
             tracing::debug!("Retrieved {} sensors", sensors.len());
             Ok(Json(sensors))
         }
-        Err(error) => Err(ApiError::database_error(
-            "get sensors list",
-            &error.to_string(),
-        )),
+        Err(error) => Err(ApiError::store_error("get sensors list", &error)),
+    }
+}
+
+/// Reading of a sensor along with the alias it was given
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AliasedReading {
+    #[serde(flatten)]
+    pub reading: Event,
+    pub alias: Option<String>,
+}
+
+/// Latest reading of each sensor heard in the last 24 hours, with its alias
+///
+/// A sensor heard by several gateways is listed once, with its most recent
+/// reading, unless `?dedup_by=sensor_gateway` asks for one row per gateway.
+/// `?gateway_mac=` lists only the sensors heard by that gateway.
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if `dedup_by` is not a known mode or
+/// `gateway_mac` is not a valid MAC address
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn get_active_sensors(
+    State(state): State<AppState>,
+    StrictQuery(params): StrictQuery<ActiveSensorsQuery>,
+) -> ApiResult<Json<Vec<AliasedReading>>> {
+    let dedup = match params.dedup_by.as_deref() {
+        Some(dedup_by) => {
+            parse_sensor_dedup(dedup_by).ok_or_else(|| ApiError::InvalidParameter {
+                parameter: "dedup_by".to_string(),
+                value: dedup_by.to_string(),
+                expected: "one of: sensor, sensor_gateway".to_string(),
+            })?
+        }
+        None => SensorDedup::default(),
+    };
+
+    let readings = match params.gateway_mac.as_deref() {
+        Some(gateway_mac) if !is_valid_mac_format(gateway_mac) => {
+            return Err(ApiError::invalid_mac(gateway_mac));
+        }
+        Some(gateway_mac) => {
+            state
+                .store
+                .get_active_sensors_by_gateway(&normalize_mac(gateway_mac))
+                .await
+        }
+        // The default dedup is the one every backend can answer
+        None if dedup == SensorDedup::Sensor => state.readings.get_active_sensors().await,
+        None => state.store.get_active_sensors_by(dedup).await,
+    };
+    let readings = readings.map_err(|error| ApiError::store_error("get active sensors", &error))?;
+    let aliases = state
+        .store
+        .list_sensor_aliases()
+        .await
+        .map_err(|error| ApiError::store_error("list sensor aliases", &error))?;
+
+    Ok(Json(
+        readings
+            .into_iter()
+            .map(|reading| AliasedReading {
+                alias: aliases.get(&reading.sensor_mac).cloned(),
+                reading,
+            })
+            .collect(),
+    ))
+}
+
+/// Body of a sensor alias request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewSensorAlias {
+    pub alias: String,
+}
+
+/// Name a sensor, replacing its previous alias
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if MAC address format is invalid or the
+/// alias is blank or longer than `MAX_ALIAS_LEN` characters
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database update fails
+pub async fn set_sensor_alias(
+    State(state): State<AppState>,
+    Path(sensor_mac): Path<String>,
+    Json(body): Json<NewSensorAlias>,
+) -> ApiResult<Json<SensorAlias>> {
+    if !is_valid_mac_format(&sensor_mac) {
+        return Err(ApiError::invalid_mac(&sensor_mac));
+    }
+    let sensor_mac = normalize_mac(&sensor_mac);
+    let alias = normalize_alias(&body.alias).ok_or_else(|| ApiError::invalid_alias(&body.alias))?;
+
+    match state.store.set_sensor_alias(&sensor_mac, alias).await {
+        Ok(alias) => Ok(Json(alias)),
+        Err(error) => Err(ApiError::store_error("set sensor alias", &error)),
+    }
+}
+
+/// Get the alias of a sensor
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if MAC address format is invalid
+/// Returns `StatusCode::NOT_FOUND` if the sensor has no alias
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn get_sensor_alias(
+    State(state): State<AppState>,
+    Path(sensor_mac): Path<String>,
+) -> ApiResult<Json<SensorAlias>> {
+    if !is_valid_mac_format(&sensor_mac) {
+        return Err(ApiError::invalid_mac(&sensor_mac));
+    }
+    let sensor_mac = normalize_mac(&sensor_mac);
+
+    match state.store.get_sensor_alias(&sensor_mac).await {
+        Ok(Some(alias)) => Ok(Json(alias)),
+        Ok(None) => Err(ApiError::alias_not_found(&sensor_mac)),
+        Err(error) => Err(ApiError::store_error("get sensor alias", &error)),
+    }
+}
+
+/// Gateways that forwarded readings in the last 24 hours, with their sensor
+/// counts and when each was last heard
+///
+/// # Errors
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn get_gateways(State(state): State<AppState>) -> ApiResult<Json<Vec<GatewayInfo>>> {
+    match state.store.get_active_gateways().await {
+        Ok(gateways) => {
+            tracing::debug!("Retrieved {} gateways", gateways.len());
+            Ok(Json(gateways))
+        }
+        Err(error) => Err(ApiError::store_error("get gateways", &error)),
+    }
+}
+
+/// Readings a gateway forwarded from any sensor, newest first, by default
+/// over the last hour
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if MAC address format, limit or date
+/// formats are invalid
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn get_gateway_history(
+    State(state): State<AppState>,
+    RequestTimezone(timezone): RequestTimezone,
+    Path(gateway_mac): Path<String>,
+    StrictQuery(params): StrictQuery<GatewayHistoryQuery>,
+) -> ApiResult<Json<Vec<DerivedReading>>> {
+    if !is_valid_mac_format(&gateway_mac) {
+        return Err(ApiError::invalid_mac(&gateway_mac));
+    }
+    let gateway_mac = normalize_mac(&gateway_mac);
+
+    let limit = params.limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    if !validate_limit(limit) {
+        return Err(ApiError::invalid_limit(limit));
+    }
+
+    let (start, end) = parse_range_params(
+        params.preset.as_deref(),
+        params.start.as_ref(),
+        params.end.as_ref(),
+        Duration::hours(1),
+        timezone,
+    )?;
+    let units = parse_units(params.units.as_deref());
+
+    match state
+        .store
+        .get_gateway_history(&gateway_mac, start, end, limit)
+        .await
+    {
+        Ok(readings) => {
+            tracing::debug!(
+                "Retrieved {} readings forwarded by gateway: {}",
+                readings.len(),
+                sanitize_mac_for_logging(&gateway_mac)
+            );
+            Ok(Json(
+                readings
+                    .into_iter()
+                    .map(|reading| DerivedReading::from(reading).in_units(units))
+                    .collect(),
+            ))
+        }
+        Err(error) => Err(ApiError::store_error("get gateway history", &error)),
+    }
+}
+
+/// Ingest a batch of readings, e.g. when replaying data captured offline
+///
+/// Readings outside the ranges the database accepts are skipped and listed
+/// in the response instead of failing the whole batch. Send an
+/// `Idempotency-Key` header to make retries safe.
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if any reading has an invalid sensor or
+/// gateway MAC address
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database insert fails
+pub async fn ingest_readings(
+    State(state): State<AppState>,
+    Json(mut events): Json<Vec<Event>>,
+) -> ApiResult<(StatusCode, Json<ImportSummary>)> {
+    if let Some(mac) = events
+        .iter()
+        .flat_map(|event| [&event.sensor_mac, &event.gateway_mac])
+        .find(|mac| !is_valid_mac_format(mac))
+    {
+        return Err(ApiError::invalid_mac(mac));
+    }
+    for event in &mut events {
+        event.sensor_mac = normalize_mac(&event.sensor_mac);
+        event.gateway_mac = normalize_mac(&event.gateway_mac);
+    }
+
+    let summary = state
+        .store
+        .import_events(&events)
+        .await
+        .map_err(|error| ApiError::store_error("import readings", &error))?;
+
+    tracing::debug!(
+        "Ingested {} readings, skipped {}",
+        summary.imported,
+        summary.skipped_with_reason.len()
+    );
+    Ok((StatusCode::CREATED, Json(summary)))
+}
+
+/// Get the latest reading, metadata and health flags of every sensor
+///
+/// # Errors
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn get_dashboard(State(state): State<AppState>) -> ApiResult<Json<Vec<SensorCard>>> {
+    match state.store.get_dashboard().await {
+        Ok(cards) => {
+            tracing::debug!("Assembled dashboard for {} sensors", cards.len());
+            Ok(Json(cards))
+        }
+        Err(error) => Err(ApiError::store_error("get dashboard", &error)),
+    }
+}
+
+/// Reading along with values derived from it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DerivedReading {
+    #[serde(flatten)]
+    pub reading: Event,
+    /// Dew point in °C, absent for readings without humidity
+    pub dew_point: Option<f64>,
+    /// Estimated battery charge, absent for readings without a voltage
+    pub battery_percentage: Option<u8>,
+}
+
+impl DerivedReading {
+    /// Report the temperatures and pressure in `units`
+    #[must_use]
+    pub fn in_units(mut self, units: UnitSystem) -> Self {
+        self.reading.temperature = units.temperature(self.reading.temperature);
+        self.reading.pressure = units.pressure(self.reading.pressure);
+        self.dew_point = self.dew_point.map(|dew_point| units.temperature(dew_point));
+        self
+    }
+}
+
+impl From<Event> for DerivedReading {
+    fn from(reading: Event) -> Self {
+        Self {
+            dew_point: reading.dew_point(),
+            battery_percentage: reading.battery_percentage(),
+            reading,
+        }
     }
 }
 
 /// Get latest reading for a specific sensor
 ///
+/// The response carries an `ETag`; presenting it in `If-None-Match` returns
+/// 304 Not Modified until a newer reading arrives.
+///
 /// # Errors
 /// Returns `StatusCode::BAD_REQUEST` if MAC address format is invalid
 /// Returns `StatusCode::NOT_FOUND` if sensor has no readings
@@ -70,19 +534,32 @@ pub async fn get_sensors(State(state): State<AppState>) -> ApiResult<Json<Vec<St
 pub async fn get_sensor_latest(
     State(state): State<AppState>,
     Path(sensor_mac): Path<String>,
-) -> ApiResult<Json<Event>> {
+    StrictQuery(params): StrictQuery<UnitsQuery>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
     // Validate MAC format
     if !is_valid_mac_format(&sensor_mac) {
         return Err(ApiError::invalid_mac(&sensor_mac));
     }
+    let sensor_mac = normalize_mac(&sensor_mac);
 
-    match state.store.get_latest_reading(&sensor_mac).await {
+    match state.readings.get_latest_reading(&sensor_mac).await {
         Ok(Some(reading)) => {
             tracing::debug!(
                 "Retrieved latest reading for sensor: {}",
                 sanitize_mac_for_logging(&sensor_mac)
             );
-            Ok(Json(reading))
+            let units = parse_units(params.units.as_deref());
+            let etag = reading_etag(&reading, units);
+            let mut response = if etag_matches(&headers, &etag) {
+                StatusCode::NOT_MODIFIED.into_response()
+            } else {
+                Json(DerivedReading::from(reading).in_units(units)).into_response()
+            };
+            if let Ok(value) = HeaderValue::from_str(&etag) {
+                response.headers_mut().insert(header::ETAG, value);
+            }
+            Ok(response)
         }
         Ok(None) => {
             tracing::debug!(
@@ -91,169 +568,1566 @@ pub async fn get_sensor_latest(
             );
             Err(ApiError::readings_not_found(&sensor_mac))
         }
-        Err(error) => Err(ApiError::database_error(
-            "get latest reading",
-            &error.to_string(),
-        )),
+        Err(error) => Err(ApiError::store_error("get latest reading", &error)),
     }
 }
 
-/// Get historical data for a sensor
+/// Weak entity tag of a sensor's latest reading, which changes whenever a
+/// newer reading arrives. Weak because the `?tz=` offset and compression
+/// change the bytes but not the reading.
+fn reading_etag(reading: &Event, units: UnitSystem) -> String {
+    let units = match units {
+        UnitSystem::Metric => "metric",
+        UnitSystem::Imperial => "imperial",
+    };
+    format!(
+        "W/\"{}-{}-{units}\"",
+        reading.timestamp.timestamp_micros(),
+        reading.measurement_sequence_number
+    )
+}
+
+/// Whether `If-None-Match` lists `etag` or `*`, comparing weakly
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = opaque(etag);
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|candidate| candidate.trim() == "*" || opaque(candidate) == etag)
+}
+
+/// Latest reading of each sensor in `?macs=`, by MAC, in one query. Sensors
+/// without readings are left out.
 ///
 /// # Errors
-/// Returns `StatusCode::BAD_REQUEST` if MAC address format is invalid, limit is
-/// invalid, or date formats are invalid
+/// Returns `StatusCode::BAD_REQUEST` if `macs` is missing, lists more than
+/// `max_bulk_sensors` sensors, or contains an invalid MAC address
 /// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
-#[allow(clippy::too_many_lines)]
-pub async fn get_sensor_history(
+pub async fn get_latest_readings(
+    State(state): State<AppState>,
+    StrictQuery(params): StrictQuery<LatestReadingsQuery>,
+) -> ApiResult<Json<BTreeMap<String, DerivedReading>>> {
+    let sensor_macs = parse_bulk_macs(params.macs.as_deref(), state.max_bulk_sensors)?;
+    let units = parse_units(params.units.as_deref());
+
+    match state.readings.get_latest_for(&sensor_macs).await {
+        Ok(readings) => {
+            tracing::debug!(
+                "Retrieved latest readings of {} of {} sensors",
+                readings.len(),
+                sensor_macs.len()
+            );
+            Ok(Json(
+                readings
+                    .into_iter()
+                    .map(|reading| {
+                        let mac = reading.sensor_mac.clone();
+                        (mac, DerivedReading::from(reading).in_units(units))
+                    })
+                    .collect(),
+            ))
+        }
+        Err(error) => Err(ApiError::store_error("get latest readings", &error)),
+    }
+}
+
+/// Get the newest value of each metric, each with its own timestamp
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if MAC address format is invalid
+/// Returns `StatusCode::NOT_FOUND` if sensor has no readings
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn get_sensor_latest_per_metric(
     State(state): State<AppState>,
     Path(sensor_mac): Path<String>,
-    Query(params): Query<HistoricalQuery>,
-) -> ApiResult<Json<Vec<Event>>> {
-    // Validate MAC format
+) -> ApiResult<Json<LatestPerMetric>> {
     if !is_valid_mac_format(&sensor_mac) {
         return Err(ApiError::invalid_mac(&sensor_mac));
     }
+    let sensor_mac = normalize_mac(&sensor_mac);
 
-    // Validate limit if provided
-    if let Some(limit) = params.limit {
-        if !validate_limit(limit) {
-            return Err(ApiError::invalid_limit(limit));
-        }
+    match state.store.get_latest_per_metric(&sensor_mac).await {
+        Ok(latest) if latest.is_empty() => Err(ApiError::readings_not_found(&sensor_mac)),
+        Ok(latest) => Ok(Json(latest)),
+        Err(error) => Err(ApiError::store_error("get latest per metric", &error)),
     }
+}
 
-    let start = match params.start.as_ref() {
-        Some(date_str) => {
-            if let Ok(dt) = parse_datetime(date_str) {
-                Some(dt)
-            } else {
-                return Err(ApiError::invalid_date(date_str));
-            }
-        }
-        #[allow(clippy::arithmetic_side_effects)]
-        None => Some(Utc::now() - Duration::hours(1)),
-    };
+/// Number of readings a sensor recorded in a time range
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadingCount {
+    pub sensor_mac: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub count: i64,
+}
 
-    let end = match params.end.as_ref() {
-        Some(date_str) => {
-            if let Ok(dt) = parse_datetime(date_str) {
-                Some(dt)
-            } else {
-                return Err(ApiError::invalid_date(date_str));
-            }
-        }
-        None => Some(Utc::now()),
-    };
+/// Count readings for a sensor without fetching them
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if MAC address format or dates are invalid
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn get_sensor_count(
+    State(state): State<AppState>,
+    Path(sensor_mac): Path<String>,
+    StrictQuery(params): StrictQuery<TimeRangeQuery>,
+) -> ApiResult<Json<ReadingCount>> {
+    if !is_valid_mac_format(&sensor_mac) {
+        return Err(ApiError::invalid_mac(&sensor_mac));
+    }
+    let sensor_mac = normalize_mac(&sensor_mac);
+
+    let (start, end) = parse_time_range(
+        params.start.as_ref(),
+        params.end.as_ref(),
+        Duration::hours(24),
+    )?;
+
+    match state.store.count_readings(&sensor_mac, start, end).await {
+        Ok(count) => Ok(Json(ReadingCount {
+            sensor_mac,
+            start,
+            end,
+            count,
+        })),
+        Err(error) => Err(ApiError::store_error("count readings", &error)),
+    }
+}
+
+/// Body of a threshold creation request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewThreshold {
+    pub metric: ThresholdMetric,
+    pub min_value: Option<f64>,
+    pub max_value: Option<f64>,
+}
 
-    // Validate date range
-    if let (Some(start_dt), Some(end_dt)) = (start, end) {
-        if start_dt >= end_dt {
-            return Err(ApiError::invalid_date_range(
-                "Start date must be before end date",
+/// Ensure a threshold has at least one finite bound and a non-inverted range
+fn validate_threshold_bounds(min_value: Option<f64>, max_value: Option<f64>) -> ApiResult<()> {
+    if min_value.is_none() && max_value.is_none() {
+        return Err(ApiError::bad_request(
+            "At least one of min_value and max_value is required",
+        ));
+    }
+    if min_value
+        .into_iter()
+        .chain(max_value)
+        .any(|value| !value.is_finite())
+    {
+        return Err(ApiError::bad_request("Threshold bounds must be finite"));
+    }
+    if let (Some(min), Some(max)) = (min_value, max_value) {
+        if min > max {
+            return Err(ApiError::bad_request(
+                "min_value must not be greater than max_value",
             ));
         }
     }
+    Ok(())
+}
+
+/// Configure a threshold for one metric of a sensor
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if MAC address format or bounds are
+/// invalid Returns `StatusCode::INTERNAL_SERVER_ERROR` if database insert fails
+pub async fn create_sensor_threshold(
+    State(state): State<AppState>,
+    Path(sensor_mac): Path<String>,
+    Json(threshold): Json<NewThreshold>,
+) -> ApiResult<(StatusCode, Json<SensorThreshold>)> {
+    if !is_valid_mac_format(&sensor_mac) {
+        return Err(ApiError::invalid_mac(&sensor_mac));
+    }
+    let sensor_mac = normalize_mac(&sensor_mac);
+    validate_threshold_bounds(threshold.min_value, threshold.max_value)?;
 
     match state
         .store
-        .get_historical_data(&sensor_mac, start, end, params.limit)
+        .create_threshold(
+            &sensor_mac,
+            threshold.metric,
+            threshold.min_value,
+            threshold.max_value,
+        )
         .await
     {
-        Ok(readings) => {
-            tracing::debug!(
-                "Retrieved {} historical readings for sensor: {}",
-                readings.len(),
-                sanitize_mac_for_logging(&sensor_mac)
-            );
-            Ok(Json(readings))
-        }
-        Err(error) => Err(ApiError::database_error(
-            "get historical data",
-            &error.to_string(),
-        )),
+        Ok(created) => Ok((StatusCode::CREATED, Json(created))),
+        Err(error) => Err(ApiError::store_error("create threshold", &error)),
     }
 }
 
-/// Get aggregated data for a sensor
+/// Body of an alert rule creation request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewAlert {
+    pub metric: Metric,
+    pub comparator: AlertComparator,
+    pub threshold: f64,
+}
+
+/// Create an alert rule that fires while the latest reading of a sensor is
+/// above or below a threshold
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if MAC address format is invalid or the
+/// threshold is not finite
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database insert fails
+pub async fn create_sensor_alert(
+    State(state): State<AppState>,
+    Path(sensor_mac): Path<String>,
+    Json(alert): Json<NewAlert>,
+) -> ApiResult<(StatusCode, Json<AlertRule>)> {
+    if !is_valid_mac_format(&sensor_mac) {
+        return Err(ApiError::invalid_mac(&sensor_mac));
+    }
+    let sensor_mac = normalize_mac(&sensor_mac);
+    if !alert.threshold.is_finite() {
+        return Err(ApiError::bad_request("threshold must be a finite number"));
+    }
+
+    match state
+        .store
+        .create_alert(&sensor_mac, alert.metric, alert.comparator, alert.threshold)
+        .await
+    {
+        Ok(created) => Ok((StatusCode::CREATED, Json(created))),
+        Err(error) => Err(ApiError::store_error("create alert", &error)),
+    }
+}
+
+/// Alert rules breached by the latest reading of their sensor
 ///
 /// # Errors
-/// Returns `StatusCode::BAD_REQUEST` if MAC address format is invalid, date
-/// formats are invalid, or interval is invalid
 /// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
-#[allow(clippy::too_many_lines)]
-pub async fn get_sensor_aggregates(
+pub async fn get_active_alerts(State(state): State<AppState>) -> ApiResult<Json<Vec<AlertHit>>> {
+    match state.store.evaluate_alerts().await {
+        Ok(hits) => Ok(Json(hits)),
+        Err(error) => Err(ApiError::store_error("evaluate alerts", &error)),
+    }
+}
+
+/// List the thresholds configured for a sensor
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if MAC address format is invalid
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn get_sensor_thresholds(
     State(state): State<AppState>,
     Path(sensor_mac): Path<String>,
-    Query(params): Query<TimeBucketQuery>,
-) -> ApiResult<Json<Vec<TimeBucketedData>>> {
-    // Validate MAC format
+) -> ApiResult<Json<Vec<SensorThreshold>>> {
     if !is_valid_mac_format(&sensor_mac) {
         return Err(ApiError::invalid_mac(&sensor_mac));
     }
+    let sensor_mac = normalize_mac(&sensor_mac);
 
-    let start = match params.start.as_ref() {
-        Some(date_str) => {
-            if let Ok(dt) = parse_datetime(date_str) {
-                dt
-            } else {
-                return Err(ApiError::invalid_date(date_str));
-            }
+    match state.store.list_thresholds(&sensor_mac).await {
+        Ok(thresholds) => Ok(Json(thresholds)),
+        Err(error) => Err(ApiError::store_error("list thresholds", &error)),
+    }
+}
+
+/// Delete a threshold by id, optionally only if it belongs to `?sensor_mac=`
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if the MAC address format is invalid
+/// Returns `StatusCode::NOT_FOUND` if no such threshold exists for the sensor
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database delete fails
+pub async fn delete_threshold(
+    State(state): State<AppState>,
+    Path(threshold_id): Path<i64>,
+    StrictQuery(params): StrictQuery<ThresholdDeleteQuery>,
+) -> ApiResult<StatusCode> {
+    if let Some(sensor_mac) = &params.sensor_mac {
+        if !is_valid_mac_format(sensor_mac) {
+            return Err(ApiError::invalid_mac(sensor_mac));
         }
-        #[allow(clippy::arithmetic_side_effects)]
-        None => Utc::now() - Duration::hours(24),
-    };
+    }
+    let sensor_mac = params.sensor_mac.as_deref().map(normalize_mac);
+
+    match state
+        .store
+        .delete_threshold(threshold_id, sensor_mac.as_deref())
+        .await
+    {
+        Ok(Some(_)) => Ok(StatusCode::NO_CONTENT),
+        Ok(None) => Err(ApiError::threshold_not_found(threshold_id)),
+        Err(error) => Err(ApiError::store_error("delete threshold", &error)),
+    }
+}
+
+/// Delete every reading of a sensor, such as when its tag is retired
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if the MAC address format is invalid
+/// Returns `StatusCode::NOT_FOUND` if the sensor has no readings
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database delete fails
+pub async fn delete_sensor(
+    State(state): State<AppState>,
+    Path(sensor_mac): Path<String>,
+) -> ApiResult<StatusCode> {
+    if !is_valid_mac_format(&sensor_mac) {
+        return Err(ApiError::invalid_mac(&sensor_mac));
+    }
+    let sensor_mac = normalize_mac(&sensor_mac);
+
+    match state.store.delete_sensor(&sensor_mac).await {
+        Ok(0) => Err(ApiError::sensor_not_found(&sensor_mac)),
+        Ok(deleted) => {
+            tracing::info!("Deleted {} readings of sensor {}", deleted, sensor_mac);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Err(error) => Err(ApiError::store_error("delete sensor", &error)),
+    }
+}
+
+/// Readings whose acceleration crossed one of the sensor's acceleration
+/// thresholds, defaulting to the last 24 hours
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if MAC address format or dates are invalid
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn get_sensor_vibration_alerts(
+    State(state): State<AppState>,
+    Path(sensor_mac): Path<String>,
+    StrictQuery(params): StrictQuery<TimeRangeQuery>,
+) -> ApiResult<Json<Vec<VibrationAlert>>> {
+    if !is_valid_mac_format(&sensor_mac) {
+        return Err(ApiError::invalid_mac(&sensor_mac));
+    }
+    let sensor_mac = normalize_mac(&sensor_mac);
+
+    let (start, end) = parse_time_range(
+        params.start.as_ref(),
+        params.end.as_ref(),
+        Duration::hours(24),
+    )?;
+
+    match state
+        .store
+        .get_vibration_alerts(&sensor_mac, start, end)
+        .await
+    {
+        Ok(alerts) => Ok(Json(alerts)),
+        Err(error) => Err(ApiError::store_error("get vibration alerts", &error)),
+    }
+}
+
+/// Stream newly stored readings to a WebSocket client as JSON text messages,
+/// optionally only those of `?sensor_mac=`.
+///
+/// Readings stored by the MQTT reader reach the feed through the database's
+/// insert notifications; without the notify trigger only readings ingested
+/// through this API are streamed. A client too slow to keep up skips the
+/// readings it missed instead of being disconnected. Timestamps are sent in
+/// the request timezone.
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if the MAC address format is invalid
+pub async fn live_sensor_updates(
+    State(state): State<AppState>,
+    RequestTimezone(timezone): RequestTimezone,
+    StrictQuery(params): StrictQuery<LiveQuery>,
+    upgrade: WebSocketUpgrade,
+) -> ApiResult<Response> {
+    if let Some(sensor_mac) = &params.sensor_mac {
+        if !is_valid_mac_format(sensor_mac) {
+            return Err(ApiError::invalid_mac(sensor_mac));
+        }
+    }
+    let sensor_mac = params.sensor_mac.as_deref().map(normalize_mac);
+
+    // Subscribe before the handshake so nothing stored meanwhile is missed
+    let events = state.readings.subscribe_to_events();
+    Ok(upgrade.on_upgrade(move |socket| forward_live_events(socket, events, sensor_mac, timezone)))
+}
+
+/// Forward broadcast events to `socket` until either side goes away
+async fn forward_live_events(
+    mut socket: WebSocket,
+    mut events: broadcast::Receiver<Event>,
+    sensor_mac: Option<String>,
+    timezone: Tz,
+) {
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => {
+                    if sensor_mac
+                        .as_ref()
+                        .is_some_and(|mac| !mac.eq_ignore_ascii_case(&event.sensor_mac))
+                    {
+                        continue;
+                    }
+                    let Ok(mut value) = serde_json::to_value(&event) else {
+                        continue;
+                    };
+                    localize_value(&mut value, timezone);
+                    let json = value.to_string();
+                    if socket.send(Message::Text(json.into())).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Live update client lagged behind, skipped {skipped} readings");
+                }
+                Err(RecvError::Closed) => break,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+/// Get historical data for a sensor, newest first unless `?order=asc` asks
+/// for oldest first. When the range holds more than `limit` readings, the
+/// ones first in that order are kept.
+///
+/// A full newest-first page carries the cursor of the following page in the
+/// `X-Next-Cursor` header, and any non-empty newest-first page the cursor of
+/// the readings on its other side in `X-Prev-Cursor`. Pages read without a
+/// cursor or with `before` continue back in time and pages read with `after`
+/// forward. Cursors record their direction, so each must be passed back as
+/// `before` or `after` accordingly. Ascending pages cannot be continued with
+/// a cursor and carry none.
+///
+/// With `max_points` the whole range is instead averaged into about that many
+/// readings, clamped to `MIN_HISTORY_POINTS..=MAX_HISTORY_POINTS`, for charts.
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if MAC address format is invalid, limit is
+/// invalid, a cursor is malformed, both `before` and `after` are given or
+/// either is combined with `max_points` or `order=asc`, `order` is not `asc`
+/// or `desc`, or date formats are invalid
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+#[allow(clippy::too_many_lines)]
+pub async fn get_sensor_history(
+    State(state): State<AppState>,
+    RequestTimezone(timezone): RequestTimezone,
+    Path(sensor_mac): Path<String>,
+    StrictQuery(params): StrictQuery<HistoricalQuery>,
+) -> ApiResult<Response> {
+    // Validate MAC format
+    if !is_valid_mac_format(&sensor_mac) {
+        return Err(ApiError::invalid_mac(&sensor_mac));
+    }
+    let sensor_mac = normalize_mac(&sensor_mac);
+
+    // Validate limit if provided
+    if let Some(limit) = params.limit {
+        if !validate_limit(limit) {
+            return Err(ApiError::invalid_limit(limit));
+        }
+    }
+
+    let cursor = parse_page_cursor(params.before.as_deref(), params.after.as_deref())?;
+    let order = parse_history_order(params.order.as_deref(), cursor)?;
+
+    let (start, end) = parse_range_params(
+        params.preset.as_deref(),
+        params.start.as_ref(),
+        params.end.as_ref(),
+        Duration::hours(1),
+        timezone,
+    )?;
+
+    let units = parse_units(params.units.as_deref());
+    if let Some(max_points) = params.max_points {
+        if cursor.is_some() {
+            return Err(ApiError::bad_request(
+                "max_points cannot be combined with before or after",
+            ));
+        }
+        return downsampled_history(&state, &sensor_mac, (start, end), max_points, units).await;
+    }
+
+    let limit = params.limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    // Paging and ordering are PostgreSQL-only; a plain newest-first page is
+    // served by whichever backend holds the readings
+    let readings = if cursor.is_none() && order == Order::Desc {
+        state
+            .readings
+            .get_historical_data(&sensor_mac, Some(start), Some(end), Some(limit))
+            .await
+    } else {
+        state
+            .store
+            .get_historical_data(
+                &sensor_mac,
+                Some(start),
+                Some(end),
+                Some(limit),
+                cursor,
+                order,
+            )
+            .await
+    }
+    .map_err(|error| ApiError::store_error("get historical data", &error))?;
+
+    tracing::debug!(
+        "Retrieved {} historical readings for sensor: {}",
+        readings.len(),
+        sanitize_mac_for_logging(&sensor_mac)
+    );
+
+    // Only a full page can have more readings beyond it, and only
+    // newest-first pages can be continued since `order=asc` takes no cursor
+    let next_cursor = if order == Order::Desc && i64::try_from(readings.len()) == Ok(limit) {
+        match cursor {
+            Some(PageCursor::After(_)) => readings
+                .first()
+                .map(|reading| PageCursor::After(HistoryCursor::of(reading))),
+            _ => readings
+                .last()
+                .map(|reading| PageCursor::Before(HistoryCursor::of(reading))),
+        }
+        .map(|cursor| cursor.encode())
+    } else {
+        None
+    };
+    let prev_cursor = if order == Order::Desc {
+        match cursor {
+            Some(PageCursor::After(_)) => readings
+                .last()
+                .map(|reading| PageCursor::Before(HistoryCursor::of(reading))),
+            _ => readings
+                .first()
+                .map(|reading| PageCursor::After(HistoryCursor::of(reading))),
+        }
+        .map(|cursor| cursor.encode())
+    } else {
+        None
+    };
+
+    let mut response = Json(
+        readings
+            .into_iter()
+            .map(|reading| DerivedReading::from(reading).in_units(units))
+            .collect::<Vec<_>>(),
+    )
+    .into_response();
+    for (header, cursor) in [
+        (NEXT_CURSOR_HEADER, next_cursor),
+        (PREV_CURSOR_HEADER, prev_cursor),
+    ] {
+        if let Some(value) = cursor.and_then(|cursor| HeaderValue::from_str(&cursor).ok()) {
+            response.headers_mut().insert(header, value);
+        }
+    }
+    Ok(response)
+}
+
+/// History of a sensor averaged into about `max_points` readings
+#[allow(clippy::too_many_arguments)]
+async fn downsampled_history(
+    state: &AppState,
+    sensor_mac: &str,
+    (start, end): TimeRange,
+    max_points: i64,
+    units: UnitSystem,
+) -> ApiResult<Response> {
+    let readings = state
+        .store
+        .get_historical_downsampled(sensor_mac, start, end, clamp_max_points(max_points))
+        .await
+        .map_err(|error| ApiError::store_error("get downsampled history", &error))?;
+
+    Ok(Json(
+        readings
+            .into_iter()
+            .map(|reading| DerivedReading::from(reading).in_units(units))
+            .collect::<Vec<_>>(),
+    )
+    .into_response())
+}
+
+/// Download the historical data of a sensor as CSV, oldest first.
+///
+/// Takes the same range as [`get_sensor_history`] but returns every reading
+/// in it, streamed row by row so that long ranges are never held in memory.
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if MAC address format or dates are
+/// invalid
+pub async fn get_sensor_history_csv(
+    State(state): State<AppState>,
+    RequestTimezone(timezone): RequestTimezone,
+    Path(sensor_mac): Path<String>,
+    StrictQuery(params): StrictQuery<HistoryCsvQuery>,
+) -> ApiResult<Response> {
+    if !is_valid_mac_format(&sensor_mac) {
+        return Err(ApiError::invalid_mac(&sensor_mac));
+    }
+    let sensor_mac = normalize_mac(&sensor_mac);
+
+    let (start, end) = parse_range_params(
+        params.preset.as_deref(),
+        params.start.as_ref(),
+        params.end.as_ref(),
+        Duration::hours(1),
+        timezone,
+    )?;
+
+    tracing::debug!(
+        "Exporting history as CSV for sensor: {}",
+        sanitize_mac_for_logging(&sensor_mac)
+    );
+
+    let events = state.store.stream_readings(&sensor_mac, start, end);
+    let disposition = format!(
+        "attachment; filename=\"{}-history.csv\"",
+        sensor_mac.replace(':', "")
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, CSV_CONTENT_TYPE.to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(csv_stream(events, timezone)),
+    )
+        .into_response())
+}
+
+/// Parse the `before`/`after` history cursors, at most one of which may be
+/// given
+fn parse_page_cursor(before: Option<&str>, after: Option<&str>) -> ApiResult<Option<PageCursor>> {
+    // Cursors record the direction they page in and are only accepted for it
+    let decode = |parameter: &str, encoded: &str| {
+        PageCursor::decode(encoded)
+            .filter(|cursor| {
+                matches!(
+                    (parameter, cursor),
+                    ("before", PageCursor::Before(_)) | ("after", PageCursor::After(_))
+                )
+            })
+            .ok_or_else(|| ApiError::InvalidParameter {
+                parameter: parameter.to_string(),
+                value: encoded.to_string(),
+                expected: format!("a {parameter} cursor from the {NEXT_CURSOR_HEADER} header"),
+            })
+    };
+
+    match (before, after) {
+        (Some(_), Some(after)) => Err(ApiError::InvalidParameter {
+            parameter: "after".to_string(),
+            value: after.to_string(),
+            expected: "either before or after, not both".to_string(),
+        }),
+        (Some(before), None) => decode("before", before).map(Some),
+        (None, Some(after)) => decode("after", after).map(Some),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Parse the optional `order` of a history page, newest first by default.
+///
+/// Cursors page through history newest first, so they only combine with
+/// that order.
+fn parse_history_order(order: Option<&str>, cursor: Option<PageCursor>) -> ApiResult<Order> {
+    let parsed = match order {
+        Some(order) => parse_order(order).ok_or_else(|| ApiError::InvalidParameter {
+            parameter: "order".to_string(),
+            value: order.to_string(),
+            expected: "one of: asc, desc".to_string(),
+        })?,
+        None => Order::default(),
+    };
+    if parsed == Order::Asc && cursor.is_some() {
+        return Err(ApiError::bad_request(
+            "order=asc cannot be combined with before or after",
+        ));
+    }
+    Ok(parsed)
+}
+
+/// Parse an optional `start`/`end` pair, defaulting to the `default_span`
+/// leading up to now, and ensure the range is not empty or inverted.
+fn parse_time_range(
+    start: Option<&String>,
+    end: Option<&String>,
+    default_span: Duration,
+) -> ApiResult<TimeRange> {
+    let start = match start {
+        Some(date_str) => parse_datetime(date_str).map_err(|_| ApiError::invalid_date(date_str))?,
+        #[allow(clippy::arithmetic_side_effects)]
+        None => Utc::now() - default_span,
+    };
+
+    let end = match end {
+        Some(date_str) => parse_datetime(date_str).map_err(|_| ApiError::invalid_date(date_str))?,
+        None => Utc::now(),
+    };
+
+    if start >= end {
+        return Err(ApiError::invalid_date_range(
+            "Start date must be before end date",
+        ));
+    }
+
+    Ok((start, end))
+}
+
+/// Resolve the range of an endpoint that takes either a named `preset`,
+/// with day boundaries in `timezone`, or an explicit `start`/`end` pair
+/// defaulting to the `default_span` leading up to now.
+#[allow(clippy::too_many_arguments)]
+fn parse_range_params(
+    preset: Option<&str>,
+    start: Option<&String>,
+    end: Option<&String>,
+    default_span: Duration,
+    timezone: Tz,
+) -> ApiResult<TimeRange> {
+    let Some(preset) = preset else {
+        return parse_time_range(start, end, default_span);
+    };
+
+    if start.is_some() || end.is_some() {
+        return Err(ApiError::bad_request(
+            "preset cannot be combined with start or end",
+        ));
+    }
+    let preset = parse_range_preset(preset).ok_or_else(|| ApiError::InvalidParameter {
+        parameter: "preset".to_string(),
+        value: preset.to_string(),
+        expected: format!("one of: {RANGE_PRESETS}"),
+    })?;
+
+    preset_range(preset, Utc::now(), timezone)
+        .ok_or_else(|| ApiError::internal_error("Failed to compute preset range"))
+}
+
+/// Parse an optional bucket interval parameter, defaulting to one hour
+fn parse_interval_param(parameter: &str, interval: Option<&str>) -> ApiResult<TimeInterval> {
+    match interval {
+        Some(interval_str) => {
+            parse_interval(interval_str).ok_or_else(|| ApiError::InvalidParameter {
+                parameter: parameter.to_string(),
+                value: interval_str.to_string(),
+                expected: "one of: 1m, 5m, 15m, 30m, 1h, 6h, 12h, 1d".to_string(),
+            })
+        }
+        None => Ok(TimeInterval::Hours(1)),
+    }
+}
+
+/// Parse a required sensor MAC parameter
+fn parse_mac_param(parameter: &str, mac: Option<&String>) -> ApiResult<String> {
+    let mac = mac.ok_or_else(|| ApiError::missing_parameter(parameter))?;
+    if !is_valid_mac_format(mac) {
+        return Err(ApiError::invalid_mac(mac));
+    }
+    Ok(normalize_mac(mac))
+}
+
+/// Parse the `macs` list of a multi-sensor endpoint, enforcing the configured
+/// maximum so a single request cannot fan out over an unbounded sensor set
+///
+/// # Errors
+/// Returns `ApiError` if the list is missing or empty, longer than
+/// `max_bulk_sensors`, or contains an invalid MAC address
+pub fn parse_bulk_macs(macs: Option<&str>, max_bulk_sensors: usize) -> ApiResult<Vec<String>> {
+    let macs = parse_mac_list(macs.ok_or_else(|| ApiError::missing_parameter("macs"))?);
+    if macs.is_empty() {
+        return Err(ApiError::missing_parameter("macs"));
+    }
+    if macs.len() > max_bulk_sensors {
+        return Err(ApiError::InvalidParameter {
+            parameter: "macs".to_string(),
+            value: format!("{} sensors", macs.len()),
+            expected: format!("at most {max_bulk_sensors} sensors"),
+        });
+    }
+    if let Some(mac) = macs.iter().find(|mac| !is_valid_mac_format(mac)) {
+        return Err(ApiError::invalid_mac(mac));
+    }
+    let mut normalized: Vec<String> = Vec::with_capacity(macs.len());
+    for mac in macs.iter().map(|mac| normalize_mac(mac)) {
+        if !normalized.contains(&mac) {
+            normalized.push(mac);
+        }
+    }
+    Ok(normalized)
+}
+
+/// Parse the optional `metric` parameter, defaulting to temperature
+fn parse_metric_param(metric: Option<&str>) -> ApiResult<Metric> {
+    match metric {
+        Some(metric_str) => parse_metric(metric_str).ok_or_else(|| ApiError::InvalidParameter {
+            parameter: "metric".to_string(),
+            value: metric_str.to_string(),
+            expected: "one of: temperature, humidity, pressure".to_string(),
+        }),
+        None => Ok(Metric::Temperature),
+    }
+}
+
+/// Parse the comma-separated `metrics` list, defaulting to every metric
+fn parse_metrics_param(metrics: Option<&str>) -> ApiResult<Vec<Metric>> {
+    let Some(metrics_str) = metrics else {
+        return Ok(Metric::ALL.to_vec());
+    };
+
+    let mut parsed = Vec::new();
+    for name in metrics_str.split(',').map(str::trim) {
+        if name.is_empty() {
+            continue;
+        }
+        let metric = parse_metric(name).ok_or_else(|| ApiError::InvalidParameter {
+            parameter: "metrics".to_string(),
+            value: name.to_string(),
+            expected: "comma-separated list of: temperature, humidity, pressure".to_string(),
+        })?;
+        if !parsed.contains(&metric) {
+            parsed.push(metric);
+        }
+    }
+
+    if parsed.is_empty() {
+        return Err(ApiError::missing_parameter("metrics"));
+    }
+    Ok(parsed)
+}
+
+/// Longest look-back accepted by the trends endpoint
+const MAX_TREND_HOURS: i32 = 24 * 366;
+
+/// Longest look-back accepted by the statistics and health endpoints
+const MAX_SUMMARY_HOURS: i32 = 24 * 365;
+
+/// Parse the optional `hours` look-back, defaulting to one day
+fn parse_hours_param(hours: Option<i32>, max_hours: i32) -> ApiResult<i32> {
+    match hours {
+        Some(hours) if !(1..=max_hours).contains(&hours) => Err(ApiError::InvalidParameter {
+            parameter: "hours".to_string(),
+            value: hours.to_string(),
+            expected: format!("between 1 and {max_hours}"),
+        }),
+        Some(hours) => Ok(hours),
+        None => Ok(24),
+    }
+}
+
+/// Get temperature, humidity and pressure statistics of a sensor over the
+/// last `hours`
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if MAC address format or hours are invalid
+/// Returns `StatusCode::NOT_FOUND` if the sensor has no readings in that time
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn get_sensor_statistics(
+    State(state): State<AppState>,
+    Path(sensor_mac): Path<String>,
+    StrictQuery(params): StrictQuery<HoursQuery>,
+) -> ApiResult<Json<SensorStats>> {
+    if !is_valid_mac_format(&sensor_mac) {
+        return Err(ApiError::invalid_mac(&sensor_mac));
+    }
+    let sensor_mac = normalize_mac(&sensor_mac);
+
+    let hours = parse_hours_param(params.hours, MAX_SUMMARY_HOURS)?;
+
+    match state.store.get_sensor_statistics(&sensor_mac, hours).await {
+        Ok(statistics) if statistics.reading_count == 0 => {
+            Err(ApiError::readings_not_found(&sensor_mac))
+        }
+        Ok(statistics) => Ok(Json(statistics)),
+        Err(error) => Err(ApiError::store_error("get sensor statistics", &error)),
+    }
+}
+
+/// Get sensor count, average signal strength and reading rate of a gateway
+/// over the last `hours`
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if MAC address format or hours are invalid
+/// Returns `StatusCode::NOT_FOUND` if the gateway forwarded no readings in that
+/// time Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn get_gateway_statistics(
+    State(state): State<AppState>,
+    Path(gateway_mac): Path<String>,
+    StrictQuery(params): StrictQuery<HoursQuery>,
+) -> ApiResult<Json<GatewayStats>> {
+    if !is_valid_mac_format(&gateway_mac) {
+        return Err(ApiError::invalid_mac(&gateway_mac));
+    }
+    let gateway_mac = normalize_mac(&gateway_mac);
+
+    let hours = parse_hours_param(params.hours, MAX_SUMMARY_HOURS)?;
+
+    match state
+        .store
+        .get_gateway_statistics(&gateway_mac, hours)
+        .await
+    {
+        Ok(statistics) if statistics.reading_count == 0 => {
+            Err(ApiError::gateway_readings_not_found(&gateway_mac))
+        }
+        Ok(statistics) => Ok(Json(statistics)),
+        Err(error) => Err(ApiError::store_error("get gateway statistics", &error)),
+    }
+}
+
+/// Get battery, signal and last-seen health metrics of a sensor over the last
+/// `hours`.
+///
+/// A sensor without readings in that time is reported with zero readings and
+/// no last reading rather than as missing.
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if MAC address format or hours are invalid
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn get_sensor_health(
+    State(state): State<AppState>,
+    Path(sensor_mac): Path<String>,
+    StrictQuery(params): StrictQuery<HoursQuery>,
+) -> ApiResult<Json<SensorHealthMetrics>> {
+    if !is_valid_mac_format(&sensor_mac) {
+        return Err(ApiError::invalid_mac(&sensor_mac));
+    }
+    let sensor_mac = normalize_mac(&sensor_mac);
+
+    let hours = parse_hours_param(params.hours, MAX_SUMMARY_HOURS)?;
+
+    match state
+        .store
+        .get_sensor_health_metrics(&sensor_mac, hours)
+        .await
+    {
+        Ok(health) => Ok(Json(health)),
+        Err(error) => Err(ApiError::store_error("get sensor health metrics", &error)),
+    }
+}
+
+/// Get bucketed averages of several metrics for a sensor in one response
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if MAC address format is invalid, or if
+/// the metrics, hours or bucket are invalid
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn get_sensor_trends(
+    State(state): State<AppState>,
+    Path(sensor_mac): Path<String>,
+    StrictQuery(params): StrictQuery<TrendsQuery>,
+) -> ApiResult<Json<MetricTrends>> {
+    if !is_valid_mac_format(&sensor_mac) {
+        return Err(ApiError::invalid_mac(&sensor_mac));
+    }
+    let sensor_mac = normalize_mac(&sensor_mac);
+
+    let metrics = parse_metrics_param(params.metrics.as_deref())?;
+    let hours = parse_hours_param(params.hours, MAX_TREND_HOURS)?;
+    let interval = parse_interval_param("bucket", params.bucket.as_deref())?;
+    let (start, end) = parse_time_range(None, None, Duration::hours(i64::from(hours)))?;
+
+    match state
+        .store
+        .get_metric_trends(&sensor_mac, &metrics, &interval, start, end)
+        .await
+    {
+        Ok(trends) => Ok(Json(trends)),
+        Err(error) => Err(ApiError::store_error("get metric trends", &error)),
+    }
+}
+
+/// Average temperature of one trend bucket
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TemperatureTrendPoint {
+    pub timestamp: DateTime<Utc>,
+    pub avg_temp: f64,
+}
+
+/// Bucket width of the temperature trend unless `interval` overrides it
+const DEFAULT_TEMPERATURE_TREND_INTERVAL: TimeInterval = TimeInterval::Minutes(15);
+
+/// Get a sensor's average temperature per bucket over the last `hours`,
+/// oldest first
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if MAC address format, hours or interval
+/// are invalid
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn get_sensor_temperature_trend(
+    State(state): State<AppState>,
+    Path(sensor_mac): Path<String>,
+    StrictQuery(params): StrictQuery<TemperatureTrendQuery>,
+) -> ApiResult<Json<Vec<TemperatureTrendPoint>>> {
+    if !is_valid_mac_format(&sensor_mac) {
+        return Err(ApiError::invalid_mac(&sensor_mac));
+    }
+    let sensor_mac = normalize_mac(&sensor_mac);
+
+    let hours = parse_hours_param(params.hours, MAX_TREND_HOURS)?;
+    let interval = match params.interval.as_deref() {
+        Some(interval) => parse_interval_param("interval", Some(interval))?,
+        None => DEFAULT_TEMPERATURE_TREND_INTERVAL,
+    };
+
+    match state
+        .store
+        .get_temperature_trend(&sensor_mac, hours, &interval)
+        .await
+    {
+        Ok(trend) => Ok(Json(
+            trend
+                .into_iter()
+                .map(|(timestamp, avg_temp)| TemperatureTrendPoint {
+                    timestamp,
+                    avg_temp,
+                })
+                .collect(),
+        )),
+        Err(error) => Err(ApiError::store_error("get temperature trend", &error)),
+    }
+}
+
+/// Longest battery projection look-back accepted, in days
+const MAX_BATTERY_LOOKBACK_DAYS: i32 = 366;
+
+/// Parse the optional `days` look-back, defaulting to 30 days
+fn parse_lookback_days_param(days: Option<i32>, max_days: i32) -> ApiResult<i32> {
+    match days {
+        Some(days) if !(1..=max_days).contains(&days) => Err(ApiError::InvalidParameter {
+            parameter: "days".to_string(),
+            value: days.to_string(),
+            expected: format!("between 1 and {max_days}"),
+        }),
+        Some(days) => Ok(days),
+        None => Ok(30),
+    }
+}
+
+/// Estimate when a sensor's battery runs empty from its recent voltage trend
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if MAC address format or days are invalid
+/// Returns `StatusCode::NOT_FOUND` if the sensor reported no battery voltage
+/// in the look-back window
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn get_sensor_battery_projection(
+    State(state): State<AppState>,
+    Path(sensor_mac): Path<String>,
+    StrictQuery(params): StrictQuery<BatteryProjectionQuery>,
+) -> ApiResult<Json<BatteryProjection>> {
+    if !is_valid_mac_format(&sensor_mac) {
+        return Err(ApiError::invalid_mac(&sensor_mac));
+    }
+    let sensor_mac = normalize_mac(&sensor_mac);
+
+    let days = parse_lookback_days_param(params.days, MAX_BATTERY_LOOKBACK_DAYS)?;
+
+    match state.store.project_battery_life(&sensor_mac, days).await {
+        Ok(projection) if projection.sample_count == 0 => {
+            Err(ApiError::readings_not_found(&sensor_mac))
+        }
+        Ok(projection) => Ok(Json(projection)),
+        Err(error) => Err(ApiError::store_error("project battery life", &error)),
+    }
+}
+
+/// Body of a sensor group creation request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewSensorGroup {
+    pub name: String,
+    #[serde(default)]
+    pub sensor_macs: Vec<String>,
+}
+
+/// Group name and sensor MAC of a group member route
+type GroupMemberPath = (String, String);
+
+/// Reject names that are not valid group names
+fn check_group_name(name: &str) -> ApiResult<()> {
+    if is_valid_group_name(name) {
+        Ok(())
+    } else {
+        Err(ApiError::invalid_group_name(name))
+    }
+}
+
+/// Look up a group, mapping a missing one to `NOT_FOUND`
+async fn find_sensor_group(state: &AppState, name: &str) -> ApiResult<SensorGroup> {
+    check_group_name(name)?;
+    match state.store.get_sensor_group(name).await {
+        Ok(Some(group)) => Ok(group),
+        Ok(None) => Err(ApiError::group_not_found(name)),
+        Err(error) => Err(ApiError::store_error("get sensor group", &error)),
+    }
+}
+
+/// Members are capped like the `macs` list of multi-sensor endpoints, as
+/// group reads fan out over every member
+fn check_group_size(members: usize, max_bulk_sensors: usize) -> ApiResult<()> {
+    if members > max_bulk_sensors {
+        return Err(ApiError::InvalidParameter {
+            parameter: "sensor_macs".to_string(),
+            value: format!("{members} sensors"),
+            expected: format!("at most {max_bulk_sensors} sensors"),
+        });
+    }
+    Ok(())
+}
+
+/// List every sensor group with its members
+///
+/// # Errors
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn list_sensor_groups(
+    State(state): State<AppState>,
+) -> ApiResult<Json<Vec<SensorGroup>>> {
+    match state.store.list_sensor_groups().await {
+        Ok(groups) => Ok(Json(groups)),
+        Err(error) => Err(ApiError::store_error("list sensor groups", &error)),
+    }
+}
+
+/// Create a sensor group, optionally with its initial members
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if the name or a MAC address is invalid,
+/// or there are more members than `max_bulk_sensors`
+/// Returns `StatusCode::CONFLICT` if a group of that name exists
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database insert fails
+pub async fn create_sensor_group(
+    State(state): State<AppState>,
+    Json(group): Json<NewSensorGroup>,
+) -> ApiResult<(StatusCode, Json<SensorGroup>)> {
+    check_group_name(&group.name)?;
+    let mut sensor_macs = Vec::with_capacity(group.sensor_macs.len());
+    for mac in group.sensor_macs {
+        if !is_valid_mac_format(&mac) {
+            return Err(ApiError::invalid_mac(&mac));
+        }
+        let mac = normalize_mac(&mac);
+        if !sensor_macs.contains(&mac) {
+            sensor_macs.push(mac);
+        }
+    }
+    check_group_size(sensor_macs.len(), state.max_bulk_sensors)?;
+
+    match state
+        .store
+        .create_sensor_group(&group.name, &sensor_macs)
+        .await
+    {
+        Ok(Some(created)) => Ok((StatusCode::CREATED, Json(created))),
+        Ok(None) => Err(ApiError::Conflict {
+            message: format!("Sensor group {} already exists", group.name),
+        }),
+        Err(error) => Err(ApiError::store_error("create sensor group", &error)),
+    }
+}
+
+/// Get a sensor group with its members
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if the group name is invalid
+/// Returns `StatusCode::NOT_FOUND` if the group does not exist
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn get_sensor_group(
+    State(state): State<AppState>,
+    Path(group): Path<String>,
+) -> ApiResult<Json<SensorGroup>> {
+    find_sensor_group(&state, &group).await.map(Json)
+}
+
+/// Delete a sensor group; the readings of its members are kept
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if the group name is invalid
+/// Returns `StatusCode::NOT_FOUND` if the group does not exist
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database delete fails
+pub async fn delete_sensor_group(
+    State(state): State<AppState>,
+    Path(group): Path<String>,
+) -> ApiResult<StatusCode> {
+    check_group_name(&group)?;
+    match state.store.delete_sensor_group(&group).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(ApiError::group_not_found(&group)),
+        Err(error) => Err(ApiError::store_error("delete sensor group", &error)),
+    }
+}
+
+/// Add a sensor to a group; adding an existing member is a no-op
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if the group name or MAC address is
+/// invalid, or the group is full
+/// Returns `StatusCode::NOT_FOUND` if the group does not exist
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database insert fails
+pub async fn add_group_member(
+    State(state): State<AppState>,
+    Path((group, sensor_mac)): Path<GroupMemberPath>,
+) -> ApiResult<Json<SensorGroup>> {
+    if !is_valid_mac_format(&sensor_mac) {
+        return Err(ApiError::invalid_mac(&sensor_mac));
+    }
+    let sensor_mac = normalize_mac(&sensor_mac);
+    let existing = find_sensor_group(&state, &group).await?;
+    if !existing.sensor_macs.contains(&sensor_mac) {
+        check_group_size(
+            existing.sensor_macs.len().saturating_add(1),
+            state.max_bulk_sensors,
+        )?;
+    }
+
+    match state.store.add_group_member(&group, &sensor_mac).await {
+        Ok(Some(updated)) => Ok(Json(updated)),
+        Ok(None) => Err(ApiError::group_not_found(&group)),
+        Err(error) => Err(ApiError::store_error("add group member", &error)),
+    }
+}
+
+/// Remove a sensor from a group
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if the group name or MAC address is
+/// invalid
+/// Returns `StatusCode::NOT_FOUND` if the group does not exist
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database delete fails
+pub async fn remove_group_member(
+    State(state): State<AppState>,
+    Path((group, sensor_mac)): Path<GroupMemberPath>,
+) -> ApiResult<Json<SensorGroup>> {
+    check_group_name(&group)?;
+    if !is_valid_mac_format(&sensor_mac) {
+        return Err(ApiError::invalid_mac(&sensor_mac));
+    }
+    let sensor_mac = normalize_mac(&sensor_mac);
+
+    match state.store.remove_group_member(&group, &sensor_mac).await {
+        Ok(Some(updated)) => Ok(Json(updated)),
+        Ok(None) => Err(ApiError::group_not_found(&group)),
+        Err(error) => Err(ApiError::store_error("remove group member", &error)),
+    }
+}
+
+/// Latest reading of every member of a group that has reported
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if the group name is invalid
+/// Returns `StatusCode::NOT_FOUND` if the group does not exist
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn get_group_latest(
+    State(state): State<AppState>,
+    Path(group): Path<String>,
+    StrictQuery(params): StrictQuery<UnitsQuery>,
+) -> ApiResult<Json<Vec<DerivedReading>>> {
+    let group = find_sensor_group(&state, &group).await?;
+    let units = parse_units(params.units.as_deref());
+
+    match state.readings.get_latest_for(&group.sensor_macs).await {
+        Ok(readings) => Ok(Json(
+            readings
+                .into_iter()
+                .map(|reading| DerivedReading::from(reading).in_units(units))
+                .collect(),
+        )),
+        Err(error) => Err(ApiError::store_error("get group latest readings", &error)),
+    }
+}
+
+/// Aggregated data over the readings of all members of a group combined
+///
+/// Accepts the same parameters as [`get_sensor_aggregates`].
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if the group name, dates or interval are
+/// invalid
+/// Returns `StatusCode::NOT_FOUND` if the group does not exist
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn get_group_aggregates(
+    State(state): State<AppState>,
+    RequestTimezone(timezone): RequestTimezone,
+    Path(group): Path<String>,
+    StrictQuery(params): StrictQuery<TimeBucketQuery>,
+) -> ApiResult<Json<Vec<TimeBucketedData>>> {
+    let group = find_sensor_group(&state, &group).await?;
+    let (start, end) = parse_range_params(
+        params.preset.as_deref(),
+        params.start.as_ref(),
+        params.end.as_ref(),
+        Duration::hours(24),
+        timezone,
+    )?;
+    let interval = parse_interval_param("interval", params.interval.as_deref())?;
+
+    match state
+        .store
+        .get_combined_bucketed_data(&group.sensor_macs, &interval, start, end, timezone.name())
+        .await
+    {
+        Ok(data) => Ok(Json(buckets_in_units(data, params.units.as_deref()))),
+        Err(error) => Err(ApiError::store_error("get group aggregates", &error)),
+    }
+}
+
+/// Report aggregate buckets in the `units` a request asked for
+fn buckets_in_units(data: Vec<TimeBucketedData>, units: Option<&str>) -> Vec<TimeBucketedData> {
+    let units = parse_units(units);
+    data.into_iter()
+        .map(|bucket| bucket_in_units(bucket, units))
+        .collect()
+}
+
+/// Get aggregated data for a sensor
+///
+/// Buckets are aligned in the request's timezone, so daily buckets start at
+/// local midnight.
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if MAC address format is invalid, date
+/// formats are invalid, or interval is invalid
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn get_sensor_aggregates(
+    State(state): State<AppState>,
+    RequestTimezone(timezone): RequestTimezone,
+    Path(sensor_mac): Path<String>,
+    StrictQuery(params): StrictQuery<TimeBucketQuery>,
+) -> ApiResult<Json<Vec<TimeBucketedData>>> {
+    // Validate MAC format
+    if !is_valid_mac_format(&sensor_mac) {
+        return Err(ApiError::invalid_mac(&sensor_mac));
+    }
+    let sensor_mac = normalize_mac(&sensor_mac);
+
+    let (start, end) = parse_range_params(
+        params.preset.as_deref(),
+        params.start.as_ref(),
+        params.end.as_ref(),
+        Duration::hours(24),
+        timezone,
+    )?;
+    let interval = parse_interval_param("interval", params.interval.as_deref())?;
+
+    match state
+        .store
+        .get_time_bucketed_data(&sensor_mac, &interval, start, end, timezone.name())
+        .await
+    {
+        Ok(data) => {
+            tracing::debug!(
+                "Retrieved {} aggregated data points for sensor: {}",
+                data.len(),
+                sanitize_mac_for_logging(&sensor_mac)
+            );
+            Ok(Json(buckets_in_units(data, params.units.as_deref())))
+        }
+        Err(error) => Err(ApiError::store_error("get aggregated data", &error)),
+    }
+}
+
+/// Stream aggregated data for a sensor as newline-delimited JSON
+///
+/// Accepts the same parameters as [`get_sensor_aggregates`] but emits one
+/// bucket per line as soon as it is read from the database, which lets
+/// clients render long ranges progressively.
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if MAC address format is invalid, date
+/// formats are invalid, or interval is invalid
+pub async fn stream_sensor_aggregates(
+    State(state): State<AppState>,
+    RequestTimezone(timezone): RequestTimezone,
+    Path(sensor_mac): Path<String>,
+    StrictQuery(params): StrictQuery<TimeBucketQuery>,
+) -> ApiResult<Response> {
+    // Validate MAC format
+    if !is_valid_mac_format(&sensor_mac) {
+        return Err(ApiError::invalid_mac(&sensor_mac));
+    }
+    let sensor_mac = normalize_mac(&sensor_mac);
+
+    let (start, end) = parse_range_params(
+        params.preset.as_deref(),
+        params.start.as_ref(),
+        params.end.as_ref(),
+        Duration::hours(24),
+        timezone,
+    )?;
+    let interval = parse_interval_param("interval", params.interval.as_deref())?;
+
+    let units = parse_units(params.units.as_deref());
+
+    tracing::debug!(
+        "Streaming aggregated data for sensor: {}",
+        sanitize_mac_for_logging(&sensor_mac)
+    );
+
+    let lines = state
+        .store
+        .stream_time_bucketed_data(&sensor_mac, &interval, start, end, timezone.name())
+        .map(move |bucket| {
+            let mut line = serde_json::to_vec(&bucket_in_units(bucket?, units))?;
+            line.push(b'\n');
+            Ok::<_, anyhow::Error>(line)
+        });
+
+    Ok((
+        [(header::CONTENT_TYPE, NDJSON_CONTENT_TYPE)],
+        Body::from_stream(lines),
+    )
+        .into_response())
+}
+
+/// Parse an optional `start`/`end` pair of an export, which defaults to
+/// everything recorded up to now
+fn parse_export_range(start: Option<&String>, end: Option<&String>) -> ApiResult<TimeRange> {
+    let parse_date =
+        |date_str: &String| parse_datetime(date_str).map_err(|_| ApiError::invalid_date(date_str));
+    let start = start
+        .map(parse_date)
+        .transpose()?
+        .unwrap_or(DateTime::UNIX_EPOCH);
+    let end = end.map(parse_date).transpose()?.unwrap_or_else(Utc::now);
+
+    if start >= end {
+        return Err(ApiError::invalid_date_range(
+            "Start date must be before end date",
+        ));
+    }
+
+    Ok((start, end))
+}
+
+/// Download a sensor's raw readings as a file, by default its whole history
+///
+/// The file is streamed while it is being encoded, so large exports do not
+/// have to fit in memory. Timestamps are annotated with the request
+/// timezone.
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if MAC address format, format or dates
+/// are invalid
+pub async fn export_sensor_readings(
+    State(state): State<AppState>,
+    RequestTimezone(timezone): RequestTimezone,
+    Path(sensor_mac): Path<String>,
+    StrictQuery(params): StrictQuery<ExportQuery>,
+) -> ApiResult<Response> {
+    if !is_valid_mac_format(&sensor_mac) {
+        return Err(ApiError::invalid_mac(&sensor_mac));
+    }
+    let sensor_mac = normalize_mac(&sensor_mac);
 
-    let end = match params.end.as_ref() {
-        Some(date_str) => {
-            if let Ok(dt) = parse_datetime(date_str) {
-                dt
-            } else {
-                return Err(ApiError::invalid_date(date_str));
-            }
-        }
-        None => Utc::now(),
+    let format = match params.format.as_deref() {
+        Some(format) => ExportFormat::parse(format).ok_or_else(|| ApiError::InvalidParameter {
+            parameter: "format".to_string(),
+            value: format.to_string(),
+            expected: "parquet".to_string(),
+        })?,
+        None => ExportFormat::Parquet,
     };
 
-    // Validate date range
-    if start >= end {
-        return Err(ApiError::invalid_date_range(
-            "Start date must be before end date",
-        ));
-    }
+    let (start, end) = parse_export_range(params.start.as_ref(), params.end.as_ref())?;
 
-    let interval = match params.interval.as_deref() {
-        Some(interval_str) => {
-            if let Some(interval) = parse_interval(interval_str) {
-                interval
-            } else {
-                return Err(ApiError::InvalidParameter {
-                    parameter: "interval".to_string(),
-                    value: interval_str.to_string(),
-                    expected: "one of: 1m, 5m, 15m, 30m, 1h, 6h, 12h, 1d".to_string(),
-                });
-            }
-        }
-        None => postgres_store::TimeInterval::Hours(1),
+    tracing::debug!(
+        "Exporting readings for sensor: {}",
+        sanitize_mac_for_logging(&sensor_mac)
+    );
+
+    let events = state.store.stream_readings(&sensor_mac, start, end);
+    let (content_type, body) = match format {
+        ExportFormat::Parquet => (
+            PARQUET_CONTENT_TYPE,
+            Body::from_stream(parquet_stream(events, timezone)),
+        ),
     };
+    let disposition = format!(
+        "attachment; filename=\"{}.{}\"",
+        sensor_mac.replace(':', ""),
+        format.extension()
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// Get the correlation of one metric between two sensors
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if either MAC address is missing or
+/// invalid, or if the metric, dates or interval are invalid
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn get_sensor_correlation(
+    State(state): State<AppState>,
+    StrictQuery(params): StrictQuery<CorrelationQuery>,
+) -> ApiResult<Json<SensorCorrelation>> {
+    let from_mac = parse_mac_param("from", params.from.as_ref())?;
+    let to_mac = parse_mac_param("to", params.to.as_ref())?;
+    let metric = parse_metric_param(params.metric.as_deref())?;
+    let (start, end) = parse_time_range(
+        params.start.as_ref(),
+        params.end.as_ref(),
+        Duration::hours(24),
+    )?;
+    let interval = parse_interval_param("interval", params.interval.as_deref())?;
 
     match state
         .store
-        .get_time_bucketed_data(&sensor_mac, &interval, start, end)
+        .get_sensor_correlation(&from_mac, &to_mac, metric, start, end, &interval)
         .await
     {
-        Ok(data) => {
-            tracing::debug!(
-                "Retrieved {} aggregated data points for sensor: {}",
-                data.len(),
-                sanitize_mac_for_logging(&sensor_mac)
-            );
-            Ok(Json(data))
-        }
-        Err(error) => Err(ApiError::database_error(
-            "get aggregated data",
-            &error.to_string(),
-        )),
+        Ok(correlation) => Ok(Json(correlation)),
+        Err(error) => Err(ApiError::store_error("get sensor correlation", &error)),
+    }
+}
+
+/// Most sensors one comparison may overlay
+pub const MAX_COMPARE_SENSORS: usize = 10;
+
+/// Aggregated data of several sensors over the same range and interval, by
+/// MAC, for overlaying them on one chart
+///
+/// Accepts the same `start`, `end`, `interval` and `units` as
+/// [`get_sensor_aggregates`].
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if `macs` is missing, lists more than
+/// `MAX_COMPARE_SENSORS` or `max_bulk_sensors` sensors or an invalid MAC
+/// address, or if the dates
+/// or interval are invalid
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn compare_sensors(
+    State(state): State<AppState>,
+    RequestTimezone(timezone): RequestTimezone,
+    StrictQuery(params): StrictQuery<CompareQuery>,
+) -> ApiResult<Json<BTreeMap<String, Vec<TimeBucketedData>>>> {
+    let sensor_macs = parse_bulk_macs(
+        params.macs.as_deref(),
+        MAX_COMPARE_SENSORS.min(state.max_bulk_sensors),
+    )?;
+    let (start, end) = parse_range_params(
+        None,
+        params.start.as_ref(),
+        params.end.as_ref(),
+        Duration::hours(24),
+        timezone,
+    )?;
+    let interval = parse_interval_param("interval", params.interval.as_deref())?;
+
+    let mut series = BTreeMap::new();
+    for sensor_mac in sensor_macs {
+        let data = state
+            .store
+            .get_time_bucketed_data(&sensor_mac, &interval, start, end, timezone.name())
+            .await
+            .map_err(|error| ApiError::store_error("compare sensors", &error))?;
+        series.insert(sensor_mac, buckets_in_units(data, params.units.as_deref()));
     }
+    Ok(Json(series))
 }
 
 /// Get hourly aggregated data for a sensor
@@ -265,47 +2139,27 @@ pub async fn get_sensor_aggregates(
 #[allow(clippy::too_many_lines)]
 pub async fn get_sensor_hourly_aggregates(
     State(state): State<AppState>,
+    RequestTimezone(timezone): RequestTimezone,
     Path(sensor_mac): Path<String>,
-    Query(params): Query<TimeBucketQuery>,
+    StrictQuery(params): StrictQuery<TimeBucketQuery>,
 ) -> ApiResult<Json<Vec<TimeBucketedData>>> {
     // Validate MAC format
     if !is_valid_mac_format(&sensor_mac) {
         return Err(ApiError::invalid_mac(&sensor_mac));
     }
+    let sensor_mac = normalize_mac(&sensor_mac);
 
-    let start = match params.start.as_ref() {
-        Some(date_str) => {
-            if let Ok(dt) = parse_datetime(date_str) {
-                dt
-            } else {
-                return Err(ApiError::invalid_date(date_str));
-            }
-        }
-        #[allow(clippy::arithmetic_side_effects)]
-        None => Utc::now() - Duration::hours(72),
-    };
-
-    let end = match params.end.as_ref() {
-        Some(date_str) => {
-            if let Ok(dt) = parse_datetime(date_str) {
-                dt
-            } else {
-                return Err(ApiError::invalid_date(date_str));
-            }
-        }
-        None => Utc::now(),
-    };
-
-    // Validate date range
-    if start >= end {
-        return Err(ApiError::invalid_date_range(
-            "Start date must be before end date",
-        ));
-    }
+    let (start, end) = parse_range_params(
+        params.preset.as_deref(),
+        params.start.as_ref(),
+        params.end.as_ref(),
+        Duration::hours(72),
+        timezone,
+    )?;
 
     match state
         .store
-        .get_hourly_aggregates(&sensor_mac, start, end)
+        .get_hourly_aggregates(&sensor_mac, start, end, timezone.name())
         .await
     {
         Ok(data) => {
@@ -314,12 +2168,9 @@ pub async fn get_sensor_hourly_aggregates(
                 data.len(),
                 sanitize_mac_for_logging(&sensor_mac)
             );
-            Ok(Json(data))
+            Ok(Json(buckets_in_units(data, params.units.as_deref())))
         }
-        Err(error) => Err(ApiError::database_error(
-            "get hourly aggregated data",
-            &error.to_string(),
-        )),
+        Err(error) => Err(ApiError::store_error("get hourly aggregated data", &error)),
     }
 }
 
@@ -332,47 +2183,27 @@ pub async fn get_sensor_hourly_aggregates(
 #[allow(clippy::too_many_lines)]
 pub async fn get_sensor_daily_aggregates(
     State(state): State<AppState>,
+    RequestTimezone(timezone): RequestTimezone,
     Path(sensor_mac): Path<String>,
-    Query(params): Query<TimeBucketQuery>,
+    StrictQuery(params): StrictQuery<TimeBucketQuery>,
 ) -> ApiResult<Json<Vec<TimeBucketedData>>> {
     // Validate MAC format
     if !is_valid_mac_format(&sensor_mac) {
         return Err(ApiError::invalid_mac(&sensor_mac));
     }
+    let sensor_mac = normalize_mac(&sensor_mac);
 
-    let start = match params.start.as_ref() {
-        Some(date_str) => {
-            if let Ok(dt) = parse_datetime(date_str) {
-                dt
-            } else {
-                return Err(ApiError::invalid_date(date_str));
-            }
-        }
-        #[allow(clippy::arithmetic_side_effects)]
-        None => Utc::now() - Duration::days(30),
-    };
-
-    let end = match params.end.as_ref() {
-        Some(date_str) => {
-            if let Ok(dt) = parse_datetime(date_str) {
-                dt
-            } else {
-                return Err(ApiError::invalid_date(date_str));
-            }
-        }
-        None => Utc::now(),
-    };
-
-    // Validate date range
-    if start >= end {
-        return Err(ApiError::invalid_date_range(
-            "Start date must be before end date",
-        ));
-    }
+    let (start, end) = parse_range_params(
+        params.preset.as_deref(),
+        params.start.as_ref(),
+        params.end.as_ref(),
+        Duration::days(30),
+        timezone,
+    )?;
 
     match state
         .store
-        .get_daily_aggregates(&sensor_mac, start, end)
+        .get_daily_aggregates(&sensor_mac, start, end, timezone.name())
         .await
     {
         Ok(data) => {
@@ -381,12 +2212,9 @@ pub async fn get_sensor_daily_aggregates(
                 data.len(),
                 sanitize_mac_for_logging(&sensor_mac)
             );
-            Ok(Json(data))
+            Ok(Json(buckets_in_units(data, params.units.as_deref())))
         }
-        Err(error) => Err(ApiError::database_error(
-            "get daily aggregated data",
-            &error.to_string(),
-        )),
+        Err(error) => Err(ApiError::store_error("get daily aggregated data", &error)),
     }
 }
 
@@ -400,15 +2228,80 @@ pub async fn get_storage_stats(State(state): State<AppState>) -> ApiResult<Json<
             tracing::debug!("Retrieved storage statistics");
             Ok(Json(storage_stats))
         }
-        Err(error) => Err(ApiError::database_error(
-            "get storage statistics",
-            &error.to_string(),
-        )),
+        Err(error) => Err(ApiError::store_error("get storage statistics", &error)),
+    }
+}
+
+/// Longest storage growth look-back accepted, in days
+const MAX_GROWTH_DAYS: i32 = 3650;
+
+/// Get how many readings were stored over the last `days` and the space they
+/// take
+///
+/// # Errors
+/// Returns `StatusCode::BAD_REQUEST` if days are out of range
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database query fails
+pub async fn get_storage_growth(
+    State(state): State<AppState>,
+    StrictQuery(params): StrictQuery<DaysQuery>,
+) -> ApiResult<Json<GrowthStatistics>> {
+    let days = parse_lookback_days_param(params.days, MAX_GROWTH_DAYS)?;
+
+    match state.store.get_growth_statistics(days).await {
+        Ok(growth) => Ok(Json(growth)),
+        Err(error) => Err(ApiError::store_error("get growth statistics", &error)),
+    }
+}
+
+/// Rows removed by a storage cleanup
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CleanupSummary {
+    pub rows_deleted: u64,
+}
+
+/// Parse the required `days` of readings a cleanup keeps, which must be at
+/// least 1 since keeping 0 days would delete every reading
+fn parse_days_to_keep_param(days: Option<i32>) -> ApiResult<i32> {
+    match days {
+        Some(days) if days < 1 => Err(ApiError::InvalidParameter {
+            parameter: "days".to_string(),
+            value: days.to_string(),
+            expected: "at least 1 day of readings to keep".to_string(),
+        }),
+        Some(days) => Ok(days),
+        None => Err(ApiError::missing_parameter("days")),
+    }
+}
+
+/// Delete readings older than `days`; requires the admin API key
+///
+/// # Errors
+/// Returns `StatusCode::UNAUTHORIZED` if the admin API key is missing or wrong
+/// Returns `StatusCode::BAD_REQUEST` if days are missing or below 1
+/// Returns `StatusCode::INTERNAL_SERVER_ERROR` if database delete fails
+pub async fn cleanup_storage(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    StrictQuery(params): StrictQuery<DaysQuery>,
+) -> ApiResult<Json<CleanupSummary>> {
+    require_admin(state.admin_api_key.as_ref(), &headers)?;
+    let days = parse_days_to_keep_param(params.days)?;
+
+    match state.store.cleanup_old_data(days).await {
+        Ok(rows_deleted) => {
+            tracing::info!("Deleted {} readings older than {} days", rows_deleted, days);
+            Ok(Json(CleanupSummary { rows_deleted }))
+        }
+        Err(error) => Err(ApiError::store_error("clean up old data", &error)),
     }
 }
 
 /// Get storage requirements estimate
 ///
+/// With `observed=true` the estimate uses the reading interval each known
+/// sensor actually reports at, falling back to `interval_seconds` for sensors
+/// with too little recent data.
+///
 /// # Errors
 /// Returns `StatusCode::BAD_REQUEST` if parameters are invalid (negative, zero,
 /// or out of range) Returns `StatusCode::INTERNAL_SERVER_ERROR` if database
@@ -416,7 +2309,7 @@ pub async fn get_storage_stats(State(state): State<AppState>) -> ApiResult<Json<
 #[allow(clippy::too_many_lines)]
 pub async fn get_storage_estimate(
     State(state): State<AppState>,
-    Query(params): Query<StorageEstimateQuery>,
+    StrictQuery(params): StrictQuery<StorageEstimateQuery>,
 ) -> ApiResult<Json<StorageEstimate>> {
     let sensor_count = params.sensor_count.unwrap_or(10);
     let interval_seconds = params.interval_seconds.unwrap_or(10);
@@ -472,11 +2365,19 @@ pub async fn get_storage_estimate(
         });
     }
 
-    match state
-        .store
-        .estimate_storage_requirements(sensor_count, interval_seconds, retention_years)
-        .await
-    {
+    let estimate = if params.observed.unwrap_or(false) {
+        state
+            .store
+            .estimate_observed_storage_requirements(sensor_count, interval_seconds, retention_years)
+            .await
+    } else {
+        state
+            .store
+            .estimate_storage_requirements(sensor_count, interval_seconds, retention_years)
+            .await
+    };
+
+    match estimate {
         Ok(estimate) => {
             tracing::debug!(
                 "Generated storage estimate for {} sensors, {}s interval, {} years retention",
@@ -486,17 +2387,18 @@ pub async fn get_storage_estimate(
             );
             Ok(Json(estimate))
         }
-        Err(error) => Err(ApiError::database_error(
-            "calculate storage estimate",
-            &error.to_string(),
-        )),
+        Err(error) => Err(ApiError::store_error("calculate storage estimate", &error)),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::queries::*;
+    use crate::{
+        config::DEFAULT_MAX_BULK_SENSORS,
+        errors::ApiErrorResponse,
+        queries::*,
+    };
 
     #[tokio::test]
     async fn test_health_check() {
@@ -504,6 +2406,460 @@ mod tests {
         assert_eq!(result, "OK");
     }
 
+    #[allow(clippy::expect_used)]
+    fn unconnected_state() -> AppState {
+        use std::sync::Arc;
+
+        use postgres_store::PostgresStore;
+
+        // The pool never connects: requests must be rejected before any query
+        let store = PostgresStore::new_lazy("postgresql://localhost:1/unused").expect("lazy pool");
+        AppState::with_store(Arc::new(store))
+    }
+
+    #[allow(clippy::expect_used)]
+    async fn request_error(uri: &str) -> (StatusCode, ApiErrorResponse) {
+        let request = axum::http::Request::get(uri)
+            .body(Body::empty())
+            .expect("valid request");
+        send_for_error(unconnected_state(), request).await
+    }
+
+    #[allow(clippy::expect_used)]
+    async fn send_for_error(
+        state: AppState,
+        request: axum::http::Request<Body>,
+    ) -> (StatusCode, ApiErrorResponse) {
+        use http_body_util::BodyExt;
+        use tower::ServiceExt;
+
+        let router = crate::create_router(state);
+        let response = router.oneshot(request).await.expect("infallible router");
+        let status = response.status();
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("readable body")
+            .to_bytes();
+        let error = serde_json::from_slice(&body).expect("JSON error body");
+        (status, error)
+    }
+
+    #[test]
+    fn test_etag_matches_if_none_match() {
+        let etag = "W/\"1700000000000000-42-metric\"";
+        let if_none_match = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static(value));
+            etag_matches(&headers, etag)
+        };
+
+        assert!(if_none_match("W/\"1700000000000000-42-metric\""));
+        assert!(if_none_match("\"1700000000000000-42-metric\""));
+        assert!(if_none_match("\"other\", W/\"1700000000000000-42-metric\""));
+        assert!(if_none_match("*"));
+        assert!(!if_none_match("W/\"1700000000000000-43-metric\""));
+        assert!(!if_none_match("W/\"1700000000000000-42-imperial\""));
+        assert!(!etag_matches(&HeaderMap::new(), etag));
+    }
+
+    #[tokio::test]
+    async fn test_typo_query_param_rejected() {
+        let (status, error) =
+            request_error("/api/sensors/AA:BB:CC:DD:EE:FF/history?limt=100").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("limt"));
+
+        let (status, error) =
+            request_error("/api/sensors/AA:BB:CC:DD:EE:FF/aggregates?intervall=1h").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("intervall"));
+
+        let (status, error) = request_error("/api/storage/estimate?sensors=5").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("sensors"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_mac_error_body() {
+        for uri in [
+            "/api/sensors/not-a-mac/latest",
+            "/api/sensors/not-a-mac/history",
+            "/api/sensors/not-a-mac/aggregates",
+            "/api/sensors/not-a-mac/statistics",
+            "/api/sensors/not-a-mac/alias",
+        ] {
+            let (status, error) = request_error(uri).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+            assert_eq!(error.error, "INVALID_MAC_FORMAT", "{uri}");
+            assert_eq!(error.status_code, 400, "{uri}");
+            assert!(error.message.contains("not-a-mac"), "{uri}");
+            assert!(error.details.is_some(), "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::expect_used)]
+    async fn test_compare_validates_macs() {
+        let (status, error) = request_error("/api/compare").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("macs"));
+
+        let (_, error) = request_error("/api/compare?macs=AA:BB:CC:DD:EE:01,not-a-mac").await;
+        assert_eq!(error.error, "INVALID_MAC_FORMAT");
+
+        let macs: Vec<String> = (0..=MAX_COMPARE_SENSORS)
+            .map(|index| format!("AA:BB:CC:DD:EE:{index:02X}"))
+            .collect();
+        let (status, error) = request_error(&format!("/api/compare?macs={}", macs.join(","))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("macs"));
+
+        // A lower configured bulk limit also caps comparisons
+        let request = axum::http::Request::get(
+            "/api/compare?macs=AA:BB:CC:DD:EE:01,AA:BB:CC:DD:EE:02,AA:BB:CC:DD:EE:03",
+        )
+        .body(Body::empty())
+        .expect("valid request");
+        let (status, error) =
+            send_for_error(unconnected_state().with_max_bulk_sensors(2), request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("macs"));
+    }
+
+    #[test]
+    fn test_range_presets() {
+        let range = |preset: &str| {
+            parse_range_params(Some(preset), None, None, Duration::hours(1), Tz::UTC)
+        };
+
+        for preset in ["today", "yesterday", "7d", "30d", "this_month"] {
+            assert!(
+                matches!(range(preset), Ok((start, end)) if start < end),
+                "{preset}"
+            );
+        }
+        assert!(matches!(
+            range("7d"),
+            Ok((start, end)) if end - start == Duration::days(7)
+        ));
+        assert!(matches!(
+            range("yesterday"),
+            Ok((start, end)) if end - start == Duration::days(1) && end <= Utc::now()
+        ));
+        assert!(matches!(
+            range("fortnight"),
+            Err(ApiError::InvalidParameter { ref parameter, .. }) if parameter == "preset"
+        ));
+    }
+
+    #[test]
+    fn test_range_preset_conflicts_with_dates() {
+        let start = "2024-01-01T00:00:00Z".to_string();
+        let end = "2024-01-02T00:00:00Z".to_string();
+
+        assert!(matches!(
+            parse_range_params(
+                Some("today"),
+                Some(&start),
+                None,
+                Duration::hours(1),
+                Tz::UTC
+            ),
+            Err(ApiError::BadRequest { .. })
+        ));
+        assert!(matches!(
+            parse_range_params(Some("today"), None, Some(&end), Duration::hours(1), Tz::UTC),
+            Err(ApiError::BadRequest { .. })
+        ));
+        assert!(matches!(
+            parse_range_params(None, Some(&start), Some(&end), Duration::hours(1), Tz::UTC),
+            Ok((range_start, range_end)) if range_end - range_start == Duration::days(1)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_history_order_validation() {
+        let (status, error) =
+            request_error("/api/sensors/AA:BB:CC:DD:EE:FF/history?order=newest").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("order"));
+
+        let cursor = PageCursor::Before(HistoryCursor {
+            timestamp: Utc::now(),
+            measurement_sequence_number: 1,
+        })
+        .encode();
+        let (status, error) = request_error(&format!(
+            "/api/sensors/AA:BB:CC:DD:EE:FF/history?order=asc&before={cursor}"
+        ))
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("order=asc"));
+    }
+
+    #[tokio::test]
+    async fn test_preset_rejected_with_explicit_dates() {
+        let (status, error) = request_error(
+            "/api/sensors/AA:BB:CC:DD:EE:FF/history?preset=today&start=2024-01-01T00:00:00Z",
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("preset"));
+
+        let (status, error) =
+            request_error("/api/sensors/AA:BB:CC:DD:EE:FF/daily?preset=last_year").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("preset"));
+
+        let (status, _) =
+            request_error("/api/sensors/AA:BB:CC:DD:EE:FF/aggregates?preset=today&tz=Mars/Base")
+                .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_typo_query_param_allowed_when_not_strict() {
+        // With strict=false the typo is ignored and validation proceeds to the
+        // next check, which here is the out-of-range limit
+        let (status, error) =
+            request_error("/api/sensors/AA:BB:CC:DD:EE:FF/history?limt=100&limit=0&strict=false")
+                .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("'limit'"));
+        assert!(!error.message.contains("limt"));
+    }
+
+    #[tokio::test]
+    async fn test_correlation_parameter_validation() {
+        let (status, error) = request_error("/api/sensors/correlate?to=AA:BB:CC:DD:EE:FF").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("'from'"));
+
+        let (status, error) = request_error(
+            "/api/sensors/correlate?from=AA:BB:CC:DD:EE:01&to=AA:BB:CC:DD:EE:02&metric=battery",
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("metric"));
+    }
+
+    #[tokio::test]
+    async fn test_trends_parameter_validation() {
+        let (status, error) =
+            request_error("/api/sensors/AA:BB:CC:DD:EE:FF/trends?metrics=temperature,battery")
+                .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("battery"));
+
+        let (status, error) =
+            request_error("/api/sensors/AA:BB:CC:DD:EE:FF/trends?metrics=,").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("metrics"));
+
+        let (status, error) = request_error("/api/sensors/AA:BB:CC:DD:EE:FF/trends?hours=0").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("hours"));
+
+        let (status, error) =
+            request_error("/api/sensors/AA:BB:CC:DD:EE:FF/trends?bucket=7m").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("bucket"));
+    }
+
+    #[tokio::test]
+    async fn test_statistics_parameter_validation() {
+        let (status, error) = request_error("/api/sensors/not-a-mac/statistics").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("not-a-mac"));
+
+        for hours in [0, MAX_SUMMARY_HOURS + 1] {
+            let (status, error) = request_error(&format!(
+                "/api/sensors/AA:BB:CC:DD:EE:FF/statistics?hours={hours}"
+            ))
+            .await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert!(error.message.contains("hours"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_gateway_history_parameter_validation() {
+        let (status, error) = request_error("/api/gateways/not-a-mac/history").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("not-a-mac"));
+
+        let (status, error) =
+            request_error("/api/gateways/FF:FF:FF:FF:FF:01/history?limit=0").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("limit"));
+    }
+
+    #[tokio::test]
+    async fn test_gateway_statistics_parameter_validation() {
+        let (status, error) = request_error("/api/gateways/not-a-mac/statistics").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("not-a-mac"));
+
+        for hours in [0, MAX_SUMMARY_HOURS + 1] {
+            let (status, error) = request_error(&format!(
+                "/api/gateways/FF:FF:FF:FF:FF:01/statistics?hours={hours}"
+            ))
+            .await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert!(error.message.contains("hours"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_health_parameter_validation() {
+        let (status, error) = request_error("/api/sensors/AA:BB:CC:DD:EE/health").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("AA:BB:CC:DD:EE"));
+
+        let (status, error) = request_error("/api/sensors/AA:BB:CC:DD:EE:FF/health?hours=-1").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("hours"));
+    }
+
+    #[tokio::test]
+    async fn test_storage_growth_days_out_of_range() {
+        for days in [0, MAX_GROWTH_DAYS + 1] {
+            let (status, error) = request_error(&format!("/api/storage/growth?days={days}")).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert!(error.message.contains("days"));
+        }
+    }
+
+    #[allow(clippy::expect_used)]
+    fn cleanup_request(uri: &str, admin_key: Option<&str>) -> axum::http::Request<Body> {
+        let mut request = axum::http::Request::post(uri);
+        if let Some(admin_key) = admin_key {
+            request = request.header(crate::auth::ADMIN_KEY_HEADER, admin_key);
+        }
+        request.body(Body::empty()).expect("valid request")
+    }
+
+    #[tokio::test]
+    #[allow(clippy::expect_used)]
+    async fn test_storage_cleanup_requires_admin_key_and_days() {
+        use crate::auth::ApiKey;
+
+        let (status, _) = send_for_error(
+            unconnected_state(),
+            cleanup_request("/api/storage/cleanup?days=90", Some("secret")),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let state = unconnected_state().with_admin_api_key(ApiKey::new("secret").expect("key"));
+        for admin_key in [None, Some("guess")] {
+            let request = cleanup_request("/api/storage/cleanup?days=90", admin_key);
+            let (status, _) = send_for_error(state.clone(), request).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        }
+
+        for uri in ["/api/storage/cleanup", "/api/storage/cleanup?days=0"] {
+            let request = cleanup_request(uri, Some("secret"));
+            let (status, error) = send_for_error(state.clone(), request).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+            assert!(error.message.contains("days"), "{uri}");
+        }
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_cleanup_summary_shape() {
+        let summary = serde_json::to_value(CleanupSummary { rows_deleted: 42 }).expect("JSON");
+        assert_eq!(summary, serde_json::json!({ "rows_deleted": 42 }));
+    }
+
+    #[tokio::test]
+    async fn test_temperature_trend_parameter_validation() {
+        let (status, error) = request_error("/api/sensors/AABBCCDDEEFF/trend").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("AABBCCDDEEFF"));
+
+        let (status, error) =
+            request_error("/api/sensors/AA:BB:CC:DD:EE:FF/trend?interval=7m").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("interval"));
+
+        let (status, error) =
+            request_error("/api/sensors/AA:BB:CC:DD:EE:FF/trend?hours=0&interval=5m").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("hours"));
+    }
+
+    #[test]
+    fn test_metrics_param_defaults_and_dedup() {
+        assert_eq!(parse_metrics_param(None).ok(), Some(Metric::ALL.to_vec()));
+        assert_eq!(
+            parse_metrics_param(Some("pressure, temperature,pressure")).ok(),
+            Some(vec![Metric::Pressure, Metric::Temperature])
+        );
+    }
+
+    fn mac_list(count: usize) -> String {
+        (1..=count)
+            .map(|index| format!("AA:BB:CC:DD:{:02X}:{:02X}", index / 256, index % 256))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    #[test]
+    fn test_bulk_macs_at_limit_accepted() {
+        let macs = mac_list(3);
+        assert_eq!(
+            parse_bulk_macs(Some(&macs), 3).map(|macs| macs.len()).ok(),
+            Some(3)
+        );
+        assert_eq!(
+            parse_bulk_macs(
+                Some(&mac_list(DEFAULT_MAX_BULK_SENSORS)),
+                DEFAULT_MAX_BULK_SENSORS
+            )
+            .map(|macs| macs.len())
+            .ok(),
+            Some(DEFAULT_MAX_BULK_SENSORS)
+        );
+    }
+
+    #[test]
+    fn test_bulk_macs_over_limit_rejected() {
+        let error = parse_bulk_macs(Some(&mac_list(4)), 3);
+        assert!(matches!(
+            error,
+            Err(ApiError::InvalidParameter { ref parameter, .. }) if parameter == "macs"
+        ));
+        assert_eq!(
+            error.err().map(|error| error.status_code()),
+            Some(StatusCode::BAD_REQUEST)
+        );
+
+        assert!(parse_bulk_macs(None, 3).is_err());
+        assert!(parse_bulk_macs(Some(" , "), 3).is_err());
+    }
+
+    #[test]
+    fn test_store_timeout_maps_to_gateway_timeout() {
+        let timeout = anyhow::Error::new(postgres_store::QueryTimeout {
+            operation: "get_latest_reading",
+            timeout: std::time::Duration::from_secs(1),
+        });
+        assert_eq!(
+            ApiError::store_error("get latest reading", &timeout).status_code(),
+            StatusCode::GATEWAY_TIMEOUT
+        );
+
+        let other = anyhow::anyhow!("connection refused");
+        assert_eq!(
+            ApiError::store_error("get latest reading", &other).status_code(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
     #[test]
     fn test_validate_mac_in_handlers() {
         // Test the MAC validation logic used in handlers
@@ -596,6 +2952,31 @@ mod tests {
         assert_eq!(storage.retention_years, Some(3));
     }
 
+    #[test]
+    fn test_threshold_bounds_validation() {
+        assert!(validate_threshold_bounds(Some(500.0), Some(2000.0)).is_ok());
+        assert!(validate_threshold_bounds(None, Some(2000.0)).is_ok());
+        assert!(validate_threshold_bounds(Some(500.0), None).is_ok());
+        assert!(validate_threshold_bounds(Some(500.0), Some(500.0)).is_ok());
+
+        assert!(validate_threshold_bounds(None, None).is_err());
+        assert!(validate_threshold_bounds(Some(2000.0), Some(500.0)).is_err());
+        assert!(validate_threshold_bounds(None, Some(f64::NAN)).is_err());
+        assert!(validate_threshold_bounds(Some(f64::INFINITY), None).is_err());
+    }
+
+    #[test]
+    fn test_lookback_days_validation() {
+        let max_days = MAX_BATTERY_LOOKBACK_DAYS;
+        assert!(matches!(parse_lookback_days_param(None, max_days), Ok(30)));
+        assert!(matches!(
+            parse_lookback_days_param(Some(7), max_days),
+            Ok(7)
+        ));
+        assert!(parse_lookback_days_param(Some(0), max_days).is_err());
+        assert!(parse_lookback_days_param(Some(max_days + 1), max_days).is_err());
+    }
+
     // Note: Full handler tests with actual HTTP requests would require
     // setting up a test server and database, which would be in integration
     // tests
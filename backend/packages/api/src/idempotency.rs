@@ -0,0 +1,242 @@
+//! Replay protection for mutating requests carrying an `Idempotency-Key`
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Mutex,
+        MutexGuard,
+        PoisonError,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use axum::{
+    body::{
+        Body,
+        Bytes,
+    },
+    http::{
+        header,
+        HeaderValue,
+        StatusCode,
+    },
+    response::{
+        IntoResponse,
+        Response,
+    },
+};
+
+/// How long a completed request is remembered by default
+pub const DEFAULT_IDEMPOTENCY_TTL: Duration = Duration::from_hours(24);
+
+/// Maximum number of remembered keys by default
+pub const DEFAULT_IDEMPOTENCY_CAPACITY: usize = 10_000;
+
+/// Response recorded for a completed request so it can be replayed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredResponse {
+    pub status: StatusCode,
+    pub content_type: Option<HeaderValue>,
+    pub body: Bytes,
+}
+
+impl IntoResponse for StoredResponse {
+    fn into_response(self) -> Response {
+        let mut response = (self.status, Body::from(self.body)).into_response();
+        if let Some(content_type) = self.content_type {
+            response
+                .headers_mut()
+                .insert(header::CONTENT_TYPE, content_type);
+        }
+        response
+    }
+}
+
+/// Outcome of trying to claim a key before executing a request
+#[derive(Debug, PartialEq, Eq)]
+pub enum Reservation {
+    /// First time this key is seen: the caller should execute the request
+    Acquired,
+    /// Another request with this key is still executing
+    InFlight,
+    /// The request already completed; replay this response
+    Completed(StoredResponse),
+}
+
+#[derive(Debug)]
+enum EntryState {
+    InFlight,
+    Completed(StoredResponse),
+}
+
+#[derive(Debug)]
+struct Entry {
+    created: Instant,
+    state: EntryState,
+}
+
+/// Recorded requests keyed by their idempotency key
+type Entries = HashMap<String, Entry>;
+
+/// In-memory, bounded record of recently seen idempotency keys
+///
+/// Keys expire after `ttl`. When `capacity` is reached, expired keys are
+/// dropped first and then the oldest remaining key is evicted.
+#[derive(Debug)]
+pub struct IdempotencyStore {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+impl IdempotencyStore {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Claim `key` for execution, or report why the caller must not execute
+    pub fn reserve(&self, key: &str) -> Reservation {
+        let now = Instant::now();
+        let mut entries = self.lock();
+
+        if let Some(entry) = entries.get(key) {
+            if now.duration_since(entry.created) < self.ttl {
+                return match &entry.state {
+                    EntryState::InFlight => Reservation::InFlight,
+                    EntryState::Completed(response) => Reservation::Completed(response.clone()),
+                };
+            }
+        }
+
+        if entries.len() >= self.capacity && !entries.contains_key(key) {
+            self.evict(&mut entries, now);
+        }
+
+        entries.insert(
+            key.to_string(),
+            Entry {
+                created: now,
+                state: EntryState::InFlight,
+            },
+        );
+        Reservation::Acquired
+    }
+
+    /// Record the response for a key claimed with [`Self::reserve`]
+    pub fn complete(&self, key: &str, response: StoredResponse) {
+        if let Some(entry) = self.lock().get_mut(key) {
+            entry.state = EntryState::Completed(response);
+        }
+    }
+
+    /// Forget a claimed key so a retry executes again
+    pub fn release(&self, key: &str) {
+        self.lock().remove(key);
+    }
+
+    /// Number of keys currently remembered, including expired ones not yet
+    /// evicted
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn evict(&self, entries: &mut Entries, now: Instant) {
+        entries.retain(|_, entry| now.duration_since(entry.created) < self.ttl);
+
+        while entries.len() >= self.capacity {
+            let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.created)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            entries.remove(&oldest);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Entries> {
+        // The map is left consistent after every operation, so a panic while
+        // holding the lock cannot corrupt it
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for IdempotencyStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_IDEMPOTENCY_TTL, DEFAULT_IDEMPOTENCY_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored(body: &'static str) -> StoredResponse {
+        StoredResponse {
+            status: StatusCode::OK,
+            content_type: Some(HeaderValue::from_static("application/json")),
+            body: Bytes::from_static(body.as_bytes()),
+        }
+    }
+
+    #[test]
+    fn test_reserve_then_replay() {
+        let store = IdempotencyStore::default();
+
+        assert_eq!(store.reserve("key-1"), Reservation::Acquired);
+        assert_eq!(store.reserve("key-1"), Reservation::InFlight);
+
+        store.complete("key-1", stored("{\"inserted\":1}"));
+        assert_eq!(
+            store.reserve("key-1"),
+            Reservation::Completed(stored("{\"inserted\":1}"))
+        );
+
+        assert_eq!(store.reserve("key-2"), Reservation::Acquired);
+    }
+
+    #[test]
+    fn test_release_allows_retry() {
+        let store = IdempotencyStore::default();
+
+        assert_eq!(store.reserve("key"), Reservation::Acquired);
+        store.release("key");
+        assert_eq!(store.reserve("key"), Reservation::Acquired);
+    }
+
+    #[test]
+    fn test_expired_keys_execute_again() {
+        let store = IdempotencyStore::new(Duration::ZERO, 10);
+
+        assert_eq!(store.reserve("key"), Reservation::Acquired);
+        store.complete("key", stored("{}"));
+        assert_eq!(store.reserve("key"), Reservation::Acquired);
+    }
+
+    #[test]
+    fn test_capacity_evicts_oldest() {
+        let store = IdempotencyStore::new(DEFAULT_IDEMPOTENCY_TTL, 2);
+
+        // Space the keys out so their creation times are distinct
+        for key in ["first", "second", "third"] {
+            assert_eq!(store.reserve(key), Reservation::Acquired);
+            std::thread::sleep(Duration::from_millis(2));
+        }
+
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.reserve("second"), Reservation::InFlight);
+        assert_eq!(store.reserve("first"), Reservation::Acquired);
+    }
+}
@@ -7,10 +7,17 @@
 #![cfg_attr(not(test), deny(clippy::expect_used, clippy::unwrap_used))]
 #![cfg_attr(not(test), deny(clippy::panic))]
 
+pub mod auth;
 pub mod config;
 pub mod errors;
+pub mod export;
+pub mod extract;
 pub mod handlers;
+pub mod idempotency;
+pub mod metrics;
+pub mod middleware;
 pub mod queries;
+pub mod rate_limit;
 pub mod state;
 pub mod utils;
 
@@ -18,7 +25,12 @@ pub mod utils;
 
 use axum::{
     http::HeaderValue,
-    routing::get,
+    routing::{
+        delete,
+        get,
+        post,
+        put,
+    },
     Router,
 };
 pub use config::Config;
@@ -26,39 +38,167 @@ pub use errors::*;
 pub use handlers::*;
 pub use queries::*;
 pub use state::AppState;
-use tower_http::cors::{
-    AllowOrigin,
-    Any,
-    CorsLayer,
+use tower_http::{
+    compression::CompressionLayer,
+    cors::{
+        AllowOrigin,
+        Any,
+        CorsLayer,
+    },
 };
 
 /// Create the main application router with all routes configured
 pub fn create_router(state: AppState) -> Router {
-    let cors = CorsLayer::new()
-        .allow_origin(AllowOrigin::predicate(|origin: &HeaderValue, _| {
-            origin
-                .to_str()
-                .map(|s| s.starts_with("http://localhost:") || s.starts_with("https://localhost:"))
-                .unwrap_or(false)
-        }))
-        .allow_methods(Any)
-        .allow_headers(Any);
+    let cors = cors_layer(&state.cors_allowed_origins);
 
     Router::new()
         .route("/health", get(handlers::health_check))
+        .route("/health/ready", get(handlers::readiness_check))
+        .route("/metrics", get(handlers::get_metrics))
+        .merge(sensor_routes())
+        .merge(analytics_routes())
+        .merge(aggregate_routes())
+        .merge(alert_routes())
+        .merge(group_routes())
+        .merge(storage_routes())
+        .route("/api/readings", post(handlers::ingest_readings))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::idempotent_requests,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::localize_timestamps,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::authenticate,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::limit_rate,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middleware::record_metrics,
+        ))
+        .layer(CompressionLayer::new())
+        .layer(cors)
+        .layer(axum::middleware::from_fn(middleware::assign_request_id))
+        .with_state(state)
+}
+
+/// Allow cross-origin requests from `origins`, from anywhere when they
+/// include `*`, or only from `http(s)://localhost` when there are none
+fn cors_layer(origins: &[String]) -> CorsLayer {
+    let allow_origin = if origins.is_empty() {
+        AllowOrigin::predicate(|origin: &HeaderValue, _| {
+            origin.to_str().is_ok_and(|s| {
+                s.starts_with("http://localhost:") || s.starts_with("https://localhost:")
+            })
+        })
+    } else if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(
+            origins
+                .iter()
+                .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+        )
+    };
+
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(Any)
+        .allow_headers(Any)
+}
+
+fn sensor_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/dashboard", get(handlers::get_dashboard))
         .route("/api/sensors", get(handlers::get_sensors))
+        .route("/api/sensors/active", get(handlers::get_active_sensors))
+        .route("/api/sensors/latest", get(handlers::get_latest_readings))
+        .route("/api/sensors/live", get(handlers::live_sensor_updates))
+        .route("/api/gateways", get(handlers::get_gateways))
+        .route(
+            "/api/gateways/{gateway_mac}/history",
+            get(handlers::get_gateway_history),
+        )
+        .route("/api/sensors/{sensor_mac}", delete(handlers::delete_sensor))
+        .route(
+            "/api/sensors/{sensor_mac}/alias",
+            get(handlers::get_sensor_alias).put(handlers::set_sensor_alias),
+        )
         .route(
             "/api/sensors/{sensor_mac}/latest",
             get(handlers::get_sensor_latest),
         )
+        .route(
+            "/api/sensors/{sensor_mac}/latest-per-metric",
+            get(handlers::get_sensor_latest_per_metric),
+        )
+        .route(
+            "/api/sensors/{sensor_mac}/export",
+            get(handlers::export_sensor_readings),
+        )
+        .route(
+            "/api/sensors/{sensor_mac}/count",
+            get(handlers::get_sensor_count),
+        )
         .route(
             "/api/sensors/{sensor_mac}/history",
             get(handlers::get_sensor_history),
         )
+        .route(
+            "/api/sensors/{sensor_mac}/history.csv",
+            get(handlers::get_sensor_history_csv),
+        )
+}
+
+fn analytics_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/sensors/correlate",
+            get(handlers::get_sensor_correlation),
+        )
+        .route("/api/compare", get(handlers::compare_sensors))
+        .route(
+            "/api/sensors/{sensor_mac}/trends",
+            get(handlers::get_sensor_trends),
+        )
+        .route(
+            "/api/sensors/{sensor_mac}/statistics",
+            get(handlers::get_sensor_statistics),
+        )
+        .route(
+            "/api/gateways/{gateway_mac}/statistics",
+            get(handlers::get_gateway_statistics),
+        )
+        .route(
+            "/api/sensors/{sensor_mac}/health",
+            get(handlers::get_sensor_health),
+        )
+        .route(
+            "/api/sensors/{sensor_mac}/trend",
+            get(handlers::get_sensor_temperature_trend),
+        )
+        .route(
+            "/api/sensors/{sensor_mac}/battery/projection",
+            get(handlers::get_sensor_battery_projection),
+        )
+}
+
+fn aggregate_routes() -> Router<AppState> {
+    Router::new()
         .route(
             "/api/sensors/{sensor_mac}/aggregates",
             get(handlers::get_sensor_aggregates),
         )
+        .route(
+            "/api/sensors/{sensor_mac}/aggregates/stream",
+            get(handlers::stream_sensor_aggregates),
+        )
         .route(
             "/api/sensors/{sensor_mac}/hourly",
             get(handlers::get_sensor_hourly_aggregates),
@@ -67,8 +207,521 @@ pub fn create_router(state: AppState) -> Router {
             "/api/sensors/{sensor_mac}/daily",
             get(handlers::get_sensor_daily_aggregates),
         )
+}
+
+fn alert_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/sensors/{sensor_mac}/thresholds",
+            get(handlers::get_sensor_thresholds).post(handlers::create_sensor_threshold),
+        )
+        .route(
+            "/api/thresholds/{threshold_id}",
+            delete(handlers::delete_threshold),
+        )
+        .route(
+            "/api/sensors/{sensor_mac}/vibration-alerts",
+            get(handlers::get_sensor_vibration_alerts),
+        )
+        .route(
+            "/api/sensors/{sensor_mac}/alerts",
+            post(handlers::create_sensor_alert),
+        )
+        .route("/api/alerts/active", get(handlers::get_active_alerts))
+}
+
+fn group_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/groups",
+            get(handlers::list_sensor_groups).post(handlers::create_sensor_group),
+        )
+        .route(
+            "/api/groups/{group}",
+            get(handlers::get_sensor_group).delete(handlers::delete_sensor_group),
+        )
+        .route(
+            "/api/groups/{group}/members/{sensor_mac}",
+            put(handlers::add_group_member).delete(handlers::remove_group_member),
+        )
+        .route(
+            "/api/groups/{group}/latest",
+            get(handlers::get_group_latest),
+        )
+        .route(
+            "/api/groups/{group}/aggregates",
+            get(handlers::get_group_aggregates),
+        )
+}
+
+fn storage_routes() -> Router<AppState> {
+    Router::new()
         .route("/api/storage/stats", get(handlers::get_storage_stats))
         .route("/api/storage/estimate", get(handlers::get_storage_estimate))
-        .layer(cors)
-        .with_state(state)
+        .route("/api/storage/growth", get(handlers::get_storage_growth))
+        .route("/api/storage/cleanup", post(handlers::cleanup_storage))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::SocketAddr,
+        sync::Arc,
+    };
+
+    use async_trait::async_trait;
+    use axum::{
+        body::Body,
+        extract::ConnectInfo,
+        http::{
+            header,
+            Request,
+            StatusCode,
+        },
+        response::Response,
+    };
+    use chrono::{
+        DateTime,
+        Utc,
+    };
+    use postgres_store::{
+        Event,
+        PostgresStore,
+        Store,
+    };
+    use redis_store::RedisStore;
+    use testcontainers_modules::{
+        redis::Redis,
+        testcontainers::runners::AsyncRunner,
+    };
+    use tokio::sync::broadcast;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{
+        auth::ApiKey,
+        handlers::ReadinessReport,
+        rate_limit::RateLimit,
+    };
+
+    #[allow(clippy::expect_used)]
+    fn unconnected_state() -> AppState {
+        let store = PostgresStore::new_lazy("postgresql://localhost:1/unused").expect("lazy pool");
+        AppState::with_store(Arc::new(store))
+    }
+
+    #[allow(clippy::expect_used)]
+    async fn allowed_origin(origins: &[&str], origin: &str) -> Option<String> {
+        let state = unconnected_state()
+            .with_cors_allowed_origins(origins.iter().map(ToString::to_string).collect());
+        let request = Request::get("/health")
+            .header(header::ORIGIN, origin)
+            .body(Body::empty())
+            .expect("valid request");
+        let response = create_router(state)
+            .oneshot(request)
+            .await
+            .expect("infallible router");
+        response
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .and_then(|value| value.to_str().ok())
+            .map(ToString::to_string)
+    }
+
+    #[tokio::test]
+    async fn test_cors_defaults_to_localhost() {
+        assert_eq!(
+            allowed_origin(&[], "http://localhost:3000").await,
+            Some("http://localhost:3000".to_string())
+        );
+        assert_eq!(allowed_origin(&[], "https://ruuvi.example.com").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_cors_honors_configured_origins() {
+        let origins = ["https://ruuvi.example.com", "http://192.168.1.10:3000"];
+
+        assert_eq!(
+            allowed_origin(&origins, "https://ruuvi.example.com").await,
+            Some("https://ruuvi.example.com".to_string())
+        );
+        assert_eq!(
+            allowed_origin(&origins, "http://192.168.1.10:3000").await,
+            Some("http://192.168.1.10:3000".to_string())
+        );
+        assert_eq!(
+            allowed_origin(&origins, "http://localhost:3000").await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cors_wildcard_allows_any_origin() {
+        assert_eq!(
+            allowed_origin(&["*"], "https://anywhere.example.org").await,
+            Some("*".to_string())
+        );
+    }
+
+    #[allow(clippy::expect_used)]
+    async fn status_of(state: AppState, uri: &str, authorization: Option<&str>) -> StatusCode {
+        let mut request = Request::get(uri);
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+        let request = request.body(Body::empty()).expect("valid request");
+        create_router(state)
+            .oneshot(request)
+            .await
+            .expect("infallible router")
+            .status()
+    }
+
+    #[tokio::test]
+    #[allow(clippy::expect_used)]
+    async fn test_api_key_required_when_configured() {
+        let state = unconnected_state().with_api_key(ApiKey::new("secret").expect("key"));
+
+        assert_eq!(
+            status_of(state.clone(), "/metrics", Some("Bearer secret")).await,
+            StatusCode::OK
+        );
+        for authorization in [None, Some("Bearer guess")] {
+            assert_eq!(
+                status_of(state.clone(), "/metrics", authorization).await,
+                StatusCode::UNAUTHORIZED
+            );
+        }
+        assert_eq!(status_of(state, "/health", None).await, StatusCode::OK);
+    }
+
+    #[allow(clippy::expect_used)]
+    async fn send_from(state: AppState, uri: &str, client: SocketAddr) -> Response {
+        let request = Request::get(uri)
+            .extension(ConnectInfo(client))
+            .body(Body::empty())
+            .expect("valid request");
+        create_router(state)
+            .oneshot(request)
+            .await
+            .expect("infallible router")
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_rejects_requests_over_the_limit() {
+        let state = unconnected_state().with_rate_limit(RateLimit {
+            requests_per_second: 1,
+            burst: 3,
+        });
+        let client = SocketAddr::from(([192, 168, 1, 10], 40000));
+
+        for _ in 0..3 {
+            let response = send_from(state.clone(), "/metrics", client).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let response = send_from(state.clone(), "/metrics", client).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(
+            response
+                .headers()
+                .get(header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok()),
+            Some("1")
+        );
+
+        let other_client = SocketAddr::from(([192, 168, 1, 11], 40000));
+        let response = send_from(state.clone(), "/metrics", other_client).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send_from(state, "/health", client).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    /// Store holding a single fixed reading
+    #[derive(Debug)]
+    struct MockStore {
+        reading: Event,
+        events: broadcast::Sender<Event>,
+    }
+
+    #[async_trait]
+    impl Store for MockStore {
+        async fn insert_event(&self, event: &Event) -> anyhow::Result<()> {
+            let _ = self.events.send(event.clone());
+            Ok(())
+        }
+
+        async fn get_latest_reading(&self, sensor_mac: &str) -> anyhow::Result<Option<Event>> {
+            Ok((sensor_mac == self.reading.sensor_mac).then(|| self.reading.clone()))
+        }
+
+        async fn get_historical_data(
+            &self,
+            sensor_mac: &str,
+            _start: Option<DateTime<Utc>>,
+            _end: Option<DateTime<Utc>>,
+            _limit: Option<i64>,
+        ) -> anyhow::Result<Vec<Event>> {
+            Ok(self
+                .get_latest_reading(sensor_mac)
+                .await?
+                .into_iter()
+                .collect())
+        }
+
+        async fn get_active_sensors(&self) -> anyhow::Result<Vec<Event>> {
+            Ok(vec![self.reading.clone()])
+        }
+
+        fn subscribe_to_events(&self) -> broadcast::Receiver<Event> {
+            self.events.subscribe()
+        }
+    }
+
+    fn sample_reading() -> Event {
+        Event::new_with_current_time(
+            "AA:BB:CC:DD:EE:01".to_string(),
+            "FF:FF:FF:FF:FF:01".to_string(),
+            21.5,
+            45.0,
+            1013.0,
+            2900,
+            4,
+            0,
+            7,
+            1000.0,
+            0,
+            0,
+            1000,
+            -60,
+        )
+    }
+
+    #[tokio::test]
+    #[allow(clippy::expect_used)]
+    async fn test_latest_reading_served_from_mock_store() {
+        let store = MockStore {
+            reading: sample_reading(),
+            events: broadcast::channel(1).0,
+        };
+        let state = unconnected_state().with_readings(Arc::new(store));
+
+        let request = Request::get("/api/sensors/AA:BB:CC:DD:EE:01/latest")
+            .body(Body::empty())
+            .expect("valid request");
+        let response = create_router(state.clone())
+            .oneshot(request)
+            .await
+            .expect("infallible router");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let latest: serde_json::Value = serde_json::from_slice(&body).expect("JSON body");
+        assert_eq!(latest.get("temperature"), Some(&serde_json::json!(21.5)));
+        assert_eq!(
+            latest.get("measurement_sequence_number"),
+            Some(&serde_json::json!(7))
+        );
+
+        assert_eq!(
+            status_of(state, "/api/sensors/AA:BB:CC:DD:EE:02/latest", None).await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    #[allow(clippy::expect_used)]
+    async fn test_history_served_from_mock_store() {
+        let store = MockStore {
+            reading: sample_reading(),
+            events: broadcast::channel(1).0,
+        };
+        let state = unconnected_state().with_readings(Arc::new(store));
+
+        let request = Request::get("/api/sensors/AA:BB:CC:DD:EE:01/history")
+            .body(Body::empty())
+            .expect("valid request");
+        let response = create_router(state)
+            .oneshot(request)
+            .await
+            .expect("infallible router");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let history: Vec<serde_json::Value> = serde_json::from_slice(&body).expect("JSON body");
+        assert_eq!(history.len(), 1);
+    }
+
+    /// `macs` query listing `count` distinct sensors
+    fn bulk_macs_uri(count: u8) -> String {
+        let macs: Vec<String> = (1..=count)
+            .map(|index| format!("AA:BB:CC:DD:EE:{index:02X}"))
+            .collect();
+        format!("/api/sensors/latest?macs={}", macs.join(","))
+    }
+
+    #[tokio::test]
+    #[allow(clippy::expect_used)]
+    async fn test_bulk_latest_enforces_max_sensors() {
+        let store = MockStore {
+            reading: sample_reading(),
+            events: broadcast::channel(1).0,
+        };
+        let state = unconnected_state()
+            .with_readings(Arc::new(store))
+            .with_max_bulk_sensors(3);
+
+        let request = Request::get(bulk_macs_uri(3))
+            .body(Body::empty())
+            .expect("valid request");
+        let response = create_router(state.clone())
+            .oneshot(request)
+            .await
+            .expect("infallible router");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let latest: serde_json::Value = serde_json::from_slice(&body).expect("JSON body");
+        assert!(latest.get("AA:BB:CC:DD:EE:01").is_some());
+
+        let request = Request::get(bulk_macs_uri(4))
+            .body(Body::empty())
+            .expect("valid request");
+        let response = create_router(state)
+            .oneshot(request)
+            .await
+            .expect("infallible router");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let error: serde_json::Value = serde_json::from_slice(&body).expect("JSON body");
+        assert_eq!(
+            error.get("error"),
+            Some(&serde_json::json!("INVALID_PARAMETER"))
+        );
+        assert_eq!(
+            error.get("details"),
+            Some(&serde_json::json!("Expected: at most 3 sensors"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lowercase_mac_finds_stored_sensor() {
+        let store = MockStore {
+            reading: sample_reading(),
+            events: broadcast::channel(1).0,
+        };
+        let state = unconnected_state().with_readings(Arc::new(store));
+
+        assert_eq!(
+            status_of(state, "/api/sensors/aa:bb:cc:dd:ee:01/latest", None).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "Requires Docker for Redis"]
+    #[allow(clippy::expect_used)]
+    async fn test_latest_reading_served_from_redis_store() {
+        let container = Redis::default().start().await.expect("redis");
+        let redis_url = format!(
+            "redis://localhost:{}",
+            container
+                .get_host_port_ipv4(6379)
+                .await
+                .expect("Failed to get host port")
+        );
+        let store = RedisStore::new(&redis_url).await.expect("redis store");
+        store
+            .insert_event(&sample_reading())
+            .await
+            .expect("insert reading");
+        let state = unconnected_state().with_readings(Arc::new(store));
+
+        let request = Request::get("/api/sensors/AA:BB:CC:DD:EE:01/latest")
+            .body(Body::empty())
+            .expect("valid request");
+        let response = create_router(state.clone())
+            .oneshot(request)
+            .await
+            .expect("infallible router");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let latest: serde_json::Value = serde_json::from_slice(&body).expect("JSON body");
+        assert_eq!(latest.get("temperature"), Some(&serde_json::json!(21.5)));
+        assert_eq!(
+            latest.get("measurement_sequence_number"),
+            Some(&serde_json::json!(7))
+        );
+
+        assert_eq!(
+            status_of(state, "/api/sensors/AA:BB:CC:DD:EE:02/latest", None).await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    #[allow(clippy::expect_used)]
+    async fn test_readiness_fails_without_database() {
+        let state = unconnected_state();
+        assert_eq!(
+            status_of(state.clone(), "/health", None).await,
+            StatusCode::OK
+        );
+
+        let request = Request::get("/health/ready")
+            .body(Body::empty())
+            .expect("valid request");
+        let response = create_router(state)
+            .oneshot(request)
+            .await
+            .expect("infallible router");
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let report: ReadinessReport = serde_json::from_slice(&body).expect("JSON body");
+        assert!(!report.ready);
+        let database = report.dependencies.get("database").expect("database");
+        assert_eq!(database.status, "unavailable");
+        assert!(database.error.is_some());
+    }
+
+    #[tokio::test]
+    #[allow(clippy::expect_used)]
+    async fn test_responses_carry_request_id() {
+        let mut request_ids = Vec::new();
+        for uri in ["/health", "/api/sensors/not-a-mac/latest"] {
+            let request = Request::get(uri)
+                .body(Body::empty())
+                .expect("valid request");
+            let response = create_router(unconnected_state())
+                .oneshot(request)
+                .await
+                .expect("infallible router");
+            let request_id = response
+                .headers()
+                .get(middleware::REQUEST_ID_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .expect("request ID header");
+            let request_id = uuid::Uuid::parse_str(request_id).expect("UUID request ID");
+            assert_eq!(request_id.get_version_num(), 4);
+            request_ids.push(request_id);
+        }
+        assert_ne!(request_ids.first(), request_ids.last());
+    }
+
+    #[tokio::test]
+    async fn test_api_key_not_required_when_unconfigured() {
+        assert_eq!(
+            status_of(unconnected_state(), "/metrics", None).await,
+            StatusCode::OK
+        );
+    }
 }
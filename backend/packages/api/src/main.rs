@@ -3,15 +3,25 @@
 //! Main entry point for the REST API server that provides access to Ruuvi
 //! sensor data.
 
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+};
+
 use anyhow::Result;
 // Import our modular API library
 use api::{
+    config::StoreBackend,
     create_router,
     AppState,
     Config,
 };
+use redis_store::RedisStore;
 use tokio::net::TcpListener;
-use tracing::info;
+use tracing::{
+    info,
+    warn,
+};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -20,16 +30,34 @@ async fn main() -> Result<()> {
     let config = Config::from_env()?;
     info!("Starting API server on port {}", config.api_port);
     info!("Database URL: {}", config.database_url);
+    info!("Default timezone: {}", config.default_timezone);
 
-    let state = AppState::new(config.clone()).await?;
+    let mut state = AppState::new(config.clone()).await?;
     info!("Connected to PostgreSQL database with TimescaleDB");
 
+    if let StoreBackend::Redis(redis_url) = &config.store_backend {
+        state = state.with_readings(Arc::new(RedisStore::new(redis_url).await?));
+        info!("Serving readings from Redis at {}", redis_url);
+    }
+
+    if config.bootstrap_continuous_aggregates {
+        if state.store.ensure_continuous_aggregates().await? {
+            info!("Continuous aggregates are in place");
+        } else {
+            warn!("TimescaleDB is not installed; aggregates are computed from raw data");
+        }
+    }
+
     let app = create_router(state);
 
     let listener = TcpListener::bind(format!("0.0.0.0:{}", config.api_port)).await?;
     info!("API server listening on {}", listener.local_addr()?);
 
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
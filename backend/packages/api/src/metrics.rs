@@ -0,0 +1,244 @@
+//! Prometheus metrics of the API server
+
+use std::{
+    collections::BTreeMap,
+    fmt::{
+        Display,
+        Write,
+    },
+    sync::{
+        Mutex,
+        MutexGuard,
+        PoisonError,
+    },
+    time::Duration,
+};
+
+/// Content type of the Prometheus text exposition format
+pub const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Upper bounds, in seconds, of the handler latency histogram buckets. A final
+/// unbounded bucket catches everything slower.
+pub const LATENCY_BUCKETS_SECONDS: [f64; 10] =
+    [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 10.0];
+
+const BUCKET_COUNT: usize = 11;
+
+/// Route label of requests that matched no route
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+const REQUESTS: &str = "ruuvi_api_requests_total";
+const DURATION: &str = "ruuvi_api_request_duration_seconds";
+const DURATION_BUCKET: &str = "ruuvi_api_request_duration_seconds_bucket";
+const DURATION_SUM: &str = "ruuvi_api_request_duration_seconds_sum";
+const DURATION_COUNT: &str = "ruuvi_api_request_duration_seconds_count";
+const POOL: &str = "ruuvi_api_db_pool_connections";
+
+/// Route a request matched, labelled by its template such as
+/// `/api/sensors/{sensor_mac}/latest` so the number of series stays bounded
+/// however many sensors are queried
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Route {
+    pub method: String,
+    pub path: String,
+}
+
+impl Route {
+    fn labels(&self) -> String {
+        format!(
+            "method=\"{}\",route=\"{}\"",
+            escape_label(&self.method),
+            escape_label(&self.path)
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct Latency {
+    buckets: [u64; BUCKET_COUNT],
+    count: u64,
+    sum_seconds: f64,
+}
+
+/// Route and response status a request is counted under
+type RequestKey = (Route, u16);
+
+#[derive(Debug, Default)]
+struct Registry {
+    requests: BTreeMap<RequestKey, u64>,
+    latencies: BTreeMap<Route, Latency>,
+}
+
+/// Connections of the database pool at the time of a scrape
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolUsage {
+    pub active: usize,
+    pub idle: usize,
+}
+
+/// Request counters and handler latency histograms, per route
+#[derive(Debug, Default)]
+pub struct ApiMetrics {
+    registry: Mutex<Registry>,
+}
+
+impl ApiMetrics {
+    /// Record a handled request
+    pub fn record(&self, route: Route, status: u16, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let bucket = LATENCY_BUCKETS_SECONDS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS_SECONDS.len());
+
+        let mut registry = self.lock();
+        let requests = registry
+            .requests
+            .entry((route.clone(), status))
+            .or_default();
+        *requests = requests.saturating_add(1);
+
+        let latency = registry.latencies.entry(route).or_default();
+        if let Some(bucket) = latency.buckets.get_mut(bucket) {
+            *bucket = bucket.saturating_add(1);
+        }
+        latency.count = latency.count.saturating_add(1);
+        latency.sum_seconds += seconds;
+    }
+
+    /// Render every metric in the Prometheus text format
+    pub fn render(&self, pool: PoolUsage) -> String {
+        let registry = self.lock();
+        let mut output = String::new();
+
+        describe(&mut output, REQUESTS, "Requests handled", "counter");
+        for ((route, status), count) in &registry.requests {
+            let labels = format!("{},status=\"{status}\"", route.labels());
+            sample(&mut output, REQUESTS, &labels, count);
+        }
+
+        describe(
+            &mut output,
+            DURATION,
+            "Time spent handling requests",
+            "histogram",
+        );
+        for (route, latency) in &registry.latencies {
+            let labels = route.labels();
+            let bounds = LATENCY_BUCKETS_SECONDS
+                .iter()
+                .map(ToString::to_string)
+                .chain(["+Inf".to_string()]);
+            let mut cumulative = 0_u64;
+            for (bound, count) in bounds.zip(latency.buckets) {
+                cumulative = cumulative.saturating_add(count);
+                let bucket_labels = format!("{labels},le=\"{bound}\"");
+                sample(&mut output, DURATION_BUCKET, &bucket_labels, cumulative);
+            }
+            sample(&mut output, DURATION_SUM, &labels, latency.sum_seconds);
+            sample(&mut output, DURATION_COUNT, &labels, latency.count);
+        }
+
+        describe(&mut output, POOL, "Database pool connections", "gauge");
+        sample(&mut output, POOL, "state=\"active\"", pool.active);
+        sample(&mut output, POOL, "state=\"idle\"", pool.idle);
+
+        output
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Registry> {
+        self.registry.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Write the `HELP` and `TYPE` lines of a metric
+fn describe(output: &mut String, name: &str, help: &str, kind: &str) {
+    // Writing to a String cannot fail
+    let _ = writeln!(output, "# HELP {name} {help}\n# TYPE {name} {kind}");
+}
+
+/// Write one sample of a metric
+fn sample(output: &mut String, name: &str, labels: &str, value: impl Display) {
+    let _ = writeln!(output, "{name}{{{labels}}} {value}");
+}
+
+/// Escape a label value as the text format requires
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDLE_POOL: PoolUsage = PoolUsage { active: 0, idle: 2 };
+
+    fn get(path: &str) -> Route {
+        Route {
+            method: "GET".to_string(),
+            path: path.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_render_counts_requests_per_route_and_status() {
+        let metrics = ApiMetrics::default();
+        metrics.record(get("/health"), 200, Duration::from_millis(1));
+        metrics.record(get("/health"), 200, Duration::from_millis(2));
+        metrics.record(
+            get("/api/sensors/{sensor_mac}/latest"),
+            404,
+            Duration::from_millis(30),
+        );
+
+        let output = metrics.render(IDLE_POOL);
+
+        assert!(output.contains(
+            "ruuvi_api_requests_total{method=\"GET\",route=\"/health\",status=\"200\"} 2\n"
+        ));
+        let labels = "method=\"GET\",route=\"/api/sensors/{sensor_mac}/latest\",status=\"404\"";
+        assert!(output.contains(&format!("ruuvi_api_requests_total{{{labels}}} 1\n")));
+    }
+
+    #[test]
+    fn test_render_histogram_is_cumulative() {
+        let metrics = ApiMetrics::default();
+        metrics.record(get("/health"), 200, Duration::from_millis(1));
+        metrics.record(get("/health"), 200, Duration::from_millis(200));
+        metrics.record(get("/health"), 200, Duration::from_secs(20));
+
+        let output = metrics.render(IDLE_POOL);
+        let labels = "method=\"GET\",route=\"/health\"";
+
+        for (bound, count) in [
+            ("0.005", 1),
+            ("0.1", 1),
+            ("0.25", 2),
+            ("10", 2),
+            ("+Inf", 3),
+        ] {
+            let bucket = format!("{DURATION_BUCKET}{{{labels},le=\"{bound}\"}} {count}\n");
+            assert!(output.contains(&bucket), "missing {bucket} in {output}");
+        }
+        assert!(output.contains(&format!(
+            "ruuvi_api_request_duration_seconds_count{{{labels}}} 3\n"
+        )));
+    }
+
+    #[test]
+    fn test_render_pool_gauge() {
+        let output = ApiMetrics::default().render(PoolUsage { active: 3, idle: 1 });
+
+        assert!(output.contains("# TYPE ruuvi_api_db_pool_connections gauge\n"));
+        assert!(output.contains("ruuvi_api_db_pool_connections{state=\"active\"} 3\n"));
+        assert!(output.contains("ruuvi_api_db_pool_connections{state=\"idle\"} 1\n"));
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
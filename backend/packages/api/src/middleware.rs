@@ -0,0 +1,437 @@
+//! Middleware applied to every API route
+
+use std::{
+    net::{
+        IpAddr,
+        Ipv4Addr,
+        SocketAddr,
+    },
+    time::Instant,
+};
+
+use axum::{
+    body::{
+        self,
+        Body,
+        Bytes,
+        HttpBody,
+    },
+    extract::{
+        ConnectInfo,
+        MatchedPath,
+        Request,
+        State,
+    },
+    http::{
+        header,
+        HeaderMap,
+        HeaderValue,
+        Method,
+    },
+    middleware::Next,
+    response::{
+        IntoResponse,
+        Response,
+    },
+};
+use chrono::{
+    DateTime,
+    SecondsFormat,
+};
+use chrono_tz::Tz;
+use futures::StreamExt;
+use serde_json::Value;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        is_public_path,
+        require_api_key,
+    },
+    errors::ApiError,
+    extract::requested_timezone,
+    handlers::NDJSON_CONTENT_TYPE,
+    idempotency::{
+        Reservation,
+        StoredResponse,
+    },
+    metrics::{
+        Route,
+        UNMATCHED_ROUTE,
+    },
+    rate_limit::retry_after_seconds,
+    state::AppState,
+};
+
+/// Largest JSON response body buffered for timestamp localization
+pub const MAX_LOCALIZED_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+/// Convert every timestamp in JSON and NDJSON responses to the zone chosen by
+/// `?tz=`, falling back to the configured default timezone.
+///
+/// Handlers keep working in UTC; only the serialized output is shifted.
+/// Unknown zones are rejected with 400 before the handler runs. JSON bodies
+/// larger than [`MAX_LOCALIZED_BODY_BYTES`], or of unknown size, are passed
+/// through in UTC rather than buffered. CSV, Parquet and WebSocket outputs
+/// apply the timezone in their own serializers.
+pub async fn localize_timestamps(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let timezone = match requested_timezone(request.uri()) {
+        Ok(timezone) => timezone.unwrap_or(state.default_timezone),
+        Err(error) => return error.into_response(),
+    };
+
+    let response = next.run(request).await;
+    if timezone == Tz::UTC {
+        return response;
+    }
+
+    match content_type(response.headers()) {
+        Some("application/json") => localize_json_response(response, timezone).await,
+        Some(NDJSON_CONTENT_TYPE) => localize_ndjson_response(response, timezone),
+        _ => response,
+    }
+}
+
+fn content_type(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::CONTENT_TYPE)?
+        .to_str()
+        .ok()?
+        .split(';')
+        .next()
+        .map(str::trim)
+}
+
+async fn localize_json_response(response: Response, timezone: Tz) -> Response {
+    let within_cap = response
+        .body()
+        .size_hint()
+        .upper()
+        .and_then(|upper| usize::try_from(upper).ok())
+        .is_some_and(|upper| upper <= MAX_LOCALIZED_BODY_BYTES);
+    if !within_cap {
+        tracing::debug!("Response body too large to localize, serving UTC timestamps");
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = body::to_bytes(body, MAX_LOCALIZED_BODY_BYTES).await else {
+        return ApiError::internal_error("Failed to read response body").into_response();
+    };
+
+    let bytes = localize_json_bytes(bytes, timezone);
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+fn localize_ndjson_response(response: Response, timezone: Tz) -> Response {
+    let (parts, body) = response.into_parts();
+    let mut chunks = body.into_data_stream();
+
+    // Chunks are not guaranteed to end on a line boundary, so carry partial
+    // lines over until their newline arrives
+    let lines = async_stream::stream! {
+        let mut pending = Vec::new();
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk?;
+            pending.extend_from_slice(&chunk);
+            if let Some(last_newline) = pending.iter().rposition(|byte| *byte == b'\n') {
+                let rest = pending.split_off(last_newline.saturating_add(1));
+                let complete = std::mem::replace(&mut pending, rest);
+                yield Ok::<_, axum::Error>(localize_ndjson_lines(&complete, timezone));
+            }
+        }
+        if !pending.is_empty() {
+            yield Ok(localize_ndjson_lines(&pending, timezone));
+        }
+    };
+
+    Response::from_parts(parts, Body::from_stream(lines))
+}
+
+fn localize_ndjson_lines(lines: &[u8], timezone: Tz) -> Bytes {
+    let mut output = Vec::with_capacity(lines.len());
+    for line in lines.split_inclusive(|byte| *byte == b'\n') {
+        let (content, newline) = match line.split_last() {
+            Some((b'\n', content)) => (content, true),
+            _ => (line, false),
+        };
+        output.extend_from_slice(&localize_json_bytes(
+            Bytes::copy_from_slice(content),
+            timezone,
+        ));
+        if newline {
+            output.push(b'\n');
+        }
+    }
+    Bytes::from(output)
+}
+
+/// Rewrite timestamps in a JSON document, leaving non-JSON bodies untouched
+fn localize_json_bytes(bytes: Bytes, timezone: Tz) -> Bytes {
+    let Ok(mut value) = serde_json::from_slice::<Value>(&bytes) else {
+        return bytes;
+    };
+    localize_value(&mut value, timezone);
+    serde_json::to_vec(&value).map_or(bytes, Bytes::from)
+}
+
+/// Convert every RFC 3339 timestamp string in `value` to `timezone`
+pub fn localize_value(value: &mut Value, timezone: Tz) {
+    match value {
+        Value::String(text) => {
+            if let Ok(timestamp) = DateTime::parse_from_rfc3339(text) {
+                *text = timestamp
+                    .with_timezone(&timezone)
+                    .to_rfc3339_opts(SecondsFormat::AutoSi, true);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                localize_value(item, timezone);
+            }
+        }
+        Value::Object(fields) => {
+            for field in fields.values_mut() {
+                localize_value(field, timezone);
+            }
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
+
+/// Request header carrying the client's idempotency key
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Response header marking a response replayed from the idempotency store
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Execute a POST carrying an `Idempotency-Key` at most once per key.
+///
+/// Replays return the recorded status and body. Server errors are not
+/// recorded so the client can retry them, and a retry that arrives while the
+/// first attempt is still running gets 409 Conflict.
+pub async fn idempotent_requests(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method() != Method::POST {
+        return next.run(request).await;
+    }
+    let Some(client_key) = request
+        .headers()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return next.run(request).await;
+    };
+
+    // Scope keys to the endpoint so one key cannot replay another route's result
+    let key = format!("{} {client_key}", request.uri().path());
+    match state.idempotency.reserve(&key) {
+        Reservation::Acquired => {}
+        Reservation::InFlight => {
+            return ApiError::conflict("A request with this Idempotency-Key is still in progress")
+                .into_response();
+        }
+        Reservation::Completed(stored) => {
+            let mut response = stored.into_response();
+            response
+                .headers_mut()
+                .insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+            return response;
+        }
+    }
+
+    let response = next.run(request).await;
+    if response.status().is_server_error() {
+        state.idempotency.release(&key);
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let Ok(bytes) = body::to_bytes(body, usize::MAX).await else {
+        state.idempotency.release(&key);
+        return ApiError::internal_error("Failed to read response body").into_response();
+    };
+    state.idempotency.complete(
+        &key,
+        StoredResponse {
+            status: parts.status,
+            content_type: parts.headers.get(header::CONTENT_TYPE).cloned(),
+            body: bytes.clone(),
+        },
+    );
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// Reject requests without the configured API key with 401, except for the
+/// health checks. Does nothing when no API key is configured.
+pub async fn authenticate(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if is_public_path(request.uri().path()) {
+        return next.run(request).await;
+    }
+    match require_api_key(state.api_key.as_ref(), request.headers()) {
+        Ok(()) => next.run(request).await,
+        Err(error) => {
+            let mut response = error.into_response();
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            response
+        }
+    }
+}
+
+/// Reject clients that exceed the configured request rate with 429 and a
+/// `Retry-After` header. Health checks are never limited.
+///
+/// Clients are told apart by the peer address the server recorded; without
+/// one every request shares a single bucket.
+pub async fn limit_rate(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(limiter) = state.rate_limiter.as_ref() else {
+        return next.run(request).await;
+    };
+    if is_public_path(request.uri().path()) {
+        return next.run(request).await;
+    }
+    let client = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |info| info.0.ip());
+
+    match limiter.check(client) {
+        Ok(()) => next.run(request).await,
+        Err(wait) => {
+            let retry_after_seconds = retry_after_seconds(wait);
+            let mut response = ApiError::rate_limited(retry_after_seconds).into_response();
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after_seconds));
+            response
+        }
+    }
+}
+
+/// Response header carrying the ID assigned to a request
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Give every request a fresh UUID, returned in `x-request-id`, and handle it
+/// inside a tracing span carrying that ID so its logs can be correlated
+pub async fn assign_request_id(request: Request, next: Next) -> Response {
+    let request_id = Uuid::new_v4();
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %request.method(),
+        path = request.uri().path(),
+    );
+
+    let mut response = next.run(request).instrument(span).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id.to_string()) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// Count every request and time its handling for `/metrics`, labelled by
+/// the route it matched
+pub async fn record_metrics(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = Route {
+        method: request.method().to_string(),
+        path: request
+            .extensions()
+            .get::<MatchedPath>()
+            .map_or(UNMATCHED_ROUTE, MatchedPath::as_str)
+            .to_string(),
+    };
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+    state
+        .metrics
+        .record(route, response.status().as_u16(), started.elapsed());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_localize_value_shifts_nested_timestamps() {
+        let mut value = json!({
+            "sensor_mac": "AA:BB:CC:DD:EE:FF",
+            "timestamp": "2024-01-15T10:00:00Z",
+            "buckets": [{ "bucket": "2024-07-15T10:00:00.5Z", "reading_count": 3 }],
+        });
+
+        localize_value(&mut value, Tz::Europe__Helsinki);
+
+        assert_eq!(
+            value,
+            json!({
+                "sensor_mac": "AA:BB:CC:DD:EE:FF",
+                "timestamp": "2024-01-15T12:00:00+02:00",
+                "buckets": [{ "bucket": "2024-07-15T13:00:00.500+03:00", "reading_count": 3 }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_localize_ndjson_lines_keeps_line_structure() {
+        let input =
+            b"{\"bucket\":\"2024-01-15T10:00:00Z\"}\n{\"bucket\":\"2024-01-15T11:00:00Z\"}\n";
+
+        let output = localize_ndjson_lines(input, Tz::Asia__Tokyo);
+
+        assert_eq!(
+            output,
+            Bytes::from_static(
+                b"{\"bucket\":\"2024-01-15T19:00:00+09:00\"}\n{\"bucket\":\"2024-01-15T20:00:00+09:00\"}\n"
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_oversized_json_response_passed_through() {
+        let oversized = format!(
+            "[\"2024-01-15T10:00:00Z\",\"{}\"]",
+            "x".repeat(MAX_LOCALIZED_BODY_BYTES)
+        );
+        let response = Response::new(Body::from(oversized.clone()));
+
+        let response = localize_json_response(response, Tz::Asia__Tokyo).await;
+        let bytes = body::to_bytes(response.into_body(), usize::MAX).await;
+
+        assert!(matches!(bytes, Ok(ref bytes) if *bytes == oversized.as_bytes()));
+    }
+
+    #[tokio::test]
+    async fn test_json_response_within_cap_localized() {
+        let response = Response::new(Body::from("[\"2024-01-15T10:00:00Z\"]"));
+
+        let response = localize_json_response(response, Tz::Asia__Tokyo).await;
+        let bytes = body::to_bytes(response.into_body(), usize::MAX).await;
+
+        assert!(matches!(bytes, Ok(ref bytes) if *bytes == b"[\"2024-01-15T19:00:00+09:00\"]"[..]));
+    }
+
+    #[test]
+    fn test_non_json_body_untouched() {
+        let body = Bytes::from_static(b"OK");
+        assert_eq!(localize_json_bytes(body.clone(), Tz::Asia__Tokyo), body);
+    }
+}
@@ -2,36 +2,595 @@
 
 use serde::Deserialize;
 
+use crate::extract::KnownParams;
+
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct HistoricalQuery {
     pub start: Option<String>,
     pub end: Option<String>,
     pub limit: Option<i64>,
+    pub preset: Option<String>,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub units: Option<String>,
+    pub max_points: Option<i64>,
+    pub order: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct GatewayHistoryQuery {
+    pub start: Option<String>,
+    pub end: Option<String>,
+    pub preset: Option<String>,
+    pub limit: Option<i64>,
+    pub units: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct HistoryCsvQuery {
+    pub start: Option<String>,
+    pub end: Option<String>,
+    pub preset: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct TimeBucketQuery {
+    pub start: Option<String>,
+    pub end: Option<String>,
+    pub interval: Option<String>,
+    pub preset: Option<String>,
+    pub units: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct StorageEstimateQuery {
+    pub sensor_count: Option<i32>,
+    pub interval_seconds: Option<i32>,
+    pub retention_years: Option<i32>,
+    pub observed: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct TimeRangeQuery {
+    pub start: Option<String>,
+    pub end: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct CorrelationQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub metric: Option<String>,
+    pub start: Option<String>,
+    pub end: Option<String>,
+    pub interval: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct TrendsQuery {
+    pub metrics: Option<String>,
+    pub hours: Option<i32>,
+    pub bucket: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct HoursQuery {
+    pub hours: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct TemperatureTrendQuery {
+    pub hours: Option<i32>,
+    pub interval: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct BatteryProjectionQuery {
+    pub days: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct DaysQuery {
+    pub days: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct ExportQuery {
+    pub format: Option<String>,
+    pub start: Option<String>,
+    pub end: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct ActiveSensorsQuery {
+    pub dedup_by: Option<String>,
+    pub gateway_mac: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct LatestReadingsQuery {
+    pub macs: Option<String>,
+    pub units: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct CompareQuery {
+    pub macs: Option<String>,
+    pub start: Option<String>,
+    pub end: Option<String>,
+    pub interval: Option<String>,
+    pub units: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct UnitsQuery {
+    pub units: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct LiveQuery {
+    pub sensor_mac: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct ThresholdDeleteQuery {
+    pub sensor_mac: Option<String>,
+}
+
+impl KnownParams for HistoricalQuery {
+    const FIELDS: &'static [&'static str] = &[
+        "start",
+        "end",
+        "limit",
+        "preset",
+        "before",
+        "after",
+        "units",
+        "max_points",
+        "order",
+    ];
+}
+
+impl KnownParams for GatewayHistoryQuery {
+    const FIELDS: &'static [&'static str] = &["start", "end", "preset", "limit", "units"];
+}
+
+impl KnownParams for HistoryCsvQuery {
+    const FIELDS: &'static [&'static str] = &["start", "end", "preset"];
+}
+
+impl KnownParams for TimeBucketQuery {
+    const FIELDS: &'static [&'static str] = &["start", "end", "interval", "preset", "units"];
+}
+
+impl KnownParams for StorageEstimateQuery {
+    const FIELDS: &'static [&'static str] = &[
+        "sensor_count",
+        "interval_seconds",
+        "retention_years",
+        "observed",
+    ];
+}
+
+impl KnownParams for TimeRangeQuery {
+    const FIELDS: &'static [&'static str] = &["start", "end"];
+}
+
+impl KnownParams for CorrelationQuery {
+    const FIELDS: &'static [&'static str] = &["from", "to", "metric", "start", "end", "interval"];
+}
+
+impl KnownParams for TrendsQuery {
+    const FIELDS: &'static [&'static str] = &["metrics", "hours", "bucket"];
+}
+
+impl KnownParams for HoursQuery {
+    const FIELDS: &'static [&'static str] = &["hours"];
+}
+
+impl KnownParams for TemperatureTrendQuery {
+    const FIELDS: &'static [&'static str] = &["hours", "interval"];
+}
+
+impl KnownParams for BatteryProjectionQuery {
+    const FIELDS: &'static [&'static str] = &["days"];
+}
+
+impl KnownParams for DaysQuery {
+    const FIELDS: &'static [&'static str] = &["days"];
+}
+
+impl KnownParams for ExportQuery {
+    const FIELDS: &'static [&'static str] = &["format", "start", "end"];
+}
+
+impl KnownParams for ActiveSensorsQuery {
+    const FIELDS: &'static [&'static str] = &["dedup_by", "gateway_mac"];
+}
+
+impl KnownParams for LatestReadingsQuery {
+    const FIELDS: &'static [&'static str] = &["macs", "units"];
+}
+
+impl KnownParams for CompareQuery {
+    const FIELDS: &'static [&'static str] = &["macs", "start", "end", "interval", "units"];
+}
+
+impl KnownParams for UnitsQuery {
+    const FIELDS: &'static [&'static str] = &["units"];
+}
+
+impl KnownParams for LiveQuery {
+    const FIELDS: &'static [&'static str] = &["sensor_mac"];
+}
+
+impl KnownParams for ThresholdDeleteQuery {
+    const FIELDS: &'static [&'static str] = &["sensor_mac"];
+}
+
+impl HistoricalQuery {
+    pub const fn new() -> Self {
+        Self {
+            start: None,
+            end: None,
+            limit: None,
+            preset: None,
+            before: None,
+            after: None,
+            units: None,
+            max_points: None,
+            order: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_start(mut self, start: String) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    #[must_use]
+    pub fn with_end(mut self, end: String) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    #[must_use]
+    pub const fn with_limit(mut self, limit: i64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    #[must_use]
+    pub fn with_preset(mut self, preset: String) -> Self {
+        self.preset = Some(preset);
+        self
+    }
+
+    #[must_use]
+    pub fn with_before(mut self, cursor: String) -> Self {
+        self.before = Some(cursor);
+        self
+    }
+
+    #[must_use]
+    pub fn with_after(mut self, cursor: String) -> Self {
+        self.after = Some(cursor);
+        self
+    }
+
+    #[must_use]
+    pub fn with_units(mut self, units: String) -> Self {
+        self.units = Some(units);
+        self
+    }
+
+    #[must_use]
+    pub const fn with_max_points(mut self, max_points: i64) -> Self {
+        self.max_points = Some(max_points);
+        self
+    }
+
+    #[must_use]
+    pub fn with_order(mut self, order: String) -> Self {
+        self.order = Some(order);
+        self
+    }
+}
+
+impl Default for HistoricalQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GatewayHistoryQuery {
+    pub const fn new() -> Self {
+        Self {
+            start: None,
+            end: None,
+            preset: None,
+            limit: None,
+            units: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_start(mut self, start: String) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    #[must_use]
+    pub fn with_end(mut self, end: String) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    #[must_use]
+    pub fn with_preset(mut self, preset: String) -> Self {
+        self.preset = Some(preset);
+        self
+    }
+
+    #[must_use]
+    pub const fn with_limit(mut self, limit: i64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    #[must_use]
+    pub fn with_units(mut self, units: String) -> Self {
+        self.units = Some(units);
+        self
+    }
+}
+
+impl Default for GatewayHistoryQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HistoryCsvQuery {
+    pub const fn new() -> Self {
+        Self {
+            start: None,
+            end: None,
+            preset: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_start(mut self, start: String) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    #[must_use]
+    pub fn with_end(mut self, end: String) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    #[must_use]
+    pub fn with_preset(mut self, preset: String) -> Self {
+        self.preset = Some(preset);
+        self
+    }
+}
+
+impl Default for HistoryCsvQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimeRangeQuery {
+    pub const fn new() -> Self {
+        Self {
+            start: None,
+            end: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_start(mut self, start: String) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    #[must_use]
+    pub fn with_end(mut self, end: String) -> Self {
+        self.end = Some(end);
+        self
+    }
+}
+
+impl Default for TimeRangeQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimeBucketQuery {
+    pub const fn new() -> Self {
+        Self {
+            start: None,
+            end: None,
+            interval: None,
+            preset: None,
+            units: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_start(mut self, start: String) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    #[must_use]
+    pub fn with_end(mut self, end: String) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    #[must_use]
+    pub fn with_interval(mut self, interval: String) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    #[must_use]
+    pub fn with_preset(mut self, preset: String) -> Self {
+        self.preset = Some(preset);
+        self
+    }
+
+    #[must_use]
+    pub fn with_units(mut self, units: String) -> Self {
+        self.units = Some(units);
+        self
+    }
+}
+
+impl Default for TimeBucketQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TrendsQuery {
+    pub const fn new() -> Self {
+        Self {
+            metrics: None,
+            hours: None,
+            bucket: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_metrics(mut self, metrics: String) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    #[must_use]
+    pub const fn with_hours(mut self, hours: i32) -> Self {
+        self.hours = Some(hours);
+        self
+    }
+
+    #[must_use]
+    pub fn with_bucket(mut self, bucket: String) -> Self {
+        self.bucket = Some(bucket);
+        self
+    }
+}
+
+impl Default for TrendsQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BatteryProjectionQuery {
+    pub const fn new() -> Self {
+        Self { days: None }
+    }
+
+    #[must_use]
+    pub const fn with_days(mut self, days: i32) -> Self {
+        self.days = Some(days);
+        self
+    }
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
-pub struct TimeBucketQuery {
-    pub start: Option<String>,
-    pub end: Option<String>,
-    pub interval: Option<String>,
+impl Default for BatteryProjectionQuery {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
-pub struct StorageEstimateQuery {
-    pub sensor_count: Option<i32>,
-    pub interval_seconds: Option<i32>,
-    pub retention_years: Option<i32>,
+impl DaysQuery {
+    pub const fn new() -> Self {
+        Self { days: None }
+    }
+
+    #[must_use]
+    pub const fn with_days(mut self, days: i32) -> Self {
+        self.days = Some(days);
+        self
+    }
 }
 
-impl HistoricalQuery {
+impl Default for DaysQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HoursQuery {
+    pub const fn new() -> Self {
+        Self { hours: None }
+    }
+
+    #[must_use]
+    pub const fn with_hours(mut self, hours: i32) -> Self {
+        self.hours = Some(hours);
+        self
+    }
+}
+
+impl Default for HoursQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TemperatureTrendQuery {
+    pub const fn new() -> Self {
+        Self {
+            hours: None,
+            interval: None,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_hours(mut self, hours: i32) -> Self {
+        self.hours = Some(hours);
+        self
+    }
+
+    #[must_use]
+    pub fn with_interval(mut self, interval: String) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+}
+
+impl Default for TemperatureTrendQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExportQuery {
     pub const fn new() -> Self {
         Self {
+            format: None,
             start: None,
             end: None,
-            limit: None,
         }
     }
 
+    #[must_use]
+    pub fn with_format(mut self, format: String) -> Self {
+        self.format = Some(format);
+        self
+    }
+
     #[must_use]
     pub fn with_start(mut self, start: String) -> Self {
         self.start = Some(start);
@@ -43,29 +602,85 @@ impl HistoricalQuery {
         self.end = Some(end);
         self
     }
+}
+
+impl Default for ExportQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ActiveSensorsQuery {
+    pub const fn new() -> Self {
+        Self {
+            dedup_by: None,
+            gateway_mac: None,
+        }
+    }
 
     #[must_use]
-    pub const fn with_limit(mut self, limit: i64) -> Self {
-        self.limit = Some(limit);
+    pub fn with_dedup_by(mut self, dedup_by: String) -> Self {
+        self.dedup_by = Some(dedup_by);
+        self
+    }
+
+    #[must_use]
+    pub fn with_gateway_mac(mut self, gateway_mac: String) -> Self {
+        self.gateway_mac = Some(gateway_mac);
         self
     }
 }
 
-impl Default for HistoricalQuery {
+impl Default for ActiveSensorsQuery {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl TimeBucketQuery {
+impl LatestReadingsQuery {
+    pub const fn new() -> Self {
+        Self {
+            macs: None,
+            units: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_macs(mut self, macs: String) -> Self {
+        self.macs = Some(macs);
+        self
+    }
+
+    #[must_use]
+    pub fn with_units(mut self, units: String) -> Self {
+        self.units = Some(units);
+        self
+    }
+}
+
+impl Default for LatestReadingsQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CompareQuery {
     pub const fn new() -> Self {
         Self {
+            macs: None,
             start: None,
             end: None,
             interval: None,
+            units: None,
         }
     }
 
+    #[must_use]
+    pub fn with_macs(mut self, macs: String) -> Self {
+        self.macs = Some(macs);
+        self
+    }
+
     #[must_use]
     pub fn with_start(mut self, start: String) -> Self {
         self.start = Some(start);
@@ -83,9 +698,69 @@ impl TimeBucketQuery {
         self.interval = Some(interval);
         self
     }
+
+    #[must_use]
+    pub fn with_units(mut self, units: String) -> Self {
+        self.units = Some(units);
+        self
+    }
 }
 
-impl Default for TimeBucketQuery {
+impl Default for CompareQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UnitsQuery {
+    pub const fn new() -> Self {
+        Self { units: None }
+    }
+
+    #[must_use]
+    pub fn with_units(mut self, units: String) -> Self {
+        self.units = Some(units);
+        self
+    }
+}
+
+impl Default for UnitsQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LiveQuery {
+    pub const fn new() -> Self {
+        Self { sensor_mac: None }
+    }
+
+    #[must_use]
+    pub fn with_sensor_mac(mut self, sensor_mac: String) -> Self {
+        self.sensor_mac = Some(sensor_mac);
+        self
+    }
+}
+
+impl Default for LiveQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ThresholdDeleteQuery {
+    pub const fn new() -> Self {
+        Self { sensor_mac: None }
+    }
+
+    #[must_use]
+    pub fn with_sensor_mac(mut self, sensor_mac: String) -> Self {
+        self.sensor_mac = Some(sensor_mac);
+        self
+    }
+}
+
+impl Default for ThresholdDeleteQuery {
     fn default() -> Self {
         Self::new()
     }
@@ -97,6 +772,7 @@ impl StorageEstimateQuery {
             sensor_count: None,
             interval_seconds: None,
             retention_years: None,
+            observed: None,
         }
     }
 
@@ -117,6 +793,12 @@ impl StorageEstimateQuery {
         self.retention_years = Some(years);
         self
     }
+
+    #[must_use]
+    pub const fn with_observed(mut self, observed: bool) -> Self {
+        self.observed = Some(observed);
+        self
+    }
 }
 
 impl Default for StorageEstimateQuery {
@@ -148,6 +830,34 @@ mod tests {
         assert_eq!(query.start, None);
         assert_eq!(query.end, None);
         assert_eq!(query.limit, Some(50));
+        assert_eq!(query.before, None);
+        assert_eq!(query.after, None);
+    }
+
+    #[test]
+    fn test_historical_query_cursors() {
+        let query = HistoricalQuery::new()
+            .with_before("older".to_string())
+            .with_after("newer".to_string());
+
+        assert_eq!(query.before, Some("older".to_string()));
+        assert_eq!(query.after, Some("newer".to_string()));
+    }
+
+    #[test]
+    fn test_historical_query_max_points() {
+        let query = HistoricalQuery::new().with_max_points(500);
+
+        assert_eq!(query.max_points, Some(500));
+        assert_eq!(HistoricalQuery::default().max_points, None);
+    }
+
+    #[test]
+    fn test_historical_query_order() {
+        let query = HistoricalQuery::new().with_order("asc".to_string());
+
+        assert_eq!(query.order, Some("asc".to_string()));
+        assert_eq!(HistoricalQuery::default().order, None);
     }
 
     #[test]
@@ -162,6 +872,27 @@ mod tests {
         assert_eq!(query.interval, Some("1h".to_string()));
     }
 
+    #[test]
+    fn test_preset_builders() {
+        let historical = HistoricalQuery::new().with_preset("today".to_string());
+        assert_eq!(historical.preset, Some("today".to_string()));
+
+        let time_bucket = TimeBucketQuery::new().with_preset("7d".to_string());
+        assert_eq!(time_bucket.preset, Some("7d".to_string()));
+        assert_eq!(TimeBucketQuery::default().preset, None);
+    }
+
+    #[test]
+    fn test_time_range_query_builder() {
+        let query = TimeRangeQuery::new()
+            .with_start("2024-01-01T00:00:00Z".to_string())
+            .with_end("2024-01-02T00:00:00Z".to_string());
+
+        assert_eq!(query.start, Some("2024-01-01T00:00:00Z".to_string()));
+        assert_eq!(query.end, Some("2024-01-02T00:00:00Z".to_string()));
+        assert_eq!(TimeRangeQuery::default(), TimeRangeQuery::new());
+    }
+
     #[test]
     fn test_time_bucket_query_interval_only() {
         let query = TimeBucketQuery::new().with_interval("15m".to_string());
@@ -171,6 +902,186 @@ mod tests {
         assert_eq!(query.interval, Some("15m".to_string()));
     }
 
+    #[test]
+    fn test_trends_query_builder() {
+        let query = TrendsQuery::new()
+            .with_metrics("temperature,humidity".to_string())
+            .with_hours(48)
+            .with_bucket("15m".to_string());
+
+        assert_eq!(query.metrics, Some("temperature,humidity".to_string()));
+        assert_eq!(query.hours, Some(48));
+        assert_eq!(query.bucket, Some("15m".to_string()));
+        assert_eq!(TrendsQuery::default(), TrendsQuery::new());
+    }
+
+    #[test]
+    fn test_battery_projection_query_builder() {
+        let query = BatteryProjectionQuery::new().with_days(14);
+
+        assert_eq!(query.days, Some(14));
+        assert_eq!(BatteryProjectionQuery::default().days, None);
+    }
+
+    #[test]
+    fn test_days_query_builder() {
+        let query = DaysQuery::new().with_days(90);
+
+        assert_eq!(query.days, Some(90));
+        assert_eq!(DaysQuery::default().days, None);
+    }
+
+    #[test]
+    fn test_hours_query_builder() {
+        let query = HoursQuery::new().with_hours(48);
+
+        assert_eq!(query.hours, Some(48));
+        assert_eq!(HoursQuery::default().hours, None);
+    }
+
+    #[test]
+    fn test_temperature_trend_query_builder() {
+        let query = TemperatureTrendQuery::new()
+            .with_hours(6)
+            .with_interval("5m".to_string());
+
+        assert_eq!(query.hours, Some(6));
+        assert_eq!(query.interval, Some("5m".to_string()));
+        assert_eq!(
+            TemperatureTrendQuery::default(),
+            TemperatureTrendQuery::new()
+        );
+    }
+
+    #[test]
+    fn test_active_sensors_query_builder() {
+        let query = ActiveSensorsQuery::new()
+            .with_dedup_by("sensor_gateway".to_string())
+            .with_gateway_mac("FF:FF:FF:FF:FF:01".to_string());
+
+        assert_eq!(query.dedup_by, Some("sensor_gateway".to_string()));
+        assert_eq!(query.gateway_mac, Some("FF:FF:FF:FF:FF:01".to_string()));
+        assert_eq!(ActiveSensorsQuery::default().dedup_by, None);
+        assert_eq!(ActiveSensorsQuery::default().gateway_mac, None);
+    }
+
+    #[test]
+    fn test_latest_readings_query_builder() {
+        let query =
+            LatestReadingsQuery::new().with_macs("AA:BB:CC:DD:EE:01,AA:BB:CC:DD:EE:02".to_string());
+
+        assert_eq!(
+            query.macs,
+            Some("AA:BB:CC:DD:EE:01,AA:BB:CC:DD:EE:02".to_string())
+        );
+        assert_eq!(LatestReadingsQuery::default().macs, None);
+    }
+
+    #[test]
+    fn test_compare_query_builder() {
+        let query = CompareQuery::new()
+            .with_macs("AA:BB:CC:DD:EE:01,AA:BB:CC:DD:EE:02".to_string())
+            .with_start("2024-01-01T00:00:00Z".to_string())
+            .with_end("2024-01-02T00:00:00Z".to_string())
+            .with_interval("1h".to_string());
+
+        assert_eq!(
+            query.macs,
+            Some("AA:BB:CC:DD:EE:01,AA:BB:CC:DD:EE:02".to_string())
+        );
+        assert_eq!(query.start, Some("2024-01-01T00:00:00Z".to_string()));
+        assert_eq!(query.end, Some("2024-01-02T00:00:00Z".to_string()));
+        assert_eq!(query.interval, Some("1h".to_string()));
+        assert_eq!(CompareQuery::default(), CompareQuery::new());
+    }
+
+    #[test]
+    fn test_units_builders() {
+        let historical = HistoricalQuery::new().with_units("imperial".to_string());
+        assert_eq!(historical.units, Some("imperial".to_string()));
+
+        let time_bucket = TimeBucketQuery::new().with_units("metric".to_string());
+        assert_eq!(time_bucket.units, Some("metric".to_string()));
+
+        let latest = LatestReadingsQuery::new().with_units("imperial".to_string());
+        assert_eq!(latest.units, Some("imperial".to_string()));
+
+        assert_eq!(
+            UnitsQuery::new().with_units("imperial".to_string()).units,
+            Some("imperial".to_string())
+        );
+        assert_eq!(UnitsQuery::default().units, None);
+    }
+
+    #[test]
+    fn test_live_query_builder() {
+        let query = LiveQuery::new().with_sensor_mac("AA:BB:CC:DD:EE:FF".to_string());
+
+        assert_eq!(query.sensor_mac, Some("AA:BB:CC:DD:EE:FF".to_string()));
+        assert_eq!(LiveQuery::default().sensor_mac, None);
+    }
+
+    #[test]
+    fn test_threshold_delete_query_builder() {
+        let query = ThresholdDeleteQuery::new().with_sensor_mac("AA:BB:CC:DD:EE:FF".to_string());
+
+        assert_eq!(query.sensor_mac, Some("AA:BB:CC:DD:EE:FF".to_string()));
+        assert_eq!(ThresholdDeleteQuery::default().sensor_mac, None);
+    }
+
+    #[test]
+    fn test_gateway_history_query_builder() {
+        let query = GatewayHistoryQuery::new()
+            .with_start("2024-01-01T00:00:00Z".to_string())
+            .with_end("2024-01-02T00:00:00Z".to_string())
+            .with_limit(50)
+            .with_units("imperial".to_string());
+
+        assert_eq!(query.start, Some("2024-01-01T00:00:00Z".to_string()));
+        assert_eq!(query.end, Some("2024-01-02T00:00:00Z".to_string()));
+        assert_eq!(query.limit, Some(50));
+        assert_eq!(query.units, Some("imperial".to_string()));
+        assert_eq!(query.preset, None);
+        assert_eq!(
+            GatewayHistoryQuery::new()
+                .with_preset("today".to_string())
+                .preset,
+            Some("today".to_string())
+        );
+        assert_eq!(GatewayHistoryQuery::default(), GatewayHistoryQuery::new());
+    }
+
+    #[test]
+    fn test_history_csv_query_builder() {
+        let query = HistoryCsvQuery::new()
+            .with_start("2024-01-01T00:00:00Z".to_string())
+            .with_end("2024-01-02T00:00:00Z".to_string());
+
+        assert_eq!(query.start, Some("2024-01-01T00:00:00Z".to_string()));
+        assert_eq!(query.end, Some("2024-01-02T00:00:00Z".to_string()));
+        assert_eq!(query.preset, None);
+        assert_eq!(
+            HistoryCsvQuery::new()
+                .with_preset("today".to_string())
+                .preset,
+            Some("today".to_string())
+        );
+        assert_eq!(HistoryCsvQuery::default(), HistoryCsvQuery::new());
+    }
+
+    #[test]
+    fn test_export_query_builder() {
+        let query = ExportQuery::new()
+            .with_format("parquet".to_string())
+            .with_start("2024-01-01T00:00:00Z".to_string())
+            .with_end("2024-02-01T00:00:00Z".to_string());
+
+        assert_eq!(query.format, Some("parquet".to_string()));
+        assert_eq!(query.start, Some("2024-01-01T00:00:00Z".to_string()));
+        assert_eq!(query.end, Some("2024-02-01T00:00:00Z".to_string()));
+        assert_eq!(ExportQuery::default(), ExportQuery::new());
+    }
+
     #[test]
     fn test_storage_estimate_query_builder() {
         let query = StorageEstimateQuery::new()
@@ -190,6 +1101,10 @@ mod tests {
         assert_eq!(query.sensor_count, Some(5));
         assert_eq!(query.interval_seconds, None);
         assert_eq!(query.retention_years, None);
+        assert_eq!(query.observed, None);
+
+        let query = StorageEstimateQuery::new().with_observed(true);
+        assert_eq!(query.observed, Some(true));
     }
 
     #[test]
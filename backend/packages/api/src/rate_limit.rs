@@ -0,0 +1,181 @@
+//! Per-client request rate limiting with token buckets
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{
+        Mutex,
+        MutexGuard,
+        PoisonError,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+/// Number of clients tracked before buckets that have refilled are dropped
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Requests a client may make: a sustained rate plus a burst on top of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub requests_per_second: u32,
+    pub burst: u32,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token bucket of each client IP
+type Buckets = HashMap<IpAddr, Bucket>;
+
+/// In-memory token buckets, one per client IP
+///
+/// Every client starts with `burst` tokens, each request takes one, and
+/// tokens refill at `requests_per_second` up to `burst` again.
+#[derive(Debug)]
+pub struct RateLimiter {
+    limit: RateLimit,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a token for a request from `client`
+    ///
+    /// # Errors
+    /// Returns how long the client has to wait for its next token when its
+    /// bucket is empty
+    pub fn check(&self, client: IpAddr) -> Result<(), Duration> {
+        self.check_at(client, Instant::now())
+    }
+
+    fn check_at(&self, client: IpAddr, now: Instant) -> Result<(), Duration> {
+        let rate = f64::from(self.limit.requests_per_second);
+        let burst = f64::from(self.limit.burst);
+        let mut buckets = self.lock();
+
+        if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(&client) {
+            // A full bucket behaves like a fresh one, so forgetting it is free
+            buckets.retain(|_, bucket| {
+                let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+                elapsed.mul_add(rate, bucket.tokens) < burst
+            });
+        }
+
+        let bucket = buckets.entry(client).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = elapsed.mul_add(rate, bucket.tokens).min(burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Buckets> {
+        // Buckets are updated in place, so a panic while holding the lock
+        // cannot leave the map inconsistent
+        self.buckets.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Whole seconds to send in `Retry-After`, rounded up and at least one so
+/// clients never retry too early
+pub fn retry_after_seconds(wait: Duration) -> u64 {
+    let seconds = wait.as_secs();
+    let seconds = if wait.subsec_nanos() > 0 {
+        seconds.saturating_add(1)
+    } else {
+        seconds
+    };
+    seconds.max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10));
+    const OTHER_CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 11));
+
+    fn limiter(requests_per_second: u32, burst: u32) -> RateLimiter {
+        RateLimiter::new(RateLimit {
+            requests_per_second,
+            burst,
+        })
+    }
+
+    #[test]
+    fn test_burst_then_limited() {
+        let limiter = limiter(2, 3);
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.check_at(CLIENT, now).is_ok());
+        }
+        let retry_after = limiter.check_at(CLIENT, now);
+        assert_eq!(retry_after, Err(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn test_tokens_refill_over_time() {
+        let limiter = limiter(2, 1);
+        let now = Instant::now();
+
+        assert!(limiter.check_at(CLIENT, now).is_ok());
+        assert!(limiter.check_at(CLIENT, now).is_err());
+        assert!(limiter
+            .check_at(CLIENT, now + Duration::from_millis(500))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_refill_is_capped_at_burst() {
+        let limiter = limiter(10, 2);
+        let now = Instant::now();
+        assert!(limiter.check_at(CLIENT, now).is_ok());
+
+        let later = now + Duration::from_mins(1);
+        for _ in 0..2 {
+            assert!(limiter.check_at(CLIENT, later).is_ok());
+        }
+        assert!(limiter.check_at(CLIENT, later).is_err());
+    }
+
+    #[test]
+    fn test_clients_are_limited_separately() {
+        let limiter = limiter(1, 1);
+        let now = Instant::now();
+
+        assert!(limiter.check_at(CLIENT, now).is_ok());
+        assert!(limiter.check_at(CLIENT, now).is_err());
+        assert!(limiter.check_at(OTHER_CLIENT, now).is_ok());
+    }
+
+    #[test]
+    fn test_retry_after_seconds_rounds_up() {
+        assert_eq!(retry_after_seconds(Duration::ZERO), 1);
+        assert_eq!(retry_after_seconds(Duration::from_millis(200)), 1);
+        assert_eq!(retry_after_seconds(Duration::from_secs(2)), 2);
+        assert_eq!(retry_after_seconds(Duration::from_millis(2001)), 3);
+    }
+}
@@ -3,28 +3,140 @@
 use std::sync::Arc;
 
 use anyhow::Result;
-use postgres_store::PostgresStore;
-
-use crate::config::Config;
+use chrono_tz::Tz;
+use postgres_store::{
+    PostgresStore,
+    Store,
+};
+use tracing::warn;
+
+use crate::{
+    auth::ApiKey,
+    config::{
+        Config,
+        DEFAULT_MAX_BULK_SENSORS,
+    },
+    idempotency::IdempotencyStore,
+    metrics::ApiMetrics,
+    rate_limit::{
+        RateLimit,
+        RateLimiter,
+    },
+};
 
 #[derive(Clone)]
 pub struct AppState {
     pub store: Arc<PostgresStore>,
+    /// Backend-neutral view of the readings, serving the endpoints every
+    /// [`Store`] can answer; by default the same database as `store`
+    pub readings: Arc<dyn Store>,
+    pub default_timezone: Tz,
+    pub idempotency: Arc<IdempotencyStore>,
+    pub max_bulk_sensors: usize,
+    pub metrics: Arc<ApiMetrics>,
+    pub api_key: Option<ApiKey>,
+    pub admin_api_key: Option<ApiKey>,
+    pub cors_allowed_origins: Vec<String>,
+    pub rate_limiter: Option<Arc<RateLimiter>>,
 }
 
 impl AppState {
     /// Create a new `AppState` from a Config
     ///
     /// # Errors
-    /// Returns an error if the database connection fails
+    /// Returns an error if connecting to the database or the read replica
+    /// fails
     pub async fn new(config: Config) -> Result<Self> {
-        let store = Arc::new(PostgresStore::new(&config.database_url).await?);
-        Ok(Self { store })
+        let mut store = PostgresStore::new_with_pool_config(
+            &config.database_url,
+            config.query_timeout,
+            config.pool_config,
+        )
+        .await?;
+        if let Some(read_database_url) = &config.read_database_url {
+            store = store.with_read_replica(read_database_url).await?;
+        }
+        store.spawn_keep_alive(config.keep_alive_interval);
+        if !store.relay_database_inserts().await? {
+            warn!(
+                "Insert notify trigger is missing; live updates only carry readings ingested by \
+                 the API"
+            );
+        }
+        let store = Arc::new(store);
+        Ok(Self {
+            readings: store.clone(),
+            store,
+            default_timezone: config.default_timezone,
+            idempotency: Arc::new(IdempotencyStore::default()),
+            max_bulk_sensors: config.max_bulk_sensors,
+            metrics: Arc::new(ApiMetrics::default()),
+            api_key: config.api_key,
+            admin_api_key: config.admin_api_key,
+            cors_allowed_origins: config.cors_allowed_origins,
+            rate_limiter: config
+                .rate_limit
+                .map(|limit| Arc::new(RateLimiter::new(limit))),
+        })
     }
 
     /// Create a new `AppState` with a provided store (for testing)
-    pub const fn with_store(store: Arc<PostgresStore>) -> Self {
-        Self { store }
+    pub fn with_store(store: Arc<PostgresStore>) -> Self {
+        Self {
+            readings: store.clone(),
+            store,
+            default_timezone: Tz::UTC,
+            idempotency: Arc::new(IdempotencyStore::default()),
+            max_bulk_sensors: DEFAULT_MAX_BULK_SENSORS,
+            metrics: Arc::new(ApiMetrics::default()),
+            api_key: None,
+            admin_api_key: None,
+            cors_allowed_origins: Vec::new(),
+            rate_limiter: None,
+        }
+    }
+
+    /// Serve the readings endpoints from another [`Store`]
+    #[must_use]
+    pub fn with_readings(mut self, readings: Arc<dyn Store>) -> Self {
+        self.readings = readings;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_default_timezone(mut self, timezone: Tz) -> Self {
+        self.default_timezone = timezone;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_max_bulk_sensors(mut self, max_bulk_sensors: usize) -> Self {
+        self.max_bulk_sensors = max_bulk_sensors;
+        self
+    }
+
+    #[must_use]
+    pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limiter = Some(Arc::new(RateLimiter::new(rate_limit)));
+        self
+    }
+
+    #[must_use]
+    pub fn with_api_key(mut self, api_key: ApiKey) -> Self {
+        self.api_key = Some(api_key);
+        self
+    }
+
+    #[must_use]
+    pub fn with_admin_api_key(mut self, admin_api_key: ApiKey) -> Self {
+        self.admin_api_key = Some(admin_api_key);
+        self
+    }
+
+    #[must_use]
+    pub fn with_cors_allowed_origins(mut self, cors_allowed_origins: Vec<String>) -> Self {
+        self.cors_allowed_origins = cors_allowed_origins;
+        self
     }
 
     /// Get a reference to the store
@@ -38,6 +150,15 @@ impl std::fmt::Debug for AppState {
         formatter
             .debug_struct("AppState")
             .field("store", &"PostgresStore")
+            .field("readings", &self.readings)
+            .field("default_timezone", &self.default_timezone)
+            .field("idempotency", &"IdempotencyStore")
+            .field("max_bulk_sensors", &self.max_bulk_sensors)
+            .field("metrics", &"ApiMetrics")
+            .field("api_key", &self.api_key)
+            .field("admin_api_key", &self.admin_api_key)
+            .field("cors_allowed_origins", &self.cors_allowed_origins)
+            .field("rate_limiter", &self.rate_limiter)
             .finish()
     }
 }
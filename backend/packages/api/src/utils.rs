@@ -2,12 +2,25 @@
 
 use chrono::{
     DateTime,
+    Datelike,
+    Duration,
+    NaiveDate,
+    TimeZone,
     Utc,
 };
 
 // Type alias to reduce complexity
 type ParseResult = Result<DateTime<Utc>, chrono::ParseError>;
-use postgres_store::TimeInterval;
+/// Start and end of a query window
+pub type TimeRange = (DateTime<Utc>, DateTime<Utc>);
+use chrono_tz::Tz;
+use postgres_store::{
+    Metric,
+    Order,
+    SensorDedup,
+    TimeBucketedData,
+    TimeInterval,
+};
 
 /// Parse a datetime string into a `DateTime<Utc>`
 ///
@@ -28,25 +41,142 @@ pub fn parse_interval(interval_str: &str) -> Option<TimeInterval> {
     }
 }
 
+/// Parse a metric name into a `Metric`
+pub fn parse_metric(metric_str: &str) -> Option<Metric> {
+    match metric_str {
+        "temperature" => Some(Metric::Temperature),
+        "humidity" => Some(Metric::Humidity),
+        "pressure" => Some(Metric::Pressure),
+        _ => None,
+    }
+}
+
+/// Parse a `dedup_by` value into a `SensorDedup`
+pub fn parse_sensor_dedup(dedup_str: &str) -> Option<SensorDedup> {
+    match dedup_str {
+        "sensor" => Some(SensorDedup::Sensor),
+        "sensor_gateway" => Some(SensorDedup::SensorGateway),
+        _ => None,
+    }
+}
+
+/// Parse an `order` value into an `Order`
+pub fn parse_order(order_str: &str) -> Option<Order> {
+    match order_str {
+        "asc" => Some(Order::Asc),
+        "desc" => Some(Order::Desc),
+        _ => None,
+    }
+}
+
+/// Parse an IANA timezone name such as `Europe/Helsinki`
+pub fn parse_timezone(timezone_str: &str) -> Option<Tz> {
+    timezone_str.parse().ok()
+}
+
+/// Named time range relative to now, as offered by dashboards
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangePreset {
+    /// From local midnight until now
+    Today,
+    /// The whole previous local day
+    Yesterday,
+    /// The last 7 × 24 hours
+    Last7Days,
+    /// The last 30 × 24 hours
+    Last30Days,
+    /// From local midnight on the first of the month until now
+    ThisMonth,
+}
+
+/// Accepted values of a `preset` parameter
+pub const RANGE_PRESETS: &str = "today, yesterday, 7d, 30d, this_month";
+
+/// Parse a `preset` parameter into a `RangePreset`
+pub fn parse_range_preset(preset_str: &str) -> Option<RangePreset> {
+    match preset_str {
+        "today" => Some(RangePreset::Today),
+        "yesterday" => Some(RangePreset::Yesterday),
+        "7d" => Some(RangePreset::Last7Days),
+        "30d" => Some(RangePreset::Last30Days),
+        "this_month" => Some(RangePreset::ThisMonth),
+        _ => None,
+    }
+}
+
+/// Start of a calendar day in `timezone`.
+///
+/// Where a DST change skips midnight the day starts at the first existing
+/// local time, and where midnight repeats it starts at the earlier one.
+fn start_of_day(date: NaiveDate, timezone: Tz) -> Option<DateTime<Utc>> {
+    let midnight = date.and_hms_opt(0, 0, 0)?;
+    (0..=2)
+        .filter_map(|hours| midnight.checked_add_signed(Duration::hours(hours)))
+        .find_map(|local| timezone.from_local_datetime(&local).earliest())
+        .map(|start| start.with_timezone(&Utc))
+}
+
+/// Range a preset covers at `now`, with day boundaries taken in `timezone`
+pub fn preset_range(preset: RangePreset, now: DateTime<Utc>, timezone: Tz) -> Option<TimeRange> {
+    let today = now.with_timezone(&timezone).date_naive();
+    match preset {
+        RangePreset::Today => Some((start_of_day(today, timezone)?, now)),
+        RangePreset::Yesterday => Some((
+            start_of_day(today.pred_opt()?, timezone)?,
+            start_of_day(today, timezone)?,
+        )),
+        RangePreset::Last7Days => Some((now.checked_sub_signed(Duration::days(7))?, now)),
+        RangePreset::Last30Days => Some((now.checked_sub_signed(Duration::days(30))?, now)),
+        RangePreset::ThisMonth => Some((start_of_day(today.with_day(1)?, timezone)?, now)),
+    }
+}
+
+/// Split a comma-separated MAC list, trimming whitespace and dropping empty
+/// entries and repeats while keeping the original order
+pub fn parse_mac_list(macs_str: &str) -> Vec<String> {
+    let mut macs: Vec<String> = Vec::new();
+    for mac in macs_str.split(',').map(str::trim) {
+        if !mac.is_empty() && !macs.iter().any(|seen| seen == mac) {
+            macs.push(mac.to_string());
+        }
+    }
+    macs
+}
+
 /// Validate that a MAC address has a reasonable format
-pub const fn is_valid_mac_format(_mac: &str) -> bool {
-    // Basic validation - MAC addresses should be 17 characters with colons
-    // Format: XX:XX:XX:XX:XX:XX
-    // if mac.len() != 17 {
-    //     return false;
-    // }
-
-    // let parts: Vec<&str> = mac.split(':').collect();
-    // if parts.len() != 6 {
-    //     return false;
-    // }
-
-    // parts
-    //     .iter()
-    //     .all(|part| part.len() == 2 && part.chars().all(|c|
-    // c.is_ascii_hexdigit()))
-    //
-    true
+pub fn is_valid_mac_format(mac: &str) -> bool {
+    // Format: XX:XX:XX:XX:XX:XX, as stored by the reader
+    mac.len() == 17
+        && mac.split(':').count() == 6
+        && mac
+            .split(':')
+            .all(|part| part.len() == 2 && part.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Canonical uppercase form the reader stores MACs in, so a MAC accepted by
+/// [`is_valid_mac_format`] in any case finds the sensor's readings
+pub use ruuvi_decoder::normalize_mac;
+
+/// Longest accepted sensor group name
+pub const MAX_GROUP_NAME_LEN: usize = 64;
+
+/// Validate a sensor group name: 1-64 ASCII letters, digits, `-` or `_`, so
+/// names are safe to use as path segments
+pub fn is_valid_group_name(name: &str) -> bool {
+    (1..=MAX_GROUP_NAME_LEN).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Longest accepted sensor alias, in characters
+pub const MAX_ALIAS_LEN: usize = 100;
+
+/// Trim a sensor alias, rejecting blank ones and ones over `MAX_ALIAS_LEN`
+/// characters
+pub fn normalize_alias(alias: &str) -> Option<&str> {
+    let alias = alias.trim();
+    (!alias.is_empty() && alias.chars().count() <= MAX_ALIAS_LEN).then_some(alias)
 }
 
 /// Validate that a sensor MAC follows expected patterns
@@ -74,6 +204,95 @@ pub const fn validate_limit(limit: i64) -> bool {
     limit > 0 && limit <= 10000 // Reasonable bounds
 }
 
+/// Fewest points a downsampled history may be asked for
+pub const MIN_HISTORY_POINTS: u32 = 2;
+
+/// Most points a downsampled history may be asked for
+pub const MAX_HISTORY_POINTS: u32 = 5_000;
+
+/// Clamp a requested `max_points` into
+/// `MIN_HISTORY_POINTS..=MAX_HISTORY_POINTS`
+pub fn clamp_max_points(max_points: i64) -> u32 {
+    let clamped = max_points.clamp(i64::from(MIN_HISTORY_POINTS), i64::from(MAX_HISTORY_POINTS));
+    u32::try_from(clamped).unwrap_or(MAX_HISTORY_POINTS)
+}
+
+/// Hectopascals in one inch of mercury
+const HPA_PER_INHG: f64 = 33.863_886_666_7;
+
+/// Convert a temperature from degrees Celsius to degrees Fahrenheit
+pub const fn c_to_f(celsius: f64) -> f64 {
+    celsius.mul_add(1.8, 32.0)
+}
+
+/// Convert a pressure from hectopascals to inches of mercury
+pub const fn hpa_to_inhg(hpa: f64) -> f64 {
+    hpa / HPA_PER_INHG
+}
+
+/// Units readings are reported in, chosen by `?units=`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnitSystem {
+    /// Degrees Celsius and hectopascals, as stored
+    #[default]
+    Metric,
+    /// Degrees Fahrenheit and inches of mercury
+    Imperial,
+}
+
+impl UnitSystem {
+    /// Convert a temperature stored in degrees Celsius
+    pub const fn temperature(self, celsius: f64) -> f64 {
+        match self {
+            Self::Metric => celsius,
+            Self::Imperial => c_to_f(celsius),
+        }
+    }
+
+    /// Convert a pressure stored in hectopascals
+    pub const fn pressure(self, hpa: f64) -> f64 {
+        match self {
+            Self::Metric => hpa,
+            Self::Imperial => hpa_to_inhg(hpa),
+        }
+    }
+}
+
+/// Parse a `units` value, defaulting to metric when absent or unknown
+pub fn parse_units(units_str: Option<&str>) -> UnitSystem {
+    match units_str {
+        Some("imperial") => UnitSystem::Imperial,
+        _ => UnitSystem::Metric,
+    }
+}
+
+/// Convert the temperature and pressure figures of an aggregate bucket
+#[allow(clippy::cast_precision_loss)]
+pub fn bucket_in_units(bucket: TimeBucketedData, units: UnitSystem) -> TimeBucketedData {
+    if units == UnitSystem::Metric {
+        return bucket;
+    }
+    let readings = bucket.reading_count.unwrap_or(0) as f64;
+    TimeBucketedData {
+        avg_temperature: bucket.avg_temperature.map(c_to_f),
+        min_temperature: bucket.min_temperature.map(c_to_f),
+        max_temperature: bucket.max_temperature.map(c_to_f),
+        // Every summed reading carries the 32 °F offset
+        sum_temperature: bucket
+            .sum_temperature
+            .map(|sum| sum.mul_add(1.8, 32.0 * readings)),
+        p50_temperature: bucket.p50_temperature.map(c_to_f),
+        p95_temperature: bucket.p95_temperature.map(c_to_f),
+        first_temperature: bucket.first_temperature.map(c_to_f),
+        last_temperature: bucket.last_temperature.map(c_to_f),
+        avg_pressure: bucket.avg_pressure.map(hpa_to_inhg),
+        min_pressure: bucket.min_pressure.map(hpa_to_inhg),
+        max_pressure: bucket.max_pressure.map(hpa_to_inhg),
+        sum_pressure: bucket.sum_pressure.map(hpa_to_inhg),
+        ..bucket
+    }
+}
+
 /// Format duration in human readable form
 pub fn format_duration_human(seconds: i64) -> String {
     match seconds {
@@ -138,6 +357,125 @@ mod tests {
         }
     }
 
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "Expected {actual} to equal {expected}"
+        );
+    }
+
+    #[test]
+    fn test_c_to_f() {
+        assert_close(c_to_f(0.0), 32.0);
+        assert_close(c_to_f(100.0), 212.0);
+        assert_close(c_to_f(-40.0), -40.0);
+        assert_close(c_to_f(21.5), 70.7);
+    }
+
+    #[test]
+    fn test_hpa_to_inhg() {
+        assert_close(hpa_to_inhg(0.0), 0.0);
+        assert_close(hpa_to_inhg(1013.25), 29.921_255);
+        assert_close(hpa_to_inhg(HPA_PER_INHG), 1.0);
+    }
+
+    #[test]
+    fn test_parse_units() {
+        assert_eq!(parse_units(Some("imperial")), UnitSystem::Imperial);
+        assert_eq!(parse_units(Some("metric")), UnitSystem::Metric);
+        assert_eq!(parse_units(Some("kelvin")), UnitSystem::Metric);
+        assert_eq!(parse_units(None), UnitSystem::Metric);
+    }
+
+    #[test]
+    fn test_bucket_in_units() {
+        let bucket = TimeBucketedData {
+            bucket: Utc::now(),
+            avg_temperature: Some(20.0),
+            min_temperature: Some(10.0),
+            max_temperature: Some(30.0),
+            sum_temperature: Some(40.0),
+            p50_temperature: Some(15.0),
+            p95_temperature: Some(25.0),
+            first_temperature: Some(0.0),
+            last_temperature: Some(100.0),
+            avg_humidity: Some(50.0),
+            min_humidity: None,
+            max_humidity: None,
+            sum_humidity: None,
+            avg_pressure: Some(1013.25),
+            min_pressure: None,
+            max_pressure: None,
+            sum_pressure: None,
+            reading_count: Some(2),
+        };
+
+        let imperial = bucket_in_units(bucket, UnitSystem::Imperial);
+
+        let value = |figure: Option<f64>| figure.unwrap_or(f64::NAN);
+        assert_close(value(imperial.avg_temperature), 68.0);
+        assert_close(value(imperial.min_temperature), 50.0);
+        assert_close(value(imperial.p50_temperature), 59.0);
+        assert_close(value(imperial.p95_temperature), 77.0);
+        assert_close(value(imperial.first_temperature), 32.0);
+        assert_close(value(imperial.last_temperature), 212.0);
+        assert_close(value(imperial.max_temperature), 86.0);
+        // Two readings averaging 68 °F
+        assert_close(value(imperial.sum_temperature), 136.0);
+        assert_close(value(imperial.avg_humidity), 50.0);
+        assert_close(value(imperial.avg_pressure), 29.921_255);
+        assert!(imperial.min_pressure.is_none());
+    }
+
+    #[test]
+    fn test_parse_metric() {
+        assert_eq!(parse_metric("temperature"), Some(Metric::Temperature));
+        assert_eq!(parse_metric("humidity"), Some(Metric::Humidity));
+        assert_eq!(parse_metric("pressure"), Some(Metric::Pressure));
+        assert_eq!(parse_metric("Temperature"), None);
+        assert_eq!(parse_metric("battery"), None);
+    }
+
+    #[test]
+    fn test_parse_sensor_dedup() {
+        assert_eq!(parse_sensor_dedup("sensor"), Some(SensorDedup::Sensor));
+        assert_eq!(
+            parse_sensor_dedup("sensor_gateway"),
+            Some(SensorDedup::SensorGateway)
+        );
+        assert_eq!(parse_sensor_dedup("gateway"), None);
+        assert_eq!(parse_sensor_dedup(""), None);
+    }
+
+    #[test]
+    fn test_parse_order() {
+        assert_eq!(parse_order("asc"), Some(Order::Asc));
+        assert_eq!(parse_order("desc"), Some(Order::Desc));
+        assert_eq!(parse_order("ASC"), None);
+        assert_eq!(parse_order(""), None);
+    }
+
+    #[test]
+    fn test_parse_timezone() {
+        assert_eq!(parse_timezone("UTC"), Some(Tz::UTC));
+        assert_eq!(
+            parse_timezone("Europe/Helsinki"),
+            Some(Tz::Europe__Helsinki)
+        );
+        assert_eq!(parse_timezone("Europe/Atlantis"), None);
+        assert_eq!(parse_timezone(""), None);
+    }
+
+    #[test]
+    fn test_parse_mac_list() {
+        assert_eq!(
+            parse_mac_list("AA:BB:CC:DD:EE:01, AA:BB:CC:DD:EE:02,,AA:BB:CC:DD:EE:01"),
+            vec!["AA:BB:CC:DD:EE:01", "AA:BB:CC:DD:EE:02"]
+        );
+        assert!(parse_mac_list("").is_empty());
+        assert!(parse_mac_list(" , ").is_empty());
+    }
+
     #[test]
     fn test_is_valid_mac_format() {
         // Valid MAC addresses
@@ -154,6 +492,13 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_normalize_mac_after_validation() {
+        let mac = "aa:bb:cc:dd:ee:0f";
+        assert!(is_valid_mac_format(mac));
+        assert_eq!(normalize_mac(mac), "AA:BB:CC:DD:EE:0F");
+    }
+
     #[test]
     fn test_is_valid_mac_format_invalid() {
         let invalid_macs = vec![
@@ -174,6 +519,37 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_is_valid_group_name() {
+        for name in [
+            "living-room",
+            "Cellar_2",
+            "a",
+            &"x".repeat(MAX_GROUP_NAME_LEN),
+        ] {
+            assert!(is_valid_group_name(name), "Expected valid for: {name}");
+        }
+        for name in ["", "living room", "sauna/1", "kellari-ä", &"x".repeat(65)] {
+            assert!(!is_valid_group_name(name), "Expected invalid for: {name}");
+        }
+    }
+
+    #[test]
+    fn test_normalize_alias() {
+        assert_eq!(normalize_alias("Bedroom"), Some("Bedroom"));
+        assert_eq!(normalize_alias("  Sauna lauteet "), Some("Sauna lauteet"));
+        let longest = "ä".repeat(MAX_ALIAS_LEN);
+        assert_eq!(normalize_alias(&longest), Some(longest.as_str()));
+
+        for alias in ["", "   ", &"x".repeat(MAX_ALIAS_LEN + 1)] {
+            assert_eq!(
+                normalize_alias(alias),
+                None,
+                "Expected invalid for: {alias}"
+            );
+        }
+    }
+
     #[test]
     fn test_is_test_mac() {
         // Test MACs (should return true)
@@ -243,6 +619,14 @@ mod tests {
         assert!(!validate_limit(100_000));
     }
 
+    #[test]
+    fn test_clamp_max_points() {
+        assert_eq!(clamp_max_points(500), 500);
+        assert_eq!(clamp_max_points(0), MIN_HISTORY_POINTS);
+        assert_eq!(clamp_max_points(-5), MIN_HISTORY_POINTS);
+        assert_eq!(clamp_max_points(1_000_000), MAX_HISTORY_POINTS);
+    }
+
     #[test]
     fn test_format_duration_human() {
         assert_eq!(format_duration_human(30), "30s");
@@ -257,6 +641,108 @@ mod tests {
         assert_eq!(format_duration_human(172_800), "2d");
     }
 
+    #[allow(clippy::expect_used)]
+    fn utc(datetime: &str) -> DateTime<Utc> {
+        parse_datetime(datetime).expect("valid datetime")
+    }
+
+    #[test]
+    fn test_parse_range_preset() {
+        assert_eq!(parse_range_preset("today"), Some(RangePreset::Today));
+        assert_eq!(
+            parse_range_preset("yesterday"),
+            Some(RangePreset::Yesterday)
+        );
+        assert_eq!(parse_range_preset("7d"), Some(RangePreset::Last7Days));
+        assert_eq!(parse_range_preset("30d"), Some(RangePreset::Last30Days));
+        assert_eq!(
+            parse_range_preset("this_month"),
+            Some(RangePreset::ThisMonth)
+        );
+        assert_eq!(parse_range_preset("Today"), None);
+        assert_eq!(parse_range_preset("1y"), None);
+    }
+
+    #[test]
+    fn test_preset_range_in_utc() {
+        let now = utc("2024-03-15T10:30:00Z");
+        let range = |preset| preset_range(preset, now, Tz::UTC);
+
+        assert_eq!(
+            range(RangePreset::Today),
+            Some((utc("2024-03-15T00:00:00Z"), now))
+        );
+        assert_eq!(
+            range(RangePreset::Yesterday),
+            Some((utc("2024-03-14T00:00:00Z"), utc("2024-03-15T00:00:00Z")))
+        );
+        assert_eq!(
+            range(RangePreset::Last7Days),
+            Some((utc("2024-03-08T10:30:00Z"), now))
+        );
+        assert_eq!(
+            range(RangePreset::Last30Days),
+            Some((utc("2024-02-14T10:30:00Z"), now))
+        );
+        assert_eq!(
+            range(RangePreset::ThisMonth),
+            Some((utc("2024-03-01T00:00:00Z"), now))
+        );
+    }
+
+    #[test]
+    fn test_preset_range_uses_local_day() {
+        // 00:30 in Helsinki (UTC+2) is still the previous day in UTC
+        let now = utc("2024-01-31T22:30:00Z");
+        let helsinki = Tz::Europe__Helsinki;
+
+        assert_eq!(
+            preset_range(RangePreset::Today, now, helsinki),
+            Some((utc("2024-01-31T22:00:00Z"), now))
+        );
+        assert_eq!(
+            preset_range(RangePreset::Yesterday, now, helsinki),
+            Some((utc("2024-01-30T22:00:00Z"), utc("2024-01-31T22:00:00Z")))
+        );
+        assert_eq!(
+            preset_range(RangePreset::ThisMonth, now, helsinki),
+            Some((utc("2024-01-31T22:00:00Z"), now))
+        );
+        assert_eq!(
+            preset_range(RangePreset::Today, now, Tz::UTC),
+            Some((utc("2024-01-31T00:00:00Z"), now))
+        );
+    }
+
+    #[test]
+    fn test_preset_range_across_dst_change() {
+        // Helsinki moved from UTC+2 to UTC+3 on 2024-03-31, so that day is
+        // only 23 hours long
+        let now = utc("2024-04-01T09:00:00Z");
+        let helsinki = Tz::Europe__Helsinki;
+
+        assert_eq!(
+            preset_range(RangePreset::Yesterday, now, helsinki),
+            Some((utc("2024-03-30T22:00:00Z"), utc("2024-03-31T21:00:00Z")))
+        );
+        assert_eq!(
+            preset_range(RangePreset::ThisMonth, now, helsinki),
+            Some((utc("2024-03-31T21:00:00Z"), now))
+        );
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_start_of_day_skipped_midnight() {
+        // Santiago skipped from 00:00 to 01:00 on 2022-09-11 (UTC-4 to UTC-3)
+        let date = NaiveDate::from_ymd_opt(2022, 9, 11).expect("valid date");
+
+        assert_eq!(
+            start_of_day(date, Tz::America__Santiago),
+            Some(utc("2022-09-11T04:00:00Z"))
+        );
+    }
+
     #[test]
     fn test_format_duration_edge_cases() {
         assert_eq!(format_duration_human(0), "0s");
@@ -3,8 +3,52 @@
 //! These tests verify the core functionality of the API library including
 //! configuration, query parsing, utility functions, and business logic.
 
+use std::collections::BTreeMap;
+
 use anyhow::Result;
-use postgres_store::Event;
+use api::handlers::{
+    AliasedReading,
+    CleanupSummary,
+    ReadinessReport,
+    TemperatureTrendPoint,
+};
+use axum::http::{
+    header,
+    StatusCode,
+};
+use chrono::{
+    DateTime,
+    Duration,
+    DurationRound,
+    SecondsFormat,
+    Utc,
+};
+use postgres_store::{
+    AlertHit,
+    AlertRule,
+    Event,
+    GatewayInfo,
+    GatewayStats,
+    HistoryCursor,
+    Metric,
+    MetricTrends,
+    PageCursor,
+    SensorAlias,
+    SensorCard,
+    SensorGroup,
+    SensorHealthMetrics,
+    SensorStats,
+    SensorThreshold,
+    TimeBucketedData,
+    VibrationAlert,
+};
+
+mod utils;
+use utils::{
+    body_bytes,
+    body_text,
+    TestDatabase,
+};
 
 const EPSILON: f64 = 1e-10;
 
@@ -36,12 +80,46 @@ fn create_test_event(sensor_mac: &str) -> Event {
     )
 }
 
+/// Helper to create a test event at a specific point in time
+fn create_test_event_at(sensor_mac: &str, timestamp: DateTime<Utc>) -> Event {
+    let mut event = create_test_event(sensor_mac);
+    event.timestamp = timestamp;
+    event
+}
+
+/// Format a timestamp the way clients pass it in query strings
+fn query_time(timestamp: DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
 #[tokio::test]
 async fn test_health_handler_directly() {
     let result = api::handlers::health_check().await;
     assert_eq!(result, "OK");
 }
 
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_readiness_reports_healthy_database() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+
+    let response = test_db.get("/health/ready").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let report: ReadinessReport =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert!(report.ready);
+    let database = report.dependencies.get("database").expect("database");
+    assert_eq!(database.status, "ok");
+    assert_eq!(database.error, None);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
 // The following tests would require a real database connection
 // They are marked as ignored and would need Docker/testcontainers to run
 
@@ -273,3 +351,2003 @@ async fn test_event_creation() {
 
 // Note: Full HTTP integration tests would require a test server setup
 // For now, we focus on unit testing the core logic and utility functions
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_stream_aggregates_emits_ordered_ndjson() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+
+    let now = Utc::now();
+    let hour_start = now
+        .duration_trunc(Duration::hours(1))
+        .expect("truncate to hour");
+    let mac = "AA:BB:CC:DD:EE:01";
+    for hours_ago in [5, 3, 1] {
+        let reading_time = hour_start - Duration::hours(hours_ago);
+        for event in [
+            create_test_event_at(mac, reading_time),
+            create_test_event_at(mac, reading_time + Duration::minutes(10)),
+        ] {
+            test_db
+                .store
+                .insert_event(&event)
+                .await
+                .expect("Failed to insert event");
+        }
+    }
+
+    let uri = format!(
+        "/api/sensors/{mac}/aggregates/stream?start={}&end={}&interval=1h",
+        query_time(now - Duration::hours(6)),
+        query_time(now)
+    );
+    let response = test_db.get(&uri).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get(header::CONTENT_TYPE),
+        Some(&header::HeaderValue::from_static(api::NDJSON_CONTENT_TYPE))
+    );
+
+    let text = body_text(response).await;
+    let buckets: Vec<TimeBucketedData> = text
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("Each line should be valid JSON"))
+        .collect();
+
+    assert_eq!(buckets.len(), 3, "Expected one line per hourly bucket");
+    assert!(
+        buckets
+            .iter()
+            .zip(buckets.iter().skip(1))
+            .all(|(earlier, later)| earlier.bucket < later.bucket),
+        "Buckets should be streamed in ascending order"
+    );
+    assert!(buckets.iter().all(|bucket| bucket.reading_count == Some(2)));
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_tz_param_shifts_output_timestamps() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+
+    let mac = "AA:BB:CC:DD:EE:01";
+    let reading_time = DateTime::parse_from_rfc3339("2024-01-15T10:00:00Z")
+        .expect("valid timestamp")
+        .with_timezone(&Utc);
+    test_db
+        .store
+        .insert_event(&create_test_event_at(mac, reading_time))
+        .await
+        .expect("Failed to insert event");
+
+    let utc: serde_json::Value = serde_json::from_str(
+        &body_text(test_db.get(&format!("/api/sensors/{mac}/latest")).await).await,
+    )
+    .expect("JSON body");
+    assert_eq!(
+        utc.get("timestamp"),
+        Some(&serde_json::json!("2024-01-15T10:00:00Z"))
+    );
+    let expected_dew_point = create_test_event(mac)
+        .dew_point()
+        .expect("test event has humidity");
+    assert_float_eq(
+        utc.get("dew_point")
+            .and_then(serde_json::Value::as_f64)
+            .expect("dew_point field"),
+        expected_dew_point,
+    );
+    assert_eq!(utc.get("battery_percentage"), Some(&serde_json::json!(100)));
+
+    let response = test_db
+        .get(&format!("/api/sensors/{mac}/latest?tz=Europe/Helsinki"))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let localized: serde_json::Value =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert_eq!(
+        localized.get("timestamp"),
+        Some(&serde_json::json!("2024-01-15T12:00:00+02:00"))
+    );
+    assert_eq!(localized.get("sensor_mac"), Some(&serde_json::json!(mac)));
+
+    let response = test_db
+        .get(&format!("/api/sensors/{mac}/latest?tz=Europe/Atlantis"))
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+/// Aggregate buckets of each compared sensor, by MAC
+type SensorSeries = BTreeMap<String, Vec<TimeBucketedData>>;
+
+/// Bucket starts and reading counts returned by a GET of aggregates
+#[allow(clippy::expect_used)]
+async fn bucket_counts(test_db: &TestDatabase, uri: &str) -> Vec<(DateTime<Utc>, i64)> {
+    let response = test_db.get(uri).await;
+    assert_eq!(response.status(), StatusCode::OK, "{uri}");
+    let buckets: Vec<TimeBucketedData> =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+    buckets
+        .iter()
+        .map(|bucket| (bucket.bucket, bucket.reading_count.unwrap_or_default()))
+        .collect()
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_daily_aggregates_align_to_requested_timezone() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+
+    let mac = "AA:BB:CC:DD:EE:01";
+    let parse = |timestamp: &str| {
+        DateTime::parse_from_rfc3339(timestamp)
+            .expect("valid timestamp")
+            .with_timezone(&Utc)
+    };
+    // Same day in Helsinki (UTC+2), but two different days in UTC
+    for timestamp in ["2024-01-14T23:00:00Z", "2024-01-15T10:00:00Z"] {
+        test_db
+            .store
+            .insert_event(&create_test_event_at(mac, parse(timestamp)))
+            .await
+            .expect("Failed to insert event");
+    }
+    let uri = format!(
+        "/api/sensors/{mac}/aggregates?start={}&end={}&interval=1d",
+        query_time(parse("2024-01-13T00:00:00Z")),
+        query_time(parse("2024-01-17T00:00:00Z"))
+    );
+
+    assert_eq!(
+        bucket_counts(&test_db, &format!("{uri}&tz=UTC")).await,
+        vec![
+            (parse("2024-01-14T00:00:00Z"), 1),
+            (parse("2024-01-15T00:00:00Z"), 1)
+        ]
+    );
+    assert_eq!(
+        bucket_counts(&test_db, &format!("{uri}&tz=Europe/Helsinki")).await,
+        vec![(parse("2024-01-14T22:00:00Z"), 2)]
+    );
+
+    let response = test_db.get(&format!("{uri}&tz=Mars/Olympus")).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_compare_returns_hourly_buckets_per_sensor() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+
+    let (kitchen, bedroom) = ("AA:BB:CC:DD:EE:01", "AA:BB:CC:DD:EE:02");
+    let start = DateTime::parse_from_rfc3339("2024-01-15T10:00:00Z")
+        .expect("valid timestamp")
+        .with_timezone(&Utc);
+    for (mac, minutes) in [(kitchen, 5), (kitchen, 65), (bedroom, 10), (bedroom, 20)] {
+        test_db
+            .store
+            .insert_event(&create_test_event_at(
+                mac,
+                start + Duration::minutes(minutes),
+            ))
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let response = test_db
+        .get(&format!(
+            "/api/compare?macs={kitchen},{bedroom}&start={}&end={}&interval=1h",
+            query_time(start),
+            query_time(start + Duration::hours(3))
+        ))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let series: SensorSeries = serde_json::from_str(&body_text(response).await).expect("JSON body");
+    let counts = |mac: &str| {
+        series
+            .get(mac)
+            .expect("series of each sensor")
+            .iter()
+            .map(|bucket| (bucket.bucket, bucket.reading_count.unwrap_or_default()))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(series.len(), 2);
+    assert_eq!(
+        counts(kitchen),
+        vec![(start, 1), (start + Duration::hours(1), 1)]
+    );
+    assert_eq!(counts(bedroom), vec![(start, 2)]);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_idempotent_ingest_executes_once() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+
+    let mac = "AA:BB:CC:DD:EE:01";
+    let readings = serde_json::to_value(vec![create_test_event(mac)]).expect("serializable");
+    let headers = [("Idempotency-Key", "replay-2024-01-15")];
+
+    let first = test_db
+        .post_json("/api/readings", &readings, &headers)
+        .await;
+    assert_eq!(first.status(), StatusCode::CREATED);
+    assert!(first.headers().get("idempotent-replayed").is_none());
+    let first_body = body_text(first).await;
+
+    let second = test_db
+        .post_json("/api/readings", &readings, &headers)
+        .await;
+    assert_eq!(second.status(), StatusCode::CREATED);
+    assert_eq!(
+        second.headers().get("idempotent-replayed"),
+        Some(&header::HeaderValue::from_static("true"))
+    );
+    assert_eq!(body_text(second).await, first_body);
+
+    let row_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sensor_data")
+        .fetch_one(&test_db.store.pool)
+        .await
+        .expect("count rows");
+    assert_eq!(row_count, 1, "Replayed ingest must not insert again");
+
+    // A new key is a new request
+    let third = test_db
+        .post_json(
+            "/api/readings",
+            &readings,
+            &[("Idempotency-Key", "another")],
+        )
+        .await;
+    assert_eq!(third.status(), StatusCode::CREATED);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_ingest_rejects_invalid_gateway_mac() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+
+    let mut event = create_test_event("AA:BB:CC:DD:EE:01");
+    event.gateway_mac = "not-a-gateway".to_string();
+    let readings = serde_json::to_value(vec![event]).expect("serializable");
+
+    let response = test_db.post_json("/api/readings", &readings, &[]).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(body_text(response).await.contains("not-a-gateway"));
+
+    let row_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sensor_data")
+        .fetch_one(&test_db.store.pool)
+        .await
+        .expect("count rows");
+    assert_eq!(row_count, 0);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used, clippy::too_many_lines)]
+async fn test_trends_returns_aligned_series() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+
+    let hour_start = Utc::now()
+        .duration_trunc(Duration::hours(1))
+        .expect("truncate to hour");
+    let mac = "AA:BB:CC:DD:EE:01";
+    for hours_ago in [4, 2, 1] {
+        test_db
+            .store
+            .insert_event(&create_test_event_at(
+                mac,
+                hour_start - Duration::hours(hours_ago),
+            ))
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let response = test_db
+        .get(&format!(
+            "/api/sensors/{mac}/trends?metrics=temperature,humidity,pressure&hours=6&bucket=1h"
+        ))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let trends: MetricTrends = serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert_eq!(trends.sensor_mac, mac);
+    assert_eq!(trends.series.len(), 3);
+
+    let expected_buckets: Vec<_> = [4, 2, 1]
+        .iter()
+        .map(|hours_ago| hour_start - Duration::hours(*hours_ago))
+        .collect();
+    for (metric, points) in &trends.series {
+        let buckets: Vec<_> = points.iter().map(|point| point.bucket).collect();
+        assert_eq!(buckets, expected_buckets, "{metric:?} buckets");
+        assert!(points.iter().all(|point| point.value.is_some()));
+    }
+
+    let temperature = trends
+        .series
+        .get(&Metric::Temperature)
+        .expect("temperature series");
+    assert!(temperature.iter().all(|point| point
+        .value
+        .is_some_and(|value| (value - 22.5).abs() < EPSILON)));
+
+    // Only the requested metrics are returned
+    let response = test_db
+        .get(&format!("/api/sensors/{mac}/trends?metrics=humidity"))
+        .await;
+    let trends: MetricTrends = serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert_eq!(
+        trends.series.keys().collect::<Vec<_>>(),
+        vec![&Metric::Humidity]
+    );
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used, clippy::too_many_lines)]
+async fn test_dashboard_combines_latest_metadata_and_health() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+
+    let now = Utc::now();
+    let named = "AA:BB:CC:DD:EE:01";
+    let unnamed = "AA:BB:CC:DD:EE:02";
+
+    let mut old = create_test_event_at(named, now - Duration::minutes(30));
+    old.temperature = 18.0;
+    let mut latest = create_test_event_at(named, now - Duration::minutes(1));
+    latest.temperature = 21.0;
+    let mut stale = create_test_event_at(unnamed, now - Duration::hours(2));
+    stale.battery = 2300;
+    for event in [&old, &latest, &stale] {
+        test_db
+            .store
+            .insert_event(event)
+            .await
+            .expect("Failed to insert event");
+    }
+    sqlx::query("INSERT INTO sensor_metadata (sensor_mac, name, location) VALUES ($1, $2, $3)")
+        .bind(named)
+        .bind("Sauna")
+        .bind("Basement")
+        .execute(&test_db.store.pool)
+        .await
+        .expect("Failed to insert metadata");
+
+    let response = test_db.get("/api/dashboard").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let cards: Vec<SensorCard> =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert_eq!(cards.len(), 2);
+
+    let sauna = cards
+        .iter()
+        .find(|card| card.sensor_mac == named)
+        .expect("named sensor card");
+    assert_eq!(sauna.name.as_deref(), Some("Sauna"));
+    assert_eq!(sauna.location.as_deref(), Some("Basement"));
+    assert_float_eq(sauna.latest.temperature, 21.0);
+    assert_eq!(sauna.last_seen, sauna.latest.timestamp);
+    assert!(!sauna.health.stale && !sauna.health.low_battery);
+
+    let other = cards
+        .iter()
+        .find(|card| card.sensor_mac == unnamed)
+        .expect("unnamed sensor card");
+    assert_eq!(other.name, None);
+    assert_eq!(other.location, None);
+    assert!(other.health.stale);
+    assert!(other.health.low_battery);
+    assert!(!other.health.weak_signal);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_active_sensors_dedup_by() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let now = Utc::now();
+
+    for (gateway, minutes_ago) in [("FF:FF:FF:FF:FF:01", 5), ("FF:FF:FF:FF:FF:02", 1)] {
+        let mut event = create_test_event_at(mac, now - Duration::minutes(minutes_ago));
+        event.gateway_mac = gateway.to_string();
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let active = |uri: &'static str| async {
+        let response = test_db.get(uri).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        serde_json::from_str::<Vec<Event>>(&body_text(response).await).expect("JSON body")
+    };
+
+    for uri in ["/api/sensors/active", "/api/sensors/active?dedup_by=sensor"] {
+        let readings = active(uri).await;
+        assert_eq!(readings.len(), 1, "{uri}");
+        let reading = readings.first().expect("one reading");
+        assert_eq!(reading.sensor_mac, mac);
+        assert_eq!(
+            reading.gateway_mac, "FF:FF:FF:FF:FF:02",
+            "most recent gateway"
+        );
+    }
+
+    let readings = active("/api/sensors/active?dedup_by=sensor_gateway").await;
+    let mut gateways: Vec<&str> = readings
+        .iter()
+        .map(|reading| reading.gateway_mac.as_str())
+        .collect();
+    gateways.sort_unstable();
+    assert_eq!(gateways, ["FF:FF:FF:FF:FF:01", "FF:FF:FF:FF:FF:02"]);
+
+    let response = test_db.get("/api/sensors/active?dedup_by=gateway").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_active_sensors_filtered_by_gateway() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let now = Utc::now();
+
+    for (mac, gateway, minutes_ago) in [
+        ("AA:BB:CC:DD:EE:01", "FF:FF:FF:FF:FF:01", 5),
+        ("AA:BB:CC:DD:EE:02", "FF:FF:FF:FF:FF:01", 5),
+        ("AA:BB:CC:DD:EE:02", "FF:FF:FF:FF:FF:02", 4),
+        ("AA:BB:CC:DD:EE:03", "FF:FF:FF:FF:FF:02", 5),
+    ] {
+        let mut event = create_test_event_at(mac, now - Duration::minutes(minutes_ago));
+        event.gateway_mac = gateway.to_string();
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let response = test_db
+        .get("/api/sensors/active?gateway_mac=FF:FF:FF:FF:FF:02")
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let readings: Vec<Event> = serde_json::from_str(&body_text(response).await).expect("JSON body");
+    let macs: Vec<&str> = readings
+        .iter()
+        .map(|reading| reading.sensor_mac.as_str())
+        .collect();
+    assert_eq!(macs, ["AA:BB:CC:DD:EE:02", "AA:BB:CC:DD:EE:03"]);
+    assert!(readings
+        .iter()
+        .all(|reading| reading.gateway_mac == "FF:FF:FF:FF:FF:02"));
+
+    let response = test_db.get("/api/sensors/active?gateway_mac=gateway").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_gateways_lists_sensor_counts_and_last_seen() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let now = Utc::now()
+        .duration_trunc(Duration::seconds(1))
+        .expect("Failed to truncate timestamp");
+
+    for (mac, gateway, minutes_ago) in [
+        ("AA:BB:CC:DD:EE:01", "FF:FF:FF:FF:FF:01", 10),
+        ("AA:BB:CC:DD:EE:01", "FF:FF:FF:FF:FF:01", 3),
+        ("AA:BB:CC:DD:EE:02", "FF:FF:FF:FF:FF:01", 5),
+        ("AA:BB:CC:DD:EE:02", "FF:FF:FF:FF:FF:02", 1),
+        ("AA:BB:CC:DD:EE:03", "FF:FF:FF:FF:FF:03", 60 * 48),
+    ] {
+        let mut event = create_test_event_at(mac, now - Duration::minutes(minutes_ago));
+        event.gateway_mac = gateway.to_string();
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let response = test_db.get("/api/gateways").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let gateways: Vec<GatewayInfo> =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert_eq!(
+        gateways,
+        [
+            GatewayInfo {
+                gateway_mac: "FF:FF:FF:FF:FF:01".to_string(),
+                sensor_count: 2,
+                last_seen: now - Duration::minutes(3),
+            },
+            GatewayInfo {
+                gateway_mac: "FF:FF:FF:FF:FF:02".to_string(),
+                sensor_count: 1,
+                last_seen: now - Duration::minutes(1),
+            },
+        ]
+    );
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+/// Sensor and time of each reading in a gateway history response, checking
+/// that the gateway forwarded every one of them
+#[allow(clippy::expect_used)]
+async fn forwarded_readings(test_db: &TestDatabase, uri: &str) -> Vec<(String, DateTime<Utc>)> {
+    let response = test_db.get(uri).await;
+    assert_eq!(response.status(), StatusCode::OK, "{uri}");
+    let readings: Vec<Event> = serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert!(readings
+        .iter()
+        .all(|reading| reading.gateway_mac == "FF:FF:FF:FF:FF:01"));
+    readings
+        .into_iter()
+        .map(|reading| (reading.sensor_mac, reading.timestamp))
+        .collect()
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_gateway_history_only_returns_gateway_readings() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let gateway = "FF:FF:FF:FF:FF:01";
+    let now = Utc::now()
+        .duration_trunc(Duration::seconds(1))
+        .expect("Failed to truncate timestamp");
+
+    for (mac, gateway_mac, minutes_ago) in [
+        ("AA:BB:CC:DD:EE:01", gateway, 30),
+        ("AA:BB:CC:DD:EE:02", gateway, 20),
+        ("AA:BB:CC:DD:EE:01", "FF:FF:FF:FF:FF:02", 10),
+        ("AA:BB:CC:DD:EE:03", "FF:FF:FF:FF:FF:02", 5),
+    ] {
+        let mut event = create_test_event_at(mac, now - Duration::minutes(minutes_ago));
+        event.gateway_mac = gateway_mac.to_string();
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let uri = format!("/api/gateways/{gateway}/history");
+    assert_eq!(
+        forwarded_readings(&test_db, &uri).await,
+        vec![
+            ("AA:BB:CC:DD:EE:02".to_string(), now - Duration::minutes(20)),
+            ("AA:BB:CC:DD:EE:01".to_string(), now - Duration::minutes(30)),
+        ]
+    );
+    assert_eq!(
+        forwarded_readings(&test_db, &format!("{uri}?limit=1"))
+            .await
+            .len(),
+        1
+    );
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_gateway_statistics_across_sensors() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let gateway = "FF:FF:FF:FF:FF:01";
+    let now = Utc::now()
+        .duration_trunc(Duration::seconds(1))
+        .expect("Failed to truncate timestamp");
+
+    for (mac, gateway_mac, rssi, minutes_ago) in [
+        ("AA:BB:CC:DD:EE:01", gateway, -40, 30),
+        ("AA:BB:CC:DD:EE:02", gateway, -50, 20),
+        ("AA:BB:CC:DD:EE:03", gateway, -60, 10),
+        ("AA:BB:CC:DD:EE:01", "FF:FF:FF:FF:FF:02", -90, 5),
+        ("AA:BB:CC:DD:EE:02", gateway, -10, 60 * 5),
+    ] {
+        let mut event = create_test_event_at(mac, now - Duration::minutes(minutes_ago));
+        event.gateway_mac = gateway_mac.to_string();
+        event.rssi = rssi;
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let response = test_db
+        .get(&format!("/api/gateways/{gateway}/statistics?hours=2"))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let stats: GatewayStats = serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert_eq!(
+        stats,
+        GatewayStats {
+            gateway_mac: gateway.to_string(),
+            sensor_count: 3,
+            reading_count: 3,
+            readings_per_hour: 1.5,
+            avg_rssi: Some(-50.0),
+            last_seen: Some(now - Duration::minutes(10)),
+        }
+    );
+
+    let response = test_db
+        .get("/api/gateways/FF:FF:FF:FF:FF:09/statistics")
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_sensor_alias_set_overwritten_and_listed() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let uri = format!("/api/sensors/{mac}/alias");
+    test_db
+        .store
+        .insert_event(&create_test_event_at(
+            mac,
+            Utc::now() - Duration::minutes(5),
+        ))
+        .await
+        .expect("Failed to insert event");
+
+    assert_eq!(test_db.get(&uri).await.status(), StatusCode::NOT_FOUND);
+
+    for alias in ["Bedroom", "  Guest room "] {
+        let response = test_db
+            .put_json(&uri, &serde_json::json!({ "alias": alias }))
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let response = test_db.get(&uri).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let alias: SensorAlias = serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert_eq!(alias.sensor_mac, mac);
+    assert_eq!(alias.alias, "Guest room");
+
+    let response = test_db.get("/api/sensors/active").await;
+    let readings: Vec<AliasedReading> =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+    let reading = readings.first().expect("one reading");
+    assert_eq!(reading.reading.sensor_mac, mac);
+    assert_eq!(reading.alias.as_deref(), Some("Guest room"));
+
+    for (uri, alias) in [
+        (uri.as_str(), "   "),
+        ("/api/sensors/not-a-mac/alias", "Bedroom"),
+    ] {
+        let response = test_db
+            .put_json(uri, &serde_json::json!({ "alias": alias }))
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{uri}");
+    }
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_active_alerts_list_crossed_thresholds() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let freezer = "AA:BB:CC:DD:EE:01";
+    let now = Utc::now();
+
+    for (minutes_ago, temperature) in [(10, -12.0), (1, -18.0)] {
+        let mut event = create_test_event_at(freezer, now - Duration::minutes(minutes_ago));
+        event.temperature = temperature;
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let uri = format!("/api/sensors/{freezer}/alerts");
+    let mut rules = Vec::new();
+    for (metric, comparator, threshold) in [
+        ("temperature", "above", -15.0),
+        ("temperature", "below", -17.0),
+        ("humidity", "above", 60.0),
+    ] {
+        let body = serde_json::json!({
+            "metric": metric,
+            "comparator": comparator,
+            "threshold": threshold,
+        });
+        let response = test_db.post_json(&uri, &body, &[]).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let rule: AlertRule = serde_json::from_str(&body_text(response).await).expect("JSON body");
+        rules.push(rule);
+    }
+
+    let response = test_db.get("/api/alerts/active").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let hits: Vec<AlertHit> = serde_json::from_str(&body_text(response).await).expect("JSON body");
+    let fired: Vec<i64> = hits.iter().map(|hit| hit.alert_id).collect();
+    let expected: Vec<i64> = rules.iter().skip(1).map(|rule| rule.id).collect();
+    assert_eq!(fired, expected, "-18 °C is not above -15 °C");
+    let cold = hits.first().expect("temperature alert");
+    assert_float_eq(cold.value, -18.0);
+    assert_eq!(cold.comparator, "below");
+
+    let body = serde_json::json!({ "metric": "battery", "comparator": "above", "threshold": 1.0 });
+    let response = test_db.post_json(&uri, &body, &[]).await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_vibration_alerts_endpoint_flags_readings_above_threshold() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let now = Utc::now();
+
+    for (minutes_ago, acceleration) in [(2, 1050.0), (1, 2400.0)] {
+        let mut event = create_test_event_at(mac, now - Duration::minutes(minutes_ago));
+        event.acceleration = acceleration;
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let response = test_db
+        .post_json(
+            &format!("/api/sensors/{mac}/thresholds"),
+            &serde_json::json!({ "metric": "acceleration", "max_value": 1500.0 }),
+            &[],
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let threshold: SensorThreshold =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert_eq!(threshold.min_value, None);
+
+    let response = test_db
+        .get(&format!("/api/sensors/{mac}/vibration-alerts"))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let alerts: Vec<VibrationAlert> =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+
+    assert_eq!(alerts.len(), 1);
+    let alert = alerts.first().expect("one alert");
+    assert_eq!(alert.threshold_id, threshold.id);
+    assert_float_eq(alert.acceleration, 2400.0);
+    assert_float_eq(alert.limit_value, 1500.0);
+    assert!(alert.above_max);
+
+    let response = test_db
+        .post_json(
+            &format!("/api/sensors/{mac}/thresholds"),
+            &serde_json::json!({ "metric": "acceleration", "min_value": 2000.0, "max_value": 1000.0 }),
+            &[],
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used, clippy::too_many_lines)]
+async fn test_list_and_delete_thresholds() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let other_mac = "AA:BB:CC:DD:EE:02";
+
+    let mut event = create_test_event_at(mac, Utc::now() - Duration::minutes(1));
+    event.acceleration = 2400.0;
+    test_db
+        .store
+        .insert_event(&event)
+        .await
+        .expect("Failed to insert event");
+
+    let mut created = Vec::new();
+    for (sensor, max_value) in [(mac, 1500.0), (mac, 2000.0), (other_mac, 1500.0)] {
+        let response = test_db
+            .post_json(
+                &format!("/api/sensors/{sensor}/thresholds"),
+                &serde_json::json!({ "metric": "acceleration", "max_value": max_value }),
+                &[],
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let threshold: SensorThreshold =
+            serde_json::from_str(&body_text(response).await).expect("JSON body");
+        created.push(threshold.id);
+    }
+
+    let response = test_db.get(&format!("/api/sensors/{mac}/thresholds")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let thresholds: Vec<SensorThreshold> =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+    let ids: Vec<i64> = thresholds.iter().map(|threshold| threshold.id).collect();
+    assert_eq!(ids, created.get(..2).expect("two thresholds").to_vec());
+
+    let response = test_db
+        .get(&format!("/api/sensors/{mac}/vibration-alerts"))
+        .await;
+    let alerts: Vec<VibrationAlert> =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert_eq!(alerts.len(), 2);
+
+    // A threshold cannot be deleted through another sensor
+    let first = *created.first().expect("first threshold");
+    let response = test_db
+        .delete(&format!("/api/thresholds/{first}?sensor_mac={other_mac}"))
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = test_db
+        .delete(&format!("/api/thresholds/{first}?sensor_mac={mac}"))
+        .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = test_db.delete(&format!("/api/thresholds/{first}")).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let second = *created.get(1).expect("second threshold");
+    let response = test_db.delete(&format!("/api/thresholds/{second}")).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = test_db.get(&format!("/api/sensors/{mac}/thresholds")).await;
+    let thresholds: Vec<SensorThreshold> =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert!(thresholds.is_empty());
+
+    // With its thresholds gone the sensor no longer raises alerts
+    let response = test_db
+        .get(&format!("/api/sensors/{mac}/vibration-alerts"))
+        .await;
+    let alerts: Vec<VibrationAlert> =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert!(alerts.is_empty());
+
+    let response = test_db
+        .get(&format!("/api/sensors/{other_mac}/thresholds"))
+        .await;
+    let thresholds: Vec<SensorThreshold> =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert_eq!(thresholds.len(), 1);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used, clippy::too_many_lines)]
+async fn test_export_parquet_round_trip() {
+    use api::export::PARQUET_CONTENT_TYPE;
+    use arrow_array::{
+        Float64Array,
+        StringArray,
+    };
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+
+    // More readings than fit in one row group, one per second and warming up
+    // by a thousandth of a degree each
+    sqlx::query(
+        r"
+        INSERT INTO sensor_data (
+            sensor_mac, gateway_mac, temperature, humidity, pressure, battery, tx_power,
+            movement_counter, measurement_sequence_number, acceleration, acceleration_x,
+            acceleration_y, acceleration_z, rssi, timestamp
+        )
+        SELECT $1, 'FF:FF:FF:FF:FF:01', 20.0 + n / 1000.0, 50.0, 1000.0, 3000, 4,
+               0, n, 1000.0, 0, 0, 1000, -60,
+               NOW() - INTERVAL '1 day' + n * INTERVAL '1 second'
+        FROM generate_series(0, 12344) AS n
+        ",
+    )
+    .bind(mac)
+    .execute(&test_db.store.pool)
+    .await
+    .expect("Failed to insert readings");
+
+    let response = test_db
+        .get(&format!("/api/sensors/{mac}/export?format=parquet"))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get(header::CONTENT_TYPE),
+        Some(&header::HeaderValue::from_static(PARQUET_CONTENT_TYPE))
+    );
+
+    let builder = ParquetRecordBatchReaderBuilder::try_new(body_bytes(response).await)
+        .expect("valid Parquet file");
+    assert_eq!(builder.metadata().num_row_groups(), 2);
+    let batches = builder
+        .build()
+        .expect("Parquet reader")
+        .collect::<Result<Vec<_>, _>>()
+        .expect("readable batches");
+
+    let rows: usize = batches.iter().map(arrow_array::RecordBatch::num_rows).sum();
+    assert_eq!(rows, 12_345);
+
+    let first = batches.first().expect("at least one batch");
+    let temperatures = first
+        .column_by_name("temperature")
+        .and_then(|column| column.as_any().downcast_ref::<Float64Array>())
+        .expect("temperature column");
+    assert_float_eq(temperatures.value(1), 20.001);
+    let macs = first
+        .column_by_name("sensor_mac")
+        .and_then(|column| column.as_any().downcast_ref::<StringArray>())
+        .expect("sensor_mac column");
+    assert_eq!(macs.value(0), mac);
+
+    let response = test_db
+        .get(&format!("/api/sensors/{mac}/export?format=xlsx"))
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = test_db
+        .get(&format!(
+            "/api/sensors/{mac}/export?format=parquet&tz=Europe/Helsinki"
+        ))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let builder = ParquetRecordBatchReaderBuilder::try_new(body_bytes(response).await)
+        .expect("valid Parquet file");
+    assert_eq!(
+        builder
+            .schema()
+            .field_with_name("timestamp")
+            .expect("timestamp column")
+            .data_type(),
+        &arrow_schema::DataType::Timestamp(
+            arrow_schema::TimeUnit::Microsecond,
+            Some("Europe/Helsinki".into())
+        )
+    );
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used, clippy::too_many_lines)]
+async fn test_sensor_group_latest_and_aggregates() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let kitchen = "AA:BB:CC:DD:EE:01";
+    let hallway = "AA:BB:CC:DD:EE:02";
+    let garage = "AA:BB:CC:DD:EE:03";
+
+    let reading_time = Utc::now() - Duration::minutes(10);
+    for (mac, temperature) in [(kitchen, 21.0), (hallway, 19.0), (garage, 5.0)] {
+        let mut event = create_test_event_at(mac, reading_time);
+        event.temperature = temperature;
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    for invalid in [
+        serde_json::json!({ "name": "living room" }),
+        serde_json::json!({ "name": "downstairs", "sensor_macs": ["not-a-mac"] }),
+    ] {
+        let response = test_db.post_json("/api/groups", &invalid, &[]).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{invalid}");
+    }
+
+    let body = serde_json::json!({ "name": "downstairs", "sensor_macs": [kitchen, kitchen] });
+    let response = test_db.post_json("/api/groups", &body, &[]).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let group: SensorGroup = serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert_eq!(group.sensor_macs, vec![kitchen]);
+
+    let response = test_db.post_json("/api/groups", &body, &[]).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let response = test_db
+        .put(&format!("/api/groups/downstairs/members/{hallway}"))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let group: SensorGroup = serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert_eq!(group.sensor_macs, vec![kitchen, hallway]);
+
+    let response = test_db
+        .put(&format!("/api/groups/upstairs/members/{hallway}"))
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = test_db.put("/api/groups/downstairs/members/invalid").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = test_db.get("/api/groups/downstairs/latest").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let latest: Vec<serde_json::Value> =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+    let macs: Vec<&str> = latest
+        .iter()
+        .filter_map(|reading| reading.get("sensor_mac")?.as_str())
+        .collect();
+    assert_eq!(macs, vec![kitchen, hallway]);
+    assert!(latest
+        .iter()
+        .all(|reading| reading.get("dew_point").is_some()));
+
+    let response = test_db
+        .get("/api/groups/downstairs/aggregates?interval=1d")
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let buckets: Vec<TimeBucketedData> =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+    let readings: i64 = buckets
+        .iter()
+        .filter_map(|bucket| bucket.reading_count)
+        .sum();
+    assert_eq!(readings, 2, "garage is not in the group");
+    let sum: f64 = buckets
+        .iter()
+        .filter_map(|bucket| bucket.sum_temperature)
+        .sum();
+    assert_float_eq(sum, 40.0);
+
+    let response = test_db.get("/api/groups/upstairs/aggregates").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = test_db
+        .delete(&format!("/api/groups/downstairs/members/{kitchen}"))
+        .await;
+    let group: SensorGroup = serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert_eq!(group.sensor_macs, vec![hallway]);
+
+    let response = test_db.get("/api/groups").await;
+    let groups: Vec<SensorGroup> =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert_eq!(groups, vec![group]);
+
+    let response = test_db.delete("/api/groups/downstairs").await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = test_db.get("/api/groups/downstairs").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_history_includes_battery_percentage() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+
+    for (minutes_ago, battery) in [(20, 2600), (10, 0)] {
+        let mut event = create_test_event_at(mac, Utc::now() - Duration::minutes(minutes_ago));
+        event.battery = battery;
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let response = test_db.get(&format!("/api/sensors/{mac}/history")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let readings: Vec<serde_json::Value> =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+    let percentages: Vec<Option<&serde_json::Value>> = readings
+        .iter()
+        .map(|reading| reading.get("battery_percentage"))
+        .collect();
+    assert_eq!(percentages.len(), 2);
+    assert!(percentages.contains(&Some(&serde_json::json!(25))));
+    assert!(percentages.contains(&Some(&serde_json::Value::Null)));
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_history_downsampled_to_max_points() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let start = (Utc::now() - Duration::hours(3))
+        .duration_trunc(Duration::hours(1))
+        .expect("Failed to truncate timestamp");
+    let end = start + Duration::hours(2);
+
+    // One reading every 10 seconds for two hours
+    let events: Vec<Event> = (0..720)
+        .map(|step| create_test_event_at(mac, start + Duration::seconds(step * 10)))
+        .collect();
+    let summary = test_db
+        .store
+        .import_events(&events)
+        .await
+        .expect("Failed to import events");
+    assert_eq!(summary.imported, 720);
+
+    let uri = format!(
+        "/api/sensors/{mac}/history?start={}&end={}&max_points=12",
+        query_time(start),
+        query_time(end)
+    );
+    let response = test_db.get(&uri).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let readings: Vec<Event> = serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert!(
+        (11..=13).contains(&readings.len()),
+        "{} points",
+        readings.len()
+    );
+    let newest = readings.first().expect("newest point");
+    let oldest = readings.last().expect("oldest point");
+    assert!(newest.timestamp > oldest.timestamp);
+    assert_eq!(oldest.timestamp, start);
+    assert_float_eq(oldest.temperature, 22.5);
+
+    let cursor = PageCursor::Before(HistoryCursor::of(newest)).encode();
+    let response = test_db.get(&format!("{uri}&before={cursor}")).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_history_cursor_pages_without_gaps() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let base = Utc::now() - Duration::minutes(30);
+
+    for sequence in 0..250 {
+        let mut event = create_test_event_at(mac, base + Duration::seconds(sequence / 2));
+        event.measurement_sequence_number = sequence;
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let mut sequences = Vec::new();
+    let mut uri = format!("/api/sensors/{mac}/history?limit=100");
+    let mut pages = 0;
+    loop {
+        let response = test_db.get(&uri).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let next_cursor = response
+            .headers()
+            .get(api::handlers::NEXT_CURSOR_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let readings: Vec<serde_json::Value> =
+            serde_json::from_str(&body_text(response).await).expect("JSON body");
+        sequences.extend(readings.iter().filter_map(|reading| {
+            reading
+                .get("measurement_sequence_number")
+                .and_then(serde_json::Value::as_i64)
+        }));
+        pages += 1;
+
+        match next_cursor {
+            Some(cursor) => uri = format!("/api/sensors/{mac}/history?limit=100&before={cursor}"),
+            None => break,
+        }
+    }
+
+    assert_eq!(pages, 3);
+    assert_eq!(sequences, (0..250).rev().collect::<Vec<_>>());
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used, clippy::too_many_lines)]
+async fn test_history_after_cursor_pages_without_gaps() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let base = (Utc::now() - Duration::minutes(30))
+        .duration_trunc(Duration::seconds(1))
+        .expect("Truncated timestamp");
+
+    for sequence in 0..250 {
+        let mut event = create_test_event_at(mac, base + Duration::seconds(sequence / 2));
+        event.measurement_sequence_number = sequence;
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let next_cursors = |response: &axum::response::Response| {
+        [
+            api::handlers::NEXT_CURSOR_HEADER,
+            api::handlers::PREV_CURSOR_HEADER,
+        ]
+        .map(|header| {
+            response
+                .headers()
+                .get(header)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        })
+    };
+    let page_sequences = |body: &str| -> Vec<i64> {
+        let readings: Vec<Event> = serde_json::from_str(body).expect("JSON body");
+        readings
+            .iter()
+            .map(|reading| reading.measurement_sequence_number)
+            .collect()
+    };
+
+    // The oldest 50 readings, whose previous-page cursor continues forward
+    let response = test_db
+        .get(&format!(
+            "/api/sensors/{mac}/history?limit=100&end={}",
+            query_time(base + Duration::seconds(24))
+        ))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let [next, prev] = next_cursors(&response);
+    assert_eq!(next, None);
+    let mut sequences = page_sequences(&body_text(response).await);
+    assert_eq!(sequences, (0..50).rev().collect::<Vec<_>>());
+
+    let mut cursor = prev.expect("previous page cursor");
+    let mut pages = 0;
+    loop {
+        let response = test_db
+            .get(&format!(
+                "/api/sensors/{mac}/history?limit=100&after={cursor}"
+            ))
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let [next, _] = next_cursors(&response);
+        sequences.extend(page_sequences(&body_text(response).await));
+        pages += 1;
+
+        match next {
+            Some(next) => cursor = next,
+            None => break,
+        }
+    }
+
+    assert_eq!(pages, 3);
+    sequences.sort_unstable();
+    assert_eq!(sequences, (0..250).collect::<Vec<_>>());
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_full_ascending_history_page_has_no_cursor() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let base = Utc::now() - Duration::minutes(30);
+
+    for sequence in 0..100 {
+        let mut event = create_test_event_at(mac, base + Duration::seconds(sequence));
+        event.measurement_sequence_number = sequence;
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let response = test_db
+        .get(&format!("/api/sensors/{mac}/history?limit=100&order=asc"))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get(api::handlers::NEXT_CURSOR_HEADER),
+        None
+    );
+    let readings: Vec<Event> = serde_json::from_str(&body_text(response).await).expect("JSON body");
+    let sequences: Vec<i64> = readings
+        .iter()
+        .map(|reading| reading.measurement_sequence_number)
+        .collect();
+    assert_eq!(sequences, (0..100).collect::<Vec<_>>());
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_large_history_is_compressed_on_request() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let base = Utc::now() - Duration::minutes(30);
+
+    for second in 0..200 {
+        test_db
+            .store
+            .insert_event(&create_test_event_at(mac, base + Duration::seconds(second)))
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let uri = format!("/api/sensors/{mac}/history?limit=200");
+    let response = test_db
+        .get_with_headers(&uri, &[("accept-encoding", "gzip")])
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get(header::CONTENT_ENCODING),
+        Some(&header::HeaderValue::from_static("gzip"))
+    );
+    assert_eq!(
+        response.headers().get(header::CONTENT_TYPE),
+        Some(&header::HeaderValue::from_static("application/json"))
+    );
+    let compressed = body_bytes(response).await;
+    assert!(compressed.starts_with(&[0x1f, 0x8b]), "gzip magic bytes");
+
+    let response = test_db.get(&uri).await;
+    assert_eq!(response.headers().get(header::CONTENT_ENCODING), None);
+    let plain = body_bytes(response).await;
+    assert!(compressed.len() < plain.len());
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_history_rejects_bad_cursors() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+
+    let response = test_db
+        .get(&format!("/api/sensors/{mac}/history?before=not-a-cursor"))
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let position = HistoryCursor {
+        timestamp: Utc::now(),
+        measurement_sequence_number: 1,
+    };
+    let before = PageCursor::Before(position).encode();
+    let after = PageCursor::After(position).encode();
+    let response = test_db
+        .get(&format!(
+            "/api/sensors/{mac}/history?before={before}&after={after}"
+        ))
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // A cursor only pages in the direction it was made for
+    for uri in [
+        format!("/api/sensors/{mac}/history?before={after}"),
+        format!("/api/sensors/{mac}/history?after={before}"),
+    ] {
+        let response = test_db.get(&uri).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_history_csv_streams_every_reading() {
+    use api::export::{
+        csv_header,
+        CSV_CONTENT_TYPE,
+    };
+
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let base = (Utc::now() - Duration::minutes(30))
+        .duration_trunc(Duration::seconds(1))
+        .expect("Failed to truncate timestamp");
+
+    for minute in 0..5 {
+        test_db
+            .store
+            .insert_event(&create_test_event_at(mac, base + Duration::minutes(minute)))
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let response = test_db
+        .get(&format!("/api/sensors/{mac}/history.csv"))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get(header::CONTENT_TYPE),
+        Some(&header::HeaderValue::from_static(CSV_CONTENT_TYPE))
+    );
+
+    let body = body_text(response).await;
+    let mut lines = body.lines();
+    assert_eq!(lines.next(), Some(csv_header().trim_end()));
+    let rows: Vec<&str> = lines.collect();
+    assert_eq!(rows.len(), 5);
+    let first = rows.first().expect("first row");
+    assert!(first.starts_with(mac));
+    assert!(first.ends_with(&base.to_rfc3339_opts(SecondsFormat::Micros, true)));
+
+    let response = test_db
+        .get(&format!("/api/sensors/{mac}/history.csv?limit=10"))
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_metrics_count_requests() {
+    use api::metrics::METRICS_CONTENT_TYPE;
+
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let health_requests = |metrics: &str| {
+        metrics
+            .lines()
+            .find_map(|line| {
+                line.strip_prefix(
+                    "ruuvi_api_requests_total{method=\"GET\",route=\"/health\",status=\"200\"} ",
+                )
+            })
+            .map_or(0, |count| count.parse::<u64>().expect("numeric counter"))
+    };
+
+    let response = test_db.get("/metrics").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get(header::CONTENT_TYPE),
+        Some(&header::HeaderValue::from_static(METRICS_CONTENT_TYPE))
+    );
+    let before = health_requests(&body_text(response).await);
+
+    assert_eq!(test_db.get("/health").await.status(), StatusCode::OK);
+
+    let metrics = body_text(test_db.get("/metrics").await).await;
+    assert_eq!(health_requests(&metrics), before + 1);
+    assert!(metrics.contains("# TYPE ruuvi_api_request_duration_seconds histogram"));
+    assert!(metrics.contains("ruuvi_api_db_pool_connections{state=\"idle\"}"));
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_live_updates_forward_inserted_events() {
+    use axum_test::TestServer;
+
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let server = TestServer::builder()
+        .http_transport()
+        .build(test_db.router())
+        .expect("Failed to start test server");
+
+    let mut socket = server
+        .get_websocket(&format!("/api/sensors/live?sensor_mac={mac}"))
+        .await
+        .into_websocket()
+        .await;
+
+    // Readings of other sensors are filtered out
+    for sensor_mac in ["AA:BB:CC:DD:EE:02", mac] {
+        test_db
+            .store
+            .insert_event(&create_test_event(sensor_mac))
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let event: Event =
+        tokio::time::timeout(std::time::Duration::from_secs(5), socket.receive_json())
+            .await
+            .expect("Live update should arrive");
+    assert_eq!(event.sensor_mac, mac);
+
+    server
+        .get_websocket("/api/sensors/live?sensor_mac=not-a-mac")
+        .expect_failure()
+        .await
+        .assert_status_bad_request();
+
+    socket.close().await;
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_sensor_statistics() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+
+    let response = test_db.get(&format!("/api/sensors/{mac}/statistics")).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    for (minutes, temperature) in [(10, 20.0), (20, 24.0)] {
+        let mut event = create_test_event_at(mac, Utc::now() - Duration::minutes(minutes));
+        event.temperature = temperature;
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let response = test_db
+        .get(&format!("/api/sensors/{mac}/statistics?hours=1"))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let stats: SensorStats = serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert_eq!(stats.reading_count, 2);
+    assert_float_eq(stats.avg_temperature, 22.0);
+    assert_float_eq(stats.max_temperature, 24.0);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_sensor_health() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+
+    let response = test_db.get(&format!("/api/sensors/{mac}/health")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let health: SensorHealthMetrics =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert_eq!(health.total_readings, 0);
+    assert_eq!(health.last_reading, None);
+
+    let timestamp = (Utc::now() - Duration::minutes(5))
+        .duration_trunc(Duration::seconds(1))
+        .expect("Failed to truncate timestamp");
+    test_db
+        .store
+        .insert_event(&create_test_event_at(mac, timestamp))
+        .await
+        .expect("Failed to insert event");
+
+    let response = test_db
+        .get(&format!("/api/sensors/{mac}/health?hours=2"))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let health: SensorHealthMetrics =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert_eq!(health.total_readings, 1);
+    assert_eq!(health.last_reading, Some(timestamp));
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[allow(clippy::expect_used)]
+async fn temperature_trend(test_db: &TestDatabase, uri: &str) -> Vec<TemperatureTrendPoint> {
+    let response = test_db.get(uri).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    serde_json::from_str(&body_text(response).await).expect("JSON body")
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_temperature_trend_buckets() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let hour = (Utc::now() - Duration::hours(3))
+        .duration_trunc(Duration::hours(1))
+        .expect("Failed to truncate timestamp");
+
+    for (minutes, temperature) in [(5, 20.0), (10, 22.0), (40, 30.0)] {
+        let mut event = create_test_event_at(mac, hour + Duration::minutes(minutes));
+        event.temperature = temperature;
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    // Default 15 minute buckets keep the readings apart
+    let points = temperature_trend(&test_db, &format!("/api/sensors/{mac}/trend?hours=4")).await;
+    assert_eq!(points.len(), 2);
+
+    let points = temperature_trend(
+        &test_db,
+        &format!("/api/sensors/{mac}/trend?hours=4&interval=1h"),
+    )
+    .await;
+    assert_eq!(points.len(), 1);
+    let point = points.first().expect("one bucket");
+    assert_eq!(point.timestamp, hour);
+    assert_float_eq(point.avg_temp, 24.0);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_delete_sensor_removes_its_readings() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let retired = "AA:BB:CC:DD:EE:01";
+    let kept = "AA:BB:CC:DD:EE:02";
+
+    for (mac, minutes) in [(retired, 30), (retired, 20), (retired, 10), (kept, 10)] {
+        let event = create_test_event_at(mac, Utc::now() - Duration::minutes(minutes));
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let response = test_db.delete("/api/sensors/not-a-mac").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = test_db.delete(&format!("/api/sensors/{retired}")).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let latest = test_db
+        .store
+        .get_latest_reading(retired)
+        .await
+        .expect("Failed to query latest reading");
+    assert!(latest.is_none());
+    let latest = test_db
+        .store
+        .get_latest_reading(kept)
+        .await
+        .expect("Failed to query latest reading");
+    assert!(latest.is_some());
+
+    let response = test_db.delete(&format!("/api/sensors/{retired}")).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_storage_cleanup_deletes_old_readings() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database")
+        .with_admin_api_key("secret");
+    let mac = "AA:BB:CC:DD:EE:01";
+
+    for days_ago in [200, 100, 1] {
+        let event = create_test_event_at(mac, Utc::now() - Duration::days(days_ago));
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let body = serde_json::json!({});
+    let response = test_db
+        .post_json("/api/storage/cleanup?days=90", &body, &[])
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let admin_key = [("x-admin-key", "secret")];
+    let response = test_db
+        .post_json("/api/storage/cleanup?days=90", &body, &admin_key)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let summary: CleanupSummary =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert_eq!(summary, CleanupSummary { rows_deleted: 2 });
+
+    let response = test_db
+        .post_json("/api/storage/cleanup?days=90", &body, &admin_key)
+        .await;
+    let summary: CleanupSummary =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert_eq!(summary.rows_deleted, 0);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_sensor_latest_not_modified_for_same_etag() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let base = Utc::now() - Duration::minutes(10);
+    test_db
+        .store
+        .insert_event(&create_test_event_at(mac, base))
+        .await
+        .expect("Failed to insert event");
+
+    let uri = format!("/api/sensors/{mac}/latest");
+    let response = test_db.get(&uri).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response
+        .headers()
+        .get(header::ETAG)
+        .and_then(|value| value.to_str().ok())
+        .expect("ETag header")
+        .to_string();
+
+    let response = test_db
+        .get_with_headers(&uri, &[("if-none-match", &etag)])
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert!(body_bytes(response).await.is_empty());
+
+    let response = test_db
+        .get_with_headers(
+            &format!("{uri}?units=imperial"),
+            &[("if-none-match", &etag)],
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    test_db
+        .store
+        .insert_event(&create_test_event_at(mac, base + Duration::minutes(1)))
+        .await
+        .expect("Failed to insert event");
+    let response = test_db
+        .get_with_headers(&uri, &[("if-none-match", &etag)])
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_latest_readings_of_several_sensors() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let kitchen = "AA:BB:CC:DD:EE:01";
+    let hallway = "AA:BB:CC:DD:EE:02";
+    let silent = "AA:BB:CC:DD:EE:03";
+
+    for (mac, minutes, temperature) in
+        [(kitchen, 20, 20.0), (kitchen, 5, 21.5), (hallway, 10, 18.0)]
+    {
+        let mut event = create_test_event_at(mac, Utc::now() - Duration::minutes(minutes));
+        event.temperature = temperature;
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let response = test_db
+        .get(&format!("/api/sensors/latest?macs={kitchen},not-a-mac"))
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = test_db
+        .get(&format!(
+            "/api/sensors/latest?macs={kitchen},{hallway},{silent}"
+        ))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let latest: BTreeMap<String, Event> =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+
+    assert_eq!(latest.len(), 2);
+    assert!(!latest.contains_key(silent));
+    assert_float_eq(latest.get(kitchen).expect("kitchen").temperature, 21.5);
+    assert_float_eq(latest.get(hallway).expect("hallway").temperature, 18.0);
+
+    let response = test_db
+        .get(&format!(
+            "/api/sensors/latest?macs={kitchen}&units=imperial"
+        ))
+        .await;
+    let latest: BTreeMap<String, Event> =
+        serde_json::from_str(&body_text(response).await).expect("JSON body");
+    assert_float_eq(latest.get(kitchen).expect("kitchen").temperature, 70.7);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
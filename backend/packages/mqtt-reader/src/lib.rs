@@ -4,5 +4,6 @@
 #![cfg_attr(not(test), deny(clippy::panic))]
 
 mod env;
+pub mod metrics;
 pub mod read;
 pub mod write;
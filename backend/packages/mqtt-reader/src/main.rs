@@ -1,14 +1,24 @@
-use std::pin::pin;
+use std::{
+    sync::Arc,
+    time::Duration,
+};
 
 use futures::StreamExt;
 use mqtt_reader::{
+    metrics::{
+        self,
+        ReaderMetrics,
+    },
     read::{
         self,
+        dead_letter::DeadLetterLog,
     },
     write::{
         self,
+        buffer::EventBuffer,
     },
 };
+use tokio::net::TcpListener;
 use tracing::{
     error,
     info,
@@ -16,6 +26,33 @@ use tracing::{
 
 type AppResult = Result<(), Box<dyn std::error::Error>>;
 
+/// How often the reader logs its ingest status
+const STATUS_LOG_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Completes on Ctrl-C, or never if the signal can't be listened for
+async fn shutdown_signal() {
+    match tokio::signal::ctrl_c().await {
+        Ok(()) => info!("Received Ctrl-C, shutting down"),
+        Err(err) => {
+            error!("Failed to listen for Ctrl-C: {err}");
+            futures::future::pending::<()>().await;
+        }
+    }
+}
+
+/// Serve `metrics` for Prometheus on the configured port in the background
+async fn start_metrics_server(metrics: Arc<ReaderMetrics>) -> std::io::Result<()> {
+    let config = metrics::Config::from_env();
+    let listener = TcpListener::bind(("0.0.0.0", config.port)).await?;
+    info!("Serving metrics on {}", listener.local_addr()?);
+    tokio::spawn(async move {
+        if let Err(err) = metrics::serve(listener, metrics).await {
+            error!("Metrics server failed: {err}");
+        }
+    });
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> AppResult {
     tracing_subscriber::fmt::init();
@@ -34,21 +71,32 @@ async fn main() -> AppResult {
         write_config.database_url
     );
 
-    let stream = read::create(read_config).await?;
-    let mut stream = pin!(stream);
+    let metrics = Arc::new(ReaderMetrics::default());
+    start_metrics_server(Arc::clone(&metrics)).await?;
+
+    let stream = read::create(&read_config, Arc::clone(&metrics))?;
 
     info!("Successfully connected to MQTT broker. Waiting for messages...");
 
-    let postgres_writer = write::create(write_config).await?;
+    let postgres_writer = write::create(write_config.clone()).await?;
 
-    while let Some(decoded_message) = stream.next().await {
-        if let Err(err) = postgres_writer
-            .write_sensor_data(vec![decoded_message.into()])
-            .await
-        {
-            error!("Failed to write to PostgreSQL: {err}");
+    let ingest_latency = postgres_writer.ingest_latency();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(STATUS_LOG_INTERVAL);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            info!("Status: {}", ingest_latency.snapshot());
         }
-    }
+    });
+
+    let dead_letter = read_config
+        .dead_letter_log
+        .then(|| DeadLetterLog::new(&read_config.log_filepath));
+    let mut buffer =
+        EventBuffer::new(postgres_writer, &write_config, metrics).with_dead_letter(dead_letter);
+    buffer.run(stream.map(Into::into), shutdown_signal()).await;
+    info!("Stopped cleanly");
 
     Ok(())
 }
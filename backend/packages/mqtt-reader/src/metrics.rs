@@ -0,0 +1,199 @@
+//! Prometheus metrics of the MQTT reader
+
+use std::{
+    fmt::{
+        Display,
+        Write,
+    },
+    io,
+    sync::{
+        atomic::{
+            AtomicU64,
+            Ordering,
+        },
+        Arc,
+    },
+};
+
+use axum::{
+    extract::State,
+    http::header,
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use tokio::net::TcpListener;
+
+use crate::env::try_from_env;
+
+/// Content type of the Prometheus text exposition format
+pub const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Port the metrics are served on unless configured otherwise
+pub const DEFAULT_METRICS_PORT: u16 = 9091;
+
+const RECEIVED: &str = "ruuvi_reader_messages_received_total";
+const DECODED: &str = "ruuvi_reader_messages_decoded_total";
+const DECODE_FAILED: &str = "ruuvi_reader_messages_decode_failed_total";
+const DB_WRITE_FAILED: &str = "ruuvi_reader_db_write_failed_total";
+const BUFFERED: &str = "ruuvi_reader_buffered_events";
+
+#[derive(Clone)]
+#[allow(missing_debug_implementations)]
+pub struct Config {
+    pub port: u16,
+}
+
+impl Config {
+    #[must_use]
+    pub const fn new(port: u16) -> Self {
+        Self { port }
+    }
+
+    #[must_use]
+    pub fn from_env() -> Self {
+        Self {
+            port: try_from_env("METRICS_PORT")
+                .and_then(|port| port.parse().ok())
+                .unwrap_or(DEFAULT_METRICS_PORT),
+        }
+    }
+}
+
+/// Counters of messages flowing from the broker to the database
+#[derive(Debug, Default)]
+pub struct ReaderMetrics {
+    received: AtomicU64,
+    decoded: AtomicU64,
+    decode_failed: AtomicU64,
+    db_write_failed: AtomicU64,
+    buffered: AtomicU64,
+}
+
+impl ReaderMetrics {
+    /// Count a message published on the subscribed topic
+    pub fn message_received(&self) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a message decoded into a reading
+    pub fn message_decoded(&self) {
+        self.decoded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a message dropped because it couldn't be parsed or decoded
+    pub fn decode_failed(&self) {
+        self.decode_failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count `events` lost to a failed database write
+    pub fn db_write_failed(&self, events: usize) {
+        self.db_write_failed
+            .fetch_add(u64::try_from(events).unwrap_or(u64::MAX), Ordering::Relaxed);
+    }
+
+    /// Set the number of events waiting to be written
+    pub fn set_buffered_events(&self, events: usize) {
+        self.buffered
+            .store(u64::try_from(events).unwrap_or(u64::MAX), Ordering::Relaxed);
+    }
+
+    /// Render every metric in the Prometheus text format
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+        for (name, help, value) in [
+            (
+                RECEIVED,
+                "Messages received from the broker",
+                &self.received,
+            ),
+            (DECODED, "Messages decoded into readings", &self.decoded),
+            (
+                DECODE_FAILED,
+                "Messages dropped because they couldn't be decoded",
+                &self.decode_failed,
+            ),
+            (
+                DB_WRITE_FAILED,
+                "Readings lost to failed database writes",
+                &self.db_write_failed,
+            ),
+        ] {
+            describe(&mut output, name, help, "counter");
+            sample(&mut output, name, value.load(Ordering::Relaxed));
+        }
+
+        describe(
+            &mut output,
+            BUFFERED,
+            "Readings waiting to be written to the database",
+            "gauge",
+        );
+        sample(&mut output, BUFFERED, self.buffered.load(Ordering::Relaxed));
+
+        output
+    }
+}
+
+/// Router serving `metrics` on `/metrics`
+pub fn router(metrics: Arc<ReaderMetrics>) -> Router {
+    Router::new()
+        .route("/metrics", get(get_metrics))
+        .with_state(metrics)
+}
+
+/// Serve `metrics` on `listener` until the process exits
+///
+/// # Errors
+/// Fails if accepting connections fails.
+pub async fn serve(listener: TcpListener, metrics: Arc<ReaderMetrics>) -> io::Result<()> {
+    axum::serve(listener, router(metrics)).await
+}
+
+async fn get_metrics(State(metrics): State<Arc<ReaderMetrics>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, METRICS_CONTENT_TYPE)],
+        metrics.render(),
+    )
+}
+
+/// Write the `HELP` and `TYPE` lines of a metric
+fn describe(output: &mut String, name: &str, help: &str, kind: &str) {
+    // Writing to a String cannot fail
+    let _ = writeln!(output, "# HELP {name} {help}\n# TYPE {name} {kind}");
+}
+
+/// Write the single sample of an unlabelled metric
+fn sample(output: &mut String, name: &str, value: impl Display) {
+    let _ = writeln!(output, "{name} {value}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_counters_and_gauge() {
+        let metrics = ReaderMetrics::default();
+        metrics.message_received();
+        metrics.message_received();
+        metrics.message_decoded();
+        metrics.decode_failed();
+        metrics.db_write_failed(3);
+        metrics.set_buffered_events(7);
+
+        let output = metrics.render();
+
+        for line in [
+            "# TYPE ruuvi_reader_messages_received_total counter\n",
+            "ruuvi_reader_messages_received_total 2\n",
+            "ruuvi_reader_messages_decoded_total 1\n",
+            "ruuvi_reader_messages_decode_failed_total 1\n",
+            "ruuvi_reader_db_write_failed_total 3\n",
+            "# TYPE ruuvi_reader_buffered_events gauge\n",
+            "ruuvi_reader_buffered_events 7\n",
+        ] {
+            assert!(output.contains(line), "missing {line} in {output}");
+        }
+    }
+}
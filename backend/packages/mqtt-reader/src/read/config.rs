@@ -1,8 +1,18 @@
+use std::time::Duration;
+
+use ruuvi_decoder::{
+    AesKey,
+    DecoderOptions,
+};
+
 use crate::env::{
     from_env,
     try_from_env,
 };
 
+/// Sensor MAC and AES key of each sensor sending DF8 payloads
+pub type Df8Keys = Vec<(String, AesKey)>;
+
 #[derive(Clone)]
 #[allow(missing_debug_implementations)]
 pub struct Config {
@@ -11,9 +21,39 @@ pub struct Config {
     pub mqtt_host: String,
     pub mqtt_port: u16,
     pub mqtt_topic: String,
+    /// JSON lines file that undecodable messages are appended to when
+    /// `dead_letter_log` is enabled
     pub log_filepath: String,
+    /// Status topic the broker publishes `mqtt_will_payload` to if the
+    /// reader disconnects uncleanly; "online" is published on connect
+    pub mqtt_will_topic: Option<String>,
+    /// Last will payload, `DEFAULT_WILL_PAYLOAD` when unset
+    pub mqtt_will_payload: Option<String>,
+    /// Keep messages that fail to parse or decode in `log_filepath`
+    pub dead_letter_log: bool,
+    /// Connect over TLS (MQTTS), usually on port 8883
+    pub mqtt_use_tls: bool,
+    /// PEM CA certificate to trust, e.g. for a self-signed broker; the
+    /// system roots are trusted when unset
+    pub mqtt_ca_cert_path: Option<String>,
+    /// PEM client certificate for brokers requiring client authentication
+    pub mqtt_client_cert_path: Option<String>,
+    /// PEM private key of `mqtt_client_cert_path`
+    pub mqtt_client_key_path: Option<String>,
+    /// Longest wait between attempts to reconnect to the broker
+    pub mqtt_max_reconnect_backoff: Duration,
+    /// Plausibility checks and trailing RSSI handling of every data format
+    pub decoder_options: DecoderOptions,
+    /// AES keys of DF8 tags, by MAC address
+    pub df8_keys: Df8Keys,
 }
 
+/// Status published by the broker on the reader's behalf once it is gone
+pub const DEFAULT_WILL_PAYLOAD: &str = "offline";
+
+/// Longest wait between reconnection attempts unless configured otherwise
+pub const DEFAULT_MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(60);
+
 impl Config {
     #[must_use]
     #[allow(clippy::too_many_arguments)] // Establish Config
@@ -32,9 +72,68 @@ impl Config {
             mqtt_port,
             mqtt_topic,
             log_filepath,
+            mqtt_use_tls: false,
+            mqtt_ca_cert_path: None,
+            mqtt_client_cert_path: None,
+            mqtt_client_key_path: None,
+            mqtt_max_reconnect_backoff: DEFAULT_MAX_RECONNECT_BACKOFF,
+            mqtt_will_topic: None,
+            mqtt_will_payload: None,
+            dead_letter_log: false,
+            decoder_options: DecoderOptions {
+                plausibility_checks: false,
+                trailing_rssi: false,
+            },
+            df8_keys: Vec::new(),
         }
     }
 
+    /// Connect over TLS, trusting `ca_cert_path` or the system roots
+    #[must_use]
+    pub fn with_tls(mut self, ca_cert_path: Option<String>) -> Self {
+        self.mqtt_use_tls = true;
+        self.mqtt_ca_cert_path = ca_cert_path;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_max_reconnect_backoff(mut self, max_backoff: Duration) -> Self {
+        self.mqtt_max_reconnect_backoff = max_backoff;
+        self
+    }
+
+    /// Report the reader's status on `topic`, with `payload` as the last
+    /// will or `DEFAULT_WILL_PAYLOAD` without one
+    #[must_use]
+    pub fn with_last_will(mut self, topic: String, payload: Option<String>) -> Self {
+        self.mqtt_will_topic = Some(topic);
+        self.mqtt_will_payload = payload;
+        self
+    }
+
+    /// Append messages that can't be decoded to `log_filepath`
+    #[must_use]
+    pub const fn with_dead_letter_log(mut self) -> Self {
+        self.dead_letter_log = true;
+        self
+    }
+
+    /// Decode payloads with `options`, decrypting DF8 tags with `df8_keys`
+    #[must_use]
+    pub fn with_decoding(mut self, options: DecoderOptions, df8_keys: Df8Keys) -> Self {
+        self.decoder_options = options;
+        self.df8_keys = df8_keys;
+        self
+    }
+
+    /// Authenticate to the broker with a client certificate
+    #[must_use]
+    pub fn with_client_cert(mut self, cert_path: String, key_path: String) -> Self {
+        self.mqtt_client_cert_path = Some(cert_path);
+        self.mqtt_client_key_path = Some(key_path);
+        self
+    }
+
     /// # Panics
     #[must_use]
     pub fn from_env() -> Self {
@@ -48,10 +147,56 @@ impl Config {
                 .expect("Port must be a number"),
             mqtt_topic: from_env("MQTT_TOPIC"),
             log_filepath: try_from_env("LOG_FILEPATH").unwrap_or_else(|| "/tmp/mqtt-reader.log".to_string()),
+            mqtt_use_tls: try_from_env("MQTT_USE_TLS")
+                .is_some_and(|value| matches!(value.as_str(), "1" | "true")),
+            mqtt_ca_cert_path: try_from_env("MQTT_CA_CERT_PATH"),
+            mqtt_client_cert_path: try_from_env("MQTT_CLIENT_CERT_PATH"),
+            mqtt_client_key_path: try_from_env("MQTT_CLIENT_KEY_PATH"),
+            mqtt_max_reconnect_backoff: try_from_env("MQTT_MAX_RECONNECT_BACKOFF_SECS")
+                .and_then(|secs| secs.parse().ok())
+                .map_or(DEFAULT_MAX_RECONNECT_BACKOFF, Duration::from_secs),
+            mqtt_will_topic: try_from_env("MQTT_WILL_TOPIC"),
+            mqtt_will_payload: try_from_env("MQTT_WILL_PAYLOAD"),
+            dead_letter_log: try_from_env("DEAD_LETTER_LOG")
+                .is_some_and(|value| matches!(value.as_str(), "1" | "true")),
+            decoder_options: DecoderOptions {
+                plausibility_checks: try_from_env("DECODER_PLAUSIBILITY_CHECKS")
+                    .is_some_and(|value| matches!(value.as_str(), "1" | "true")),
+                trailing_rssi: try_from_env("DECODER_TRAILING_RSSI")
+                    .is_some_and(|value| matches!(value.as_str(), "1" | "true")),
+            },
+            #[allow(clippy::expect_used)] // Break early if a key is malformed
+            df8_keys: try_from_env("DF8_KEYS")
+                .map(|keys| parse_df8_keys(&keys))
+                .transpose()
+                .expect("DF8_KEYS must be MAC=<32 hex digits> pairs separated by commas")
+                .unwrap_or_default(),
         }
     }
 }
 
+/// Parse `AA:BB:CC:DD:EE:FF=<32 hex digits>` pairs separated by commas
+///
+/// # Errors
+/// Fails on a pair without `=` or a key that isn't 16 hex-encoded bytes.
+pub fn parse_df8_keys(value: &str) -> Result<Df8Keys, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (mac, key) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("Expected MAC=key, got {pair}"))?;
+            let key = hex::decode(key.trim())
+                .ok()
+                .and_then(|key| AesKey::try_from(key).ok())
+                .ok_or_else(|| format!("Key of {mac} is not 16 hex-encoded bytes"))?;
+            Ok((mac.trim().to_string(), key))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -73,6 +218,66 @@ mod tests {
         assert_eq!(config.mqtt_port, 1883);
         assert_eq!(config.mqtt_topic, "test/topic");
         assert_eq!(config.log_filepath, "/tmp/test.log");
+        assert!(!config.mqtt_use_tls);
+        assert!(!config.dead_letter_log);
+        assert_eq!(config.mqtt_will_topic, None);
+        assert_eq!(config.mqtt_ca_cert_path, None);
+        assert_eq!(
+            config.mqtt_max_reconnect_backoff,
+            DEFAULT_MAX_RECONNECT_BACKOFF
+        );
+    }
+
+    #[test]
+    fn test_parse_df8_keys() {
+        let keys = parse_df8_keys(
+            "F7:97:E3:6E:D8:11=72757576692d686f6d652d6b65793136, \
+             aabbccddeeff=00000000000000000000000000000001",
+        );
+        assert_eq!(
+            keys,
+            Ok(vec![
+                ("F7:97:E3:6E:D8:11".to_string(), *b"ruuvi-home-key16"),
+                (
+                    "aabbccddeeff".to_string(),
+                    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]
+                ),
+            ])
+        );
+        assert_eq!(parse_df8_keys(""), Ok(Vec::new()));
+        assert!(parse_df8_keys("F7:97:E3:6E:D8:11").is_err());
+        assert!(parse_df8_keys("F7:97:E3:6E:D8:11=0011").is_err());
+    }
+
+    #[test]
+    fn test_config_with_tls() {
+        let config = Config::new(
+            None,
+            None,
+            "broker.example.com".to_string(),
+            8883,
+            "sensors/data".to_string(),
+            "/tmp/test.log".to_string(),
+        )
+        .with_tls(Some("/etc/mqtt/ca.pem".to_string()))
+        .with_client_cert(
+            "/etc/mqtt/client.pem".to_string(),
+            "/etc/mqtt/client.key".to_string(),
+        );
+
+        assert!(config.mqtt_use_tls);
+        assert_eq!(
+            config.mqtt_ca_cert_path.as_deref(),
+            Some("/etc/mqtt/ca.pem")
+        );
+        assert_eq!(
+            config.mqtt_client_cert_path.as_deref(),
+            Some("/etc/mqtt/client.pem")
+        );
+        assert_eq!(
+            config.mqtt_client_key_path.as_deref(),
+            Some("/etc/mqtt/client.key")
+        );
     }
 
     #[test]
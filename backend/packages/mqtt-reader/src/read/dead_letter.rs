@@ -0,0 +1,84 @@
+use std::{
+    borrow::Cow,
+    fmt::Display,
+    fs::OpenOptions,
+    io::{
+        self,
+        Write,
+    },
+    path::PathBuf,
+};
+
+use chrono::{
+    DateTime,
+    Utc,
+};
+use serde::Serialize;
+use tracing::warn;
+
+/// Appends messages that couldn't be decoded to a JSON lines file, so they
+/// can be diagnosed or replayed later
+#[derive(Debug, Clone)]
+pub struct DeadLetterLog {
+    path: PathBuf,
+}
+
+/// One line of the dead-letter file
+#[derive(Debug, Serialize)]
+struct DeadLetter<'a> {
+    received_at: DateTime<Utc>,
+    topic: &'a str,
+    /// Raw payload, with invalid UTF-8 replaced
+    payload: Cow<'a, str>,
+    error: String,
+}
+
+impl DeadLetterLog {
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Record why `payload` from `topic` was dropped; a failed write is only
+    /// logged, as losing a dead letter must not stop ingestion
+    pub fn record(&self, topic: &str, payload: &[u8], error: &dyn Display) {
+        let letter = DeadLetter {
+            received_at: Utc::now(),
+            topic,
+            payload: String::from_utf8_lossy(payload),
+            error: error.to_string(),
+        };
+        if let Err(err) = self.append(&letter) {
+            warn!(
+                "Failed to write dead letter to {}: {err}",
+                self.path.display()
+            );
+        }
+    }
+
+    fn append(&self, letter: &DeadLetter<'_>) -> io::Result<()> {
+        let mut line = serde_json::to_vec(letter)?;
+        line.push(b'\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(&line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_unwritable_path_is_not_fatal() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let log = DeadLetterLog::new(dir.path().join("missing").join("dead.log"));
+
+        log.record("ruuvi/test", b"not json", &"expected value");
+
+        assert!(!dir.path().join("missing").exists());
+    }
+}
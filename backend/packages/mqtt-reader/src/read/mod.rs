@@ -1,6 +1,14 @@
-use std::time::Duration;
+use std::{
+    fs,
+    io,
+    sync::Arc,
+    time::Duration,
+};
 
-use config::Config;
+use config::{
+    Config,
+    DEFAULT_WILL_PAYLOAD,
+};
 use futures::Stream;
 use mqtt_stream::{
     to_stream,
@@ -8,32 +16,166 @@ use mqtt_stream::{
 };
 use rumqttc::{
     AsyncClient,
+    LastWill,
     MqttOptions,
     QoS,
+    TlsConfiguration,
+    Transport,
 };
 
+use crate::metrics::ReaderMetrics;
+
 pub mod config;
+pub mod dead_letter;
 pub mod mqtt_stream;
 pub mod ruuvi_gateway_message;
 
+/// Connection options for the broker in `config`
+///
 /// # Errors
-/// This function can fail if the MQTT client fails to connect or subscribe to
-/// the topic, or if `TimescaleDB` connection fails.
-pub async fn create(
-    config: Config,
-) -> Result<impl Stream<Item = DecodedMessage>, Box<dyn std::error::Error>> {
-    let mut mqttoptions = MqttOptions::new("rumqtt-async", config.mqtt_host, config.mqtt_port);
+/// Fails if a configured certificate or key file can't be read, or a client
+/// certificate is given without a CA certificate.
+pub fn mqtt_options(config: &Config) -> io::Result<MqttOptions> {
+    let mut mqttoptions =
+        MqttOptions::new("rumqtt-async", config.mqtt_host.clone(), config.mqtt_port);
     mqttoptions.set_keep_alive(Duration::from_secs(60));
 
     // Set credentials only if both username and password are provided
-    if let (Some(username), Some(password)) = (config.mqtt_username, config.mqtt_password) {
+    if let (Some(username), Some(password)) = (&config.mqtt_username, &config.mqtt_password) {
         mqttoptions.set_credentials(username, password);
     }
 
+    // Retained so a monitor subscribing later still sees the latest status
+    if let Some(will_topic) = &config.mqtt_will_topic {
+        let payload = config
+            .mqtt_will_payload
+            .as_deref()
+            .unwrap_or(DEFAULT_WILL_PAYLOAD);
+        mqttoptions.set_last_will(LastWill::new(will_topic, payload, QoS::AtLeastOnce, true));
+    }
+
+    if config.mqtt_use_tls {
+        mqttoptions.set_transport(Transport::tls_with_config(tls_configuration(config)?));
+    }
+
+    Ok(mqttoptions)
+}
+
+/// TLS settings trusting the configured CA, or the system roots without one
+fn tls_configuration(config: &Config) -> io::Result<TlsConfiguration> {
+    let client_auth = match (&config.mqtt_client_cert_path, &config.mqtt_client_key_path) {
+        (Some(cert_path), Some(key_path)) => Some((fs::read(cert_path)?, fs::read(key_path)?)),
+        _ => None,
+    };
+
+    match &config.mqtt_ca_cert_path {
+        Some(ca_cert_path) => Ok(TlsConfiguration::Simple {
+            ca: fs::read(ca_cert_path)?,
+            alpn: None,
+            client_auth,
+        }),
+        None if client_auth.is_some() => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "A client certificate needs MQTT_CA_CERT_PATH to be set",
+        )),
+        None => Ok(TlsConfiguration::default()),
+    }
+}
+
+/// Stream of decoded readings from the broker; connection errors are
+/// retried with backoff rather than ending the stream
+///
+/// # Errors
+/// This function can fail if the TLS certificates can't be read.
+pub fn create(
+    config: &Config,
+    metrics: Arc<ReaderMetrics>,
+) -> io::Result<impl Stream<Item = DecodedMessage>> {
+    let mqttoptions = mqtt_options(config)?;
+
+    // The stream subscribes on every connection acknowledgement, so the
+    // subscription is restored after reconnecting
     let (client, eventloop) = AsyncClient::new(mqttoptions, 10);
-    client.subscribe(config.mqtt_topic, QoS::AtMostOnce).await?;
 
-    let decoder = ruuvi_decoder::Df5Decoder;
+    Ok(to_stream(eventloop, client, config, metrics))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn plain_config() -> Config {
+        Config::new(
+            None,
+            None,
+            "broker.example.com".to_string(),
+            8883,
+            "sensors/data".to_string(),
+            "/tmp/test.log".to_string(),
+        )
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_mqtt_options_plain_tcp_by_default() {
+        let options = mqtt_options(&plain_config()).expect("options");
+        assert!(matches!(options.transport(), Transport::Tcp));
+        assert!(options.last_will().is_none());
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_mqtt_options_carry_last_will() {
+        let config = plain_config().with_last_will("ruuvi/reader/status".to_string(), None);
+        let will = mqtt_options(&config)
+            .expect("options")
+            .last_will()
+            .expect("last will");
+        assert_eq!(will.topic, "ruuvi/reader/status");
+        assert_eq!(will.message, DEFAULT_WILL_PAYLOAD.as_bytes());
+        assert_eq!(will.qos, QoS::AtLeastOnce);
+        assert!(will.retain);
 
-    Ok(to_stream(eventloop, decoder))
+        let config = plain_config()
+            .with_last_will("ruuvi/reader/status".to_string(), Some("gone".to_string()));
+        let will = mqtt_options(&config)
+            .expect("options")
+            .last_will()
+            .expect("last will");
+        assert_eq!(will.message, "gone".as_bytes());
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_mqtt_options_tls_with_ca_cert() {
+        let mut ca_cert = tempfile::NamedTempFile::new().expect("temp file");
+        ca_cert
+            .write_all(b"-----BEGIN CERTIFICATE-----\n")
+            .expect("write CA");
+        let config = plain_config().with_tls(Some(ca_cert.path().display().to_string()));
+
+        let options = mqtt_options(&config).expect("options");
+        assert!(matches!(
+            options.transport(),
+            Transport::Tls(TlsConfiguration::Simple {
+                ca,
+                client_auth: None,
+                ..
+            }) if ca == b"-----BEGIN CERTIFICATE-----\n"
+        ));
+    }
+
+    #[test]
+    fn test_mqtt_options_tls_rejects_unreadable_files() {
+        let config = plain_config().with_tls(Some("/nonexistent/ca.pem".to_string()));
+        assert!(mqtt_options(&config).is_err());
+
+        let config = plain_config().with_tls(None).with_client_cert(
+            "/nonexistent/client.pem".to_string(),
+            "/nonexistent/client.key".to_string(),
+        );
+        assert!(mqtt_options(&config).is_err());
+    }
 }
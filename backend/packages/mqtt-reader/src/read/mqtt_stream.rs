@@ -1,76 +1,641 @@
+use std::{
+    fmt::Display,
+    sync::Arc,
+    time::Duration,
+};
+
 use chrono::{
     DateTime,
     Utc,
 };
 use futures::Stream;
 use postgres_store::Event;
-use rumqttc::Incoming;
+use rumqttc::{
+    AsyncClient,
+    Incoming,
+    QoS,
+};
 use ruuvi_decoder::{
-    Decoder,
+    DecoderOptions,
+    DecoderResult,
+    Df5Decoder,
+    Df8Decoder,
     SensorData,
+    DF5_PAYLOAD_LEN,
+};
+use tracing::{
+    error,
+    info,
+    warn,
 };
-use tracing::error;
 
-use super::ruuvi_gateway_message::RuuviGatewayMessage;
+use super::{
+    config::{
+        Config,
+        Df8Keys,
+    },
+    dead_letter::DeadLetterLog,
+    ruuvi_gateway_message::RuuviGatewayMessage,
+};
+use crate::metrics::ReaderMetrics;
 
 #[derive(Debug)]
 pub struct DecodedMessage {
     pub message: RuuviGatewayMessage,
-    pub sensor_data: ruuvi_decoder::SensorData5,
+    /// Tag MAC in colon form, from the payload or, for data formats without
+    /// one, the topic the gateway published on
+    pub sensor_mac: String,
+    pub sensor_data: SensorData,
 }
 
 impl From<DecodedMessage> for Event {
     fn from(val: DecodedMessage) -> Self {
         let timestamp =
             DateTime::from_timestamp(i64::from(val.message.ts), 0).unwrap_or_else(Utc::now);
+        // Fall back to the signal strength the gateway measured itself
+        let gateway_rssi = i64::from(val.message.rssi);
+        let gateway_mac = ruuvi_decoder::normalize_mac(&val.message.gw_mac);
+
+        match val.sensor_data {
+            SensorData::Df5(data) => Event {
+                sensor_mac: val.sensor_mac,
+                gateway_mac,
+                temperature: f64::from(data.temperature),
+                humidity: f64::from(data.humidity.unwrap_or(0.0)),
+                pressure: f64::from(data.pressure.unwrap_or(0.0)),
+                battery: i64::from(data.battery.unwrap_or(0)),
+                tx_power: i64::from(data.tx_power.unwrap_or(0)),
+                movement_counter: i64::from(data.movement_counter),
+                measurement_sequence_number: i64::from(data.measurement_sequence_number),
+                acceleration: f64::from(data.acceleration),
+                acceleration_x: i64::from(data.acceleration_x),
+                acceleration_y: i64::from(data.acceleration_y),
+                acceleration_z: i64::from(data.acceleration_z),
+                rssi: data.rssi.map_or(gateway_rssi, i64::from),
+                timestamp,
+            },
+            // DF3 carries no transmit power, movement counter or sequence
+            // number
+            SensorData::Df3(data) => Event {
+                sensor_mac: val.sensor_mac,
+                gateway_mac,
+                temperature: f64::from(data.temperature),
+                humidity: f64::from(data.humidity.unwrap_or(0.0)),
+                pressure: f64::from(data.pressure.unwrap_or(0.0)),
+                battery: i64::from(data.battery.unwrap_or(0)),
+                tx_power: 0,
+                movement_counter: 0,
+                measurement_sequence_number: 0,
+                acceleration: f64::from(data.acceleration),
+                acceleration_x: i64::from(data.acceleration_x),
+                acceleration_y: i64::from(data.acceleration_y),
+                acceleration_z: i64::from(data.acceleration_z),
+                rssi: data.rssi.map_or(gateway_rssi, i64::from),
+                timestamp,
+            },
+        }
+    }
+}
+
+/// Tag MAC from the last level of a topic such as
+/// `ruuvi/<gateway mac>/<tag mac>`, which the gateway publishes to by default
+fn mac_from_topic(topic: &str) -> Option<String> {
+    let level = topic.rsplit('/').next()?;
+    let digits: Vec<char> = level.chars().filter(|c| !matches!(c, ':' | '-')).collect();
+    (digits.len() == 12 && digits.iter().all(char::is_ascii_hexdigit))
+        .then(|| ruuvi_decoder::normalize_mac(level))
+}
+
+/// Retained status published on the will topic once connected
+const ONLINE_PAYLOAD: &str = "online";
+
+/// Wait before the first reconnection attempt, doubled after each failure
+const INITIAL_RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Exponential delay between reconnection attempts, capped at a maximum
+#[derive(Debug, Clone)]
+struct ReconnectBackoff {
+    next: Duration,
+    max: Duration,
+}
 
-        Event {
-            sensor_mac: val.sensor_data.mac,
-            gateway_mac: val.message.gw_mac,
-            temperature: f64::from(val.sensor_data.temperature),
-            humidity: f64::from(val.sensor_data.humidity.unwrap_or(0.0)),
-            pressure: f64::from(val.sensor_data.pressure.unwrap_or(0.0)),
-            battery: i64::from(val.sensor_data.battery.unwrap_or(0)),
-            tx_power: i64::from(val.sensor_data.tx_power.unwrap_or(0)),
-            movement_counter: i64::from(val.sensor_data.movement_counter),
-            measurement_sequence_number: i64::from(val.sensor_data.measurement_sequence_number),
-            acceleration: f64::from(val.sensor_data.acceleration),
-            acceleration_x: i64::from(val.sensor_data.acceleration_x),
-            acceleration_y: i64::from(val.sensor_data.acceleration_y),
-            acceleration_z: i64::from(val.sensor_data.acceleration_z),
-            rssi: i64::from(val.sensor_data.rssi.unwrap_or(0)),
-            timestamp,
+impl ReconnectBackoff {
+    fn new(max: Duration) -> Self {
+        Self {
+            next: INITIAL_RECONNECT_BACKOFF.min(max),
+            max,
         }
     }
+
+    /// Delay before the next attempt; each call doubles the one after
+    fn next_delay(&mut self) -> Duration {
+        let delay = self.next;
+        self.next = self.next.saturating_mul(2).min(self.max);
+        delay
+    }
+
+    /// Start over from the initial delay once connected again
+    fn reset(&mut self) {
+        *self = Self::new(self.max);
+    }
 }
 
+/// Turns gateway message payloads into readings, counting every message and
+/// keeping undecodable payloads in the dead-letter log
+#[derive(Debug)]
+pub struct MessageDecoder {
+    options: DecoderOptions,
+    df8: Df8Decoder,
+    dead_letter: Option<DeadLetterLog>,
+    metrics: Arc<ReaderMetrics>,
+}
+
+impl MessageDecoder {
+    /// Decoder with the default options and no DF8 keys
+    #[must_use]
+    pub fn new(dead_letter: Option<DeadLetterLog>, metrics: Arc<ReaderMetrics>) -> Self {
+        Self {
+            options: DecoderOptions::default(),
+            df8: Df8Decoder::default(),
+            dead_letter,
+            metrics,
+        }
+    }
+
+    /// Decode every data format with `options`, decrypting DF8 tags with
+    /// `df8_keys`
+    #[must_use]
+    pub fn with_decoding(mut self, options: DecoderOptions, df8_keys: Df8Keys) -> Self {
+        self.options = options;
+        self.df8 = Df8Decoder::new(options, df8_keys.into_iter().collect());
+        self
+    }
+
+    /// Decode a payload received on `topic`, logging why it was dropped if
+    /// it can't be stored
+    pub fn decode(&self, topic: &str, payload: &[u8]) -> Option<DecodedMessage> {
+        self.metrics.message_received();
+        let decoded = self.decode_payload(topic, payload);
+        if decoded.is_some() {
+            self.metrics.message_decoded();
+        } else {
+            self.metrics.decode_failed();
+        }
+        decoded
+    }
+
+    fn decode_payload(&self, topic: &str, payload: &[u8]) -> Option<DecodedMessage> {
+        let message = match RuuviGatewayMessage::try_from(payload) {
+            Ok(message) => message,
+            Err(error) => {
+                error!("Error parsing message: {error}");
+                self.dead_letter(topic, payload, &error);
+                return None;
+            }
+        };
+
+        let sensor_data = match self.decode_data(&message.data) {
+            Ok(sensor_data) => sensor_data,
+            Err(error) => {
+                error!("Error decoding data attr: {error}");
+                self.dead_letter(topic, payload, &error);
+                return None;
+            }
+        };
+
+        // Store MACs in the colon form the API is queried with
+        let sensor_mac = match &sensor_data {
+            SensorData::Df5(data) => Some(ruuvi_decoder::normalize_mac(&data.mac)),
+            SensorData::Df3(_) => mac_from_topic(topic),
+        };
+        let Some(sensor_mac) = sensor_mac else {
+            let error = format!("Data format 3 reading without a tag MAC in topic {topic}");
+            error!("{error}");
+            self.dead_letter(topic, payload, &error);
+            return None;
+        };
+
+        Some(DecodedMessage {
+            message,
+            sensor_mac,
+            sensor_data,
+        })
+    }
+
+    /// Decode the BLE advertisement a gateway relays, whichever data format
+    /// the tag broadcasts
+    fn decode_data(&self, data: &str) -> DecoderResult {
+        let payload = ruuvi_decoder::manufacturer_payload(data);
+        // A malformed RSSI byte appended by the gateway shouldn't cost the
+        // whole DF5 reading
+        if self.options.trailing_rssi && ruuvi_decoder::data_format(payload) == Ok(5) {
+            if let (Some(body), Some(trailer)) = (
+                payload.get(..DF5_PAYLOAD_LEN),
+                payload.get(DF5_PAYLOAD_LEN..),
+            ) {
+                return Df5Decoder::new(self.options).decode_with_rssi(body, trailer);
+            }
+        }
+        ruuvi_decoder::detect_and_decode_with(payload, &self.df8)
+    }
+
+    fn dead_letter(&self, topic: &str, payload: &[u8], error: &dyn Display) {
+        if let Some(dead_letter) = &self.dead_letter {
+            dead_letter.record(topic, payload, error);
+        }
+    }
+}
+
+/// Readings published on the configured topic. The topic is subscribed on
+/// every connection, and a lost connection is retried with exponential
+/// backoff, so the stream never ends.
 pub fn to_stream(
     mut eventloop: rumqttc::EventLoop,
-    decoder: ruuvi_decoder::Df5Decoder,
+    client: AsyncClient,
+    config: &Config,
+    metrics: Arc<ReaderMetrics>,
 ) -> impl Stream<Item = DecodedMessage> {
-    async_stream::stream! {
-        while let Ok(notification) = eventloop.poll().await {
-            if let rumqttc::Event::Incoming(Incoming::Publish(packet)) = notification {
-                match RuuviGatewayMessage::try_from(packet.payload.as_ref()) {
-                    Ok(message) => {
-                        let sensor_data = match decoder.decode_data(&message.data) {
-                            Ok(SensorData::Df5(measure)) => measure,
-                            Err(error) => {
-                                error!("Error decoding data attr: {error}");
-                                continue;
-                            }
-                        };
-
-                        let decoded_message = DecodedMessage {
-                            message,
-                            sensor_data,
-                        };
+    let topic = config.mqtt_topic.clone();
+    let will_topic = config.mqtt_will_topic.clone();
+    let mut backoff = ReconnectBackoff::new(config.mqtt_max_reconnect_backoff);
+    let dead_letter = config
+        .dead_letter_log
+        .then(|| DeadLetterLog::new(&config.log_filepath));
+    let decoder = MessageDecoder::new(dead_letter, metrics)
+        .with_decoding(config.decoder_options, config.df8_keys.clone());
 
+    async_stream::stream! {
+        loop {
+            match eventloop.poll().await {
+                Ok(rumqttc::Event::Incoming(Incoming::ConnAck(_))) => {
+                    info!("Connected to MQTT broker, subscribing to {topic}");
+                    backoff.reset();
+                    if let Err(error) = client.try_subscribe(topic.clone(), QoS::AtMostOnce) {
+                        error!("Failed to subscribe to {topic}: {error}");
+                    }
+                    if let Some(will_topic) = &will_topic {
+                        if let Err(error) = client.try_publish(
+                            will_topic.clone(),
+                            QoS::AtLeastOnce,
+                            true,
+                            ONLINE_PAYLOAD,
+                        ) {
+                            error!("Failed to publish status to {will_topic}: {error}");
+                        }
+                    }
+                }
+                Ok(rumqttc::Event::Incoming(Incoming::Publish(packet))) => {
+                    if let Some(decoded_message) = decoder.decode(&packet.topic, &packet.payload) {
                         yield decoded_message;
                     }
-                    Err(error) => error!("Error parsing message: {error}"),
+                }
+                Ok(_) => {}
+                Err(error) => {
+                    let delay = backoff.next_delay();
+                    warn!("MQTT connection error: {error}, reconnecting in {delay:?}");
+                    tokio::time::sleep(delay).await;
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::pin::pin;
+
+    use futures::StreamExt;
+    use ruuvi_decoder::Decoder;
+    use tokio::{
+        io::{
+            AsyncReadExt,
+            AsyncWriteExt,
+        },
+        net::{
+            TcpListener,
+            TcpStream,
+        },
+        time::timeout,
+    };
+
+    use super::*;
+
+    #[allow(clippy::expect_used)]
+    fn decoded_message(payload_rssi: Option<&str>) -> DecodedMessage {
+        let data = "050F18FFFFFFFFFFF0FFEC0414AA96A8DE8EF797E36ED811";
+        let decoder = ruuvi_decoder::Df5Decoder::default();
+        let result = match payload_rssi {
+            Some(rssi_hex) => decoder.decode_with_rssi(data, rssi_hex),
+            None => decoder.decode_data(data),
+        };
+        let sensor_data = result.expect("valid DF5 payload");
+        let message = RuuviGatewayMessage::try_from(
+            format!(
+                r#"{{"gw_mac":"AA:BB:CC:DD:EE:FF","rssi":-71,"ts":1700000000,"data":"{data}"}}"#
+            )
+            .as_bytes(),
+        )
+        .expect("Valid message");
+
+        DecodedMessage {
+            message,
+            sensor_mac: "F7:97:E3:6E:D8:11".to_string(),
+            sensor_data,
+        }
+    }
+
+    #[test]
+    fn test_event_uses_payload_rssi() {
+        let event = Event::from(decoded_message(Some("C5")));
+
+        assert_eq!(event.rssi, -59);
+    }
+
+    #[test]
+    fn test_event_falls_back_to_gateway_rssi() {
+        let event = Event::from(decoded_message(None));
+
+        assert_eq!(event.rssi, -71);
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_event_mac_is_normalized() {
+        let decoded = MessageDecoder::new(None, Arc::default())
+            .decode(TEST_TOPIC, &gateway_payload(1_700_000_000))
+            .expect("decoded");
+        let event = Event::from(decoded);
+
+        assert_eq!(event.sensor_mac, "F7:97:E3:6E:D8:11");
+        assert_eq!(event.gateway_mac, "AA:BB:CC:DD:EE:FF");
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_malformed_payload_is_dead_lettered() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let path = dir.path().join("dead-letters.log");
+        let decoder = MessageDecoder::new(Some(DeadLetterLog::new(&path)), Arc::default());
+
+        for payload in [
+            &b"{not json"[..],
+            br#"{"gw_mac":"AA:BB:CC:DD:EE:FF","rssi":-71,"ts":1700000000,"data":"zz"}"#,
+        ] {
+            assert!(decoder.decode("ruuvi/test", payload).is_none());
+        }
+        let valid = gateway_payload(1_700_000_000);
+        assert!(decoder.decode("ruuvi/test", &valid).is_some());
+
+        let contents = std::fs::read_to_string(&path).expect("dead-letter file");
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("JSON line"))
+            .collect();
+        assert_eq!(lines.len(), 2);
+        let first = lines.first().expect("first line");
+        assert_eq!(first["topic"], "ruuvi/test");
+        assert_eq!(first["payload"], "{not json");
+        assert!(first["error"]
+            .as_str()
+            .is_some_and(|error| !error.is_empty()));
+        assert!(first["received_at"].is_string());
+    }
+
+    /// Decode a gateway message carrying `data`, as published on the test
+    /// topic
+    fn decode_with(decoder: &MessageDecoder, data: &str) -> Option<Event> {
+        let payload = format!(
+            r#"{{"gw_mac":"AA:BB:CC:DD:EE:FF","rssi":-71,"ts":1700000000,"data":"{data}"}}"#
+        );
+        decoder
+            .decode(TEST_TOPIC, payload.as_bytes())
+            .map(Event::from)
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_gateway_advertisement_decodes_end_to_end() {
+        // The message fixture of `ruuvi_gateway_message`, as a gateway sends it
+        let payload = br#"{"gw_mac":"AA:BB:CC:DD:EE:FF","rssi":-60,"gwts":1700000001,"ts":1700000000,"data":"0201061BFF9904050F18FFFFFFFFFFF0FFEC0414AA96A8DE8EF797E36ED811","coords":"60.17,24.94"}"#;
+        let metrics = Arc::new(ReaderMetrics::default());
+
+        let event = MessageDecoder::new(None, Arc::clone(&metrics))
+            .decode(TEST_TOPIC, payload)
+            .map(Event::from)
+            .expect("decoded");
+
+        assert_eq!(event.sensor_mac, "F7:97:E3:6E:D8:11");
+        assert!((event.temperature - 19.32).abs() < 1e-4);
+        assert_eq!(event.battery, 2964);
+        assert_eq!(event.tx_power, 4);
+        assert_eq!(event.movement_counter, 168);
+        assert_eq!(event.measurement_sequence_number, 56974);
+        assert_eq!(event.rssi, -60);
+        assert!(metrics
+            .render()
+            .contains("ruuvi_reader_messages_decode_failed_total 0\n"));
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_configured_options_and_keys_are_used() {
+        let df8 = "0201061BFF990408EFA4F2D02492F9D025F884CC8049DED493F797E36ED811";
+        let keyless = MessageDecoder::new(None, Arc::default());
+        assert!(decode_with(&keyless, df8).is_none());
+
+        let options = DecoderOptions {
+            plausibility_checks: true,
+            trailing_rssi: true,
+        };
+        let decoder = MessageDecoder::new(None, Arc::default()).with_decoding(
+            options,
+            vec![("F7:97:E3:6E:D8:11".to_string(), *b"ruuvi-home-key16")],
+        );
+        let event = decode_with(&decoder, df8).expect("decrypted DF8");
+        assert!((event.temperature - 21.5).abs() < 1e-4);
+        assert_eq!(event.measurement_sequence_number, 772);
+
+        // The RSSI byte a gateway appends is used, and a malformed one falls
+        // back to the gateway's own measurement
+        let df5 = "0201061BFF9904050F18FFFFFFFFFFF0FFEC0414AA96A8DE8EF797E36ED811";
+        let event = decode_with(&decoder, &format!("{df5}C5")).expect("DF5 with RSSI");
+        assert_eq!(event.rssi, -59);
+        let event = decode_with(&decoder, &format!("{df5}ZZ")).expect("DF5 with bad RSSI");
+        assert_eq!(event.rssi, -71);
+    }
+
+    #[test]
+    fn test_reconnect_backoff_doubles_up_to_max() {
+        let mut backoff = ReconnectBackoff::new(Duration::from_secs(5));
+        let delays: Vec<u64> = (0..5).map(|_| backoff.next_delay().as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 5, 5]);
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+
+        let mut short = ReconnectBackoff::new(Duration::from_millis(200));
+        assert_eq!(short.next_delay(), Duration::from_millis(200));
+    }
+
+    const TEST_TOPIC: &str = "ruuvi/test";
+
+    fn gateway_payload(ts: u32) -> Vec<u8> {
+        format!(
+            r#"{{"gw_mac":"AA:BB:CC:DD:EE:FF","rssi":-71,"ts":{ts},"data":"0201061BFF9904050F18FFFFFFFFFFF0FFEC0414AA96A8DE8EF797E36ED811"}}"#
+        )
+        .into_bytes()
+    }
+
+    /// Read one MQTT packet, returning its type and body
+    #[allow(clippy::arithmetic_side_effects)]
+    async fn read_packet(socket: &mut TcpStream) -> std::io::Result<(u8, Vec<u8>)> {
+        let header = socket.read_u8().await?;
+        let mut length = 0_usize;
+        let mut shift = 0;
+        loop {
+            let byte = socket.read_u8().await?;
+            length |= usize::from(byte & 0x7F) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+        }
+        let mut body = vec![0; length];
+        socket.read_exact(&mut body).await?;
+        Ok((header >> 4, body))
+    }
+
+    /// QoS 0 PUBLISH packet of `payload` on `topic`
+    #[allow(clippy::arithmetic_side_effects, clippy::cast_possible_truncation)]
+    fn publish_packet(topic: &str, payload: &[u8]) -> Vec<u8> {
+        let mut remaining = 2 + topic.len() + payload.len();
+        let mut packet = vec![0x30];
+        loop {
+            let byte = (remaining % 128) as u8;
+            remaining /= 128;
+            if remaining == 0 {
+                packet.push(byte);
+                break;
+            }
+            packet.push(byte | 0x80);
+        }
+        packet.extend_from_slice(&(topic.len() as u16).to_be_bytes());
+        packet.extend_from_slice(topic.as_bytes());
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    /// Act as a broker for one connection: acknowledge the client, publish
+    /// `payload` on `topic` once it subscribes, then hang up
+    #[allow(clippy::expect_used)]
+    async fn serve_once(listener: &TcpListener, topic: &str, payload: &[u8]) {
+        let (mut socket, _) = listener.accept().await.expect("client connects");
+        loop {
+            let (kind, body) = read_packet(&mut socket).await.expect("client packet");
+            match kind {
+                // CONNECT
+                1 => socket
+                    .write_all(&[0x20, 0x02, 0x00, 0x00])
+                    .await
+                    .expect("send CONNACK"),
+                // SUBSCRIBE
+                8 => {
+                    let mut suback = vec![0x90, 0x03];
+                    suback.extend_from_slice(body.get(..2).expect("packet id"));
+                    suback.push(0x00);
+                    socket.write_all(&suback).await.expect("send SUBACK");
+                    socket
+                        .write_all(&publish_packet(topic, payload))
+                        .await
+                        .expect("send PUBLISH");
+                    return;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn test_config(port: u16) -> Config {
+        Config::new(
+            None,
+            None,
+            "127.0.0.1".to_string(),
+            port,
+            TEST_TOPIC.to_string(),
+            "/tmp/test.log".to_string(),
+        )
+    }
+
+    #[tokio::test]
+    #[allow(clippy::expect_used)]
+    async fn test_stream_resumes_after_connection_drop() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let port = listener.local_addr().expect("address").port();
+        let broker = tokio::spawn(async move {
+            serve_once(&listener, TEST_TOPIC, &gateway_payload(1_700_000_000)).await;
+            serve_once(&listener, TEST_TOPIC, &gateway_payload(1_700_000_060)).await;
+        });
+
+        let config = test_config(port).with_max_reconnect_backoff(Duration::from_millis(50));
+        let stream = crate::read::create(&config, Arc::default()).expect("stream");
+        let mut stream = pin!(stream);
+
+        for expected_ts in [1_700_000_000, 1_700_000_060] {
+            let decoded = timeout(Duration::from_secs(5), stream.next())
+                .await
+                .expect("reading before timeout")
+                .expect("stream continues");
+            assert_eq!(decoded.message.ts, expected_ts);
+        }
+        broker.await.expect("broker finishes");
+    }
+
+    #[tokio::test]
+    #[allow(clippy::expect_used)]
+    async fn test_stream_produces_df3_event() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let port = listener.local_addr().expect("address").port();
+        let payload = br#"{"gw_mac":"AA:BB:CC:DD:EE:FF","rssi":-71,"ts":1700000000,"data":"02010611FF990403291A1ECE1EFC18F94202CA0B53"}"#;
+        let broker = tokio::spawn(async move {
+            serve_once(
+                &listener,
+                "ruuvi/AA:BB:CC:DD:EE:FF/c7:58:2b:1d:0a:3e",
+                payload,
+            )
+            .await;
+        });
+
+        let stream = crate::read::create(&test_config(port), Arc::default()).expect("stream");
+        let mut stream = pin!(stream);
+        let decoded = timeout(Duration::from_secs(5), stream.next())
+            .await
+            .expect("reading before timeout")
+            .expect("stream continues");
+        broker.await.expect("broker finishes");
+
+        let event = Event::from(decoded);
+        assert_eq!(event.sensor_mac, "C7:58:2B:1D:0A:3E");
+        assert_eq!(event.gateway_mac, "AA:BB:CC:DD:EE:FF");
+        assert!((event.temperature - 26.3).abs() < 1e-4);
+        assert!((event.humidity - 20.5).abs() < 1e-4);
+        assert!((event.pressure - 1027.66).abs() < 1e-2);
+        assert_eq!(event.battery, 2899);
+        assert_eq!(event.acceleration_x, -1000);
+        assert_eq!(event.tx_power, 0);
+        assert_eq!(event.rssi, -71);
+    }
+
+    #[test]
+    fn test_df3_without_mac_in_topic_is_dropped() {
+        let payload = br#"{"gw_mac":"AA:BB:CC:DD:EE:FF","rssi":-71,"ts":1700000000,"data":"03291A1ECE1EFC18F94202CA0B53"}"#;
+
+        assert!(MessageDecoder::new(None, Arc::default())
+            .decode(TEST_TOPIC, payload)
+            .is_none());
+        assert_eq!(
+            mac_from_topic("ruuvi/gw/c7582b1d0a3e").as_deref(),
+            Some("C7:58:2B:1D:0A:3E")
+        );
+        assert_eq!(mac_from_topic("ruuvi/gateway/data"), None);
+    }
+}
@@ -2,16 +2,29 @@ use serde::{
     Deserialize,
     Serialize,
 };
+use serde_json::{
+    Map,
+    Value,
+};
 
+/// Message published by a Ruuvi Gateway for one advertisement.
+///
+/// Firmware versions differ in which fields they send: `gwts` and `coords`
+/// are often missing, and newer firmwares add fields this reader does not
+/// know about. Those are kept in `extra` instead of failing the message.
 #[derive(Debug, Deserialize, Serialize)]
 pub struct RuuviGatewayMessage {
     pub gw_mac: String, // gateway mac
     pub rssi: i16,      // signal strength
     // pub aoa: Vec<i16>,
-    pub gwts: u32,      // gateway timestamp
-    pub ts: u32,        // timestamp
-    pub data: String,   // sensor data
-    pub coords: String, // coordinates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gwts: Option<u32>, // gateway timestamp
+    pub ts: u32,      // timestamp
+    pub data: String, // sensor data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coords: Option<String>, // coordinates
+    #[serde(flatten)]
+    pub extra: Map<String, Value>, // fields not known to this reader
 }
 
 impl TryFrom<&[u8]> for RuuviGatewayMessage {
@@ -21,3 +34,59 @@ impl TryFrom<&[u8]> for RuuviGatewayMessage {
         serde_json::from_slice(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: &str = "0201061BFF9904050F18FFFFFFFFFFF0FFEC0414AA96A8DE8EF797E36ED811";
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_full_message() {
+        let payload = format!(
+            r#"{{"gw_mac":"AA:BB:CC:DD:EE:FF","rssi":-60,"gwts":1700000001,"ts":1700000000,"data":"{DATA}","coords":"60.17,24.94"}}"#
+        );
+
+        let message = RuuviGatewayMessage::try_from(payload.as_bytes()).expect("Valid message");
+
+        assert_eq!(message.gwts, Some(1_700_000_001));
+        assert_eq!(message.ts, 1_700_000_000);
+        assert_eq!(message.coords.as_deref(), Some("60.17,24.94"));
+        assert!(message.extra.is_empty());
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_missing_gwts_and_coords() {
+        let payload = format!(
+            r#"{{"gw_mac":"AA:BB:CC:DD:EE:FF","rssi":-60,"ts":1700000000,"data":"{DATA}"}}"#
+        );
+
+        let message = RuuviGatewayMessage::try_from(payload.as_bytes()).expect("Valid message");
+
+        assert_eq!(message.gwts, None);
+        assert_eq!(message.coords, None);
+        assert_eq!(message.data, DATA);
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_extra_fields_are_kept() {
+        let payload = format!(
+            r#"{{"gw_mac":"AA:BB:CC:DD:EE:FF","rssi":-60,"aoa":[],"ts":1700000000,"data":"{DATA}","nonce":42}}"#
+        );
+
+        let message = RuuviGatewayMessage::try_from(payload.as_bytes()).expect("Valid message");
+
+        assert_eq!(message.extra.get("aoa"), Some(&Value::Array(Vec::new())));
+        assert_eq!(message.extra.get("nonce"), Some(&Value::from(42)));
+    }
+
+    #[test]
+    fn test_missing_required_field_fails() {
+        let payload = r#"{"gw_mac":"AA:BB:CC:DD:EE:FF","rssi":-60,"ts":1700000000}"#;
+
+        assert!(RuuviGatewayMessage::try_from(payload.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,400 @@
+use std::{
+    future::Future,
+    pin::pin,
+    sync::Arc,
+    time::Duration,
+};
+
+use futures::{
+    Stream,
+    StreamExt,
+};
+use postgres_store::Event;
+use tokio::time::MissedTickBehavior;
+use tracing::{
+    error,
+    info,
+    warn,
+};
+
+use super::{
+    config::Config,
+    db::PostgresWriter,
+};
+use crate::{
+    metrics::ReaderMetrics,
+    read::dead_letter::DeadLetterLog,
+};
+
+/// Topic recorded for readings dead-lettered because the database would
+/// reject them
+pub const REJECTED_TOPIC: &str = "sensor_data";
+
+pub type WriteResult = Result<(), Box<dyn std::error::Error>>;
+
+/// Destination of buffered events, written one batch at a time
+pub trait BatchWriter {
+    /// Store `events` in a single round-trip
+    fn write_batch(&self, events: Vec<Event>) -> impl Future<Output = WriteResult>;
+}
+
+impl BatchWriter for PostgresWriter {
+    fn write_batch(&self, events: Vec<Event>) -> impl Future<Output = WriteResult> {
+        self.write_sensor_data(events)
+    }
+}
+
+/// Accumulates events and hands them to the writer in batches, so a burst of
+/// readings costs a few round-trips instead of one per reading
+#[derive(Debug)]
+pub struct EventBuffer<W> {
+    writer: W,
+    events: Vec<Event>,
+    batch_size: usize,
+    flush_interval: Duration,
+    metrics: Arc<ReaderMetrics>,
+    dead_letter: Option<DeadLetterLog>,
+}
+
+impl<W: BatchWriter> EventBuffer<W> {
+    /// Buffer for `writer` flushing as configured; a zero batch size or
+    /// interval is raised to the smallest usable value
+    #[must_use]
+    pub fn new(writer: W, config: &Config, metrics: Arc<ReaderMetrics>) -> Self {
+        let batch_size = config.batch_size.max(1);
+        Self {
+            writer,
+            events: Vec::with_capacity(batch_size),
+            batch_size,
+            flush_interval: config.batch_flush_interval.max(Duration::from_millis(1)),
+            metrics,
+            dead_letter: None,
+        }
+    }
+
+    /// Record readings rejected before the write in `dead_letter`
+    #[must_use]
+    pub fn with_dead_letter(mut self, dead_letter: Option<DeadLetterLog>) -> Self {
+        self.dead_letter = dead_letter;
+        self
+    }
+
+    /// Number of events waiting to be written
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Add `event`, writing the batch once it is full
+    pub async fn push(&mut self, event: Event) {
+        self.events.push(event);
+        self.metrics.set_buffered_events(self.events.len());
+        if self.events.len() >= self.batch_size {
+            self.flush().await;
+        }
+    }
+
+    /// Write every buffered event; a failed batch is logged and dropped so
+    /// one bad write doesn't stall ingestion
+    ///
+    /// Events outside the ranges the database accepts are dropped before the
+    /// write, as a single one would fail the whole multi-row insert.
+    pub async fn flush(&mut self) {
+        if self.events.is_empty() {
+            return;
+        }
+        let buffered = std::mem::replace(&mut self.events, Vec::with_capacity(self.batch_size));
+        self.metrics.set_buffered_events(0);
+        let events: Vec<Event> = buffered
+            .into_iter()
+            .filter(|event| self.accept(event))
+            .collect();
+        if events.is_empty() {
+            return;
+        }
+        let count = events.len();
+        if let Err(err) = self.writer.write_batch(events).await {
+            self.metrics.db_write_failed(count);
+            error!("Failed to write batch of {count} events to PostgreSQL: {err}");
+        }
+    }
+
+    /// Buffer events from `stream` until it ends or `shutdown` completes,
+    /// flushing on a full batch or every flush interval, then write whatever
+    /// remains
+    pub async fn run(
+        &mut self,
+        stream: impl Stream<Item = Event>,
+        shutdown: impl Future<Output = ()>,
+    ) {
+        let mut stream = pin!(stream);
+        let mut shutdown = pin!(shutdown);
+        let mut ticker = tokio::time::interval(self.flush_interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ticker.tick().await;
+
+        loop {
+            tokio::select! {
+                () = &mut shutdown => {
+                    info!("Shutting down, flushing {} buffered events", self.len());
+                    break;
+                }
+                event = stream.next() => match event {
+                    Some(event) => self.push(event).await,
+                    None => break,
+                },
+                _ = ticker.tick() => self.flush().await,
+            }
+        }
+        self.flush().await;
+    }
+
+    /// Whether `event` can be written, dead-lettering it if not
+    fn accept(&self, event: &Event) -> bool {
+        let Err(err) = event.validate() else {
+            return true;
+        };
+        warn!("Dropping reading from {}: {err}", event.sensor_mac);
+        self.metrics.db_write_failed(1);
+        if let Some(dead_letter) = &self.dead_letter {
+            let payload = serde_json::to_vec(event).unwrap_or_default();
+            dead_letter.record(REJECTED_TOPIC, &payload, &err);
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use futures::{
+        future,
+        stream,
+    };
+    use tokio::time::timeout;
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+
+    type Batches = Arc<Mutex<Vec<Vec<Event>>>>;
+
+    /// Records every batch it is asked to write
+    #[derive(Clone, Default)]
+    struct RecordingWriter {
+        batches: Batches,
+    }
+
+    impl RecordingWriter {
+        #[allow(clippy::expect_used)]
+        fn batches(&self) -> Vec<usize> {
+            self.batches
+                .lock()
+                .expect("batches lock")
+                .iter()
+                .map(Vec::len)
+                .collect()
+        }
+
+        #[allow(clippy::expect_used)]
+        fn written(&self) -> Vec<Event> {
+            self.batches.lock().expect("batches lock").concat()
+        }
+    }
+
+    impl BatchWriter for RecordingWriter {
+        #[allow(clippy::expect_used)]
+        async fn write_batch(&self, events: Vec<Event>) -> WriteResult {
+            self.batches.lock().expect("batches lock").push(events);
+            Ok(())
+        }
+    }
+
+    /// Fails every write, as an unreachable database would
+    struct FailingWriter;
+
+    impl BatchWriter for FailingWriter {
+        async fn write_batch(&self, _events: Vec<Event>) -> WriteResult {
+            Err("connection refused".into())
+        }
+    }
+
+    fn event() -> Event {
+        Event::new_with_current_time(
+            "AA:BB:CC:DD:EE:FF".to_string(),
+            "11:22:33:44:55:66".to_string(),
+            21.0,
+            45.0,
+            1013.0,
+            3000,
+            4,
+            0,
+            1,
+            1000.0,
+            0,
+            0,
+            1000,
+            -60,
+        )
+    }
+
+    fn config(batch_size: usize, flush_interval: Duration) -> Config {
+        Config::new("postgresql://localhost/db".to_string())
+            .with_batching(batch_size, flush_interval)
+    }
+
+    #[tokio::test]
+    async fn test_burst_is_written_in_batches() {
+        let writer = RecordingWriter::default();
+        let mut buffer = EventBuffer::new(
+            writer.clone(),
+            &config(10, Duration::from_secs(60)),
+            Arc::default(),
+        );
+
+        buffer
+            .run(stream::iter((0..25).map(|_| event())), future::pending())
+            .await;
+
+        assert_eq!(writer.batches(), [10, 10, 5]);
+        assert!(buffer.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_partial_batch_is_flushed_on_interval() {
+        let writer = RecordingWriter::default();
+        let mut buffer = EventBuffer::new(
+            writer.clone(),
+            &config(10, Duration::from_millis(20)),
+            Arc::default(),
+        );
+        let events = stream::iter((0..3).map(|_| event())).chain(stream::pending());
+
+        let run = timeout(
+            Duration::from_millis(200),
+            buffer.run(events, future::pending()),
+        )
+        .await;
+
+        assert!(run.is_err(), "Stream never ends");
+        assert_eq!(writer.batches(), [3]);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::expect_used)]
+    async fn test_shutdown_stops_and_flushes() {
+        let writer = RecordingWriter::default();
+        let mut buffer = EventBuffer::new(
+            writer.clone(),
+            &config(10, Duration::from_secs(60)),
+            Arc::default(),
+        );
+        let events = stream::iter((0..13).map(|_| event())).chain(stream::pending());
+        let token = CancellationToken::new();
+
+        let cancel = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            cancel.cancel();
+        });
+        timeout(
+            Duration::from_secs(5),
+            buffer.run(events, token.cancelled()),
+        )
+        .await
+        .expect("run ends on shutdown");
+
+        assert_eq!(writer.batches(), [10, 3]);
+        assert!(buffer.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_write_is_counted() {
+        let metrics = Arc::new(ReaderMetrics::default());
+        let mut buffer = EventBuffer::new(
+            FailingWriter,
+            &config(10, Duration::from_secs(60)),
+            Arc::clone(&metrics),
+        );
+
+        buffer.push(event()).await;
+        buffer.push(event()).await;
+        assert!(metrics
+            .render()
+            .contains("ruuvi_reader_buffered_events 2\n"));
+
+        buffer.flush().await;
+        let output = metrics.render();
+        assert!(output.contains("ruuvi_reader_db_write_failed_total 2\n"));
+        assert!(output.contains("ruuvi_reader_buffered_events 0\n"));
+    }
+
+    #[tokio::test]
+    async fn test_flush_without_events_skips_writer() {
+        let writer = RecordingWriter::default();
+        let mut buffer =
+            EventBuffer::new(writer.clone(), &config(0, Duration::ZERO), Arc::default());
+
+        buffer.flush().await;
+        assert!(writer.batches().is_empty());
+
+        buffer.push(event()).await;
+        assert_eq!(writer.batches(), [1]);
+        assert_eq!(buffer.len(), 0);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::expect_used)]
+    async fn test_invalid_event_is_dead_lettered_and_rest_written() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let path = dir.path().join("dead.log");
+        let writer = RecordingWriter::default();
+        let metrics = Arc::new(ReaderMetrics::default());
+        let mut buffer = EventBuffer::new(
+            writer.clone(),
+            &config(3, Duration::from_secs(60)),
+            Arc::clone(&metrics),
+        )
+        .with_dead_letter(Some(DeadLetterLog::new(&path)));
+        let mut invalid = event();
+        invalid.humidity = 180.0;
+
+        buffer.push(event()).await;
+        buffer.push(invalid).await;
+        buffer.push(event()).await;
+
+        assert_eq!(writer.batches(), [2]);
+        assert!(writer
+            .written()
+            .iter()
+            .all(|event| event.validate().is_ok()));
+        assert!(metrics
+            .render()
+            .contains("ruuvi_reader_db_write_failed_total 1\n"));
+        let letters = std::fs::read_to_string(&path).expect("dead letter file");
+        assert_eq!(letters.lines().count(), 1);
+        assert!(letters.contains("humidity 180"));
+    }
+
+    #[tokio::test]
+    async fn test_batch_of_only_invalid_events_skips_writer() {
+        let writer = RecordingWriter::default();
+        let mut buffer = EventBuffer::new(
+            writer.clone(),
+            &config(1, Duration::from_secs(60)),
+            Arc::default(),
+        );
+        let mut invalid = event();
+        invalid.temperature = -150.0;
+
+        buffer.push(invalid).await;
+
+        assert!(writer.batches().is_empty());
+        assert!(buffer.is_empty());
+    }
+}
@@ -1,15 +1,41 @@
-use crate::env::from_env;
+use std::time::Duration;
+
+use crate::env::{
+    from_env,
+    try_from_env,
+};
 
 #[derive(Clone)]
 #[allow(missing_debug_implementations)]
 pub struct Config {
     pub database_url: String,
+    pub batch_size: usize,
+    pub batch_flush_interval: Duration,
 }
 
+/// Events written in one database round-trip unless configured otherwise
+pub const DEFAULT_BATCH_SIZE: usize = 100;
+
+/// Longest time an event waits in the buffer unless configured otherwise
+pub const DEFAULT_BATCH_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
 impl Config {
     #[must_use]
     pub const fn new(database_url: String) -> Self {
-        Self { database_url }
+        Self {
+            database_url,
+            batch_size: DEFAULT_BATCH_SIZE,
+            batch_flush_interval: DEFAULT_BATCH_FLUSH_INTERVAL,
+        }
+    }
+
+    /// Flush buffered events every `batch_size` events or every
+    /// `flush_interval`, whichever comes first
+    #[must_use]
+    pub const fn with_batching(mut self, batch_size: usize, flush_interval: Duration) -> Self {
+        self.batch_size = batch_size;
+        self.batch_flush_interval = flush_interval;
+        self
     }
 
     /// # Panics
@@ -17,6 +43,12 @@ impl Config {
     pub fn from_env() -> Self {
         Self {
             database_url: from_env("DATABASE_URL"),
+            batch_size: try_from_env("BATCH_SIZE")
+                .and_then(|size| size.parse().ok())
+                .unwrap_or(DEFAULT_BATCH_SIZE),
+            batch_flush_interval: try_from_env("BATCH_FLUSH_INTERVAL_MS")
+                .and_then(|millis| millis.parse().ok())
+                .map_or(DEFAULT_BATCH_FLUSH_INTERVAL, Duration::from_millis),
         }
     }
 }
@@ -33,6 +65,17 @@ mod tests {
         assert_eq!(config.database_url, db_url);
     }
 
+    #[test]
+    fn test_config_with_batching() {
+        let config = Config::new("postgresql://localhost/db".to_string());
+        assert_eq!(config.batch_size, DEFAULT_BATCH_SIZE);
+        assert_eq!(config.batch_flush_interval, DEFAULT_BATCH_FLUSH_INTERVAL);
+
+        let config = config.with_batching(10, Duration::from_millis(250));
+        assert_eq!(config.batch_size, 10);
+        assert_eq!(config.batch_flush_interval, Duration::from_millis(250));
+    }
+
     #[test]
     fn test_config_new_with_different_urls() {
         let test_cases = vec![
@@ -1,13 +1,17 @@
 use std::sync::Arc;
 
+use chrono::Utc;
 use postgres_store::{
     Event,
     PostgresStore,
 };
 
+use super::metrics::IngestLatency;
+
 #[derive(Debug)]
 pub struct PostgresWriter {
     store: Arc<PostgresStore>,
+    ingest_latency: Arc<IngestLatency>,
 }
 
 impl PostgresWriter {
@@ -15,17 +19,33 @@ impl PostgresWriter {
     /// This function can fail if the `PostgreSQL` connection fails.
     pub async fn new(database_url: &str) -> Result<Self, Box<dyn std::error::Error>> {
         let store = Arc::new(PostgresStore::new(database_url).await?);
-        Ok(Self { store })
+        Ok(Self {
+            store,
+            ingest_latency: Arc::default(),
+        })
+    }
+
+    /// Latency from gateway timestamp to insert of every stored reading
+    #[must_use]
+    pub fn ingest_latency(&self) -> Arc<IngestLatency> {
+        Arc::clone(&self.ingest_latency)
     }
 
+    /// Store `events` in a single round-trip
+    ///
     /// # Errors
     /// This function can fail if the `PostgreSQL` write operation fails.
     pub async fn write_sensor_data(
         &self,
         events: Vec<Event>,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        for event in events {
-            self.store.insert_event(&event).await?;
+        if events.is_empty() {
+            return Ok(());
+        }
+        self.store.insert_events(&events).await?;
+        let stored_at = Utc::now();
+        for event in &events {
+            self.ingest_latency.record(event.timestamp, stored_at);
         }
         Ok(())
     }
@@ -0,0 +1,190 @@
+use std::{
+    fmt,
+    sync::atomic::{
+        AtomicU64,
+        Ordering,
+    },
+    time::Duration,
+};
+
+use chrono::{
+    DateTime,
+    Utc,
+};
+
+/// Upper bounds, in seconds, of the ingest latency histogram buckets. A final
+/// unbounded bucket catches everything slower.
+pub const LATENCY_BUCKETS_SECONDS: [f64; 8] = [0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0];
+
+const BUCKET_COUNT: usize = 9;
+
+/// Upper bound in seconds and cumulative count of a latency bucket
+pub type Bucket = (f64, u64);
+
+/// Histogram of the time from a reading's gateway timestamp to its successful
+/// insert.
+///
+/// Large values point at pipeline delay or a gateway clock running behind;
+/// readings stamped in the future mean the gateway clock runs ahead. Those
+/// are counted separately and recorded as zero latency.
+#[derive(Debug, Default)]
+pub struct IngestLatency {
+    buckets: [AtomicU64; BUCKET_COUNT],
+    count: AtomicU64,
+    sum_millis: AtomicU64,
+    future_timestamps: AtomicU64,
+}
+
+impl IngestLatency {
+    /// Record a reading stamped `timestamp` that was stored at `stored_at`
+    pub fn record(&self, timestamp: DateTime<Utc>, stored_at: DateTime<Utc>) {
+        let latency = stored_at
+            .signed_duration_since(timestamp)
+            .to_std()
+            .unwrap_or_else(|_| {
+                self.future_timestamps.fetch_add(1, Ordering::Relaxed);
+                Duration::ZERO
+            });
+
+        let seconds = latency.as_secs_f64();
+        let bucket = LATENCY_BUCKETS_SECONDS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS_SECONDS.len());
+        if let Some(bucket) = self.buckets.get(bucket) {
+            bucket.fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_millis.fetch_add(
+            u64::try_from(latency.as_millis()).unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Current values of the histogram
+    pub fn snapshot(&self) -> LatencySnapshot {
+        let bounds = LATENCY_BUCKETS_SECONDS
+            .iter()
+            .copied()
+            .chain([f64::INFINITY]);
+        let buckets = bounds
+            .zip(&self.buckets)
+            .scan(0_u64, |cumulative, (bound, bucket)| {
+                *cumulative = cumulative.saturating_add(bucket.load(Ordering::Relaxed));
+                Some((bound, *cumulative))
+            })
+            .collect();
+
+        LatencySnapshot {
+            buckets,
+            count: self.count.load(Ordering::Relaxed),
+            sum: Duration::from_millis(self.sum_millis.load(Ordering::Relaxed)),
+            future_timestamps: self.future_timestamps.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Point-in-time copy of an [`IngestLatency`] histogram
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatencySnapshot {
+    /// Cumulative count of readings at or below each upper bound, ending
+    /// with an infinite bound
+    pub buckets: Vec<Bucket>,
+    pub count: u64,
+    pub sum: Duration,
+    pub future_timestamps: u64,
+}
+
+impl LatencySnapshot {
+    /// Average latency, or `None` before anything was recorded
+    pub fn mean(&self) -> Option<Duration> {
+        u32::try_from(self.count)
+            .ok()
+            .and_then(|count| self.sum.checked_div(count))
+    }
+}
+
+impl fmt::Display for LatencySnapshot {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{} readings stored", self.count)?;
+        if let Some(mean) = self.mean() {
+            write!(
+                formatter,
+                ", mean ingest latency {:.2}s",
+                mean.as_secs_f64()
+            )?;
+        }
+        if self.future_timestamps > 0 {
+            write!(
+                formatter,
+                ", {} stamped in the future",
+                self.future_timestamps
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+
+    fn stored_after(latency: &IngestLatency, delay: TimeDelta) {
+        let timestamp = Utc::now();
+        latency.record(
+            timestamp,
+            timestamp.checked_add_signed(delay).unwrap_or(timestamp),
+        );
+    }
+
+    #[test]
+    fn test_record_fills_buckets() {
+        let latency = IngestLatency::default();
+        stored_after(&latency, TimeDelta::milliseconds(50));
+        stored_after(&latency, TimeDelta::seconds(2));
+        stored_after(&latency, TimeDelta::minutes(10));
+
+        let snapshot = latency.snapshot();
+        assert_eq!(snapshot.count, 3);
+        assert_eq!(snapshot.sum, Duration::from_millis(602_050));
+        assert_eq!(snapshot.future_timestamps, 0);
+        assert_eq!(
+            snapshot.buckets,
+            vec![
+                (0.1, 1),
+                (0.5, 1),
+                (1.0, 1),
+                (5.0, 2),
+                (10.0, 2),
+                (30.0, 2),
+                (60.0, 2),
+                (300.0, 2),
+                (f64::INFINITY, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_future_timestamps_count_as_skew() {
+        let latency = IngestLatency::default();
+        stored_after(&latency, TimeDelta::seconds(-30));
+
+        let snapshot = latency.snapshot();
+        assert_eq!(snapshot.count, 1);
+        assert_eq!(snapshot.future_timestamps, 1);
+        assert_eq!(snapshot.mean(), Some(Duration::ZERO));
+        assert_eq!(snapshot.buckets.first(), Some(&(0.1, 1)));
+        assert!(snapshot.to_string().contains("1 stamped in the future"));
+    }
+
+    #[test]
+    fn test_empty_snapshot() {
+        let snapshot = IngestLatency::default().snapshot();
+
+        assert_eq!(snapshot.count, 0);
+        assert_eq!(snapshot.mean(), None);
+        assert_eq!(snapshot.to_string(), "0 readings stored");
+    }
+}
@@ -1,7 +1,9 @@
 use config::Config;
 
+pub mod buffer;
 pub mod config;
 pub mod db;
+pub mod metrics;
 
 /// # Errors
 /// This function can fail if the `PostgreSQL` connection fails.
@@ -0,0 +1,73 @@
+//! Scrape the metrics endpoint after messages went through the reader
+
+use std::sync::Arc;
+
+use mqtt_reader::{
+    metrics::{
+        self,
+        ReaderMetrics,
+    },
+    read::mqtt_stream::MessageDecoder,
+};
+use tokio::{
+    io::{
+        AsyncReadExt,
+        AsyncWriteExt,
+    },
+    net::{
+        TcpListener,
+        TcpStream,
+    },
+};
+
+const VALID_PAYLOAD: &[u8] = br#"{"gw_mac":"AA:BB:CC:DD:EE:FF","rssi":-71,"ts":1700000000,"data":"0201061BFF9904050F18FFFFFFFFFFF0FFEC0414AA96A8DE8EF797E36ED811"}"#;
+
+/// Plain HTTP/1.1 GET of `path`, returning the whole response
+#[allow(clippy::expect_used)]
+async fn get(port: u16, path: &str) -> String {
+    let mut socket = TcpStream::connect(("127.0.0.1", port))
+        .await
+        .expect("connect");
+    socket
+        .write_all(
+            format!("GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+                .as_bytes(),
+        )
+        .await
+        .expect("send request");
+    let mut response = String::new();
+    socket
+        .read_to_string(&mut response)
+        .await
+        .expect("read response");
+    response
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_scrape_counts_fed_messages() {
+    let metrics = Arc::new(ReaderMetrics::default());
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let port = listener.local_addr().expect("address").port();
+    tokio::spawn(metrics::serve(listener, Arc::clone(&metrics)));
+
+    let decoder = MessageDecoder::new(None, Arc::clone(&metrics));
+    for payload in [VALID_PAYLOAD, b"{not json", VALID_PAYLOAD] {
+        decoder.decode("ruuvi/test", payload);
+    }
+    metrics.set_buffered_events(2);
+
+    let response = get(port, "/metrics").await;
+
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+    assert!(response.contains(metrics::METRICS_CONTENT_TYPE));
+    for line in [
+        "ruuvi_reader_messages_received_total 3\n",
+        "ruuvi_reader_messages_decoded_total 2\n",
+        "ruuvi_reader_messages_decode_failed_total 1\n",
+        "ruuvi_reader_db_write_failed_total 0\n",
+        "ruuvi_reader_buffered_events 2\n",
+    ] {
+        assert!(response.contains(line), "missing {line} in {response}");
+    }
+}
@@ -1,237 +1,1601 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    str::FromStr,
+    sync::{
+        Arc,
+        Mutex,
+        PoisonError,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
 use anyhow::Result;
+use async_trait::async_trait;
+use base64::{
+    engine::general_purpose::URL_SAFE_NO_PAD,
+    Engine,
+};
 use bigdecimal::ToPrimitive;
 use chrono::{
     DateTime,
     Utc,
 };
+use futures::{
+    Stream,
+    TryStreamExt,
+};
 use serde::{
     Deserialize,
     Serialize,
 };
 use sqlx::{
+    postgres::{
+        PgArguments,
+        PgConnectOptions,
+        PgListener,
+        PgPoolOptions,
+    },
+    query::Query,
     types::BigDecimal,
+    Connection,
     FromRow,
     PgPool,
+    Postgres,
+    QueryBuilder,
     Row,
 };
-use tokio::sync::broadcast;
-use tracing::error;
+pub use store_core::{
+    battery_percentage,
+    CurvePoint,
+    Event,
+    InvalidEvent,
+    Store,
+    TimeBucketedData,
+    TimeInterval,
+    BATTERY_DISCHARGE_CURVE,
+};
+use thiserror::Error;
+use tokio::{
+    sync::{
+        broadcast,
+        OnceCell,
+    },
+    task::JoinHandle,
+    time::MissedTickBehavior,
+};
+use tracing::{
+    debug,
+    error,
+    info,
+    warn,
+};
 
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
-pub struct Event {
-    pub sensor_mac: String,
-    pub gateway_mac: String,
-    pub temperature: f64,
-    pub humidity: f64,
-    pub pressure: f64,
-    pub battery: i64,
-    pub tx_power: i64,
-    pub movement_counter: i64,
-    pub measurement_sequence_number: i64,
-    pub acceleration: f64,
-    pub acceleration_x: i64,
-    pub acceleration_y: i64,
-    pub acceleration_z: i64,
-    pub rssi: i64,
+pub mod regression;
+
+/// Readings returned by [`PostgresStore::get_historical_data`] without a
+/// limit
+pub const DEFAULT_HISTORY_LIMIT: i64 = 100;
+
+/// Position of a reading in a sensor's history, used to continue paging
+/// from it.
+///
+/// Readings are ordered by timestamp and then sequence number, so readings
+/// sharing a timestamp still page in a stable order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryCursor {
     pub timestamp: DateTime<Utc>,
+    pub measurement_sequence_number: i64,
 }
 
-impl Event {
-    #[allow(clippy::too_many_arguments)]
-    pub fn new_with_current_time(
-        sensor_mac: String,
-        gateway_mac: String,
-        temperature: f64,
-        humidity: f64,
-        pressure: f64,
-        battery: i64,
-        tx_power: i64,
-        movement_counter: i64,
-        measurement_sequence_number: i64,
-        acceleration: f64,
-        acceleration_x: i64,
-        acceleration_y: i64,
-        acceleration_z: i64,
-        rssi: i64,
-    ) -> Self {
+impl HistoryCursor {
+    /// Cursor pointing at `event`
+    pub const fn of(event: &Event) -> Self {
         Self {
-            sensor_mac,
-            gateway_mac,
-            temperature,
-            humidity,
-            pressure,
-            battery,
-            tx_power,
-            movement_counter,
-            measurement_sequence_number,
-            acceleration,
-            acceleration_x,
-            acceleration_y,
-            acceleration_z,
-            rssi,
-            timestamp: Utc::now(),
+            timestamp: event.timestamp,
+            measurement_sequence_number: event.measurement_sequence_number,
+        }
+    }
+}
+
+/// Which side of a [`HistoryCursor`] a page of history is read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageCursor {
+    /// Readings older than the cursor
+    Before(HistoryCursor),
+    /// Readings newer than the cursor
+    After(HistoryCursor),
+}
+
+impl PageCursor {
+    /// Position the page is read from
+    pub const fn position(&self) -> HistoryCursor {
+        match self {
+            Self::Before(position) | Self::After(position) => *position,
         }
     }
+
+    /// Opaque, URL-safe form of the cursor for handing to clients. The
+    /// paging direction is encoded along with the position, so a cursor
+    /// cannot be replayed in the other direction.
+    pub fn encode(&self) -> String {
+        let direction = match self {
+            Self::Before(_) => "before",
+            Self::After(_) => "after",
+        };
+        let position = self.position();
+        URL_SAFE_NO_PAD.encode(format!(
+            "{direction}:{}:{}",
+            position.timestamp.timestamp_micros(),
+            position.measurement_sequence_number
+        ))
+    }
+
+    /// Parse a cursor made by [`PageCursor::encode`]
+    pub fn decode(encoded: &str) -> Option<Self> {
+        let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(encoded).ok()?).ok()?;
+        let mut parts = decoded.splitn(3, ':');
+        let direction = parts.next()?;
+        let position = HistoryCursor {
+            timestamp: DateTime::from_timestamp_micros(parts.next()?.parse().ok()?)?,
+            measurement_sequence_number: parts.next()?.parse().ok()?,
+        };
+        match direction {
+            "before" => Some(Self::Before(position)),
+            "after" => Some(Self::After(position)),
+            _ => None,
+        }
+    }
+}
+
+/// Index of a rejected reading within its batch and why it was rejected
+pub type SkippedRow = (usize, String);
+
+/// Outcome of importing a batch of readings
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub imported: usize,
+    /// Readings skipped because they were already stored
+    pub duplicates: usize,
+    /// Index of each rejected reading within the batch and why it was
+    /// rejected
+    pub skipped_with_reason: Vec<SkippedRow>,
+}
+
+/// Most readings written by one INSERT of [`PostgresStore::insert_events`],
+/// keeping the bind parameters of a statement well under the protocol limit
+pub const MAX_INSERT_BATCH_ROWS: usize = 1_000;
+
+/// Default upper bound on how long a single store operation may run
+pub const DEFAULT_QUERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Channel the `sensor_data` insert trigger notifies with every stored
+/// reading as JSON
+pub const INSERT_NOTIFY_CHANNEL: &str = "sensor_data_inserted";
+
+/// Trigger installed by the deployment migrations to send the notifications
+const INSERT_NOTIFY_TRIGGER: &str = "sensor_data_notify_insert";
+
+/// Pause before listening again after the notification connection fails
+const NOTIFY_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Default number of connections a pool may open
+pub const DEFAULT_MAX_CONNECTIONS: u32 = 10;
+
+/// Default time a query waits for a free pooled connection
+pub const DEFAULT_ACQUIRE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default time a pooled connection may sit unused before it is closed
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Default age after which a pooled connection is replaced
+pub const DEFAULT_MAX_LIFETIME: Duration = Duration::from_secs(30 * 60);
+
+/// Size and lifetimes of pooled connections.
+///
+/// Queries beyond `max_connections` wait up to `acquire_timeout` for a
+/// connection to free up.
+///
+/// NAT gateways and firewalls silently drop connections that stay idle for
+/// too long. Pools check each connection before handing it out and retire
+/// connections well before such a drop is likely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolConfig {
+    pub max_connections: u32,
+    pub acquire_timeout: Duration,
+    pub idle_timeout: Duration,
+    pub max_lifetime: Duration,
+}
+
+impl PoolConfig {
+    pub const DEFAULT: Self = Self {
+        max_connections: DEFAULT_MAX_CONNECTIONS,
+        acquire_timeout: DEFAULT_ACQUIRE_TIMEOUT,
+        idle_timeout: DEFAULT_IDLE_TIMEOUT,
+        max_lifetime: DEFAULT_MAX_LIFETIME,
+    };
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Columns picking the temperature of the earliest and latest reading in a
+/// bucket, with TimescaleDB's `first`/`last` aggregates when available
+const fn first_last_columns(timescaledb: bool) -> &'static str {
+    if timescaledb {
+        "first(temperature, timestamp) AS first_temperature, last(temperature, timestamp) AS \
+         last_temperature"
+    } else {
+        "(ARRAY_AGG(temperature ORDER BY timestamp))[1] AS first_temperature, \
+         (ARRAY_AGG(temperature ORDER BY timestamp DESC))[1] AS last_temperature"
+    }
+}
+
+/// Per-bucket statistics over the readings of the sensors in `$1` within
+/// `[$2, $3]`, in buckets of the interval `$4` aligned to the zone `$5`,
+/// ordered by bucket.
+///
+/// The interval is bound as a parameter rather than spliced into the SQL, so
+/// no interval string can change the statement.
+fn time_bucket_query(timescaledb: bool) -> String {
+    let first_last = first_last_columns(timescaledb);
+    format!(
+        r"
+        SELECT
+            time_bucket($4::interval, timestamp, $5) AS bucket,
+            AVG(temperature) AS avg_temperature,
+            MIN(temperature) AS min_temperature,
+            MAX(temperature) AS max_temperature,
+            SUM(temperature) AS sum_temperature,
+            PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY temperature) AS p50_temperature,
+            PERCENTILE_CONT(0.95) WITHIN GROUP (ORDER BY temperature) AS p95_temperature,
+            {first_last},
+            AVG(humidity) AS avg_humidity,
+            MIN(humidity) AS min_humidity,
+            MAX(humidity) AS max_humidity,
+            SUM(humidity) AS sum_humidity,
+            AVG(pressure) AS avg_pressure,
+            MIN(pressure) AS min_pressure,
+            MAX(pressure) AS max_pressure,
+            SUM(pressure) AS sum_pressure,
+            COUNT(*) AS reading_count
+        FROM sensor_data
+        WHERE sensor_mac = ANY($1)
+          AND timestamp >= $2
+          AND timestamp <= $3
+        GROUP BY bucket
+        ORDER BY bucket
+        ",
+    )
+}
+
+/// INSERT of one reading that skips readings already stored
+fn insert_event_query(event: &Event) -> Query<'_, Postgres, PgArguments> {
+    sqlx::query(
+        r"
+        INSERT INTO sensor_data (
+            sensor_mac, gateway_mac, temperature, humidity, pressure,
+            battery, tx_power, movement_counter, measurement_sequence_number,
+            acceleration, acceleration_x, acceleration_y, acceleration_z,
+            rssi, timestamp
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+        ON CONFLICT DO NOTHING
+        ",
+    )
+    .bind(&event.sensor_mac)
+    .bind(&event.gateway_mac)
+    .bind(event.temperature)
+    .bind(event.humidity)
+    .bind(event.pressure)
+    .bind(event.battery)
+    .bind(event.tx_power)
+    .bind(event.movement_counter)
+    .bind(event.measurement_sequence_number)
+    .bind(event.acceleration)
+    .bind(event.acceleration_x)
+    .bind(event.acceleration_y)
+    .bind(event.acceleration_z)
+    .bind(event.rssi)
+    .bind(event.timestamp)
+}
+
+/// Longest a released connection may take to answer a ping before it is
+/// closed instead of going back to the pool
+const RELEASE_PING_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How long after a query was cut off released connections are pinged
+const RELEASE_CHECK_WINDOW: Duration = Duration::from_secs(60);
+
+/// When the store last gave up on a query mid-flight, shared with the pool's
+/// release check
+///
+/// A query cut off by [`PostgresStore::timed`] or a stream's row timeout can
+/// leave its connection waiting for a reply that never arrives, so it must be
+/// closed rather than reused. Only connections released shortly after such a
+/// cut-off pay for a ping; sqlx's own release check waits on them forever.
+#[derive(Debug, Clone, Default)]
+struct CutOffQueries(Arc<Mutex<LastCutOff>>);
+
+/// When a query was last cut off by `PostgresStore::timed`, if ever
+type LastCutOff = Option<Instant>;
+
+impl CutOffQueries {
+    fn record(&self) {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) = Some(Instant::now());
+    }
+
+    fn recent(&self) -> bool {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some_and(|at| at.elapsed() < RELEASE_CHECK_WINDOW)
+    }
+}
+
+impl PoolConfig {
+    fn pool_options(self, cut_offs: CutOffQueries) -> PgPoolOptions {
+        PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .acquire_timeout(self.acquire_timeout)
+            .test_before_acquire(true)
+            .idle_timeout(self.idle_timeout)
+            .max_lifetime(self.max_lifetime)
+            .after_release(move |connection, _| {
+                let check = cut_offs.recent();
+                Box::pin(async move {
+                    if !check {
+                        return Ok(true);
+                    }
+                    Ok(
+                        tokio::time::timeout(RELEASE_PING_TIMEOUT, connection.ping())
+                            .await
+                            .is_ok_and(|ping| ping.is_ok()),
+                    )
+                })
+            })
+    }
+}
+
+/// A reading of a transactional batch was rejected, so none were stored
+#[derive(Debug, Error)]
+#[error("reading {index} of the batch was rejected: {source}")]
+pub struct BatchInsertFailed {
+    pub index: usize,
+    pub source: sqlx::Error,
+}
+
+/// A store operation did not finish within the configured query timeout
+#[derive(Debug, Error)]
+#[error("{operation} timed out after {timeout:?}")]
+pub struct QueryTimeout {
+    pub operation: &'static str,
+    pub timeout: Duration,
 }
 
 #[derive(Debug, Clone)]
 pub struct PostgresStore {
     pub pool: PgPool,
+    read_pool: Option<PgPool>,
     event_sender: broadcast::Sender<Event>,
+    query_timeout: Duration,
+    retention_cleanup: SharedRetentionCleanup,
+    timescaledb: Arc<OnceCell<bool>>,
+    cut_offs: CutOffQueries,
+    /// Subscribers hear about readings from the insert trigger's
+    /// notifications instead of when this store inserts them
+    relays_inserts: bool,
 }
 
 impl PostgresStore {
     pub async fn new(database_url: &str) -> Result<Self> {
-        let pool = PgPool::connect(database_url).await?;
+        Self::new_with_query_timeout(database_url, DEFAULT_QUERY_TIMEOUT).await
+    }
+
+    /// Connect with a custom query timeout.
+    ///
+    /// Besides the client-side limit applied by [`Self::timed`], every
+    /// connection gets a matching `statement_timeout` so the server aborts the
+    /// query too. Otherwise a timed-out query keeps running and its connection
+    /// cannot be reused until it finishes.
+    pub async fn new_with_query_timeout(
+        database_url: &str,
+        query_timeout: Duration,
+    ) -> Result<Self> {
+        Self::new_with_pool_config(database_url, query_timeout, PoolConfig::default()).await
+    }
+
+    /// Connect with a custom pool size and connection timeouts, keeping the
+    /// default query timeout
+    pub async fn new_with_options(database_url: &str, pool_config: PoolConfig) -> Result<Self> {
+        Self::new_with_pool_config(database_url, DEFAULT_QUERY_TIMEOUT, pool_config).await
+    }
+
+    /// Connect with a custom query timeout and pool settings
+    pub async fn new_with_pool_config(
+        database_url: &str,
+        query_timeout: Duration,
+        pool_config: PoolConfig,
+    ) -> Result<Self> {
+        let cut_offs = CutOffQueries::default();
+        let pool = connect_pool(
+            pool_config.pool_options(cut_offs.clone()),
+            database_url,
+            query_timeout,
+        )
+        .await?;
 
         // Run migrations if needed - for now just test connection
         sqlx::query("SELECT 1").execute(&pool).await?;
 
+        Ok(Self {
+            cut_offs,
+            ..Self::from_pool(pool).with_query_timeout(query_timeout)
+        })
+    }
+
+    /// Connect to a read replica and send analytics reads to it.
+    ///
+    /// The replica uses the same query timeout and pool settings as the
+    /// primary, so set the timeout first.
+    pub async fn with_read_replica(self, read_database_url: &str) -> Result<Self> {
+        let read_pool = connect_pool(
+            self.pool.options().clone(),
+            read_database_url,
+            self.query_timeout,
+        )
+        .await?;
+        sqlx::query("SELECT 1").execute(&read_pool).await?;
+
+        Ok(self.with_read_pool(read_pool))
+    }
+
+    /// Send analytics reads (history, aggregates and statistics) to `pool`.
+    ///
+    /// Writes and latest-reading lookups stay on the primary so replication
+    /// lag never hides data that was just written.
+    #[must_use]
+    pub fn with_read_pool(mut self, pool: PgPool) -> Self {
+        self.read_pool = Some(pool);
+        self
+    }
+
+    /// Pool serving analytics reads: the replica when one is configured,
+    /// otherwise the primary
+    pub fn read_pool(&self) -> &PgPool {
+        self.read_pool.as_ref().unwrap_or(&self.pool)
+    }
+
+    /// Create a store whose pool connects on first use instead of eagerly
+    ///
+    /// Useful when the database may not be reachable yet, and for exercising
+    /// code paths that fail before any query is issued.
+    pub fn new_lazy(database_url: &str) -> Result<Self> {
+        let pool = PgPoolOptions::new().connect_lazy(database_url)?;
+        Ok(Self::from_pool(pool))
+    }
+
+    /// Create a store on top of an already configured pool
+    pub fn from_pool(pool: PgPool) -> Self {
         let (event_sender, _) = broadcast::channel(1000);
 
-        Ok(Self { pool, event_sender })
+        Self {
+            pool,
+            read_pool: None,
+            event_sender,
+            query_timeout: DEFAULT_QUERY_TIMEOUT,
+            retention_cleanup: Arc::default(),
+            timescaledb: Arc::default(),
+            cut_offs: CutOffQueries::default(),
+            relays_inserts: false,
+        }
+    }
+
+    /// Limit how long each store operation may take before failing with
+    /// [`QueryTimeout`]
+    ///
+    /// This only bounds the client side; see [`Self::new_with_query_timeout`]
+    /// for also stopping the query on the server.
+    #[must_use]
+    pub fn with_query_timeout(mut self, query_timeout: Duration) -> Self {
+        self.query_timeout = query_timeout;
+        self
     }
 
-    pub async fn insert_event(&self, event: &Event) -> Result<()> {
-        sqlx::query(
-            r"
-            INSERT INTO sensor_data (
-                sensor_mac, gateway_mac, temperature, humidity, pressure,
-                battery, tx_power, movement_counter, measurement_sequence_number,
-                acceleration, acceleration_x, acceleration_y, acceleration_z,
-                rssi, timestamp
-            )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
-            ",
+    pub fn query_timeout(&self) -> Duration {
+        self.query_timeout
+    }
+
+    /// Ping the primary and any read replica every `interval` until the
+    /// returned task is aborted.
+    ///
+    /// Regular traffic keeps at least one connection per pool fresh, so the
+    /// first request after a quiet night does not pay for reconnecting.
+    /// Failed pings are only logged; the pool recovers on its own.
+    pub fn spawn_keep_alive(&self, interval: Duration) -> JoinHandle<()> {
+        let pools: Vec<PgPool> = std::iter::once(self.pool.clone())
+            .chain(self.read_pool.clone())
+            .collect();
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                for pool in &pools {
+                    if let Err(e) = sqlx::query("SELECT 1").execute(pool).await {
+                        warn!("Database keep-alive ping failed: {e}");
+                    }
+                }
+            }
+        })
+    }
+
+    /// Broadcast every reading stored in the database, including those
+    /// written by other processes such as the MQTT reader, by listening to
+    /// the notifications of the `sensor_data` insert trigger. Returns whether
+    /// the trigger is installed.
+    ///
+    /// Without the trigger only readings inserted through this store are
+    /// broadcast. With it, this store's own inserts are also heard through
+    /// the notification, so subscribers get each reading once. Readings
+    /// stored while the listener reconnects are not broadcast.
+    pub async fn relay_database_inserts(&mut self) -> Result<bool> {
+        let installed: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM pg_trigger WHERE tgname = $1 AND NOT tgisinternal)",
         )
-        .bind(&event.sensor_mac)
-        .bind(&event.gateway_mac)
-        .bind(event.temperature)
-        .bind(event.humidity)
-        .bind(event.pressure)
-        .bind(event.battery)
-        .bind(event.tx_power)
-        .bind(event.movement_counter)
-        .bind(event.measurement_sequence_number)
-        .bind(event.acceleration)
-        .bind(event.acceleration_x)
-        .bind(event.acceleration_y)
-        .bind(event.acceleration_z)
-        .bind(event.rssi)
-        .bind(event.timestamp)
-        .execute(&self.pool)
+        .bind(INSERT_NOTIFY_TRIGGER)
+        .fetch_one(&self.pool)
         .await?;
+        if !installed {
+            return Ok(false);
+        }
+
+        let mut listener = PgListener::connect_with(&self.pool).await?;
+        listener.listen(INSERT_NOTIFY_CHANNEL).await?;
+        let sender = self.event_sender.clone();
+        tokio::spawn(async move {
+            loop {
+                match listener.recv().await {
+                    Ok(notification) => match serde_json::from_str(notification.payload()) {
+                        // Having no subscribers is not an error
+                        Ok(event) => {
+                            let _ = sender.send(event);
+                        }
+                        Err(e) => warn!("Ignoring malformed insert notification: {e}"),
+                    },
+                    Err(e) => {
+                        // The next recv reconnects and listens again
+                        warn!("Insert notification listener failed: {e}");
+                        tokio::time::sleep(NOTIFY_RETRY_DELAY).await;
+                    }
+                }
+            }
+        });
 
-        // Notify subscribers of new data
-        if self.event_sender.receiver_count() > 0 {
+        self.relays_inserts = true;
+        Ok(true)
+    }
+
+    /// Check that the primary database answers a trivial query within
+    /// `timeout`, which is usually much shorter than the query timeout
+    pub async fn ping(&self, timeout: Duration) -> Result<()> {
+        tokio::time::timeout(timeout, sqlx::query("SELECT 1").execute(&self.pool))
+            .await
+            .map_err(|_| QueryTimeout {
+                operation: "ping",
+                timeout,
+            })??;
+        Ok(())
+    }
+
+    /// Run a store operation under the configured query timeout.
+    ///
+    /// When the timeout fires the operation's future is dropped and its
+    /// connection is handed back to the pool rather than held by the caller.
+    pub async fn timed<T>(
+        &self,
+        operation: &'static str,
+        future: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        tokio::time::timeout(self.query_timeout, future)
+            .await
+            .map_err(|_| {
+                // Recorded before the cut-off future drops its connection
+                self.cut_offs.record();
+                error!("{operation} timed out after {:?}", self.query_timeout);
+                QueryTimeout {
+                    operation,
+                    timeout: self.query_timeout,
+                }
+            })?
+    }
+
+    /// Store a reading unless an exact replay of it is already stored,
+    /// returning whether a row was inserted
+    ///
+    /// A replay has the same sensor, sequence number and timestamp, whichever
+    /// gateway forwarded it. Copies stamped with different times by
+    /// different gateways are all kept.
+    ///
+    /// Only inserted readings are broadcast to subscribers.
+    pub async fn insert_event(&self, event: &Event) -> Result<bool> {
+        self.timed("insert_event", async {
+            let inserted = insert_event_query(event)
+                .execute(&self.pool)
+                .await?
+                .rows_affected()
+                > 0;
+
+            // Notify subscribers of new data
+            if inserted {
+                self.broadcast(std::slice::from_ref(event));
+            }
+
+            Ok(inserted)
+        })
+        .await
+    }
+
+    /// Insert a batch of readings in one transaction, so either all of them
+    /// are stored or none are, returning how many rows were written.
+    ///
+    /// Rows are inserted one by one, which is slower than
+    /// [`Self::insert_events`] but tells which reading was rejected: on
+    /// failure the error is a [`BatchInsertFailed`] carrying its index.
+    /// Readings already stored are skipped and subscribers only hear about
+    /// the batch once it is committed.
+    pub async fn insert_events_transactional(&self, events: &[Event]) -> Result<u64> {
+        let inserted = self
+            .timed("insert_events_transactional", async {
+                let mut transaction = self.pool.begin().await?;
+                let mut inserted = Vec::new();
+                for (index, event) in events.iter().enumerate() {
+                    let result = insert_event_query(event)
+                        .execute(&mut *transaction)
+                        .await
+                        .map_err(|source| BatchInsertFailed { index, source })?;
+                    if result.rows_affected() > 0 {
+                        inserted.push(event.clone());
+                    }
+                }
+                // Dropping the transaction on an early return rolls it back
+                transaction.commit().await?;
+                Ok(inserted)
+            })
+            .await?;
+
+        self.broadcast(&inserted);
+        Ok(u64::try_from(inserted.len()).unwrap_or(u64::MAX))
+    }
+
+    /// Notify subscribers of newly stored readings, unless they hear of them
+    /// from the insert trigger
+    fn broadcast(&self, events: &[Event]) {
+        if self.relays_inserts || self.event_sender.receiver_count() == 0 {
+            return;
+        }
+        for event in events {
             if let Err(e) = self.event_sender.send(event.clone()) {
                 error!("Failed to broadcast new event: {}", e);
             }
         }
+    }
 
-        Ok(())
+    /// Insert a batch of readings with one multi-row INSERT per
+    /// [`MAX_INSERT_BATCH_ROWS`] readings, returning how many rows were
+    /// written
+    ///
+    /// Readings already stored are skipped like in [`Self::insert_event`].
+    /// Subscribers are notified of every inserted reading once all of them
+    /// are stored.
+    pub async fn insert_events(&self, events: &[Event]) -> Result<u64> {
+        let inserted = self
+            .timed("insert_events", async {
+                let mut inserted = Vec::new();
+                for chunk in events.chunks(MAX_INSERT_BATCH_ROWS) {
+                    let mut query = QueryBuilder::<Postgres>::new(
+                        r"
+                        INSERT INTO sensor_data (
+                            sensor_mac, gateway_mac, temperature, humidity, pressure,
+                            battery, tx_power, movement_counter, measurement_sequence_number,
+                            acceleration, acceleration_x, acceleration_y, acceleration_z,
+                            rssi, timestamp
+                        )
+                        ",
+                    );
+                    query.push_values(chunk, |mut row, event| {
+                        row.push_bind(&event.sensor_mac)
+                            .push_bind(&event.gateway_mac)
+                            .push_bind(event.temperature)
+                            .push_bind(event.humidity)
+                            .push_bind(event.pressure)
+                            .push_bind(event.battery)
+                            .push_bind(event.tx_power)
+                            .push_bind(event.movement_counter)
+                            .push_bind(event.measurement_sequence_number)
+                            .push_bind(event.acceleration)
+                            .push_bind(event.acceleration_x)
+                            .push_bind(event.acceleration_y)
+                            .push_bind(event.acceleration_z)
+                            .push_bind(event.rssi)
+                            .push_bind(event.timestamp);
+                    });
+                    query.push(
+                        r"
+                        ON CONFLICT DO NOTHING
+                        RETURNING sensor_mac, gateway_mac, temperature, humidity, pressure,
+                            battery, tx_power, movement_counter, measurement_sequence_number,
+                            acceleration, acceleration_x, acceleration_y, acceleration_z,
+                            rssi, timestamp
+                        ",
+                    );
+                    inserted.extend(
+                        query
+                            .build_query_as::<Event>()
+                            .fetch_all(&self.pool)
+                            .await?,
+                    );
+                }
+                Ok(inserted)
+            })
+            .await?;
+
+        self.broadcast(&inserted);
+        Ok(u64::try_from(inserted.len()).unwrap_or(u64::MAX))
+    }
+
+    /// Insert a batch of readings, skipping the ones that fail
+    /// [`Event::validate`] instead of aborting on the first bad row
+    pub async fn import_events(&self, events: &[Event]) -> Result<ImportSummary> {
+        let mut summary = ImportSummary::default();
+        for (index, event) in events.iter().enumerate() {
+            match event.validate() {
+                Ok(()) if self.insert_event(event).await? => summary.imported += 1,
+                Ok(()) => summary.duplicates += 1,
+                Err(invalid) => summary
+                    .skipped_with_reason
+                    .push((index, invalid.to_string())),
+            }
+        }
+        Ok(summary)
     }
 
+    /// Latest reading of every sensor heard in the last 24 hours, one per
+    /// sensor regardless of how many gateways heard it
     pub async fn get_active_sensors(&self) -> Result<Vec<Event>> {
-        let rows = sqlx::query(
-            r"
-            SELECT DISTINCT ON (sensor_mac, gateway_mac)
-                sensor_mac, gateway_mac, temperature, humidity, pressure,
-                battery, tx_power, movement_counter, measurement_sequence_number,
-                acceleration, acceleration_x, acceleration_y, acceleration_z,
-                rssi, timestamp
-            FROM sensor_data
-            WHERE timestamp > NOW() - INTERVAL '24 hours'
-            ORDER BY sensor_mac, gateway_mac, timestamp DESC
-            ",
-        )
-        .fetch_all(&self.pool)
-        .await?;
+        self.get_active_sensors_by(SensorDedup::Sensor).await
+    }
 
-        let mut events = Vec::new();
-        for row in rows {
-            events.push(Event {
-                sensor_mac: row.get("sensor_mac"),
-                gateway_mac: row.get("gateway_mac"),
-                temperature: row.get("temperature"),
-                humidity: row.get("humidity"),
-                pressure: row.get("pressure"),
-                battery: row.get("battery"),
-                tx_power: row.get("tx_power"),
-                movement_counter: row.get("movement_counter"),
-                measurement_sequence_number: row.get("measurement_sequence_number"),
-                acceleration: row.get("acceleration"),
-                acceleration_x: row.get("acceleration_x"),
-                acceleration_y: row.get("acceleration_y"),
-                acceleration_z: row.get("acceleration_z"),
-                rssi: row.get("rssi"),
-                timestamp: row.get("timestamp"),
-            });
-        }
+    /// Latest readings of every sensor heard in the last 24 hours, collapsed
+    /// as `dedup` asks
+    pub async fn get_active_sensors_by(&self, dedup: SensorDedup) -> Result<Vec<Event>> {
+        let query = match dedup {
+            SensorDedup::Sensor => {
+                r"
+                SELECT DISTINCT ON (sensor_mac)
+                    sensor_mac, gateway_mac, temperature, humidity, pressure,
+                    battery, tx_power, movement_counter, measurement_sequence_number,
+                    acceleration, acceleration_x, acceleration_y, acceleration_z,
+                    rssi, timestamp
+                FROM sensor_data
+                WHERE timestamp > NOW() - INTERVAL '24 hours'
+                ORDER BY sensor_mac, timestamp DESC
+                "
+            }
+            SensorDedup::SensorGateway => {
+                r"
+                SELECT DISTINCT ON (sensor_mac, gateway_mac)
+                    sensor_mac, gateway_mac, temperature, humidity, pressure,
+                    battery, tx_power, movement_counter, measurement_sequence_number,
+                    acceleration, acceleration_x, acceleration_y, acceleration_z,
+                    rssi, timestamp
+                FROM sensor_data
+                WHERE timestamp > NOW() - INTERVAL '24 hours'
+                ORDER BY sensor_mac, gateway_mac, timestamp DESC
+                "
+            }
+        };
+
+        self.timed("get_active_sensors", async {
+            let rows = sqlx::query(query).fetch_all(&self.pool).await?;
+
+            let mut events = Vec::new();
+            for row in rows {
+                events.push(Event {
+                    sensor_mac: row.get("sensor_mac"),
+                    gateway_mac: row.get("gateway_mac"),
+                    temperature: row.get("temperature"),
+                    humidity: row.get("humidity"),
+                    pressure: row.get("pressure"),
+                    battery: row.get("battery"),
+                    tx_power: row.get("tx_power"),
+                    movement_counter: row.get("movement_counter"),
+                    measurement_sequence_number: row.get("measurement_sequence_number"),
+                    acceleration: row.get("acceleration"),
+                    acceleration_x: row.get("acceleration_x"),
+                    acceleration_y: row.get("acceleration_y"),
+                    acceleration_z: row.get("acceleration_z"),
+                    rssi: row.get("rssi"),
+                    timestamp: row.get("timestamp"),
+                });
+            }
 
-        Ok(events)
+            Ok(events)
+        })
+        .await
     }
 
-    /// Get all unique sensor MAC addresses
-    pub async fn get_sensors(&self) -> Result<Vec<String>> {
-        let rows = sqlx::query(
-            r"
-            SELECT DISTINCT sensor_mac
-            FROM sensor_data
-            ORDER BY sensor_mac
-            ",
-        )
-        .fetch_all(&self.pool)
-        .await?;
+    /// Latest reading of every sensor the gateway `gateway_mac` heard in the
+    /// last 24 hours
+    pub async fn get_active_sensors_by_gateway(&self, gateway_mac: &str) -> Result<Vec<Event>> {
+        self.timed("get_active_sensors_by_gateway", async {
+            let readings = sqlx::query_as::<_, Event>(
+                r"
+                SELECT DISTINCT ON (sensor_mac)
+                    sensor_mac, gateway_mac, temperature, humidity, pressure,
+                    battery, tx_power, movement_counter, measurement_sequence_number,
+                    acceleration, acceleration_x, acceleration_y, acceleration_z,
+                    rssi, timestamp
+                FROM sensor_data
+                WHERE gateway_mac = $1
+                  AND timestamp > NOW() - INTERVAL '24 hours'
+                ORDER BY sensor_mac, timestamp DESC
+                ",
+            )
+            .bind(gateway_mac)
+            .fetch_all(self.read_pool())
+            .await?;
 
-        let mut sensors = Vec::new();
-        for row in rows {
-            sensors.push(row.get("sensor_mac"));
-        }
+            Ok(readings)
+        })
+        .await
+    }
+
+    /// Readings a gateway forwarded within `[start, end]`, from any sensor,
+    /// newest first
+    #[allow(clippy::too_many_arguments)]
+    pub async fn get_gateway_history(
+        &self,
+        gateway_mac: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<Event>> {
+        self.timed("get_gateway_history", async {
+            let readings = sqlx::query_as::<_, Event>(
+                r"
+                SELECT sensor_mac, gateway_mac, temperature, humidity, pressure,
+                       battery, tx_power, movement_counter, measurement_sequence_number,
+                       acceleration, acceleration_x, acceleration_y, acceleration_z,
+                       rssi, timestamp
+                FROM sensor_data
+                WHERE gateway_mac = $1
+                  AND timestamp >= $2
+                  AND timestamp <= $3
+                ORDER BY timestamp DESC, sensor_mac
+                LIMIT $4
+                ",
+            )
+            .bind(gateway_mac)
+            .bind(start)
+            .bind(end)
+            .bind(limit)
+            .fetch_all(self.read_pool())
+            .await?;
 
-        Ok(sensors)
+            Ok(readings)
+        })
+        .await
+    }
+
+    /// Gateways that forwarded readings in the last 24 hours, with how many
+    /// sensors each heard, ordered by MAC
+    pub async fn get_active_gateways(&self) -> Result<Vec<GatewayInfo>> {
+        self.timed("get_active_gateways", async {
+            let gateways = sqlx::query_as::<_, GatewayInfo>(
+                r"
+                SELECT gateway_mac,
+                       COUNT(DISTINCT sensor_mac) AS sensor_count,
+                       MAX(timestamp) AS last_seen
+                FROM sensor_data
+                WHERE timestamp > NOW() - INTERVAL '24 hours'
+                GROUP BY gateway_mac
+                ORDER BY gateway_mac
+                ",
+            )
+            .fetch_all(self.read_pool())
+            .await?;
+
+            Ok(gateways)
+        })
+        .await
+    }
+
+    /// Sensor count, signal strength and reading rate of one gateway over
+    /// the last `hours`
+    pub async fn get_gateway_statistics(
+        &self,
+        gateway_mac: &str,
+        hours: i32,
+    ) -> Result<GatewayStats> {
+        self.timed("get_gateway_statistics", async {
+            let stats = sqlx::query_as::<_, GatewayStats>(
+                r"
+                SELECT $1 AS gateway_mac,
+                       COUNT(DISTINCT sensor_mac) AS sensor_count,
+                       COUNT(*) AS reading_count,
+                       COUNT(*)::DOUBLE PRECISION / $2 AS readings_per_hour,
+                       AVG(rssi)::DOUBLE PRECISION AS avg_rssi,
+                       MAX(timestamp) AS last_seen
+                FROM sensor_data
+                WHERE gateway_mac = $1
+                  AND timestamp > NOW() - INTERVAL '1 hour' * $2
+                ",
+            )
+            .bind(gateway_mac)
+            .bind(hours)
+            .fetch_one(self.read_pool())
+            .await?;
+
+            Ok(stats)
+        })
+        .await
+    }
+
+    /// Distinct MACs of every sensor with stored readings, sorted.
+    ///
+    /// Much cheaper than [`Self::get_active_sensors`] when only the MACs are
+    /// needed, e.g. to populate filters.
+    pub async fn list_sensor_macs(&self) -> Result<Vec<String>> {
+        self.timed("list_sensor_macs", async {
+            let macs = sqlx::query_scalar(
+                r"
+                SELECT DISTINCT sensor_mac
+                FROM sensor_data
+                ORDER BY sensor_mac
+                ",
+            )
+            .fetch_all(&self.pool)
+            .await?;
+
+            Ok(macs)
+        })
+        .await
     }
 
     pub async fn get_latest_reading(&self, sensor_mac: &str) -> Result<Option<Event>> {
-        let row = sqlx::query(
-            r"
-            SELECT sensor_mac, gateway_mac, temperature, humidity, pressure,
-                   battery, tx_power, movement_counter, measurement_sequence_number,
-                   acceleration, acceleration_x, acceleration_y, acceleration_z,
-                   rssi, timestamp
-            FROM sensor_data
-            WHERE sensor_mac = $1
-            ORDER BY timestamp DESC
-            LIMIT 1
-            ",
-        )
-        .bind(sensor_mac)
-        .fetch_optional(&self.pool)
-        .await?;
+        self.timed("get_latest_reading", async {
+            let row = sqlx::query(
+                r"
+                SELECT sensor_mac, gateway_mac, temperature, humidity, pressure,
+                       battery, tx_power, movement_counter, measurement_sequence_number,
+                       acceleration, acceleration_x, acceleration_y, acceleration_z,
+                       rssi, timestamp
+                FROM sensor_data
+                WHERE sensor_mac = $1
+                ORDER BY timestamp DESC
+                LIMIT 1
+                ",
+            )
+            .bind(sensor_mac)
+            .fetch_optional(&self.pool)
+            .await?;
 
-        if let Some(row) = row {
-            Ok(Some(Event {
-                sensor_mac: row.get("sensor_mac"),
-                gateway_mac: row.get("gateway_mac"),
-                temperature: row.get("temperature"),
-                humidity: row.get("humidity"),
-                pressure: row.get("pressure"),
-                battery: row.get("battery"),
-                tx_power: row.get("tx_power"),
-                movement_counter: row.get("movement_counter"),
-                measurement_sequence_number: row.get("measurement_sequence_number"),
-                acceleration: row.get("acceleration"),
-                acceleration_x: row.get("acceleration_x"),
-                acceleration_y: row.get("acceleration_y"),
-                acceleration_z: row.get("acceleration_z"),
-                rssi: row.get("rssi"),
-                timestamp: row.get("timestamp"),
-            }))
+            if let Some(row) = row {
+                Ok(Some(Event {
+                    sensor_mac: row.get("sensor_mac"),
+                    gateway_mac: row.get("gateway_mac"),
+                    temperature: row.get("temperature"),
+                    humidity: row.get("humidity"),
+                    pressure: row.get("pressure"),
+                    battery: row.get("battery"),
+                    tx_power: row.get("tx_power"),
+                    movement_counter: row.get("movement_counter"),
+                    measurement_sequence_number: row.get("measurement_sequence_number"),
+                    acceleration: row.get("acceleration"),
+                    acceleration_x: row.get("acceleration_x"),
+                    acceleration_y: row.get("acceleration_y"),
+                    acceleration_z: row.get("acceleration_z"),
+                    rssi: row.get("rssi"),
+                    timestamp: row.get("timestamp"),
+                }))
+            } else {
+                Ok(None)
+            }
+        })
+        .await
+    }
+
+    /// Latest reading of every sensor together with its name, location and
+    /// health flags.
+    ///
+    /// Name and location come from `sensor_metadata` when that table exists
+    /// and has a row for the sensor.
+    pub async fn get_dashboard(&self) -> Result<Vec<SensorCard>> {
+        let (metadata_columns, metadata_join) = if self.relation_exists("sensor_metadata").await? {
+            (
+                "sm.name::text AS name, sm.location::text AS location",
+                "LEFT JOIN sensor_metadata sm ON sm.sensor_mac = sd.sensor_mac",
+            )
         } else {
-            Ok(None)
+            ("NULL::text AS name, NULL::text AS location", "")
+        };
+
+        self.timed("get_dashboard", async {
+            let query = format!(
+                r"
+                SELECT DISTINCT ON (sd.sensor_mac)
+                    sd.sensor_mac, sd.gateway_mac, sd.temperature, sd.humidity, sd.pressure,
+                    sd.battery, sd.tx_power, sd.movement_counter,
+                    sd.measurement_sequence_number, sd.acceleration, sd.acceleration_x,
+                    sd.acceleration_y, sd.acceleration_z, sd.rssi, sd.timestamp,
+                    {metadata_columns}
+                FROM sensor_data sd
+                {metadata_join}
+                ORDER BY sd.sensor_mac, sd.timestamp DESC
+                ",
+            );
+            let rows = sqlx::query(&query).fetch_all(&self.pool).await?;
+
+            let now = Utc::now();
+            rows.iter()
+                .map(|row| {
+                    let latest = Event::from_row(row)?;
+                    Ok(SensorCard {
+                        sensor_mac: latest.sensor_mac.clone(),
+                        name: row.get("name"),
+                        location: row.get("location"),
+                        last_seen: latest.timestamp,
+                        health: SensorHealthFlags::for_reading(&latest, now),
+                        latest,
+                    })
+                })
+                .collect()
+        })
+        .await
+    }
+
+    /// Newest non-null value of every metric for a sensor.
+    ///
+    /// Each metric is looked up on its own, so a metric that is only reported
+    /// occasionally carries its own, possibly older, timestamp instead of
+    /// being hidden behind the newest row.
+    pub async fn get_latest_per_metric(&self, sensor_mac: &str) -> Result<LatestPerMetric> {
+        self.timed("get_latest_per_metric", async {
+            let query = Metric::ALL
+                .iter()
+                .map(|metric| {
+                    let column = metric.column_name();
+                    format!(
+                        r"
+                        (SELECT DISTINCT ON (sensor_mac)
+                            '{column}' AS metric, {column} AS value, timestamp
+                        FROM sensor_data
+                        WHERE sensor_mac = $1 AND {column} IS NOT NULL
+                        ORDER BY sensor_mac, timestamp DESC)
+                        "
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(" UNION ALL ");
+
+            let rows = sqlx::query(&query)
+                .bind(sensor_mac)
+                .fetch_all(&self.pool)
+                .await?;
+
+            let reading = |metric: Metric| {
+                rows.iter()
+                    .find(|row| row.get::<&str, _>("metric") == metric.column_name())
+                    .map(|row| MetricReading {
+                        value: row.get("value"),
+                        timestamp: row.get("timestamp"),
+                    })
+            };
+
+            Ok(LatestPerMetric {
+                sensor_mac: sensor_mac.to_string(),
+                temperature: reading(Metric::Temperature),
+                humidity: reading(Metric::Humidity),
+                pressure: reading(Metric::Pressure),
+            })
+        })
+        .await
+    }
+
+    /// Store a threshold for one metric of a sensor.
+    ///
+    /// A sensor may have several thresholds for the same metric; each one is
+    /// checked on its own.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_threshold(
+        &self,
+        sensor_mac: &str,
+        metric: ThresholdMetric,
+        min_value: Option<f64>,
+        max_value: Option<f64>,
+    ) -> Result<SensorThreshold> {
+        self.timed("create_threshold", async {
+            let threshold = sqlx::query_as::<_, SensorThreshold>(
+                r"
+                INSERT INTO sensor_thresholds (sensor_mac, metric, min_value, max_value)
+                VALUES ($1, $2, $3, $4)
+                RETURNING id, sensor_mac, metric, min_value, max_value, created_at
+                ",
+            )
+            .bind(sensor_mac)
+            .bind(metric.as_str())
+            .bind(min_value)
+            .bind(max_value)
+            .fetch_one(&self.pool)
+            .await?;
+
+            Ok(threshold)
+        })
+        .await
+    }
+
+    /// Thresholds configured for a sensor, oldest first
+    pub async fn list_thresholds(&self, sensor_mac: &str) -> Result<Vec<SensorThreshold>> {
+        self.timed("list_thresholds", async {
+            let thresholds = sqlx::query_as::<_, SensorThreshold>(
+                r"
+                SELECT id, sensor_mac, metric, min_value, max_value, created_at
+                FROM sensor_thresholds
+                WHERE sensor_mac = $1
+                ORDER BY id
+                ",
+            )
+            .bind(sensor_mac)
+            .fetch_all(&self.pool)
+            .await?;
+
+            Ok(thresholds)
+        })
+        .await
+    }
+
+    /// Delete a threshold, returning it if it existed.
+    ///
+    /// With `sensor_mac` set, only a threshold belonging to that sensor is
+    /// deleted, so a caller cannot remove another sensor's threshold by
+    /// guessing its id.
+    pub async fn delete_threshold(
+        &self,
+        id: i64,
+        sensor_mac: Option<&str>,
+    ) -> Result<Option<SensorThreshold>> {
+        self.timed("delete_threshold", async {
+            let threshold = sqlx::query_as::<_, SensorThreshold>(
+                r"
+                DELETE FROM sensor_thresholds
+                WHERE id = $1 AND ($2::text IS NULL OR sensor_mac = $2)
+                RETURNING id, sensor_mac, metric, min_value, max_value, created_at
+                ",
+            )
+            .bind(id)
+            .bind(sensor_mac)
+            .fetch_optional(&self.pool)
+            .await?;
+
+            Ok(threshold)
+        })
+        .await
+    }
+
+    /// Store an alert rule firing while the latest `metric` reading of a
+    /// sensor is on the `comparator` side of `threshold`
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_alert(
+        &self,
+        sensor_mac: &str,
+        metric: Metric,
+        comparator: AlertComparator,
+        threshold: f64,
+    ) -> Result<AlertRule> {
+        self.timed("create_alert", async {
+            let alert = sqlx::query_as::<_, AlertRule>(
+                r"
+                INSERT INTO alerts (sensor_mac, metric, comparator, threshold)
+                VALUES ($1, $2, $3, $4)
+                RETURNING id, sensor_mac::text AS sensor_mac, metric::text AS metric,
+                          comparator::text AS comparator, threshold, created_at
+                ",
+            )
+            .bind(sensor_mac)
+            .bind(metric.column_name())
+            .bind(comparator.as_str())
+            .bind(threshold)
+            .fetch_one(&self.pool)
+            .await?;
+
+            Ok(alert)
+        })
+        .await
+    }
+
+    /// Alert rules breached by the latest reading of their sensor, by sensor
+    /// and rule
+    pub async fn evaluate_alerts(&self) -> Result<Vec<AlertHit>> {
+        self.timed("evaluate_alerts", async {
+            let hits = sqlx::query_as::<_, AlertHit>(
+                r"
+                WITH latest AS (
+                    SELECT DISTINCT ON (sensor_mac)
+                        sensor_mac, temperature, humidity, pressure, timestamp
+                    FROM sensor_data
+                    WHERE sensor_mac IN (SELECT sensor_mac FROM alerts)
+                    ORDER BY sensor_mac, timestamp DESC
+                ),
+                evaluated AS (
+                    SELECT a.id AS alert_id, a.sensor_mac::text AS sensor_mac,
+                           a.metric::text AS metric, a.comparator::text AS comparator,
+                           a.threshold, l.timestamp,
+                           CASE a.metric
+                               WHEN 'temperature' THEN l.temperature
+                               WHEN 'humidity' THEN l.humidity
+                               WHEN 'pressure' THEN l.pressure
+                           END AS value
+                    FROM alerts a
+                    JOIN latest l ON l.sensor_mac = a.sensor_mac
+                )
+                SELECT alert_id, sensor_mac, metric, comparator, threshold, value, timestamp
+                FROM evaluated
+                WHERE (comparator = 'above' AND value > threshold)
+                   OR (comparator = 'below' AND value < threshold)
+                ORDER BY sensor_mac, alert_id
+                ",
+            )
+            .fetch_all(self.read_pool())
+            .await?;
+
+            Ok(hits)
+        })
+        .await
+    }
+
+    /// Name a sensor, replacing any alias it had
+    pub async fn set_sensor_alias(&self, sensor_mac: &str, alias: &str) -> Result<SensorAlias> {
+        self.timed("set_sensor_alias", async {
+            let alias = sqlx::query_as::<_, SensorAlias>(
+                r"
+                INSERT INTO sensor_aliases (sensor_mac, alias)
+                VALUES ($1, $2)
+                ON CONFLICT (sensor_mac)
+                DO UPDATE SET alias = EXCLUDED.alias, updated_at = NOW()
+                RETURNING sensor_mac::text AS sensor_mac, alias::text AS alias, updated_at
+                ",
+            )
+            .bind(sensor_mac)
+            .bind(alias)
+            .fetch_one(&self.pool)
+            .await?;
+
+            Ok(alias)
+        })
+        .await
+    }
+
+    /// Alias of a sensor, if it has been named
+    pub async fn get_sensor_alias(&self, sensor_mac: &str) -> Result<Option<SensorAlias>> {
+        self.timed("get_sensor_alias", async {
+            let alias = sqlx::query_as::<_, SensorAlias>(
+                r"
+                SELECT sensor_mac::text AS sensor_mac, alias::text AS alias, updated_at
+                FROM sensor_aliases
+                WHERE sensor_mac = $1
+                ",
+            )
+            .bind(sensor_mac)
+            .fetch_optional(&self.pool)
+            .await?;
+
+            Ok(alias)
+        })
+        .await
+    }
+
+    /// Aliases of every named sensor, by MAC.
+    ///
+    /// Empty when the `sensor_aliases` table has not been migrated yet, so
+    /// listings keep working without it.
+    pub async fn list_sensor_aliases(&self) -> Result<BTreeMap<String, String>> {
+        if !self.relation_exists("sensor_aliases").await? {
+            return Ok(BTreeMap::new());
         }
+
+        self.timed("list_sensor_aliases", async {
+            let aliases = sqlx::query_as::<_, SensorAlias>(
+                r"
+                SELECT sensor_mac::text AS sensor_mac, alias::text AS alias, updated_at
+                FROM sensor_aliases
+                ",
+            )
+            .fetch_all(&self.pool)
+            .await?;
+
+            Ok(aliases
+                .into_iter()
+                .map(|alias| (alias.sensor_mac, alias.alias))
+                .collect())
+        })
+        .await
+    }
+
+    /// Create a sensor group with its initial members.
+    ///
+    /// Returns `None` when a group of that name already exists.
+    pub async fn create_sensor_group(
+        &self,
+        name: &str,
+        sensor_macs: &[String],
+    ) -> Result<Option<SensorGroup>> {
+        self.timed("create_sensor_group", async {
+            let mut transaction = self.pool.begin().await?;
+            let created = sqlx::query(
+                r"
+                INSERT INTO sensor_groups (name)
+                VALUES ($1)
+                ON CONFLICT (name) DO NOTHING
+                ",
+            )
+            .bind(name)
+            .execute(&mut *transaction)
+            .await?;
+            if created.rows_affected() == 0 {
+                return Ok(None);
+            }
+
+            sqlx::query(
+                r"
+                INSERT INTO sensor_group_members (group_name, sensor_mac)
+                SELECT $1, sensor_mac FROM UNNEST($2::text[]) AS sensor_mac
+                ON CONFLICT DO NOTHING
+                ",
+            )
+            .bind(name)
+            .bind(sensor_macs)
+            .execute(&mut *transaction)
+            .await?;
+            transaction.commit().await?;
+
+            self.get_sensor_group(name).await
+        })
+        .await
+    }
+
+    /// Every sensor group with its members, by name
+    pub async fn list_sensor_groups(&self) -> Result<Vec<SensorGroup>> {
+        self.timed("list_sensor_groups", async {
+            let query = format!("{SENSOR_GROUP_SELECT} ORDER BY g.name");
+            let groups = sqlx::query_as::<_, SensorGroup>(&query)
+                .fetch_all(&self.pool)
+                .await?;
+
+            Ok(groups)
+        })
+        .await
+    }
+
+    pub async fn get_sensor_group(&self, name: &str) -> Result<Option<SensorGroup>> {
+        self.timed("get_sensor_group", async {
+            let query = format!("{SENSOR_GROUP_SELECT} HAVING g.name = $1");
+            let group = sqlx::query_as::<_, SensorGroup>(&query)
+                .bind(name)
+                .fetch_optional(&self.pool)
+                .await?;
+
+            Ok(group)
+        })
+        .await
+    }
+
+    /// Delete a group and its memberships, returning whether it existed.
+    /// Readings of the member sensors are not touched.
+    pub async fn delete_sensor_group(&self, name: &str) -> Result<bool> {
+        self.timed("delete_sensor_group", async {
+            let result = sqlx::query("DELETE FROM sensor_groups WHERE name = $1")
+                .bind(name)
+                .execute(&self.pool)
+                .await?;
+
+            Ok(result.rows_affected() > 0)
+        })
+        .await
+    }
+
+    /// Add a sensor to a group, returning the updated group or `None` when
+    /// the group does not exist. Adding an existing member changes nothing.
+    pub async fn add_group_member(
+        &self,
+        name: &str,
+        sensor_mac: &str,
+    ) -> Result<Option<SensorGroup>> {
+        self.timed("add_group_member", async {
+            sqlx::query(
+                r"
+                INSERT INTO sensor_group_members (group_name, sensor_mac)
+                SELECT name, $2 FROM sensor_groups WHERE name = $1
+                ON CONFLICT DO NOTHING
+                ",
+            )
+            .bind(name)
+            .bind(sensor_mac)
+            .execute(&self.pool)
+            .await?;
+
+            self.get_sensor_group(name).await
+        })
+        .await
+    }
+
+    /// Remove a sensor from a group, returning the updated group or `None`
+    /// when the group does not exist
+    pub async fn remove_group_member(
+        &self,
+        name: &str,
+        sensor_mac: &str,
+    ) -> Result<Option<SensorGroup>> {
+        self.timed("remove_group_member", async {
+            sqlx::query(
+                "DELETE FROM sensor_group_members WHERE group_name = $1 AND sensor_mac = $2",
+            )
+            .bind(name)
+            .bind(sensor_mac)
+            .execute(&self.pool)
+            .await?;
+
+            self.get_sensor_group(name).await
+        })
+        .await
+    }
+
+    /// Latest reading of each of `sensor_macs` that has any, ordered by MAC,
+    /// in one query. Unknown MACs are left out rather than reported.
+    pub async fn get_latest_for(&self, sensor_macs: &[String]) -> Result<Vec<Event>> {
+        self.timed("get_latest_for", async {
+            let readings = sqlx::query_as::<_, Event>(
+                r"
+                SELECT DISTINCT ON (sensor_mac)
+                    sensor_mac, gateway_mac, temperature, humidity, pressure,
+                    battery, tx_power, movement_counter, measurement_sequence_number,
+                    acceleration, acceleration_x, acceleration_y, acceleration_z,
+                    rssi, timestamp
+                FROM sensor_data
+                WHERE sensor_mac = ANY($1)
+                ORDER BY sensor_mac, timestamp DESC
+                ",
+            )
+            .bind(sensor_macs)
+            .fetch_all(&self.pool)
+            .await?;
+
+            Ok(readings)
+        })
+        .await
+    }
+
+    /// Readings whose acceleration magnitude is outside one of the sensor's
+    /// acceleration thresholds, newest first.
+    ///
+    /// A reading outside several thresholds is reported once per threshold.
+    pub async fn get_vibration_alerts(
+        &self,
+        sensor_mac: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<VibrationAlert>> {
+        self.timed("get_vibration_alerts", async {
+            let alerts = sqlx::query_as::<_, VibrationAlert>(
+                r"
+                SELECT
+                    st.id AS threshold_id,
+                    sd.timestamp,
+                    sd.acceleration,
+                    CASE WHEN sd.acceleration > st.max_value THEN st.max_value
+                         ELSE st.min_value END AS limit_value,
+                    sd.acceleration > st.max_value AS above_max
+                FROM sensor_data sd
+                JOIN sensor_thresholds st
+                    ON st.sensor_mac = sd.sensor_mac AND st.metric = $2
+                WHERE sd.sensor_mac = $1
+                    AND sd.timestamp >= $3
+                    AND sd.timestamp <= $4
+                    AND (sd.acceleration > st.max_value OR sd.acceleration < st.min_value)
+                ORDER BY sd.timestamp DESC, st.id
+                ",
+            )
+            .bind(sensor_mac)
+            .bind(ThresholdMetric::Acceleration.as_str())
+            .bind(start)
+            .bind(end)
+            .fetch_all(&self.pool)
+            .await?;
+
+            Ok(alerts)
+        })
+        .await
+    }
+
+    /// History of a sensor between `start` and `end` averaged into at most
+    /// about `max_points` equal buckets, newest first.
+    ///
+    /// Each row is stamped with the start of its bucket and holds the
+    /// averages of the readings in it; counters keep their highest value and
+    /// the gateway is the one that heard the latest reading.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn get_historical_downsampled(
+        &self,
+        sensor_mac: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        max_points: u32,
+    ) -> Result<Vec<Event>> {
+        let range_seconds = u64::try_from((end - start).num_seconds())
+            .unwrap_or(0)
+            .max(1);
+        let bucket_seconds = range_seconds.div_ceil(u64::from(max_points.max(1)));
+
+        self.timed("get_historical_downsampled", async {
+            let events = sqlx::query_as::<_, Event>(
+                r"
+                SELECT sensor_mac, gateway_mac, temperature, humidity, pressure, battery,
+                       tx_power, movement_counter, measurement_sequence_number, acceleration,
+                       acceleration_x, acceleration_y, acceleration_z, rssi,
+                       bucket AS timestamp
+                FROM (
+                    SELECT
+                        time_bucket($4::interval, timestamp) AS bucket,
+                        MAX(sensor_mac) AS sensor_mac,
+                        (array_agg(gateway_mac ORDER BY timestamp DESC))[1] AS gateway_mac,
+                        AVG(temperature) AS temperature,
+                        AVG(humidity) AS humidity,
+                        AVG(pressure) AS pressure,
+                        ROUND(AVG(battery))::BIGINT AS battery,
+                        ROUND(AVG(tx_power))::BIGINT AS tx_power,
+                        MAX(movement_counter) AS movement_counter,
+                        MAX(measurement_sequence_number) AS measurement_sequence_number,
+                        AVG(acceleration) AS acceleration,
+                        ROUND(AVG(acceleration_x))::BIGINT AS acceleration_x,
+                        ROUND(AVG(acceleration_y))::BIGINT AS acceleration_y,
+                        ROUND(AVG(acceleration_z))::BIGINT AS acceleration_z,
+                        ROUND(AVG(rssi))::BIGINT AS rssi
+                    FROM sensor_data
+                    WHERE sensor_mac = $1
+                      AND timestamp >= $2
+                      AND timestamp <= $3
+                    GROUP BY bucket
+                ) buckets
+                ORDER BY bucket DESC
+                ",
+            )
+            .bind(sensor_mac)
+            .bind(start)
+            .bind(end)
+            .bind(format!("{bucket_seconds} seconds"))
+            .fetch_all(self.read_pool())
+            .await?;
+
+            Ok(events)
+        })
+        .await
     }
 
+    /// Readings of a sensor within `[start, end]`, sorted by time in `order`.
+    ///
+    /// Without a cursor the first `limit` readings in `order` are returned,
+    /// so newest first keeps the newest readings and oldest first the oldest.
+    /// With [`PageCursor::Before`] the page continues with the readings older
+    /// than the cursor, and with [`PageCursor::After`] it holds the readings
+    /// directly newer than it, so a range can be walked either way without
+    /// gaps or repeats.
     #[allow(clippy::too_many_arguments)]
     pub async fn get_historical_data(
         &self,
@@ -239,54 +1603,91 @@ impl PostgresStore {
         start: Option<DateTime<Utc>>,
         end: Option<DateTime<Utc>>,
         limit: Option<i64>,
+        cursor: Option<PageCursor>,
+        order: Order,
     ) -> Result<Vec<Event>> {
-        let start = start.unwrap_or_else(|| Utc::now() - chrono::Duration::hours(1));
-        let end = end.unwrap_or_else(Utc::now);
-        let limit = limit.unwrap_or(100);
-
-        let rows = sqlx::query(
-            r"
-            SELECT sensor_mac, gateway_mac, temperature, humidity, pressure,
-                   battery, tx_power, movement_counter, measurement_sequence_number,
-                   acceleration, acceleration_x, acceleration_y, acceleration_z,
-                   rssi, timestamp
-            FROM sensor_data
-            WHERE sensor_mac = $1
-              AND timestamp >= $2
-              AND timestamp <= $3
-            ORDER BY timestamp DESC
-            LIMIT $4
-            ",
-        )
-        .bind(sensor_mac)
-        .bind(start)
-        .bind(end)
-        .bind(limit)
-        .fetch_all(&self.pool)
-        .await?;
+        self.timed("get_historical_data", async {
+            let start = start.unwrap_or_else(|| Utc::now() - chrono::Duration::hours(1));
+            let end = end.unwrap_or_else(Utc::now);
+            let limit = limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+
+            // Only fixed SQL fragments are spliced in; the cursor is bound
+            let (comparison, scan, position) = match cursor {
+                None => ("", order, None),
+                Some(PageCursor::Before(position)) => (
+                    "AND (timestamp, measurement_sequence_number) < ($5, $6)",
+                    Order::Desc,
+                    Some(position),
+                ),
+                Some(PageCursor::After(position)) => (
+                    "AND (timestamp, measurement_sequence_number) > ($5, $6)",
+                    Order::Asc,
+                    Some(position),
+                ),
+            };
+            let direction = scan.as_sql();
+            let query = format!(
+                r"
+                SELECT sensor_mac, gateway_mac, temperature, humidity, pressure,
+                       battery, tx_power, movement_counter, measurement_sequence_number,
+                       acceleration, acceleration_x, acceleration_y, acceleration_z,
+                       rssi, timestamp
+                FROM sensor_data
+                WHERE sensor_mac = $1
+                  AND timestamp >= $2
+                  AND timestamp <= $3
+                  {comparison}
+                ORDER BY timestamp {direction}, measurement_sequence_number {direction}
+                LIMIT $4
+                "
+            );
+
+            let mut query = sqlx::query_as::<_, Event>(&query)
+                .bind(sensor_mac)
+                .bind(start)
+                .bind(end)
+                .bind(limit);
+            if let Some(position) = position {
+                query = query
+                    .bind(position.timestamp)
+                    .bind(position.measurement_sequence_number);
+            }
+            let mut events = query.fetch_all(self.read_pool()).await?;
 
-        let mut events = Vec::new();
-        for row in rows {
-            events.push(Event {
-                sensor_mac: row.get("sensor_mac"),
-                gateway_mac: row.get("gateway_mac"),
-                temperature: row.get("temperature"),
-                humidity: row.get("humidity"),
-                pressure: row.get("pressure"),
-                battery: row.get("battery"),
-                tx_power: row.get("tx_power"),
-                movement_counter: row.get("movement_counter"),
-                measurement_sequence_number: row.get("measurement_sequence_number"),
-                acceleration: row.get("acceleration"),
-                acceleration_x: row.get("acceleration_x"),
-                acceleration_y: row.get("acceleration_y"),
-                acceleration_z: row.get("acceleration_z"),
-                rssi: row.get("rssi"),
-                timestamp: row.get("timestamp"),
-            });
-        }
+            if scan != order {
+                events.reverse();
+            }
+            Ok(events)
+        })
+        .await
+    }
 
-        Ok(events)
+    /// Number of readings a sensor recorded within `[start, end]`
+    pub async fn count_readings(
+        &self,
+        sensor_mac: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<i64> {
+        self.timed("count_readings", async {
+            let count = sqlx::query_scalar(
+                r"
+                SELECT COUNT(*)
+                FROM sensor_data
+                WHERE sensor_mac = $1
+                  AND timestamp >= $2
+                  AND timestamp <= $3
+                ",
+            )
+            .bind(sensor_mac)
+            .bind(start)
+            .bind(end)
+            .fetch_one(self.read_pool())
+            .await?;
+
+            Ok(count)
+        })
+        .await
     }
 
     pub async fn get_sensor_data_range(
@@ -295,47 +1696,50 @@ impl PostgresStore {
         start: DateTime<Utc>,
         end: DateTime<Utc>,
     ) -> Result<Vec<Event>> {
-        let rows = sqlx::query(
-            r"
-            SELECT sensor_mac, gateway_mac, temperature, humidity, pressure,
-                   battery, tx_power, movement_counter, measurement_sequence_number,
-                   acceleration, acceleration_x, acceleration_y, acceleration_z,
-                   rssi, timestamp
-            FROM sensor_data
-            WHERE sensor_mac = $1
-              AND timestamp >= $2
-              AND timestamp <= $3
-            ORDER BY timestamp ASC
-            ",
-        )
-        .bind(sensor_mac)
-        .bind(start)
-        .bind(end)
-        .fetch_all(&self.pool)
-        .await?;
+        self.timed("get_sensor_data_range", async {
+            let rows = sqlx::query(
+                r"
+                SELECT sensor_mac, gateway_mac, temperature, humidity, pressure,
+                       battery, tx_power, movement_counter, measurement_sequence_number,
+                       acceleration, acceleration_x, acceleration_y, acceleration_z,
+                       rssi, timestamp
+                FROM sensor_data
+                WHERE sensor_mac = $1
+                  AND timestamp >= $2
+                  AND timestamp <= $3
+                ORDER BY timestamp ASC
+                ",
+            )
+            .bind(sensor_mac)
+            .bind(start)
+            .bind(end)
+            .fetch_all(self.read_pool())
+            .await?;
 
-        let mut events = Vec::new();
-        for row in rows {
-            events.push(Event {
-                sensor_mac: row.get("sensor_mac"),
-                gateway_mac: row.get("gateway_mac"),
-                temperature: row.get("temperature"),
-                humidity: row.get("humidity"),
-                pressure: row.get("pressure"),
-                battery: row.get("battery"),
-                tx_power: row.get("tx_power"),
-                movement_counter: row.get("movement_counter"),
-                measurement_sequence_number: row.get("measurement_sequence_number"),
-                acceleration: row.get("acceleration"),
-                acceleration_x: row.get("acceleration_x"),
-                acceleration_y: row.get("acceleration_y"),
-                acceleration_z: row.get("acceleration_z"),
-                rssi: row.get("rssi"),
-                timestamp: row.get("timestamp"),
-            });
-        }
+            let mut events = Vec::new();
+            for row in rows {
+                events.push(Event {
+                    sensor_mac: row.get("sensor_mac"),
+                    gateway_mac: row.get("gateway_mac"),
+                    temperature: row.get("temperature"),
+                    humidity: row.get("humidity"),
+                    pressure: row.get("pressure"),
+                    battery: row.get("battery"),
+                    tx_power: row.get("tx_power"),
+                    movement_counter: row.get("movement_counter"),
+                    measurement_sequence_number: row.get("measurement_sequence_number"),
+                    acceleration: row.get("acceleration"),
+                    acceleration_x: row.get("acceleration_x"),
+                    acceleration_y: row.get("acceleration_y"),
+                    acceleration_z: row.get("acceleration_z"),
+                    rssi: row.get("rssi"),
+                    timestamp: row.get("timestamp"),
+                });
+            }
 
-        Ok(events)
+            Ok(events)
+        })
+        .await
     }
 
     pub fn subscribe_to_events(&self) -> broadcast::Receiver<Event> {
@@ -343,53 +1747,208 @@ impl PostgresStore {
     }
 
     pub async fn get_sensor_statistics(&self, sensor_mac: &str, hours: i32) -> Result<SensorStats> {
-        let row = sqlx::query(
-            r"
-            SELECT
-                AVG(temperature) as avg_temp,
-                MIN(temperature) as min_temp,
-                MAX(temperature) as max_temp,
-                AVG(humidity) as avg_humidity,
-                MIN(humidity) as min_humidity,
-                MAX(humidity) as max_humidity,
-                AVG(pressure) as avg_pressure,
-                MIN(pressure) as min_pressure,
-                MAX(pressure) as max_pressure,
-                COUNT(*) as reading_count
-            FROM sensor_data
-            WHERE sensor_mac = $1
-              AND timestamp > NOW() - INTERVAL '1 hour' * $2
-            ",
-        )
-        .bind(sensor_mac)
-        .bind(hours)
-        .fetch_one(&self.pool)
-        .await?;
+        self.timed("get_sensor_statistics", async {
+            let row = sqlx::query(
+                r"
+                SELECT
+                    AVG(temperature) as avg_temp,
+                    MIN(temperature) as min_temp,
+                    MAX(temperature) as max_temp,
+                    AVG(humidity) as avg_humidity,
+                    MIN(humidity) as min_humidity,
+                    MAX(humidity) as max_humidity,
+                    AVG(pressure) as avg_pressure,
+                    MIN(pressure) as min_pressure,
+                    MAX(pressure) as max_pressure,
+                    STDDEV_SAMP(temperature) as stddev_temp,
+                    STDDEV_SAMP(humidity) as stddev_humidity,
+                    STDDEV_SAMP(pressure) as stddev_pressure,
+                    COUNT(*) as reading_count
+                FROM sensor_data
+                WHERE sensor_mac = $1
+                  AND timestamp > NOW() - INTERVAL '1 hour' * $2
+                ",
+            )
+            .bind(sensor_mac)
+            .bind(hours)
+            .fetch_one(self.read_pool())
+            .await?;
 
-        Ok(SensorStats {
-            avg_temperature: row.get::<Option<f64>, _>("avg_temp").unwrap_or(0.0),
-            min_temperature: row.get::<Option<f64>, _>("min_temp").unwrap_or(0.0),
-            max_temperature: row.get::<Option<f64>, _>("max_temp").unwrap_or(0.0),
-            avg_humidity: row.get::<Option<f64>, _>("avg_humidity").unwrap_or(0.0),
-            min_humidity: row.get::<Option<f64>, _>("min_humidity").unwrap_or(0.0),
-            max_humidity: row.get::<Option<f64>, _>("max_humidity").unwrap_or(0.0),
-            avg_pressure: row.get::<Option<f64>, _>("avg_pressure").unwrap_or(0.0),
-            min_pressure: row.get::<Option<f64>, _>("min_pressure").unwrap_or(0.0),
-            max_pressure: row.get::<Option<f64>, _>("max_pressure").unwrap_or(0.0),
-            reading_count: row.get::<Option<i64>, _>("reading_count").unwrap_or(0),
+            Ok(SensorStats {
+                avg_temperature: row.get::<Option<f64>, _>("avg_temp").unwrap_or(0.0),
+                min_temperature: row.get::<Option<f64>, _>("min_temp").unwrap_or(0.0),
+                max_temperature: row.get::<Option<f64>, _>("max_temp").unwrap_or(0.0),
+                avg_humidity: row.get::<Option<f64>, _>("avg_humidity").unwrap_or(0.0),
+                min_humidity: row.get::<Option<f64>, _>("min_humidity").unwrap_or(0.0),
+                max_humidity: row.get::<Option<f64>, _>("max_humidity").unwrap_or(0.0),
+                avg_pressure: row.get::<Option<f64>, _>("avg_pressure").unwrap_or(0.0),
+                min_pressure: row.get::<Option<f64>, _>("min_pressure").unwrap_or(0.0),
+                max_pressure: row.get::<Option<f64>, _>("max_pressure").unwrap_or(0.0),
+                // The sample deviation of a single reading is undefined
+                stddev_temperature: row.get::<Option<f64>, _>("stddev_temp").unwrap_or(0.0),
+                stddev_humidity: row.get::<Option<f64>, _>("stddev_humidity").unwrap_or(0.0),
+                stddev_pressure: row.get::<Option<f64>, _>("stddev_pressure").unwrap_or(0.0),
+                reading_count: row.get::<Option<i64>, _>("reading_count").unwrap_or(0),
+            })
         })
+        .await
     }
 
-    pub async fn cleanup_old_data(&self, days_to_keep: i32) -> Result<u64> {
-        let result =
-            sqlx::query("DELETE FROM sensor_data WHERE timestamp < NOW() - INTERVAL '1 day' * $1")
-                .bind(days_to_keep)
+    /// Delete every reading of a sensor, returning how many were removed.
+    /// Its thresholds and group memberships are kept.
+    pub async fn delete_sensor(&self, sensor_mac: &str) -> Result<u64> {
+        self.timed("delete_sensor", async {
+            let result = sqlx::query("DELETE FROM sensor_data WHERE sensor_mac = $1")
+                .bind(sensor_mac)
                 .execute(&self.pool)
                 .await?;
 
-        Ok(result.rows_affected())
+            Ok(result.rows_affected())
+        })
+        .await
+    }
+
+    pub async fn cleanup_old_data(&self, days_to_keep: i32) -> Result<u64> {
+        self.timed("cleanup_old_data", async {
+            let result = sqlx::query(
+                "DELETE FROM sensor_data WHERE timestamp < NOW() - INTERVAL '1 day' * $1",
+            )
+            .bind(days_to_keep)
+            .execute(&self.pool)
+            .await?;
+
+            Ok(result.rows_affected())
+        })
+        .await
+    }
+
+    /// Keep only the last `days` of readings from now on, replacing any
+    /// previous policy.
+    ///
+    /// With TimescaleDB this is a retention policy on the hypertable that
+    /// drops whole chunks in the background. On plain PostgreSQL this store
+    /// runs [`Self::cleanup_old_data`] every [`RETENTION_CLEANUP_INTERVAL`]
+    /// instead, for as long as the process lives.
+    pub async fn set_retention_policy(&self, days: i32) -> Result<RetentionPolicy> {
+        anyhow::ensure!(days > 0, "retention must keep at least one day, got {days}");
+
+        let policy = if self.has_timescaledb().await? {
+            self.timed("set_retention_policy", async {
+                sqlx::query("SELECT remove_retention_policy('sensor_data', if_exists => TRUE)")
+                    .execute(&self.pool)
+                    .await?;
+                sqlx::query("SELECT add_retention_policy('sensor_data', INTERVAL '1 day' * $1)")
+                    .bind(days)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            })
+            .await?;
+            self.replace_retention_cleanup(None);
+            RetentionPolicy {
+                days,
+                enforced_by: RetentionMechanism::TimescaleDb,
+            }
+        } else {
+            self.replace_retention_cleanup(Some(RetentionCleanup {
+                days,
+                task: self.spawn_retention_cleanup(days),
+            }));
+            RetentionPolicy {
+                days,
+                enforced_by: RetentionMechanism::PeriodicCleanup,
+            }
+        };
+
+        info!("Keeping {days} days of sensor data");
+        Ok(policy)
+    }
+
+    /// The retention policy in force, if any
+    pub async fn get_retention_policy(&self) -> Result<Option<RetentionPolicy>> {
+        if !self.has_timescaledb().await? {
+            let cleanup = self
+                .retention_cleanup
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            return Ok(cleanup.as_ref().map(|cleanup| RetentionPolicy {
+                days: cleanup.days,
+                enforced_by: RetentionMechanism::PeriodicCleanup,
+            }));
+        }
+
+        self.timed("get_retention_policy", async {
+            let days: Option<i32> = sqlx::query_scalar(
+                r"
+                SELECT (EXTRACT(EPOCH FROM (config->>'drop_after')::interval) / 86400)::INTEGER
+                FROM timescaledb_information.jobs
+                WHERE proc_name = 'policy_retention'
+                  AND hypertable_name = 'sensor_data'
+                ",
+            )
+            .fetch_optional(&self.pool)
+            .await?;
+
+            Ok(days.map(|days| RetentionPolicy {
+                days,
+                enforced_by: RetentionMechanism::TimescaleDb,
+            }))
+        })
+        .await
+    }
+
+    /// Whether the TimescaleDB extension is installed in the primary
+    /// database, checked once per store
+    async fn has_timescaledb(&self) -> Result<bool> {
+        let installed = self
+            .timescaledb
+            .get_or_try_init(|| {
+                self.timed("has_timescaledb", async {
+                    let installed = sqlx::query_scalar(
+                        "SELECT EXISTS (SELECT 1 FROM pg_extension WHERE extname = 'timescaledb')",
+                    )
+                    .fetch_one(&self.pool)
+                    .await?;
+                    Ok(installed)
+                })
+            })
+            .await?;
+        Ok(*installed)
+    }
+
+    /// Delete readings older than `days` now and then every
+    /// [`RETENTION_CLEANUP_INTERVAL`]
+    fn spawn_retention_cleanup(&self, days: i32) -> JoinHandle<()> {
+        let store = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(RETENTION_CLEANUP_INTERVAL);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                match store.cleanup_old_data(days).await {
+                    Ok(deleted) => debug!("Retention cleanup deleted {deleted} readings"),
+                    Err(e) => warn!("Retention cleanup failed: {e}"),
+                }
+            }
+        })
+    }
+
+    /// Stop the running cleanup task, if any, and start tracking `cleanup`
+    fn replace_retention_cleanup(&self, cleanup: Option<RetentionCleanup>) {
+        let previous = std::mem::replace(
+            &mut *self
+                .retention_cleanup
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+            cleanup,
+        );
+        if let Some(previous) = previous {
+            previous.task.abort();
+        }
     }
 
+    /// Buckets of `interval` aligned to the wall clock of the IANA zone
+    /// `timezone`, so daily buckets start at local midnight
     #[allow(clippy::too_many_arguments)]
     pub async fn get_time_bucketed_data(
         &self,
@@ -397,80 +1956,476 @@ impl PostgresStore {
         interval: &TimeInterval,
         start_time: DateTime<Utc>,
         end_time: DateTime<Utc>,
+        timezone: &str,
     ) -> Result<Vec<TimeBucketedData>> {
-        let interval_str = interval.to_interval_string();
+        self.get_combined_bucketed_data(
+            &[sensor_mac.to_string()],
+            interval,
+            start_time,
+            end_time,
+            timezone,
+        )
+        .await
+    }
 
-        // For now, implement basic time bucketing without the custom function
-        let query = format!(
-            r"
-            SELECT
-                time_bucket(INTERVAL '{interval_str}', timestamp) AS bucket,
-                AVG(temperature) AS avg_temperature,
-                MIN(temperature) AS min_temperature,
-                MAX(temperature) AS max_temperature,
-                AVG(humidity) AS avg_humidity,
-                MIN(humidity) AS min_humidity,
-                MAX(humidity) AS max_humidity,
-                AVG(pressure) AS avg_pressure,
-                MIN(pressure) AS min_pressure,
-                MAX(pressure) AS max_pressure,
-                COUNT(*) AS reading_count
-            FROM sensor_data
-            WHERE sensor_mac = $1
-              AND timestamp >= $2
-              AND timestamp <= $3
-            GROUP BY bucket
-            ORDER BY bucket
-            ",
-        );
+    /// Buckets over the readings of several sensors combined, as if they
+    /// were one sensor. Averages weigh every reading equally, so a sensor
+    /// reporting more often counts for more.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn get_combined_bucketed_data(
+        &self,
+        sensor_macs: &[String],
+        interval: &TimeInterval,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        timezone: &str,
+    ) -> Result<Vec<TimeBucketedData>> {
+        let query = time_bucket_query(self.has_timescaledb().await?);
+        self.timed("get_time_bucketed_data", async {
+            let rows = sqlx::query(&query)
+                .bind(sensor_macs)
+                .bind(start_time)
+                .bind(end_time)
+                .bind(interval.to_interval_string())
+                .bind(timezone)
+                .fetch_all(self.read_pool())
+                .await?;
 
-        let rows = sqlx::query(&query)
-            .bind(sensor_mac)
-            .bind(start_time)
-            .bind(end_time)
-            .fetch_all(&self.pool)
-            .await?;
+            Ok(rows
+                .iter()
+                .map(TimeBucketedData::from_row)
+                .collect::<Result<_, _>>()?)
+        })
+        .await
+    }
+
+    /// Average of several metrics per time bucket, fetched in one query.
+    ///
+    /// Every series gets a point for each bucket that has readings, so the
+    /// series line up index by index.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn get_metric_trends(
+        &self,
+        sensor_mac: &str,
+        metrics: &[Metric],
+        interval: &TimeInterval,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<MetricTrends> {
+        self.timed("get_metric_trends", async {
+            let mut series: TrendSeries =
+                metrics.iter().map(|metric| (*metric, Vec::new())).collect();
+            if series.is_empty() {
+                return Ok(MetricTrends {
+                    sensor_mac: sensor_mac.to_string(),
+                    series,
+                });
+            }
+
+            let averages = series
+                .keys()
+                .map(|metric| {
+                    let column = metric.column_name();
+                    format!("AVG({column}) AS {column}")
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let query = format!(
+                r"
+                SELECT time_bucket($4::interval, timestamp) AS bucket, {averages}
+                FROM sensor_data
+                WHERE sensor_mac = $1
+                  AND timestamp >= $2
+                  AND timestamp <= $3
+                GROUP BY bucket
+                ORDER BY bucket
+                ",
+            );
+
+            let rows = sqlx::query(&query)
+                .bind(sensor_mac)
+                .bind(start_time)
+                .bind(end_time)
+                .bind(interval.to_interval_string())
+                .fetch_all(self.read_pool())
+                .await?;
+
+            for row in &rows {
+                let bucket: DateTime<Utc> = row.get("bucket");
+                for (metric, points) in &mut series {
+                    points.push(TrendPoint {
+                        bucket,
+                        value: row.get(metric.column_name()),
+                    });
+                }
+            }
+
+            Ok(MetricTrends {
+                sensor_mac: sensor_mac.to_string(),
+                series,
+            })
+        })
+        .await
+    }
+
+    /// Pearson correlation between two sensors for one metric.
+    ///
+    /// Each sensor is averaged into buckets of `interval`; only buckets where
+    /// both sensors have readings are compared, so gaps in either series are
+    /// skipped rather than treated as zero. The coefficient is `None` when
+    /// fewer than two buckets overlap or either series is constant.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn get_sensor_correlation(
+        &self,
+        from_mac: &str,
+        to_mac: &str,
+        metric: Metric,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        interval: &TimeInterval,
+    ) -> Result<SensorCorrelation> {
+        self.timed("get_sensor_correlation", async {
+            let column = metric.column_name();
+            let query = format!(
+                r"
+                WITH bucketed AS (
+                    SELECT
+                        sensor_mac,
+                        time_bucket($5::interval, timestamp) AS bucket,
+                        AVG({column}) AS value
+                    FROM sensor_data
+                    WHERE sensor_mac IN ($1, $2)
+                      AND timestamp >= $3
+                      AND timestamp <= $4
+                    GROUP BY sensor_mac, bucket
+                )
+                SELECT
+                    corr(a.value, b.value) AS coefficient,
+                    COUNT(*) AS sample_count
+                FROM bucketed a
+                JOIN bucketed b ON a.bucket = b.bucket
+                WHERE a.sensor_mac = $1
+                  AND b.sensor_mac = $2
+                ",
+            );
+
+            let row = sqlx::query(&query)
+                .bind(from_mac)
+                .bind(to_mac)
+                .bind(start_time)
+                .bind(end_time)
+                .bind(interval.to_interval_string())
+                .fetch_one(self.read_pool())
+                .await?;
+
+            Ok(SensorCorrelation {
+                from_mac: from_mac.to_string(),
+                to_mac: to_mac.to_string(),
+                metric,
+                coefficient: row.get("coefficient"),
+                sample_count: row.get("sample_count"),
+            })
+        })
+        .await
+    }
 
-        let mut data = Vec::new();
-        for row in rows {
-            data.push(TimeBucketedData {
-                bucket: row.get("bucket"),
-                avg_temperature: row.get("avg_temperature"),
-                min_temperature: row.get("min_temperature"),
-                max_temperature: row.get("max_temperature"),
-                avg_humidity: row.get("avg_humidity"),
-                min_humidity: row.get("min_humidity"),
-                max_humidity: row.get("max_humidity"),
-                avg_pressure: row.get("avg_pressure"),
-                min_pressure: row.get("min_pressure"),
-                max_pressure: row.get("max_pressure"),
-                reading_count: row.get("reading_count"),
-            });
+    /// Stream time-bucketed data one bucket at a time, ordered by bucket.
+    ///
+    /// Unlike [`Self::get_time_bucketed_data`] the rows are fetched
+    /// incrementally from the database, so long ranges never have to be
+    /// buffered in full.
+    #[allow(clippy::too_many_arguments)]
+    pub fn stream_time_bucketed_data(
+        &self,
+        sensor_mac: &str,
+        interval: &TimeInterval,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        timezone: &str,
+    ) -> impl Stream<Item = Result<TimeBucketedData>> + Send + 'static {
+        let pool = self.read_pool().clone();
+        let sensor_mac = sensor_mac.to_string();
+        let timezone = timezone.to_string();
+        let interval_str = interval.to_interval_string();
+        let query_timeout = self.query_timeout;
+        let store = self.clone();
+
+        async_stream::try_stream! {
+            let query = time_bucket_query(store.has_timescaledb().await?);
+            let mut transaction = begin_with_statement_timeout(&pool, query_timeout).await?;
+            let mut rows = sqlx::query(&query)
+                .bind(std::slice::from_ref(&sensor_mac))
+                .bind(start_time)
+                .bind(end_time)
+                .bind(&interval_str)
+                .bind(&timezone)
+                .fetch(&mut *transaction);
+
+            // Bound the wait for each row rather than the whole stream, which
+            // is consumed at the client's pace
+            loop {
+                let next = tokio::time::timeout(query_timeout, rows.try_next())
+                    .await
+                    .map_err(|_| {
+                        store.cut_offs.record();
+                        QueryTimeout {
+                            operation: "stream_time_bucketed_data",
+                            timeout: query_timeout,
+                        }
+                    })?;
+                let Some(row) = next? else { break };
+                yield TimeBucketedData::from_row(&row)?;
+            }
         }
+    }
 
-        Ok(data)
+    /// Stream a sensor's raw readings in a time range, oldest first.
+    ///
+    /// Rows are fetched incrementally, so exports of long histories never
+    /// hold more than the consumer buffers.
+    pub fn stream_readings(
+        &self,
+        sensor_mac: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> impl Stream<Item = Result<Event>> + Send + 'static {
+        let pool = self.read_pool().clone();
+        let sensor_mac = sensor_mac.to_string();
+        let query_timeout = self.query_timeout;
+        let cut_offs = self.cut_offs.clone();
+
+        async_stream::try_stream! {
+            let mut transaction = begin_with_statement_timeout(&pool, query_timeout).await?;
+            let mut rows = sqlx::query_as::<_, Event>(
+                r"
+                SELECT sensor_mac, gateway_mac, temperature, humidity, pressure,
+                       battery, tx_power, movement_counter, measurement_sequence_number,
+                       acceleration, acceleration_x, acceleration_y, acceleration_z,
+                       rssi, timestamp
+                FROM sensor_data
+                WHERE sensor_mac = $1
+                  AND timestamp >= $2
+                  AND timestamp <= $3
+                ORDER BY timestamp
+                ",
+            )
+            .bind(&sensor_mac)
+            .bind(start_time)
+            .bind(end_time)
+            .fetch(&mut *transaction);
+
+            loop {
+                let next = tokio::time::timeout(query_timeout, rows.try_next())
+                    .await
+                    .map_err(|_| {
+                        cut_offs.record();
+                        QueryTimeout {
+                            operation: "stream_readings",
+                            timeout: query_timeout,
+                        }
+                    })?;
+                let Some(event) = next? else { break };
+                yield event;
+            }
+        }
     }
 
+    /// Hourly buckets, read from the `sensor_data_hourly` continuous aggregate
+    /// when it exists and bucketed from raw rows otherwise
+    #[allow(clippy::too_many_arguments)]
     pub async fn get_hourly_aggregates(
         &self,
         sensor_mac: &str,
         start_time: DateTime<Utc>,
         end_time: DateTime<Utc>,
+        timezone: &str,
+    ) -> Result<Vec<TimeBucketedData>> {
+        self.get_rollup(
+            &HOURLY_AGGREGATE,
+            sensor_mac,
+            start_time,
+            end_time,
+            timezone,
+        )
+        .await
+    }
+
+    /// Daily buckets, read from the `sensor_data_daily` continuous aggregate
+    /// when it exists and bucketed from raw rows otherwise
+    #[allow(clippy::too_many_arguments)]
+    pub async fn get_daily_aggregates(
+        &self,
+        sensor_mac: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        timezone: &str,
     ) -> Result<Vec<TimeBucketedData>> {
-        // Fallback to basic query if continuous aggregates don't exist yet
-        self.get_time_bucketed_data(sensor_mac, &TimeInterval::Hours(1), start_time, end_time)
+        self.get_rollup(&DAILY_AGGREGATE, sensor_mac, start_time, end_time, timezone)
             .await
     }
 
-    pub async fn get_daily_aggregates(
+    /// Create the hourly and daily continuous aggregates, their refresh
+    /// policies and indexes if they are missing.
+    ///
+    /// The views are created empty and use real-time aggregation, so they
+    /// answer queries immediately while the refresh policy materializes them
+    /// in the background. Returns `false` without touching the database when
+    /// TimescaleDB is not installed.
+    pub async fn ensure_continuous_aggregates(&self) -> Result<bool> {
+        if !self.has_timescaledb().await? {
+            return Ok(false);
+        }
+
+        self.timed("ensure_continuous_aggregates", async {
+            for aggregate in [&HOURLY_AGGREGATE, &DAILY_AGGREGATE] {
+                let view = aggregate.view;
+                let bucket = aggregate.interval.to_interval_string();
+
+                sqlx::query(&format!(
+                    r"
+                    CREATE MATERIALIZED VIEW IF NOT EXISTS {view}
+                    WITH (timescaledb.continuous, timescaledb.materialized_only = false) AS
+                    SELECT
+                        sensor_mac,
+                        gateway_mac,
+                        time_bucket(INTERVAL '{bucket}', timestamp) AS bucket,
+                        AVG(temperature) AS avg_temperature,
+                        MIN(temperature) AS min_temperature,
+                        MAX(temperature) AS max_temperature,
+                        AVG(humidity) AS avg_humidity,
+                        MIN(humidity) AS min_humidity,
+                        MAX(humidity) AS max_humidity,
+                        AVG(pressure) AS avg_pressure,
+                        MIN(pressure) AS min_pressure,
+                        MAX(pressure) AS max_pressure,
+                        AVG(battery) AS avg_battery,
+                        MIN(battery) AS min_battery,
+                        MAX(battery) AS max_battery,
+                        COUNT(*) AS reading_count
+                    FROM sensor_data
+                    GROUP BY sensor_mac, gateway_mac, bucket
+                    WITH NO DATA
+                    ",
+                ))
+                .execute(&self.pool)
+                .await?;
+
+                sqlx::query(
+                    r"
+                    SELECT add_continuous_aggregate_policy(
+                        $1::regclass,
+                        start_offset => $2::interval,
+                        end_offset => $3::interval,
+                        schedule_interval => $3::interval,
+                        if_not_exists => TRUE
+                    )
+                    ",
+                )
+                .bind(view)
+                .bind(aggregate.refresh_window)
+                .bind(&bucket)
+                .execute(&self.pool)
+                .await?;
+
+                sqlx::query(&format!(
+                    "CREATE INDEX IF NOT EXISTS idx_{view}_sensor_bucket ON {view}(sensor_mac, \
+                     bucket DESC)"
+                ))
+                .execute(&self.pool)
+                .await?;
+            }
+
+            Ok(true)
+        })
+        .await
+    }
+
+    /// Whether a table or view is visible to the read pool
+    async fn relation_exists(&self, name: &str) -> Result<bool> {
+        self.timed("relation_exists", async {
+            let exists = sqlx::query_scalar("SELECT to_regclass($1) IS NOT NULL")
+                .bind(name)
+                .fetch_one(self.read_pool())
+                .await?;
+            Ok(exists)
+        })
+        .await
+    }
+
+    /// Read whole buckets for a sensor from a continuous aggregate, falling
+    /// back to bucketing raw rows when the view does not exist or buckets
+    /// are wanted in a zone other than UTC.
+    ///
+    /// The views keep one row per gateway, so rows are merged per bucket
+    /// using their reading counts. Percentiles and first/last values cannot
+    /// be merged that way and are left empty.
+    #[allow(clippy::too_many_arguments)]
+    async fn get_rollup(
         &self,
+        aggregate: &ContinuousAggregate,
         sensor_mac: &str,
         start_time: DateTime<Utc>,
         end_time: DateTime<Utc>,
+        timezone: &str,
     ) -> Result<Vec<TimeBucketedData>> {
-        // Fallback to basic query if continuous aggregates don't exist yet
-        self.get_time_bucketed_data(sensor_mac, &TimeInterval::Days(1), start_time, end_time)
-            .await
+        if timezone != "UTC" || !self.relation_exists(aggregate.view).await? {
+            return self
+                .get_time_bucketed_data(
+                    sensor_mac,
+                    &aggregate.interval,
+                    start_time,
+                    end_time,
+                    timezone,
+                )
+                .await;
+        }
+
+        self.timed("get_rollup", async {
+            let view = aggregate.view;
+            let query = format!(
+                r"
+                SELECT
+                    bucket,
+                    SUM(avg_temperature * reading_count) / SUM(reading_count) AS avg_temperature,
+                    MIN(min_temperature) AS min_temperature,
+                    MAX(max_temperature) AS max_temperature,
+                    SUM(avg_temperature * reading_count) AS sum_temperature,
+                    NULL::DOUBLE PRECISION AS p50_temperature,
+                    NULL::DOUBLE PRECISION AS p95_temperature,
+                    NULL::DOUBLE PRECISION AS first_temperature,
+                    NULL::DOUBLE PRECISION AS last_temperature,
+                    SUM(avg_humidity * reading_count) / SUM(reading_count) AS avg_humidity,
+                    MIN(min_humidity) AS min_humidity,
+                    MAX(max_humidity) AS max_humidity,
+                    SUM(avg_humidity * reading_count) AS sum_humidity,
+                    SUM(avg_pressure * reading_count) / SUM(reading_count) AS avg_pressure,
+                    MIN(min_pressure) AS min_pressure,
+                    MAX(max_pressure) AS max_pressure,
+                    SUM(avg_pressure * reading_count) AS sum_pressure,
+                    SUM(reading_count)::BIGINT AS reading_count
+                FROM {view}
+                WHERE sensor_mac = $1
+                  AND bucket >= time_bucket($4::interval, $2::timestamptz)
+                  AND bucket <= $3
+                GROUP BY bucket
+                ORDER BY bucket
+                ",
+            );
+
+            let rows = sqlx::query(&query)
+                .bind(sensor_mac)
+                .bind(start_time)
+                .bind(end_time)
+                .bind(aggregate.interval.to_interval_string())
+                .fetch_all(self.read_pool())
+                .await?;
+
+            Ok(rows
+                .iter()
+                .map(TimeBucketedData::from_row)
+                .collect::<Result<_, _>>()?)
+        })
+        .await
     }
 
     pub async fn get_recent_aggregates(
@@ -482,45 +2437,52 @@ impl PostgresStore {
         let end_time = Utc::now();
         let start_time = end_time - chrono::Duration::hours(i64::from(hours_back));
 
-        self.get_time_bucketed_data(sensor_mac, interval, start_time, end_time)
+        self.get_time_bucketed_data(sensor_mac, interval, start_time, end_time, "UTC")
             .await
     }
 
+    /// Average temperature of a sensor in `interval` wide buckets over the
+    /// last `hours_back`, oldest first
     pub async fn get_temperature_trend(
         &self,
         sensor_mac: &str,
         hours_back: i32,
+        interval: &TimeInterval,
     ) -> Result<Vec<(DateTime<Utc>, f64)>> {
-        let start_time = Utc::now() - chrono::Duration::hours(i64::from(hours_back));
-
-        let rows = sqlx::query(
-            r"
-            SELECT
-                time_bucket(INTERVAL '15 minutes', timestamp) AS bucket,
-                AVG(temperature) AS avg_temp
-            FROM sensor_data
-            WHERE sensor_mac = $1
-              AND timestamp >= $2
-            GROUP BY bucket
-            ORDER BY bucket
-            ",
-        )
-        .bind(sensor_mac)
-        .bind(start_time)
-        .fetch_all(&self.pool)
-        .await?;
+        self.timed("get_temperature_trend", async {
+            let start_time = Utc::now() - chrono::Duration::hours(i64::from(hours_back));
+
+            let rows = sqlx::query(
+                r"
+                SELECT
+                    time_bucket($3::interval, timestamp) AS bucket,
+                    AVG(temperature) AS avg_temp
+                FROM sensor_data
+                WHERE sensor_mac = $1
+                  AND timestamp >= $2
+                GROUP BY bucket
+                ORDER BY bucket
+                ",
+            )
+            .bind(sensor_mac)
+            .bind(start_time)
+            .bind(interval.to_interval_string())
+            .fetch_all(self.read_pool())
+            .await?;
 
-        let mut data = Vec::new();
-        for row in rows {
-            if let (Some(bucket), Some(avg_temp)) = (
-                row.get::<Option<DateTime<Utc>>, _>("bucket"),
-                row.get::<Option<f64>, _>("avg_temp"),
-            ) {
-                data.push((bucket, avg_temp));
+            let mut data = Vec::new();
+            for row in rows {
+                if let (Some(bucket), Some(avg_temp)) = (
+                    row.get::<Option<DateTime<Utc>>, _>("bucket"),
+                    row.get::<Option<f64>, _>("avg_temp"),
+                ) {
+                    data.push((bucket, avg_temp));
+                }
             }
-        }
 
-        Ok(data)
+            Ok(data)
+        })
+        .await
     }
 
     pub async fn get_sensor_health_metrics(
@@ -528,72 +2490,246 @@ impl PostgresStore {
         sensor_mac: &str,
         hours_back: i32,
     ) -> Result<SensorHealthMetrics> {
-        let start_time = Utc::now() - chrono::Duration::hours(i64::from(hours_back));
-
-        let row = sqlx::query(
-            r"
-            SELECT
-                COUNT(*) as total_readings,
-                AVG(battery) as avg_battery,
-                MIN(battery) as min_battery,
-                AVG(rssi) as avg_rssi,
-                MIN(rssi) as min_rssi,
-                MAX(timestamp) as last_reading
-            FROM sensor_data
-            WHERE sensor_mac = $1
-              AND timestamp >= $2
-            ",
-        )
-        .bind(sensor_mac)
-        .bind(start_time)
-        .fetch_one(&self.pool)
-        .await?;
+        self.timed("get_sensor_health_metrics", async {
+            let start_time = Utc::now() - chrono::Duration::hours(i64::from(hours_back));
+
+            let row = sqlx::query(
+                r"
+                SELECT
+                    COUNT(*) as total_readings,
+                    AVG(battery) as avg_battery,
+                    MIN(battery) as min_battery,
+                    AVG(rssi) as avg_rssi,
+                    MIN(rssi) as min_rssi,
+                    MAX(timestamp) as last_reading
+                FROM sensor_data
+                WHERE sensor_mac = $1
+                  AND timestamp >= $2
+                ",
+            )
+            .bind(sensor_mac)
+            .bind(start_time)
+            .fetch_one(self.read_pool())
+            .await?;
+
+            let avg_battery_bd: Option<BigDecimal> = row.get("avg_battery");
+            let avg_rssi_bd: Option<BigDecimal> = row.get("avg_rssi");
+
+            Ok(SensorHealthMetrics {
+                total_readings: row.get::<Option<i64>, _>("total_readings").unwrap_or(0),
+                avg_battery: avg_battery_bd.and_then(|bd| bd.to_f64()).unwrap_or(0.0),
+                min_battery: row.get::<Option<i64>, _>("min_battery").unwrap_or(0),
+                avg_rssi: avg_rssi_bd.and_then(|bd| bd.to_f64()).unwrap_or(0.0),
+                min_rssi: row.get::<Option<i64>, _>("min_rssi").unwrap_or(0),
+                last_reading: row.get("last_reading"),
+            })
+        })
+        .await
+    }
+
+    /// Estimate when a sensor's battery runs empty from a straight-line fit
+    /// of its hourly average battery voltage over the last `lookback_days`.
+    ///
+    /// Readings without a battery value are ignored. The depletion date is
+    /// `None` when there is too little data or the voltage is not falling.
+    pub async fn project_battery_life(
+        &self,
+        sensor_mac: &str,
+        lookback_days: i32,
+    ) -> Result<BatteryProjection> {
+        self.timed("project_battery_life", async {
+            let start_time = Utc::now() - chrono::Duration::days(i64::from(lookback_days));
+
+            let rows = sqlx::query(
+                r"
+                SELECT
+                    time_bucket(INTERVAL '1 hour', timestamp) AS bucket,
+                    AVG(battery)::DOUBLE PRECISION AS avg_battery,
+                    COUNT(*) AS readings
+                FROM sensor_data
+                WHERE sensor_mac = $1
+                  AND timestamp >= $2
+                  AND battery > 0
+                GROUP BY bucket
+                ORDER BY bucket
+                ",
+            )
+            .bind(sensor_mac)
+            .bind(start_time)
+            .fetch_all(self.read_pool())
+            .await?;
+
+            let points: Vec<regression::Point> = rows
+                .iter()
+                .map(|row| {
+                    let bucket: DateTime<Utc> = row.get("bucket");
+                    (days_between(start_time, bucket), row.get("avg_battery"))
+                })
+                .collect();
+            let sample_count = rows.iter().map(|row| row.get::<i64, _>("readings")).sum();
+
+            let fit = regression::linear_regression(&points);
+            let depletion_date = fit
+                .filter(|fit| fit.slope < 0.0)
+                .and_then(|fit| fit.solve_for(BATTERY_EMPTY_MV))
+                .and_then(|days| {
+                    chrono::Duration::try_seconds((days * SECONDS_PER_DAY) as i64)
+                        .and_then(|offset| start_time.checked_add_signed(offset))
+                });
+
+            Ok(BatteryProjection {
+                sensor_mac: sensor_mac.to_string(),
+                sample_count,
+                current_mv: fit.map(|fit| fit.value_at(days_between(start_time, Utc::now()))),
+                slope_mv_per_day: fit.map(|fit| fit.slope),
+                depletion_date,
+            })
+        })
+        .await
+    }
 
-        let avg_battery_bd: Option<BigDecimal> = row.get("avg_battery");
-        let avg_rssi_bd: Option<BigDecimal> = row.get("avg_rssi");
+    /// Compress `sensor_data` chunks once they are older than `after_days`.
+    ///
+    /// Chunks are segmented per sensor and gateway so each sensor's history
+    /// decompresses on its own. Returns `false` without touching the database
+    /// when TimescaleDB is not installed.
+    pub async fn enable_compression(&self, after_days: i32) -> Result<bool> {
+        anyhow::ensure!(
+            after_days > 0,
+            "compression must wait at least one day, got {after_days}"
+        );
+        if !self.has_timescaledb().await? {
+            return Ok(false);
+        }
 
-        Ok(SensorHealthMetrics {
-            total_readings: row.get::<Option<i64>, _>("total_readings").unwrap_or(0),
-            avg_battery: avg_battery_bd.and_then(|bd| bd.to_f64()).unwrap_or(0.0),
-            min_battery: row.get::<Option<i64>, _>("min_battery").unwrap_or(0),
-            avg_rssi: avg_rssi_bd.and_then(|bd| bd.to_f64()).unwrap_or(0.0),
-            min_rssi: row.get::<Option<i64>, _>("min_rssi").unwrap_or(0),
-            last_reading: row.get("last_reading"),
+        self.timed("enable_compression", async {
+            // Columns of the unique reading index must be segmented or
+            // ordered by for compressed chunks to keep enforcing it
+            sqlx::query(
+                r"
+                ALTER TABLE sensor_data SET (
+                    timescaledb.compress,
+                    timescaledb.compress_segmentby = 'sensor_mac, gateway_mac',
+                    timescaledb.compress_orderby = 'timestamp DESC, measurement_sequence_number'
+                )
+                ",
+            )
+            .execute(&self.pool)
+            .await?;
+            sqlx::query(
+                r"
+                SELECT add_compression_policy(
+                    'sensor_data',
+                    INTERVAL '1 day' * $1,
+                    if_not_exists => TRUE
+                )
+                ",
+            )
+            .bind(after_days)
+            .execute(&self.pool)
+            .await?;
+            Ok(true)
         })
+        .await
     }
 
+    /// Size of `sensor_data` on disk and before compression.
+    ///
+    /// With compression enabled the figures come from
+    /// `hypertable_compression_stats`, so `raw_size_mb` is what the table
+    /// would take uncompressed. Otherwise both sizes are the table size and
+    /// the ratio is 1.
     pub async fn get_storage_stats(&self) -> Result<StorageStats> {
-        // Simplified storage stats without custom functions
-        let row = sqlx::query(
-            r"
-            SELECT
-                'sensor_data' as table_name,
-                pg_total_relation_size('sensor_data') / 1024.0 / 1024.0 as raw_size_mb,
-                pg_total_relation_size('sensor_data') / 1024.0 / 1024.0 as compressed_size_mb,
-                1.0 as compression_ratio,
-                COUNT(*) as row_count,
-                MIN(timestamp) as oldest_data,
-                MAX(timestamp) as newest_data
-            FROM sensor_data
-            ",
-        )
-        .fetch_one(&self.pool)
-        .await?;
+        let compression = if self.has_timescaledb().await? {
+            self.get_compression_sizes().await?
+        } else {
+            None
+        };
+
+        self.timed("get_storage_stats", async {
+            // Simplified storage stats without custom functions
+            let row = sqlx::query(
+                r"
+                SELECT
+                    'sensor_data' as table_name,
+                    pg_total_relation_size('sensor_data') / 1024.0 / 1024.0 as raw_size_mb,
+                    pg_total_relation_size('sensor_data') / 1024.0 / 1024.0 as compressed_size_mb,
+                    1.0 as compression_ratio,
+                    COUNT(*) as row_count,
+                    MIN(timestamp) as oldest_data,
+                    MAX(timestamp) as newest_data
+                FROM sensor_data
+                ",
+            )
+            .fetch_one(self.read_pool())
+            .await?;
 
-        let raw_size_mb: Option<BigDecimal> = row.get("raw_size_mb");
-        let compressed_size_mb: Option<BigDecimal> = row.get("compressed_size_mb");
+            let raw_size_mb: Option<BigDecimal> = row.get("raw_size_mb");
+            let compressed_size_mb: Option<BigDecimal> = row.get("compressed_size_mb");
+
+            let compression_ratio_bd: Option<BigDecimal> = row.get("compression_ratio");
+
+            let mut stats = StorageStats {
+                table_name: row.get("table_name"),
+                raw_size_mb: raw_size_mb.and_then(|a| a.to_f64()),
+                compressed_size_mb: compressed_size_mb.and_then(|a| a.to_f64()),
+                compression_ratio: compression_ratio_bd.and_then(|bd| bd.to_f64()),
+                row_count: row.get("row_count"),
+                oldest_data: row.get("oldest_data"),
+                newest_data: row.get("newest_data"),
+            };
+            if let Some(sizes) = compression {
+                stats.raw_size_mb = Some(sizes.raw_size_mb);
+                stats.compressed_size_mb = Some(sizes.compressed_size_mb);
+                stats.compression_ratio = sizes.compression_ratio;
+            }
+            Ok(stats)
+        })
+        .await
+    }
 
-        let compression_ratio_bd: Option<BigDecimal> = row.get("compression_ratio");
+    /// Hypertable sizes before and after compression, or `None` when
+    /// compression is not enabled on `sensor_data`
+    async fn get_compression_sizes(&self) -> Result<Option<CompressionSizes>> {
+        self.timed("get_compression_sizes", async {
+            let compression_enabled: bool = sqlx::query_scalar(
+                r"
+                SELECT EXISTS (
+                    SELECT 1 FROM timescaledb_information.hypertables
+                    WHERE hypertable_name = 'sensor_data' AND compression_enabled
+                )
+                ",
+            )
+            .fetch_one(self.read_pool())
+            .await?;
+            if !compression_enabled {
+                return Ok(None);
+            }
 
-        Ok(StorageStats {
-            table_name: row.get("table_name"),
-            raw_size_mb: raw_size_mb.and_then(|a| a.to_f64()),
-            compressed_size_mb: compressed_size_mb.and_then(|a| a.to_f64()),
-            compression_ratio: compression_ratio_bd.and_then(|bd| bd.to_f64()),
-            row_count: row.get("row_count"),
-            oldest_data: row.get("oldest_data"),
-            newest_data: row.get("newest_data"),
+            let sizes = sqlx::query_as::<_, CompressionSizes>(
+                r"
+                WITH sizes AS (
+                    SELECT
+                        hypertable_size('sensor_data')::DOUBLE PRECISION AS total_bytes,
+                        COALESCE(SUM(stats.before_compression_total_bytes), 0)::DOUBLE PRECISION
+                            AS before_bytes,
+                        COALESCE(SUM(stats.after_compression_total_bytes), 0)::DOUBLE PRECISION
+                            AS after_bytes
+                    FROM hypertable_compression_stats('sensor_data') stats
+                )
+                SELECT
+                    (total_bytes - after_bytes + before_bytes) / 1024.0 / 1024.0 AS raw_size_mb,
+                    total_bytes / 1024.0 / 1024.0 AS compressed_size_mb,
+                    (total_bytes - after_bytes + before_bytes) / NULLIF(total_bytes, 0)
+                        AS compression_ratio
+                FROM sizes
+                ",
+            )
+            .fetch_one(self.read_pool())
+            .await?;
+            Ok(Some(sizes))
         })
+        .await
     }
 
     #[allow(clippy::unused_async)]
@@ -603,68 +2739,192 @@ impl PostgresStore {
         reading_interval_seconds: i32,
         retention_years: i32,
     ) -> Result<StorageEstimate> {
-        // Simple calculation
-        let readings_per_sensor_per_year = (365 * 24 * 3600) / i64::from(reading_interval_seconds);
-        let total_readings =
-            readings_per_sensor_per_year * i64::from(sensor_count) * i64::from(retention_years);
-        let bytes_per_reading = 200;
-        let compression_ratio = 10.0;
+        self.timed("estimate_storage_requirements", async {
+            // Simple calculation
+            let readings_per_sensor_per_year =
+                SECONDS_PER_YEAR / i64::from(reading_interval_seconds);
+
+            Ok(storage_estimate(
+                format!(
+                    "{sensor_count} sensors, {reading_interval_seconds} sec intervals, \
+                     {retention_years} years",
+                ),
+                readings_per_sensor_per_year * i64::from(sensor_count),
+                sensor_count,
+                retention_years,
+            ))
+        })
+        .await
+    }
 
-        #[allow(clippy::cast_precision_loss)]
-        let uncompressed_gb =
-            (total_readings * bytes_per_reading) as f64 / 1024.0 / 1024.0 / 1024.0;
-        let compressed_gb = uncompressed_gb / compression_ratio;
+    /// Average seconds between a sensor's readings over the last day, or
+    /// `None` when it has fewer than [`MIN_OBSERVED_READINGS`] readings.
+    ///
+    /// Duplicates received through several gateways count as separate
+    /// readings, since each one is stored.
+    pub async fn get_reading_interval_seconds(&self, sensor_mac: &str) -> Result<Option<f64>> {
+        self.timed("get_reading_interval_seconds", async {
+            let start_time = Utc::now() - chrono::Duration::hours(OBSERVED_INTERVAL_WINDOW_HOURS);
+
+            let row = sqlx::query(
+                r"
+                SELECT
+                    COUNT(*) AS readings,
+                    EXTRACT(EPOCH FROM MAX(timestamp) - MIN(timestamp))::DOUBLE PRECISION
+                        AS span_seconds
+                FROM sensor_data
+                WHERE sensor_mac = $1
+                  AND timestamp >= $2
+                ",
+            )
+            .bind(sensor_mac)
+            .bind(start_time)
+            .fetch_one(self.read_pool())
+            .await?;
+
+            let readings: i64 = row.get("readings");
+            let span_seconds: Option<f64> = row.get("span_seconds");
+            if readings < MIN_OBSERVED_READINGS {
+                return Ok(None);
+            }
+
+            #[allow(clippy::cast_precision_loss)]
+            Ok(span_seconds
+                .map(|span| span / (readings - 1) as f64)
+                .filter(|interval| *interval > 0.0))
+        })
+        .await
+    }
+
+    /// Average seconds between readings over the last day for every sensor
+    /// with at least [`MIN_OBSERVED_READINGS`] readings, in one query
+    ///
+    /// Each interval matches [`Self::get_reading_interval_seconds`].
+    async fn get_reading_intervals_seconds(&self) -> Result<BTreeMap<String, f64>> {
+        self.timed("get_reading_intervals_seconds", async {
+            let start_time = Utc::now() - chrono::Duration::hours(OBSERVED_INTERVAL_WINDOW_HOURS);
+
+            let rows = sqlx::query_as::<_, (String, i64, f64)>(
+                r"
+                SELECT
+                    sensor_mac,
+                    COUNT(*) AS readings,
+                    EXTRACT(EPOCH FROM MAX(timestamp) - MIN(timestamp))::DOUBLE PRECISION
+                        AS span_seconds
+                FROM sensor_data
+                WHERE timestamp >= $1
+                GROUP BY sensor_mac
+                HAVING COUNT(*) >= $2
+                ",
+            )
+            .bind(start_time)
+            .bind(MIN_OBSERVED_READINGS)
+            .fetch_all(self.read_pool())
+            .await?;
+
+            #[allow(clippy::cast_precision_loss)]
+            Ok(rows
+                .into_iter()
+                .map(|(mac, readings, span)| (mac, span / (readings - 1) as f64))
+                .filter(|(_, interval)| *interval > 0.0)
+                .collect())
+        })
+        .await
+    }
 
-        Ok(StorageEstimate {
-            scenario: format!(
-                "{sensor_count} sensors, {reading_interval_seconds} sec intervals, \
+    /// Storage estimate using each known sensor's observed reading interval.
+    ///
+    /// Sensors without enough recent readings are assumed to report every
+    /// `reading_interval_seconds`. When no sensor has enough data, this is
+    /// the uniform [`Self::estimate_storage_requirements`] for `sensor_count`
+    /// sensors.
+    pub async fn estimate_observed_storage_requirements(
+        &self,
+        sensor_count: i32,
+        reading_interval_seconds: i32,
+        retention_years: i32,
+    ) -> Result<StorageEstimate> {
+        let macs = self.list_sensor_macs().await?;
+        let intervals = self.get_reading_intervals_seconds().await?;
+
+        let mut observed_sensors = 0;
+        let mut readings_per_year = 0;
+        for mac in &macs {
+            let interval = match intervals.get(mac).copied() {
+                Some(interval) => {
+                    observed_sensors += 1;
+                    interval
+                }
+                None => f64::from(reading_interval_seconds),
+            };
+            #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+            {
+                readings_per_year += (SECONDS_PER_YEAR as f64 / interval).round() as i64;
+            }
+        }
+
+        if observed_sensors == 0 {
+            return self
+                .estimate_storage_requirements(
+                    sensor_count,
+                    reading_interval_seconds,
+                    retention_years,
+                )
+                .await;
+        }
+
+        let known_sensors = i32::try_from(macs.len())?;
+        Ok(storage_estimate(
+            format!(
+                "{known_sensors} sensors ({observed_sensors} with observed intervals), \
                  {retention_years} years",
             ),
-            total_readings: Some(total_readings),
-            uncompressed_size_gb: Some(uncompressed_gb),
-            compressed_size_gb: Some(compressed_gb),
-            daily_aggregates_size_mb: Some(
-                f64::from(sensor_count * 365 * retention_years * 150) / 1024.0 / 1024.0,
-            ),
-            hourly_aggregates_size_mb: Some(
-                f64::from(sensor_count * 365 * 24 * retention_years * 150) / 1024.0 / 1024.0,
-            ),
-            total_estimated_size_gb: Some(compressed_gb + 0.1), // Add small overhead
-        })
+            readings_per_year,
+            known_sensors,
+            retention_years,
+        ))
     }
 
+    /// Readings stored over the last `days_back` days and the disk space
+    /// they take.
+    ///
+    /// The space is the readings' share of the measured size of
+    /// `sensor_data`, so it is `None` until that size is known.
     pub async fn get_growth_statistics(&self, days_back: i32) -> Result<GrowthStatistics> {
-        let start_time = Utc::now() - chrono::Duration::days(i64::from(days_back));
-
-        let row = sqlx::query(
-            r"
-            SELECT
-                COUNT(*) as readings_added,
-                COUNT(*)::NUMERIC / $1 as readings_per_day,
-                100.0 as storage_growth_mb,
-                365.0 as estimated_yearly_growth_gb
-            FROM sensor_data
-            WHERE timestamp >= $2
-            ",
-        )
-        .bind(days_back)
-        .bind(start_time)
-        .fetch_one(&self.pool)
-        .await?;
-
-        let readings_per_day_bd: Option<BigDecimal> = row.get("readings_per_day");
-        let storage_growth_mb_bd: Option<BigDecimal> = row.get("storage_growth_mb");
-        let estimated_yearly_growth_gb_bd: Option<BigDecimal> =
-            row.get("estimated_yearly_growth_gb");
-        let readings_added: Option<i64> = row.get("readings_added");
+        let storage = self.get_storage_stats().await?;
+        #[allow(clippy::cast_precision_loss)]
+        let mb_per_reading = match (storage.compressed_size_mb, storage.row_count) {
+            (Some(size_mb), Some(rows)) if size_mb > 0.0 && rows > 0 => Some(size_mb / rows as f64),
+            _ => None,
+        };
+
+        self.timed("get_growth_statistics", async {
+            let start_time = Utc::now() - chrono::Duration::days(i64::from(days_back));
+
+            let readings_added: i64 = sqlx::query_scalar(
+                r"
+                SELECT COUNT(*)
+                FROM sensor_data
+                WHERE timestamp >= $1
+                ",
+            )
+            .bind(start_time)
+            .fetch_one(self.read_pool())
+            .await?;
 
-        Ok(GrowthStatistics {
-            period_days: Some(days_back),
-            readings_added,
-            readings_per_day: readings_per_day_bd.and_then(|bd| bd.to_f64()),
-            storage_growth_mb: storage_growth_mb_bd.and_then(|bd| bd.to_f64()),
-            estimated_yearly_growth_gb: estimated_yearly_growth_gb_bd.and_then(|bd| bd.to_f64()),
+            #[allow(clippy::cast_precision_loss)]
+            let storage_growth_mb = mb_per_reading.map(|mb| mb * readings_added as f64);
+            Ok(GrowthStatistics {
+                period_days: Some(days_back),
+                readings_added: Some(readings_added),
+                #[allow(clippy::cast_precision_loss)]
+                readings_per_day: Some(readings_added as f64 / f64::from(days_back)),
+                storage_growth_mb,
+                estimated_yearly_growth_gb: storage_growth_mb
+                    .map(|mb| mb / f64::from(days_back) * 365.0 / 1024.0),
+            })
         })
+        .await
     }
 
     pub async fn get_storage_monitoring_view(&self) -> Result<Vec<StorageStats>> {
@@ -673,6 +2933,125 @@ impl PostgresStore {
     }
 }
 
+#[async_trait]
+impl Store for PostgresStore {
+    async fn insert_event(&self, event: &Event) -> Result<()> {
+        Self::insert_event(self, event).await.map(|_| ())
+    }
+
+    async fn get_latest_reading(&self, sensor_mac: &str) -> Result<Option<Event>> {
+        Self::get_latest_reading(self, sensor_mac).await
+    }
+
+    async fn get_historical_data(
+        &self,
+        sensor_mac: &str,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        limit: Option<i64>,
+    ) -> Result<Vec<Event>> {
+        Self::get_historical_data(self, sensor_mac, start, end, limit, None, Order::Desc).await
+    }
+
+    async fn get_latest_for(&self, sensor_macs: &[String]) -> Result<Vec<Event>> {
+        Self::get_latest_for(self, sensor_macs).await
+    }
+
+    async fn get_active_sensors(&self) -> Result<Vec<Event>> {
+        Self::get_active_sensors(self).await
+    }
+
+    fn subscribe_to_events(&self) -> broadcast::Receiver<Event> {
+        Self::subscribe_to_events(self)
+    }
+}
+
+/// TimescaleDB continuous aggregate rolling raw readings up into fixed buckets
+struct ContinuousAggregate {
+    view: &'static str,
+    interval: TimeInterval,
+    /// How far back each scheduled refresh re-materializes
+    refresh_window: &'static str,
+}
+
+const HOURLY_AGGREGATE: ContinuousAggregate = ContinuousAggregate {
+    view: "sensor_data_hourly",
+    interval: TimeInterval::Hours(1),
+    refresh_window: "3 hours",
+};
+
+const DAILY_AGGREGATE: ContinuousAggregate = ContinuousAggregate {
+    view: "sensor_data_daily",
+    interval: TimeInterval::Days(1),
+    refresh_window: "3 days",
+};
+
+/// Size of `readings_per_year` readings from `sensor_count` sensors kept for
+/// `retention_years`
+fn storage_estimate(
+    scenario: String,
+    readings_per_year: i64,
+    sensor_count: i32,
+    retention_years: i32,
+) -> StorageEstimate {
+    let total_readings = readings_per_year * i64::from(retention_years);
+    let bytes_per_reading = 200;
+    let compression_ratio = 10.0;
+
+    #[allow(clippy::cast_precision_loss)]
+    let uncompressed_gb = (total_readings * bytes_per_reading) as f64 / 1024.0 / 1024.0 / 1024.0;
+    let compressed_gb = uncompressed_gb / compression_ratio;
+
+    StorageEstimate {
+        scenario,
+        total_readings: Some(total_readings),
+        uncompressed_size_gb: Some(uncompressed_gb),
+        compressed_size_gb: Some(compressed_gb),
+        daily_aggregates_size_mb: Some(
+            f64::from(sensor_count * 365 * retention_years * 150) / 1024.0 / 1024.0,
+        ),
+        hourly_aggregates_size_mb: Some(
+            f64::from(sensor_count * 365 * 24 * retention_years * 150) / 1024.0 / 1024.0,
+        ),
+        total_estimated_size_gb: Some(compressed_gb + 0.1), // Add small overhead
+    }
+}
+
+/// Start a read transaction in which the server aborts a statement after
+/// `timeout`, whether or not the pool's connections set `statement_timeout`
+async fn begin_with_statement_timeout(
+    pool: &PgPool,
+    timeout: Duration,
+) -> Result<sqlx::Transaction<'static, Postgres>> {
+    let mut transaction = pool.begin().await?;
+    // SET cannot take bind parameters
+    sqlx::query(&format!(
+        "SET LOCAL statement_timeout = {}",
+        timeout.as_millis()
+    ))
+    .execute(&mut *transaction)
+    .await?;
+    Ok(transaction)
+}
+
+/// Open a pool whose connections abort statements after `query_timeout`
+async fn connect_pool(
+    pool_options: PgPoolOptions,
+    database_url: &str,
+    query_timeout: Duration,
+) -> Result<PgPool> {
+    let options = PgConnectOptions::from_str(database_url)?
+        .options([("statement_timeout", query_timeout.as_millis().to_string())]);
+    Ok(pool_options.connect_with(options).await?)
+}
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+/// Fractional days from `from` to `to`
+fn days_between(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    (to - from).num_seconds() as f64 / SECONDS_PER_DAY
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SensorStats {
     pub avg_temperature: f64,
@@ -684,6 +3063,10 @@ pub struct SensorStats {
     pub avg_pressure: f64,
     pub min_pressure: f64,
     pub max_pressure: f64,
+    /// Sample standard deviations; 0 when there are fewer than two readings
+    pub stddev_temperature: f64,
+    pub stddev_humidity: f64,
+    pub stddev_pressure: f64,
     pub reading_count: i64,
 }
 
@@ -708,6 +3091,14 @@ pub struct StorageStats {
     pub newest_data: Option<DateTime<Utc>>,
 }
 
+/// Sizes of a hypertable with compression enabled
+#[derive(Debug, FromRow)]
+struct CompressionSizes {
+    raw_size_mb: f64,
+    compressed_size_mb: f64,
+    compression_ratio: Option<f64>,
+}
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct StorageEstimate {
     pub scenario: String,
@@ -728,36 +3119,328 @@ pub struct GrowthStatistics {
     pub estimated_yearly_growth_gb: Option<f64>,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
-pub struct TimeBucketedData {
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SensorCorrelation {
+    pub from_mac: String,
+    pub to_mac: String,
+    pub metric: Metric,
+    pub coefficient: Option<f64>,
+    pub sample_count: i64,
+}
+
+/// Battery voltage below which a sensor is flagged for a battery change
+pub const LOW_BATTERY_MV: i64 = 2500;
+
+/// Signal strength below which a sensor is flagged as poorly received
+pub const WEAK_SIGNAL_DBM: i64 = -85;
+
+/// Time without readings after which a sensor is flagged as stale
+pub const STALE_AFTER_MINUTES: i64 = 10;
+
+/// Problems worth surfacing next to a sensor's latest reading
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SensorHealthFlags {
+    pub low_battery: bool,
+    pub weak_signal: bool,
+    pub stale: bool,
+}
+
+impl SensorHealthFlags {
+    /// Flags for a sensor whose newest reading is `latest`.
+    ///
+    /// A battery or RSSI of zero means the sensor did not report it and is
+    /// not flagged.
+    pub fn for_reading(latest: &Event, now: DateTime<Utc>) -> Self {
+        Self {
+            low_battery: latest.battery > 0 && latest.battery < LOW_BATTERY_MV,
+            weak_signal: latest.rssi != 0 && latest.rssi < WEAK_SIGNAL_DBM,
+            stale: now - latest.timestamp > chrono::Duration::minutes(STALE_AFTER_MINUTES),
+        }
+    }
+}
+
+/// Everything the dashboard shows for one sensor
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SensorCard {
+    pub sensor_mac: String,
+    pub name: Option<String>,
+    pub location: Option<String>,
+    pub last_seen: DateTime<Utc>,
+    pub health: SensorHealthFlags,
+    pub latest: Event,
+}
+
+/// Most recent non-null value of a single metric
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MetricReading {
+    pub value: f64,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Newest value of each metric, each with the time it was measured
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LatestPerMetric {
+    pub sensor_mac: String,
+    pub temperature: Option<MetricReading>,
+    pub humidity: Option<MetricReading>,
+    pub pressure: Option<MetricReading>,
+}
+
+impl LatestPerMetric {
+    /// Whether the sensor has no value for any metric
+    pub fn is_empty(&self) -> bool {
+        self.temperature.is_none() && self.humidity.is_none() && self.pressure.is_none()
+    }
+}
+
+/// Average of one metric over a time bucket
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TrendPoint {
     pub bucket: DateTime<Utc>,
-    pub avg_temperature: Option<f64>,
-    pub min_temperature: Option<f64>,
-    pub max_temperature: Option<f64>,
-    pub avg_humidity: Option<f64>,
-    pub min_humidity: Option<f64>,
-    pub max_humidity: Option<f64>,
-    pub avg_pressure: Option<f64>,
-    pub min_pressure: Option<f64>,
-    pub max_pressure: Option<f64>,
-    pub reading_count: Option<i64>,
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum TimeInterval {
-    Minutes(i32),
-    Hours(i32),
-    Days(i32),
-    Weeks(i32),
-}
-
-impl TimeInterval {
-    pub fn to_interval_string(&self) -> String {
+    pub value: Option<f64>,
+}
+
+/// Bucketed points of each requested metric
+pub type TrendSeries = BTreeMap<Metric, Vec<TrendPoint>>;
+
+/// Bucketed series for several metrics of one sensor, sharing the same buckets
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MetricTrends {
+    pub sensor_mac: String,
+    pub series: TrendSeries,
+}
+
+/// Fewest readings in the last day for a sensor's observed interval to be used
+pub const MIN_OBSERVED_READINGS: i64 = 10;
+
+/// How far back reading intervals are observed
+const OBSERVED_INTERVAL_WINDOW_HOURS: i64 = 24;
+
+const SECONDS_PER_YEAR: i64 = 365 * 24 * 3600;
+
+/// Battery voltage at which a sensor is expected to stop transmitting
+pub const BATTERY_EMPTY_MV: f64 = 2000.0;
+
+/// Estimated battery life of one sensor
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatteryProjection {
+    pub sensor_mac: String,
+    /// Readings the projection is based on
+    pub sample_count: i64,
+    /// Fitted voltage now
+    pub current_mv: Option<f64>,
+    pub slope_mv_per_day: Option<f64>,
+    /// When the fitted voltage reaches [`BATTERY_EMPTY_MV`]
+    pub depletion_date: Option<DateTime<Utc>>,
+}
+
+/// Reading value a sensor threshold can be configured for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThresholdMetric {
+    /// Magnitude of the acceleration vector, used for vibration monitoring
+    Acceleration,
+}
+
+impl ThresholdMetric {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ThresholdMetric::Acceleration => "acceleration",
+        }
+    }
+}
+
+/// Side of an alert threshold on which the alert fires
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertComparator {
+    /// Fires while the value is greater than the threshold
+    Above,
+    /// Fires while the value is less than the threshold
+    Below,
+}
+
+impl AlertComparator {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AlertComparator::Above => "above",
+            AlertComparator::Below => "below",
+        }
+    }
+}
+
+/// Rule raising an alert when a metric of a sensor crosses a threshold
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct AlertRule {
+    pub id: i64,
+    pub sensor_mac: String,
+    pub metric: String,
+    pub comparator: String,
+    pub threshold: f64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Alert rule currently breached, with the reading that breaches it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct AlertHit {
+    pub alert_id: i64,
+    pub sensor_mac: String,
+    pub metric: String,
+    pub comparator: String,
+    pub threshold: f64,
+    pub value: f64,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Gateway forwarding readings, with the number of distinct sensors it heard
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, FromRow)]
+pub struct GatewayInfo {
+    pub gateway_mac: String,
+    pub sensor_count: i64,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Health of one gateway over a look-back window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct GatewayStats {
+    pub gateway_mac: String,
+    /// Distinct sensors the gateway heard
+    pub sensor_count: i64,
+    pub reading_count: i64,
+    /// Readings forwarded per hour, averaged over the whole window
+    pub readings_per_hour: f64,
+    pub avg_rssi: Option<f64>,
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+/// Allowed range for one metric of a sensor; either bound may be open
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct SensorThreshold {
+    pub id: i64,
+    pub sensor_mac: String,
+    pub metric: String,
+    pub min_value: Option<f64>,
+    pub max_value: Option<f64>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Human-readable name given to a sensor
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, FromRow)]
+pub struct SensorAlias {
+    pub sensor_mac: String,
+    pub alias: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Named set of sensors, such as the ones in one room
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct SensorGroup {
+    pub name: String,
+    pub sensor_macs: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Groups joined with their members; filter with `HAVING` on `g.name`
+const SENSOR_GROUP_SELECT: &str = r"
+    SELECT g.name::text AS name, g.created_at,
+           COALESCE(
+               array_agg(m.sensor_mac::text ORDER BY m.sensor_mac)
+                   FILTER (WHERE m.sensor_mac IS NOT NULL),
+               '{}'
+           ) AS sensor_macs
+    FROM sensor_groups g
+    LEFT JOIN sensor_group_members m ON m.group_name = g.name
+    GROUP BY g.name, g.created_at
+";
+
+/// Reading whose acceleration fell outside a configured threshold
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct VibrationAlert {
+    pub threshold_id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub acceleration: f64,
+    /// The bound that was crossed
+    pub limit_value: f64,
+    /// Whether the reading was above the maximum rather than below the minimum
+    pub above_max: bool,
+}
+
+/// Environmental measurement that can be compared across sensors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Metric {
+    Temperature,
+    Humidity,
+    Pressure,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 3] = [Metric::Temperature, Metric::Humidity, Metric::Pressure];
+
+    pub fn column_name(self) -> &'static str {
         match self {
-            TimeInterval::Minutes(minutes) => format!("{minutes} minutes"),
-            TimeInterval::Hours(hours) => format!("{hours} hours"),
-            TimeInterval::Days(days) => format!("{days} days"),
-            TimeInterval::Weeks(weeks) => format!("{weeks} weeks"),
+            Metric::Temperature => "temperature",
+            Metric::Humidity => "humidity",
+            Metric::Pressure => "pressure",
         }
     }
 }
+
+/// How often the periodic retention cleanup runs on databases without
+/// TimescaleDB
+pub const RETENTION_CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How many days of readings are kept and what enforces it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    pub days: i32,
+    pub enforced_by: RetentionMechanism,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionMechanism {
+    /// A TimescaleDB retention policy dropping old chunks
+    TimescaleDb,
+    /// A task in this process deleting old rows periodically
+    PeriodicCleanup,
+}
+
+/// Periodic cleanup started by [`PostgresStore::set_retention_policy`]
+#[derive(Debug)]
+struct RetentionCleanup {
+    days: i32,
+    task: JoinHandle<()>,
+}
+
+/// The running cleanup, shared by every clone of a store
+type SharedRetentionCleanup = Arc<Mutex<Option<RetentionCleanup>>>;
+
+/// Direction readings are sorted by time in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Order {
+    /// Oldest first
+    Asc,
+    /// Newest first
+    #[default]
+    Desc,
+}
+
+impl Order {
+    const fn as_sql(self) -> &'static str {
+        match self {
+            Self::Asc => "ASC",
+            Self::Desc => "DESC",
+        }
+    }
+}
+
+/// How many readings per sensor an active sensor listing returns
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SensorDedup {
+    /// Only the most recent reading, whichever gateway heard it
+    #[default]
+    Sensor,
+    /// The most recent reading from each gateway that heard the sensor
+    SensorGateway,
+}
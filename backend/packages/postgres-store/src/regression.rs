@@ -0,0 +1,108 @@
+//! Least-squares fitting used for projections
+
+/// Sample to fit as `(x, y)`
+pub type Point = (f64, f64);
+
+/// Straight line `y = slope * x + intercept`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinearFit {
+    pub slope: f64,
+    pub intercept: f64,
+}
+
+impl LinearFit {
+    /// Value of the line at `x`
+    pub fn value_at(&self, x: f64) -> f64 {
+        self.slope.mul_add(x, self.intercept)
+    }
+
+    /// The `x` at which the line reaches `y`, or `None` for a flat line
+    pub fn solve_for(&self, y: f64) -> Option<f64> {
+        if self.slope == 0.0 {
+            return None;
+        }
+        Some((y - self.intercept) / self.slope)
+    }
+}
+
+/// Ordinary least-squares fit of `points` given as `(x, y)` pairs.
+///
+/// Returns `None` when there are fewer than two points or all points share
+/// the same `x`, as no single line fits them.
+pub fn linear_regression(points: &[Point]) -> Option<LinearFit> {
+    if points.len() < 2 {
+        return None;
+    }
+
+    let count = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / count;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / count;
+
+    let (covariance, variance) =
+        points
+            .iter()
+            .fold((0.0, 0.0), |(covariance, variance), (x, y)| {
+                let dx = x - mean_x;
+                (dx.mul_add(y - mean_y, covariance), dx.mul_add(dx, variance))
+            });
+
+    if variance == 0.0 {
+        return None;
+    }
+
+    let slope = covariance / variance;
+    Some(LinearFit {
+        slope,
+        intercept: slope.mul_add(-mean_x, mean_y),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "Expected {actual} to equal {expected}"
+        );
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_exact_line() {
+        let points = [(0.0, 3000.0), (1.0, 2990.0), (2.0, 2980.0), (3.0, 2970.0)];
+        let fit = linear_regression(&points).expect("fit");
+
+        assert_close(fit.slope, -10.0);
+        assert_close(fit.intercept, 3000.0);
+        assert_close(fit.value_at(10.0), 2900.0);
+        assert_close(fit.solve_for(2000.0).expect("crossing"), 100.0);
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_noisy_points() {
+        let points = [(0.0, 1.0), (1.0, 3.0), (2.0, 2.0), (3.0, 4.0)];
+        let fit = linear_regression(&points).expect("fit");
+
+        assert_close(fit.slope, 0.8);
+        assert_close(fit.intercept, 1.3);
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_flat_line_never_crosses() {
+        let fit = linear_regression(&[(0.0, 3000.0), (5.0, 3000.0)]).expect("fit");
+
+        assert_close(fit.slope, 0.0);
+        assert_eq!(fit.solve_for(2000.0), None);
+    }
+
+    #[test]
+    fn test_degenerate_input() {
+        assert_eq!(linear_regression(&[]), None);
+        assert_eq!(linear_regression(&[(1.0, 2.0)]), None);
+        assert_eq!(linear_regression(&[(1.0, 2.0), (1.0, 3.0)]), None);
+    }
+}
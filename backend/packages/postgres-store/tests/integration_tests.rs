@@ -1,10 +1,23 @@
 use chrono::{
     DateTime,
     Duration,
+    DurationRound,
     Utc,
 };
 use postgres_store::{
+    AlertComparator,
+    BatchInsertFailed,
     Event,
+    HistoryCursor,
+    Metric,
+    Order,
+    PageCursor,
+    PoolConfig,
+    PostgresStore,
+    QueryTimeout,
+    RetentionMechanism,
+    RetentionPolicy,
+    ThresholdMetric,
     TimeInterval,
 };
 use sqlx::Row;
@@ -173,7 +186,7 @@ async fn test_historical_data() {
 
     let history = test_db
         .store
-        .get_historical_data(mac, Some(start), Some(end), Some(10))
+        .get_historical_data(mac, Some(start), Some(end), Some(10), None, Order::Desc)
         .await;
     assert!(
         history.is_ok(),
@@ -251,7 +264,13 @@ async fn test_time_bucketing() {
 
     let bucketed = test_db
         .store
-        .get_time_bucketed_data("AA:BB:CC:DD:EE:01", &TimeInterval::Hours(1), start, end)
+        .get_time_bucketed_data(
+            "AA:BB:CC:DD:EE:01",
+            &TimeInterval::Hours(1),
+            start,
+            end,
+            "UTC",
+        )
         .await;
 
     assert!(
@@ -349,6 +368,64 @@ async fn test_sensor_statistics() {
         .expect("Failed to cleanup test database");
 }
 
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_sensor_statistics_stddev() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let now = Utc::now();
+    let mac = "AA:BB:CC:DD:EE:01";
+    let single = "AA:BB:CC:DD:EE:02";
+
+    for (minutes_ago, temperature, pressure) in [
+        (40, 20.0, 1000.0),
+        (30, 22.0, 1010.0),
+        (20, 24.0, 1000.0),
+        (10, 26.0, 1010.0),
+    ] {
+        let mut event = create_test_event(mac, now - Duration::minutes(minutes_ago));
+        event.temperature = temperature;
+        event.pressure = pressure;
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+    test_db
+        .store
+        .insert_event(&create_test_event(single, now - Duration::minutes(5)))
+        .await
+        .expect("Failed to insert event");
+
+    let stats = test_db
+        .store
+        .get_sensor_statistics(mac, 1)
+        .await
+        .expect("Failed to get sensor statistics");
+    // Squared deviations from the 23 °C mean add up to 20 over 3 degrees of
+    // freedom; the pressures alternate 5 hPa around their mean
+    let expected_temperature = (20.0_f64 / 3.0).sqrt();
+    let expected_pressure = (100.0_f64 / 3.0).sqrt();
+    assert!((stats.stddev_temperature - expected_temperature).abs() < 1e-9);
+    assert!((stats.stddev_pressure - expected_pressure).abs() < 1e-9);
+    assert!(stats.stddev_humidity.abs() < 1e-9);
+
+    let stats = test_db
+        .store
+        .get_sensor_statistics(single, 1)
+        .await
+        .expect("Failed to get sensor statistics");
+    assert_eq!(stats.reading_count, 1);
+    assert!(stats.stddev_temperature.abs() < f64::EPSILON);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
 #[tokio::test]
 async fn test_storage_stats() {
     let test_db = TestDatabase::new()
@@ -375,6 +452,69 @@ async fn test_storage_stats() {
         .expect("Failed to cleanup test database");
 }
 
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_compression_reports_real_ratio() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let start = Utc::now() - Duration::days(30);
+    let events: Vec<_> = (0..2_000)
+        .map(|minute| create_test_event(mac, start + Duration::minutes(minute)))
+        .collect();
+    test_db
+        .store
+        .insert_events(&events)
+        .await
+        .expect("Failed to insert events");
+
+    let has_timescaledb: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM pg_extension WHERE extname = 'timescaledb')",
+    )
+    .fetch_one(&test_db.store.pool)
+    .await
+    .expect("Failed to check for TimescaleDB");
+
+    assert!(test_db.store.enable_compression(0).await.is_err());
+    let enabled = test_db
+        .store
+        .enable_compression(7)
+        .await
+        .expect("Failed to enable compression");
+    assert_eq!(enabled, has_timescaledb);
+
+    if has_timescaledb {
+        sqlx::query(
+            "SELECT compress_chunk(chunk) FROM show_chunks('sensor_data', older_than => INTERVAL \
+             '7 days') chunk",
+        )
+        .execute(&test_db.store.pool)
+        .await
+        .expect("Failed to compress chunks");
+    }
+
+    let stats = test_db
+        .store
+        .get_storage_stats()
+        .await
+        .expect("Failed to get storage stats");
+    let ratio = stats.compression_ratio.expect("compression_ratio is set");
+    if has_timescaledb {
+        assert!(ratio > 1.0, "compression ratio {ratio}");
+        assert!(stats.raw_size_mb > stats.compressed_size_mb);
+    } else {
+        assert!((ratio - 1.0).abs() < f64::EPSILON);
+        assert_eq!(stats.raw_size_mb, stats.compressed_size_mb);
+    }
+    assert_eq!(stats.row_count, Some(2_000));
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
 #[tokio::test]
 async fn test_storage_estimation() {
     let test_db = TestDatabase::new()
@@ -427,9 +567,19 @@ async fn test_growth_statistics() {
     );
 
     let stats = stats.unwrap();
-    assert!(stats.period_days.is_some());
-    assert!(stats.readings_added.is_some());
+    assert_eq!(stats.period_days, Some(30));
+    assert_eq!(stats.readings_added, Some(1));
     assert!(stats.readings_per_day.is_some());
+    let growth_mb = stats.storage_growth_mb.expect("storage growth");
+    let storage_mb = test_db
+        .store
+        .get_storage_stats()
+        .await
+        .expect("Failed to get storage stats")
+        .compressed_size_mb
+        .expect("storage size");
+    assert!(growth_mb > 0.0 && growth_mb <= storage_mb);
+    assert!(stats.estimated_yearly_growth_gb.is_some());
 
     test_db
         .cleanup()
@@ -474,7 +624,7 @@ async fn test_temperature_trend() {
 
     let trend = test_db
         .store
-        .get_temperature_trend("AA:BB:CC:DD:EE:01", 2)
+        .get_temperature_trend("AA:BB:CC:DD:EE:01", 2, &TimeInterval::Minutes(15))
         .await;
     assert!(
         trend.is_ok(),
@@ -560,6 +710,159 @@ async fn test_sensor_health_metrics() {
         .expect("Failed to cleanup test database");
 }
 
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_historical_data_cursor_pagination() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to create test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let base = Utc::now() - Duration::hours(1);
+
+    // Pairs of readings share a timestamp, so pages have to break ties by
+    // sequence number
+    for i in 0..250 {
+        let mut event = create_test_event(mac, base + Duration::seconds(i / 2));
+        event.measurement_sequence_number = i;
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let start = Some(base - Duration::minutes(1));
+    let end = Some(Utc::now());
+    let mut older = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = test_db
+            .store
+            .get_historical_data(mac, start, end, Some(100), cursor, Order::Desc)
+            .await
+            .expect("Failed to get history page");
+        older.extend(page.iter().map(|event| event.measurement_sequence_number));
+        match page.last() {
+            Some(last) if page.len() == 100 => {
+                cursor = Some(PageCursor::Before(HistoryCursor::of(last)));
+            }
+            _ => break,
+        }
+    }
+    assert_eq!(older, (0..250).rev().collect::<Vec<_>>());
+
+    // Walking back up from the oldest reading returns the newer ones in the
+    // same newest-first order
+    let oldest = test_db
+        .store
+        .get_historical_data(
+            mac,
+            start,
+            end,
+            Some(1),
+            Some(PageCursor::After(HistoryCursor {
+                timestamp: base - Duration::seconds(1),
+                measurement_sequence_number: 0,
+            })),
+            Order::Desc,
+        )
+        .await
+        .expect("Failed to get oldest reading");
+    let oldest = oldest.first().expect("Oldest reading");
+    assert_eq!(oldest.measurement_sequence_number, 0);
+
+    let newer = test_db
+        .store
+        .get_historical_data(
+            mac,
+            start,
+            end,
+            Some(100),
+            Some(PageCursor::After(HistoryCursor::of(oldest))),
+            Order::Desc,
+        )
+        .await
+        .expect("Failed to get newer page");
+    let newer: Vec<i64> = newer
+        .iter()
+        .map(|event| event.measurement_sequence_number)
+        .collect();
+    assert_eq!(newer, (1..=100).rev().collect::<Vec<_>>());
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_historical_data_order_decides_kept_rows() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let now = Utc::now();
+    for sequence in 0..5 {
+        let mut event = create_test_event(mac, now - Duration::minutes(50 - sequence * 10));
+        event.measurement_sequence_number = sequence;
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let store = &test_db.store;
+    let sequences = |order| async move {
+        store
+            .get_historical_data(
+                mac,
+                Some(now - Duration::hours(1)),
+                Some(now),
+                Some(3),
+                None,
+                order,
+            )
+            .await
+            .expect("Failed to get historical data")
+            .iter()
+            .map(|event| event.measurement_sequence_number)
+            .collect::<Vec<_>>()
+    };
+    // Newest first keeps the latest readings, oldest first the earliest
+    assert_eq!(sequences(Order::Desc).await, vec![4, 3, 2]);
+    assert_eq!(sequences(Order::Asc).await, vec![0, 1, 2]);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[test]
+fn test_page_cursor_round_trip() {
+    let position = HistoryCursor {
+        timestamp: DateTime::from_timestamp_micros(Utc::now().timestamp_micros())
+            .unwrap_or_default(),
+        measurement_sequence_number: 42,
+    };
+
+    for cursor in [PageCursor::Before(position), PageCursor::After(position)] {
+        let encoded = cursor.encode();
+        assert!(encoded
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert_eq!(PageCursor::decode(&encoded), Some(cursor));
+    }
+    assert_ne!(
+        PageCursor::Before(position).encode(),
+        PageCursor::After(position).encode()
+    );
+    assert_eq!(PageCursor::decode("not a cursor"), None);
+    assert_eq!(PageCursor::decode(""), None);
+}
+
 #[tokio::test]
 async fn test_error_handling() {
     let test_db = TestDatabase::new()
@@ -580,6 +883,8 @@ async fn test_error_handling() {
             Some(now - Duration::hours(1)),
             Some(now),
             Some(10),
+            None,
+            Order::Desc,
         )
         .await;
     assert!(history.is_ok());
@@ -591,3 +896,1798 @@ async fn test_error_handling() {
         .await
         .expect("Failed to cleanup test database");
 }
+
+#[tokio::test]
+async fn test_sensor_correlation() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+
+    let now = Utc::now();
+    let start = now - Duration::hours(12);
+
+    // The second sensor tracks the first with a scale, offset and a little noise
+    for hour in 0..8_i32 {
+        let timestamp = start + Duration::hours(hour.into()) + Duration::minutes(5);
+        let mut shower = create_test_event("AA:BB:CC:DD:EE:01", timestamp);
+        shower.humidity = 40.0 + f64::from(hour) * 5.0;
+        let mut bathroom = create_test_event("AA:BB:CC:DD:EE:02", timestamp);
+        bathroom.humidity = 30.0 + f64::from(hour) * 4.0 + f64::from(hour % 2) * 0.5;
+
+        for event in [&shower, &bathroom] {
+            test_db
+                .store
+                .insert_event(event)
+                .await
+                .expect("Failed to insert event");
+        }
+    }
+
+    // A bucket only one sensor reported must be ignored
+    let mut lonely = create_test_event("AA:BB:CC:DD:EE:01", start + Duration::hours(10));
+    lonely.humidity = 5.0;
+    test_db
+        .store
+        .insert_event(&lonely)
+        .await
+        .expect("Failed to insert event");
+
+    let correlation = test_db
+        .store
+        .get_sensor_correlation(
+            "AA:BB:CC:DD:EE:01",
+            "AA:BB:CC:DD:EE:02",
+            Metric::Humidity,
+            start,
+            now,
+            &TimeInterval::Hours(1),
+        )
+        .await
+        .expect("Failed to compute correlation");
+
+    assert_eq!(correlation.sample_count, 8);
+    let coefficient = correlation.coefficient.expect("Expected a coefficient");
+    assert!(
+        coefficient > 0.99,
+        "Expected strong correlation, got {coefficient}"
+    );
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+async fn test_count_readings() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+
+    let now = Utc::now();
+    let start = now - Duration::hours(3);
+    let end = now - Duration::hours(1);
+
+    // Four readings inside the range, two outside it and one from another sensor
+    for minutes_ago in [170, 150, 120, 61, 200, 30] {
+        let event = create_test_event("AA:BB:CC:DD:EE:01", now - Duration::minutes(minutes_ago));
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+    let other = create_test_event("AA:BB:CC:DD:EE:02", now - Duration::minutes(120));
+    test_db
+        .store
+        .insert_event(&other)
+        .await
+        .expect("Failed to insert event");
+
+    let count = test_db
+        .store
+        .count_readings("AA:BB:CC:DD:EE:01", start, end)
+        .await
+        .expect("Failed to count readings");
+    assert_eq!(count, 4);
+
+    let count = test_db
+        .store
+        .count_readings("AA:BB:CC:DD:EE:03", start, end)
+        .await
+        .expect("Failed to count readings");
+    assert_eq!(count, 0);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+async fn test_bucket_sums_rederive_overall_average() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+
+    let now = Utc::now();
+    let hour_start = now
+        .duration_trunc(Duration::hours(1))
+        .expect("Failed to truncate to hour");
+
+    // Three readings in one bucket and a single reading in the next, so the
+    // average of the bucket averages differs from the true average
+    let readings = [
+        (hour_start - Duration::minutes(110), 20.0),
+        (hour_start - Duration::minutes(100), 21.0),
+        (hour_start - Duration::minutes(90), 22.0),
+        (hour_start - Duration::minutes(30), 30.0),
+    ];
+    for (timestamp, temperature) in readings {
+        let mut event = create_test_event("AA:BB:CC:DD:EE:01", timestamp);
+        event.temperature = temperature;
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let buckets = test_db
+        .store
+        .get_time_bucketed_data(
+            "AA:BB:CC:DD:EE:01",
+            &TimeInterval::Hours(1),
+            hour_start - Duration::hours(2),
+            hour_start,
+            "UTC",
+        )
+        .await
+        .expect("Failed to get bucketed data");
+    assert_eq!(buckets.len(), 2);
+
+    let total_count: i64 = buckets
+        .iter()
+        .map(|bucket| bucket.reading_count.expect("reading_count is always set"))
+        .sum();
+    let total_sum: f64 = buckets
+        .iter()
+        .map(|bucket| bucket.sum_temperature.expect("sum_temperature is set"))
+        .sum();
+    #[allow(clippy::cast_precision_loss)]
+    let rederived = total_sum / total_count as f64;
+
+    let expected = readings
+        .iter()
+        .map(|(_, temperature)| temperature)
+        .sum::<f64>()
+        / 4.0;
+    assert_eq!(total_count, 4);
+    assert!(
+        (rederived - expected).abs() < 1e-9,
+        "{rederived} != {expected}"
+    );
+
+    let average_of_averages = buckets
+        .iter()
+        .filter_map(|bucket| bucket.avg_temperature)
+        .sum::<f64>()
+        / 2.0;
+    assert!((average_of_averages - expected).abs() > 1.0);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+async fn test_bucket_percentiles_expose_spikes() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+
+    let mac = "AA:BB:CC:DD:EE:01";
+    let hour_start = Utc::now()
+        .duration_trunc(Duration::hours(1))
+        .expect("Failed to truncate to hour");
+
+    // Two spikes among twenty readings pull the average up a little but
+    // dominate the top of the distribution
+    for minute in 0..20 {
+        let mut event = create_test_event(mac, hour_start - Duration::minutes(minute + 1));
+        event.temperature = if minute < 2 { 40.0 } else { 20.0 };
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let buckets = test_db
+        .store
+        .get_time_bucketed_data(
+            mac,
+            &TimeInterval::Hours(1),
+            hour_start - Duration::hours(1),
+            hour_start,
+            "UTC",
+        )
+        .await
+        .expect("Failed to get bucketed data");
+    assert_eq!(buckets.len(), 1);
+
+    let bucket = buckets.first().expect("one bucket");
+    let average = bucket.avg_temperature.expect("avg_temperature is set");
+    let p50 = bucket.p50_temperature.expect("p50_temperature is set");
+    let p95 = bucket.p95_temperature.expect("p95_temperature is set");
+    assert!((average - 22.0).abs() < 1e-9, "{average}");
+    assert!((p50 - 20.0).abs() < 1e-9, "{p50}");
+    assert!(p95 > average, "{p95} <= {average}");
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_bucket_first_and_last_temperature() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+
+    let mac = "AA:BB:CC:DD:EE:01";
+    let hour_start = Utc::now()
+        .duration_trunc(Duration::hours(1))
+        .expect("Failed to truncate to hour");
+
+    // Inserted out of time order, so only the timestamps can tell which
+    // reading came first
+    for (minutes_ago, temperature) in [(70, 19.0), (110, 18.0), (10, 21.0), (50, 20.0)] {
+        let mut event = create_test_event(mac, hour_start - Duration::minutes(minutes_ago));
+        event.temperature = temperature;
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let buckets = test_db
+        .store
+        .get_time_bucketed_data(
+            mac,
+            &TimeInterval::Hours(1),
+            hour_start - Duration::hours(2),
+            hour_start,
+            "UTC",
+        )
+        .await
+        .expect("Failed to get bucketed data");
+    let first_last: Vec<_> = buckets
+        .iter()
+        .map(|bucket| (bucket.first_temperature, bucket.last_temperature))
+        .collect();
+    assert_eq!(
+        first_last,
+        vec![(Some(18.0), Some(19.0)), (Some(20.0), Some(21.0))]
+    );
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+async fn test_query_timeout_releases_connection() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+
+    let store = PostgresStore::new_with_query_timeout(
+        &test_db.database_url,
+        std::time::Duration::from_millis(200),
+    )
+    .await
+    .expect("Failed to connect");
+
+    let started = std::time::Instant::now();
+    let result = store
+        .timed("slow query", async {
+            sqlx::query("SELECT pg_sleep(10)")
+                .execute(&store.pool)
+                .await?;
+            Ok(())
+        })
+        .await;
+    let error = result.expect_err("Slow query should time out");
+    let timeout = error
+        .downcast_ref::<QueryTimeout>()
+        .expect("Error should be a QueryTimeout");
+    assert_eq!(timeout.operation, "slow query");
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+    // Every connection must be idle again long before pg_sleep would finish
+    let mut idle = false;
+    for _ in 0..40 {
+        if store.pool.num_idle() == store.pool.size() as usize {
+            idle = true;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    assert!(idle, "Timed out connection was not returned to the pool");
+
+    let count = store
+        .count_readings(
+            "AA:BB:CC:DD:EE:01",
+            Utc::now() - Duration::hours(1),
+            Utc::now(),
+        )
+        .await
+        .expect("Store should be usable after a timeout");
+    assert_eq!(count, 0);
+
+    store.pool.close().await;
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_single_connection_pool_serializes_queries() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+
+    let store = PostgresStore::new_with_options(
+        &test_db.database_url,
+        PoolConfig {
+            max_connections: 1,
+            acquire_timeout: std::time::Duration::from_secs(5),
+            ..PoolConfig::default()
+        },
+    )
+    .await
+    .expect("Failed to connect");
+    assert_eq!(store.pool.options().get_max_connections(), 1);
+
+    let sleep = || sqlx::query("SELECT pg_sleep(0.3)").execute(&store.pool);
+    let started = std::time::Instant::now();
+    let (first, second) = tokio::join!(sleep(), sleep());
+    first.expect("First query failed");
+    second.expect("Second query failed");
+    assert!(
+        started.elapsed() >= std::time::Duration::from_millis(600),
+        "Queries ran concurrently on a single connection: {:?}",
+        started.elapsed()
+    );
+    assert_eq!(store.pool.size(), 1);
+
+    store.pool.close().await;
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+async fn test_pool_survives_dropped_idle_connection() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+
+    let pool_config = PoolConfig {
+        idle_timeout: std::time::Duration::from_secs(30),
+        max_lifetime: std::time::Duration::from_secs(120),
+        ..PoolConfig::default()
+    };
+    let store = PostgresStore::new_with_pool_config(
+        &test_db.database_url,
+        std::time::Duration::from_secs(5),
+        pool_config,
+    )
+    .await
+    .expect("Failed to connect");
+
+    let options = store.pool.options();
+    assert!(options.get_test_before_acquire());
+    assert_eq!(options.get_idle_timeout(), Some(pool_config.idle_timeout));
+    assert_eq!(options.get_max_lifetime(), Some(pool_config.max_lifetime));
+
+    // Drop the idle connection behind the pool's back, the way a NAT gateway
+    // forgets a quiet connection
+    let pid: i32 = sqlx::query_scalar("SELECT pg_backend_pid()")
+        .fetch_one(&store.pool)
+        .await
+        .expect("Failed to read backend pid");
+    sqlx::query("SELECT pg_terminate_backend($1)")
+        .bind(pid)
+        .execute(&test_db.store.pool)
+        .await
+        .expect("Failed to terminate backend");
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    let new_pid: i32 = sqlx::query_scalar("SELECT pg_backend_pid()")
+        .fetch_one(&store.pool)
+        .await
+        .expect("Query after the connection was dropped should succeed");
+    assert_ne!(new_pid, pid);
+
+    let keep_alive = store.spawn_keep_alive(std::time::Duration::from_millis(50));
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    assert!(!keep_alive.is_finished());
+    keep_alive.abort();
+
+    store.pool.close().await;
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+async fn test_list_sensor_macs() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+
+    let macs = test_db
+        .store
+        .list_sensor_macs()
+        .await
+        .expect("Failed to list sensor MACs");
+    assert!(macs.is_empty());
+
+    let now = Utc::now();
+    for (mac, minutes_ago) in [
+        ("AA:BB:CC:DD:EE:02", 5),
+        ("AA:BB:CC:DD:EE:01", 10),
+        ("AA:BB:CC:DD:EE:02", 15),
+        ("AA:BB:CC:DD:EE:03", 20),
+        ("AA:BB:CC:DD:EE:01", 25),
+    ] {
+        let event = create_test_event(mac, now - Duration::minutes(minutes_ago));
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let macs = test_db
+        .store
+        .list_sensor_macs()
+        .await
+        .expect("Failed to list sensor MACs");
+    assert_eq!(
+        macs,
+        vec![
+            "AA:BB:CC:DD:EE:01",
+            "AA:BB:CC:DD:EE:02",
+            "AA:BB:CC:DD:EE:03"
+        ]
+    );
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_latest_per_metric_with_intermittent_humidity() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+
+    let latest = test_db
+        .store
+        .get_latest_per_metric(mac)
+        .await
+        .expect("Failed to get latest per metric");
+    assert!(latest.is_empty());
+
+    // Humidity is only reported by some readings
+    sqlx::query("ALTER TABLE sensor_data ALTER COLUMN humidity DROP NOT NULL")
+        .execute(&test_db.store.pool)
+        .await
+        .expect("Failed to make humidity nullable");
+
+    let now = Utc::now()
+        .duration_trunc(Duration::seconds(1))
+        .expect("Failed to truncate timestamp");
+    for (minutes_ago, temperature, humidity) in [
+        (30, 20.0, Some(40.0)),
+        (20, 21.0, Some(45.0)),
+        (10, 22.0, None),
+        (0, 23.0, None),
+    ] {
+        let mut event = create_test_event(mac, now - Duration::minutes(minutes_ago));
+        event.temperature = temperature;
+        event.humidity = humidity.unwrap_or_default();
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+        if humidity.is_none() {
+            sqlx::query("UPDATE sensor_data SET humidity = NULL WHERE timestamp = $1")
+                .bind(event.timestamp)
+                .execute(&test_db.store.pool)
+                .await
+                .expect("Failed to clear humidity");
+        }
+    }
+
+    let latest = test_db
+        .store
+        .get_latest_per_metric(mac)
+        .await
+        .expect("Failed to get latest per metric");
+
+    let temperature = latest.temperature.expect("Temperature reading");
+    assert!((temperature.value - 23.0).abs() < f64::EPSILON);
+    assert_eq!(temperature.timestamp, now);
+
+    let humidity = latest.humidity.expect("Humidity reading");
+    assert!((humidity.value - 45.0).abs() < f64::EPSILON);
+    assert_eq!(humidity.timestamp, now - Duration::minutes(20));
+
+    let pressure = latest.pressure.expect("Pressure reading");
+    assert_eq!(pressure.timestamp, now);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_reads_use_read_replica() {
+    let primary = TestDatabase::new()
+        .await
+        .expect("Failed to setup primary database");
+    let replica = TestDatabase::new()
+        .await
+        .expect("Failed to setup replica database");
+
+    let store = PostgresStore::new(&primary.database_url)
+        .await
+        .expect("Failed to connect to primary")
+        .with_read_replica(&replica.database_url)
+        .await
+        .expect("Failed to connect to replica");
+
+    let mac = "AA:BB:CC:DD:EE:01";
+    let now = Utc::now();
+
+    // Writes go to the primary
+    let mut written = create_test_event(mac, now - Duration::minutes(5));
+    written.temperature = 10.0;
+    store
+        .insert_event(&written)
+        .await
+        .expect("Failed to insert event");
+
+    // Rows only present on the replica stand in for replicated data
+    let mut replicated = create_test_event(mac, now - Duration::minutes(10));
+    replicated.temperature = 30.0;
+    replica
+        .store
+        .insert_event(&replicated)
+        .await
+        .expect("Failed to insert replica event");
+
+    let history = store
+        .get_historical_data(
+            mac,
+            Some(now - Duration::hours(1)),
+            Some(now),
+            None,
+            None,
+            Order::Desc,
+        )
+        .await
+        .expect("Failed to get historical data");
+    assert_eq!(history.len(), 1);
+    assert!((history[0].temperature - 30.0).abs() < f64::EPSILON);
+
+    let count = store
+        .count_readings(mac, now - Duration::hours(1), now)
+        .await
+        .expect("Failed to count readings");
+    assert_eq!(count, 1);
+
+    let latest = store
+        .get_latest_reading(mac)
+        .await
+        .expect("Failed to get latest reading")
+        .expect("Latest reading");
+    assert!((latest.temperature - 10.0).abs() < f64::EPSILON);
+
+    store.pool.close().await;
+    store.read_pool().close().await;
+    primary.cleanup().await.expect("Failed to cleanup primary");
+    replica.cleanup().await.expect("Failed to cleanup replica");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_reads_fall_back_to_primary() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+
+    let mac = "AA:BB:CC:DD:EE:01";
+    let now = Utc::now();
+    test_db
+        .store
+        .insert_event(&create_test_event(mac, now - Duration::minutes(5)))
+        .await
+        .expect("Failed to insert event");
+
+    let count = test_db
+        .store
+        .count_readings(mac, now - Duration::hours(1), now)
+        .await
+        .expect("Failed to count readings");
+    assert_eq!(count, 1);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_continuous_aggregates() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let hour_start = Utc::now()
+        .duration_trunc(Duration::hours(1))
+        .expect("Failed to truncate timestamp");
+
+    for (timestamp, gateway) in [
+        (hour_start - Duration::minutes(110), "FF:FF:FF:FF:FF:01"),
+        (hour_start - Duration::minutes(100), "FF:FF:FF:FF:FF:02"),
+        (hour_start - Duration::minutes(50), "FF:FF:FF:FF:FF:01"),
+    ] {
+        let mut event = create_test_event(mac, timestamp);
+        event.gateway_mac = gateway.to_string();
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let has_timescaledb: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM pg_extension WHERE extname = 'timescaledb')",
+    )
+    .fetch_one(&test_db.store.pool)
+    .await
+    .expect("Failed to check for TimescaleDB");
+
+    let created = test_db
+        .store
+        .ensure_continuous_aggregates()
+        .await
+        .expect("Failed to ensure continuous aggregates");
+    assert_eq!(created, has_timescaledb);
+
+    // Same buckets whether served by the views or by raw bucketing
+    let hourly = test_db
+        .store
+        .get_hourly_aggregates(mac, hour_start - Duration::hours(2), hour_start, "UTC")
+        .await
+        .expect("Failed to get hourly aggregates");
+    let counts: Vec<_> = hourly.iter().map(|bucket| bucket.reading_count).collect();
+    assert_eq!(counts, vec![Some(2), Some(1)]);
+    assert_eq!(hourly[0].bucket, hour_start - Duration::hours(2));
+
+    if has_timescaledb {
+        for view in ["sensor_data_hourly", "sensor_data_daily"] {
+            let rows: i64 = sqlx::query_scalar(&format!(
+                "SELECT COUNT(*) FROM {view} WHERE sensor_mac = $1"
+            ))
+            .bind(mac)
+            .fetch_one(&test_db.store.pool)
+            .await
+            .expect("Failed to query continuous aggregate");
+            assert!(rows > 0, "{view} should return data");
+        }
+
+        // Safe to run again
+        assert!(test_db
+            .store
+            .ensure_continuous_aggregates()
+            .await
+            .expect("Failed to re-run ensure_continuous_aggregates"));
+    }
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[test]
+fn test_event_validate() {
+    let now = Utc::now();
+    assert_eq!(
+        create_test_event("AA:BB:CC:DD:EE:01", now).validate(),
+        Ok(())
+    );
+
+    let mut event = create_test_event("AA:BB:CC:DD:EE:01", now);
+    event.pressure = 1300.5;
+    let invalid = event.validate().unwrap_err();
+    assert_eq!(invalid.field, "pressure");
+    assert_eq!(
+        invalid.to_string(),
+        "pressure 1300.5 is outside the allowed range 300..=1300"
+    );
+
+    event.pressure = f64::NAN;
+    assert!(event.validate().is_err());
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_retention_policy_round_trip() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let now = Utc::now();
+    for timestamp in [now - Duration::days(40), now - Duration::hours(1)] {
+        test_db
+            .store
+            .insert_event(&create_test_event(mac, timestamp))
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let has_timescaledb: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM pg_extension WHERE extname = 'timescaledb')",
+    )
+    .fetch_one(&test_db.store.pool)
+    .await
+    .expect("Failed to check for TimescaleDB");
+    let enforced_by = if has_timescaledb {
+        RetentionMechanism::TimescaleDb
+    } else {
+        RetentionMechanism::PeriodicCleanup
+    };
+
+    assert_eq!(
+        test_db
+            .store
+            .get_retention_policy()
+            .await
+            .expect("Failed to get retention policy"),
+        None
+    );
+    assert!(test_db.store.set_retention_policy(0).await.is_err());
+
+    for days in [30, 60] {
+        let policy = RetentionPolicy { days, enforced_by };
+        assert_eq!(
+            test_db
+                .store
+                .set_retention_policy(days)
+                .await
+                .expect("Failed to set retention policy"),
+            policy
+        );
+        assert_eq!(
+            test_db
+                .store
+                .get_retention_policy()
+                .await
+                .expect("Failed to get retention policy"),
+            Some(policy)
+        );
+    }
+
+    if !has_timescaledb {
+        // The first cleanup runs as soon as the policy is set
+        test_db
+            .store
+            .set_retention_policy(30)
+            .await
+            .expect("Failed to set retention policy");
+        let mut remaining = i64::MAX;
+        for _ in 0..50 {
+            remaining = test_db
+                .store
+                .count_readings(mac, now - Duration::days(60), now)
+                .await
+                .expect("Failed to count readings");
+            if remaining == 1 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert_eq!(remaining, 1, "only the recent reading is kept");
+    }
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_insert_events_writes_batch_in_one_call() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let now = Utc::now();
+    let mut receiver = test_db.store.subscribe_to_events();
+
+    let events: Vec<_> = (0..500)
+        .map(|seconds_ago| create_test_event(mac, now - Duration::seconds(seconds_ago)))
+        .collect();
+    let inserted = test_db
+        .store
+        .insert_events(&events)
+        .await
+        .expect("Failed to insert events");
+    assert_eq!(inserted, 500);
+
+    let stored = test_db
+        .store
+        .count_readings(mac, now - Duration::hours(1), now + Duration::seconds(1))
+        .await
+        .expect("Failed to count readings");
+    assert_eq!(stored, 500);
+    for _ in 0..500 {
+        assert!(receiver.try_recv().is_ok(), "every reading is broadcast");
+    }
+    assert_eq!(
+        test_db.store.insert_events(&[]).await.expect("empty batch"),
+        0
+    );
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_latest_for_skips_unknown_sensors() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let now = Utc::now()
+        .duration_trunc(Duration::seconds(1))
+        .expect("Failed to truncate timestamp");
+
+    for (mac, minutes_ago) in [
+        ("AA:BB:CC:DD:EE:01", 10),
+        ("AA:BB:CC:DD:EE:01", 2),
+        ("AA:BB:CC:DD:EE:02", 5),
+        ("AA:BB:CC:DD:EE:03", 1),
+        ("AA:BB:CC:DD:EE:09", 1),
+    ] {
+        test_db
+            .store
+            .insert_event(&create_test_event(
+                mac,
+                now - Duration::minutes(minutes_ago),
+            ))
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let macs: Vec<String> = [
+        "AA:BB:CC:DD:EE:03",
+        "AA:BB:CC:DD:EE:01",
+        "AA:BB:CC:DD:EE:04",
+        "AA:BB:CC:DD:EE:02",
+        "AA:BB:CC:DD:EE:05",
+    ]
+    .iter()
+    .map(ToString::to_string)
+    .collect();
+    let latest = test_db
+        .store
+        .get_latest_for(&macs)
+        .await
+        .expect("Failed to get latest readings");
+
+    let found: Vec<_> = latest
+        .iter()
+        .map(|event| (event.sensor_mac.as_str(), event.timestamp))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("AA:BB:CC:DD:EE:01", now - Duration::minutes(2)),
+            ("AA:BB:CC:DD:EE:02", now - Duration::minutes(5)),
+            ("AA:BB:CC:DD:EE:03", now - Duration::minutes(1)),
+        ]
+    );
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_duplicate_event_stored_and_broadcast_once() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let now = Utc::now();
+    let mut receiver = test_db.store.subscribe_to_events();
+    let event = create_test_event(mac, now);
+
+    assert!(test_db
+        .store
+        .insert_event(&event)
+        .await
+        .expect("Failed to insert event"));
+    assert!(!test_db
+        .store
+        .insert_event(&event)
+        .await
+        .expect("Failed to insert duplicate event"));
+    assert_eq!(
+        test_db
+            .store
+            .insert_events(&[event.clone(), event])
+            .await
+            .expect("Failed to insert duplicate batch"),
+        0
+    );
+
+    let stored = test_db
+        .store
+        .count_readings(mac, now - Duration::hours(1), now + Duration::seconds(1))
+        .await
+        .expect("Failed to count readings");
+    assert_eq!(stored, 1);
+    assert!(receiver.try_recv().is_ok(), "the first insert is broadcast");
+    assert!(receiver.try_recv().is_err(), "duplicates are not broadcast");
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_inserts_by_another_process_are_broadcast() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let now = Utc::now()
+        .duration_trunc(Duration::seconds(1))
+        .expect("Failed to truncate timestamp");
+    // Same database, but its own broadcast channel, like the MQTT reader
+    let reader = PostgresStore::from_pool(test_db.store.pool.clone());
+    let mut relaying = PostgresStore::from_pool(test_db.store.pool.clone());
+    assert!(relaying
+        .relay_database_inserts()
+        .await
+        .expect("Failed to listen for inserts"));
+    let mut receiver = relaying.subscribe_to_events();
+
+    let event = create_test_event(mac, now - Duration::minutes(2));
+    assert!(reader
+        .insert_event(&event)
+        .await
+        .expect("Failed to insert event"));
+    assert!(!reader
+        .insert_event(&event)
+        .await
+        .expect("Failed to insert duplicate event"));
+    relaying
+        .insert_event(&create_test_event(mac, now - Duration::minutes(1)))
+        .await
+        .expect("Failed to insert event");
+
+    let wait = std::time::Duration::from_secs(5);
+    let relayed = tokio::time::timeout(wait, receiver.recv())
+        .await
+        .expect("reading relayed")
+        .expect("open channel");
+    assert_eq!(relayed.sensor_mac, mac);
+    assert_eq!(relayed.timestamp, event.timestamp);
+    let own = tokio::time::timeout(wait, receiver.recv())
+        .await
+        .expect("own insert relayed")
+        .expect("open channel");
+    assert_eq!(own.timestamp, now - Duration::minutes(1));
+    assert!(
+        receiver.try_recv().is_err(),
+        "duplicates and own inserts are not broadcast twice"
+    );
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_transactional_batch_rolls_back_on_rejected_reading() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let now = Utc::now();
+    let mut receiver = test_db.store.subscribe_to_events();
+
+    let mut events: Vec<_> = (1..=3)
+        .map(|minutes_ago| create_test_event(mac, now - Duration::minutes(minutes_ago)))
+        .collect();
+    if let Some(event) = events.get_mut(1) {
+        event.temperature = 150.0;
+    }
+
+    let error = test_db
+        .store
+        .insert_events_transactional(&events)
+        .await
+        .expect_err("out-of-range temperature is rejected");
+    let failed = error
+        .downcast_ref::<BatchInsertFailed>()
+        .expect("error names the rejected reading");
+    assert_eq!(failed.index, 1);
+
+    let store = &test_db.store;
+    let count = || store.count_readings(mac, now - Duration::hours(1), now);
+    assert_eq!(count().await.expect("Failed to count"), 0);
+    assert!(receiver.try_recv().is_err(), "nothing is broadcast");
+
+    events.remove(1);
+    let inserted = test_db
+        .store
+        .insert_events_transactional(&events)
+        .await
+        .expect("Failed to insert valid batch");
+    assert_eq!(inserted, 2);
+    assert_eq!(count().await.expect("Failed to count"), 2);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_import_events_reports_invalid_rows() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let now = Utc::now();
+
+    let mut events: Vec<_> = (0..6)
+        .map(|minutes_ago| create_test_event(mac, now - Duration::minutes(minutes_ago)))
+        .collect();
+    events[1].temperature = 150.0;
+    events[3].humidity = -1.0;
+    events[4].battery = 5000;
+
+    let summary = test_db
+        .store
+        .import_events(&events)
+        .await
+        .expect("Failed to import events");
+
+    assert_eq!(summary.imported, 3);
+    assert_eq!(
+        summary.skipped_with_reason,
+        vec![
+            (
+                1,
+                "temperature 150 is outside the allowed range -100..=100".to_string()
+            ),
+            (
+                3,
+                "humidity -1 is outside the allowed range 0..=100".to_string()
+            ),
+            (
+                4,
+                "battery 5000 is outside the allowed range 0..=4000".to_string()
+            ),
+        ]
+    );
+
+    let stored = test_db
+        .store
+        .count_readings(mac, now - Duration::hours(1), now)
+        .await
+        .expect("Failed to count readings");
+    assert_eq!(stored, 3);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_reading_forwarded_by_two_gateways_at_different_times_is_kept_twice() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let now = Utc::now();
+    let event = create_test_event(mac, now - Duration::minutes(1));
+    let mut forwarded_later = event.clone();
+    forwarded_later.gateway_mac = "FF:FF:FF:FF:FF:02".to_string();
+    forwarded_later.timestamp = event.timestamp + Duration::milliseconds(500);
+
+    for copy in [&event, &forwarded_later] {
+        assert!(test_db
+            .store
+            .insert_event(copy)
+            .await
+            .expect("Failed to insert event"));
+    }
+
+    let stored = test_db
+        .store
+        .count_readings(mac, now - Duration::hours(1), now)
+        .await
+        .expect("Failed to count readings");
+    assert_eq!(stored, 2, "only exact replays are dropped");
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_import_events_counts_duplicates() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let now = Utc::now();
+    let event = create_test_event(mac, now - Duration::minutes(1));
+    let mut forwarded_again = event.clone();
+    forwarded_again.gateway_mac = "FF:FF:FF:FF:FF:02".to_string();
+
+    let first = test_db
+        .store
+        .import_events(std::slice::from_ref(&event))
+        .await
+        .expect("Failed to import events");
+    assert_eq!((first.imported, first.duplicates), (1, 0));
+
+    let second = test_db
+        .store
+        .import_events(&[event, forwarded_again])
+        .await
+        .expect("Failed to import events");
+    assert_eq!((second.imported, second.duplicates), (0, 2));
+    assert!(second.skipped_with_reason.is_empty());
+
+    let stored = test_db
+        .store
+        .count_readings(mac, now - Duration::hours(1), now)
+        .await
+        .expect("Failed to count readings");
+    assert_eq!(stored, 1);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_dashboard_without_metadata_table() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    test_db
+        .store
+        .insert_event(&create_test_event(mac, Utc::now()))
+        .await
+        .expect("Failed to insert event");
+
+    let cards = test_db
+        .store
+        .get_dashboard()
+        .await
+        .expect("Failed to get dashboard");
+
+    assert_eq!(cards.len(), 1);
+    assert_eq!(cards[0].sensor_mac, mac);
+    assert_eq!(cards[0].name, None);
+    assert!(!cards[0].health.stale);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_vibration_alerts_flag_readings_outside_threshold() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let other_mac = "AA:BB:CC:DD:EE:02";
+    let now = Utc::now();
+
+    // Readings in mG: inside, above and below the configured range
+    for (minutes_ago, acceleration) in [(3, 1000.0), (2, 1500.0), (1, 500.0)] {
+        let mut event = create_test_event(mac, now - Duration::minutes(minutes_ago));
+        event.acceleration = acceleration;
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+    let mut unrelated = create_test_event(other_mac, now - Duration::minutes(1));
+    unrelated.acceleration = 5000.0;
+    test_db
+        .store
+        .insert_event(&unrelated)
+        .await
+        .expect("Failed to insert event");
+
+    let start = now - Duration::hours(1);
+    let alerts = test_db
+        .store
+        .get_vibration_alerts(mac, start, now)
+        .await
+        .expect("Failed to get vibration alerts");
+    assert!(alerts.is_empty(), "no threshold configured yet");
+
+    let threshold = test_db
+        .store
+        .create_threshold(
+            mac,
+            ThresholdMetric::Acceleration,
+            Some(900.0),
+            Some(1200.0),
+        )
+        .await
+        .expect("Failed to create threshold");
+    assert_eq!(threshold.sensor_mac, mac);
+    assert_eq!(threshold.metric, "acceleration");
+
+    let alerts = test_db
+        .store
+        .get_vibration_alerts(mac, start, now)
+        .await
+        .expect("Failed to get vibration alerts");
+
+    assert_eq!(alerts.len(), 2);
+    assert!(alerts
+        .iter()
+        .all(|alert| alert.threshold_id == threshold.id));
+    assert!((alerts[0].acceleration - 500.0).abs() < f64::EPSILON);
+    assert!(!alerts[0].above_max);
+    assert!((alerts[0].limit_value - 900.0).abs() < f64::EPSILON);
+    assert!((alerts[1].acceleration - 1500.0).abs() < f64::EPSILON);
+    assert!(alerts[1].above_max);
+    assert!((alerts[1].limit_value - 1200.0).abs() < f64::EPSILON);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+async fn test_list_and_delete_thresholds() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let store = &test_db.store;
+    let mac = "AA:BB:CC:DD:EE:01";
+
+    let first = store
+        .create_threshold(mac, ThresholdMetric::Acceleration, None, Some(1500.0))
+        .await
+        .expect("Failed to create threshold");
+    let second = store
+        .create_threshold(
+            mac,
+            ThresholdMetric::Acceleration,
+            Some(500.0),
+            Some(2000.0),
+        )
+        .await
+        .expect("Failed to create threshold");
+    store
+        .create_threshold(
+            "AA:BB:CC:DD:EE:02",
+            ThresholdMetric::Acceleration,
+            None,
+            Some(1500.0),
+        )
+        .await
+        .expect("Failed to create threshold");
+
+    let thresholds = store
+        .list_thresholds(mac)
+        .await
+        .expect("Failed to list thresholds");
+    assert_eq!(thresholds.len(), 2);
+    assert_eq!(thresholds[0].id, first.id);
+    assert_eq!(thresholds[1].id, second.id);
+
+    let deleted = store
+        .delete_threshold(first.id, Some("AA:BB:CC:DD:EE:02"))
+        .await
+        .expect("Failed to delete threshold");
+    assert!(deleted.is_none(), "threshold belongs to another sensor");
+
+    let deleted = store
+        .delete_threshold(first.id, Some(mac))
+        .await
+        .expect("Failed to delete threshold");
+    assert_eq!(deleted.map(|threshold| threshold.id), Some(first.id));
+
+    let deleted = store
+        .delete_threshold(first.id, None)
+        .await
+        .expect("Failed to delete threshold");
+    assert!(deleted.is_none(), "threshold was already deleted");
+
+    let thresholds = store
+        .list_thresholds(mac)
+        .await
+        .expect("Failed to list thresholds");
+    assert_eq!(thresholds.len(), 1);
+    assert_eq!(thresholds[0].id, second.id);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_evaluate_alerts_against_latest_reading() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let store = &test_db.store;
+    let mac = "AA:BB:CC:DD:EE:01";
+    let now = Utc::now();
+
+    for (minutes_ago, humidity) in [(10, 90.0), (1, 40.0)] {
+        let mut event = create_test_event(mac, now - Duration::minutes(minutes_ago));
+        event.humidity = humidity;
+        store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let damp = store
+        .create_alert(mac, Metric::Humidity, AlertComparator::Above, 80.0)
+        .await
+        .expect("Failed to create alert");
+    assert_eq!(damp.metric, "humidity");
+    assert_eq!(damp.comparator, "above");
+    let dry = store
+        .create_alert(mac, Metric::Humidity, AlertComparator::Below, 45.0)
+        .await
+        .expect("Failed to create alert");
+    store
+        .create_alert(
+            "AA:BB:CC:DD:EE:02",
+            Metric::Humidity,
+            AlertComparator::Below,
+            45.0,
+        )
+        .await
+        .expect("Failed to create alert");
+
+    let hits = store
+        .evaluate_alerts()
+        .await
+        .expect("Failed to evaluate alerts");
+    assert_eq!(hits.len(), 1, "only the latest reading counts");
+    let hit = hits.first().expect("one hit");
+    assert_eq!(hit.alert_id, dry.id);
+    assert_eq!(hit.sensor_mac, mac);
+    assert!((hit.value - 40.0).abs() < f64::EPSILON);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_set_and_overwrite_sensor_alias() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let store = &test_db.store;
+    let mac = "AA:BB:CC:DD:EE:01";
+
+    assert!(store
+        .get_sensor_alias(mac)
+        .await
+        .expect("Failed to get alias")
+        .is_none());
+
+    let first = store
+        .set_sensor_alias(mac, "Bedroom")
+        .await
+        .expect("Failed to set alias");
+    assert_eq!(first.alias, "Bedroom");
+    let second = store
+        .set_sensor_alias(mac, "Nursery")
+        .await
+        .expect("Failed to overwrite alias");
+    assert!(second.updated_at >= first.updated_at);
+
+    let alias = store
+        .get_sensor_alias(mac)
+        .await
+        .expect("Failed to get alias")
+        .expect("alias was set");
+    assert_eq!(alias, second);
+    assert_eq!(alias.alias, "Nursery");
+
+    let aliases = store
+        .list_sensor_aliases()
+        .await
+        .expect("Failed to list aliases");
+    assert_eq!(aliases.len(), 1);
+    assert_eq!(aliases.get(mac).map(String::as_str), Some("Nursery"));
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_sensor_group_crud_and_combined_data() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let store = &test_db.store;
+    let first = "AA:BB:CC:DD:EE:01";
+    let second = "AA:BB:CC:DD:EE:02";
+    let outsider = "AA:BB:CC:DD:EE:03";
+
+    let now = Utc::now();
+    for (mac, temperature) in [(first, 20.0), (second, 24.0), (outsider, 30.0)] {
+        let mut event = create_test_event(mac, now - Duration::minutes(5));
+        event.temperature = temperature;
+        store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let group = store
+        .create_sensor_group("living-room", &[first.to_string()])
+        .await
+        .expect("Failed to create group")
+        .expect("New group");
+    assert_eq!(group.sensor_macs, vec![first]);
+    assert!(store
+        .create_sensor_group("living-room", &[])
+        .await
+        .expect("Failed to create group")
+        .is_none());
+
+    let group = store
+        .add_group_member("living-room", second)
+        .await
+        .expect("Failed to add member")
+        .expect("Existing group");
+    assert_eq!(group.sensor_macs, vec![first, second]);
+    assert!(store
+        .add_group_member("cellar", second)
+        .await
+        .expect("Failed to add member")
+        .is_none());
+
+    let latest = store
+        .get_latest_for(&group.sensor_macs)
+        .await
+        .expect("Failed to get latest readings");
+    let macs: Vec<&str> = latest
+        .iter()
+        .map(|event| event.sensor_mac.as_str())
+        .collect();
+    assert_eq!(macs, vec![first, second]);
+
+    let buckets = store
+        .get_combined_bucketed_data(
+            &group.sensor_macs,
+            &TimeInterval::Days(1),
+            now - Duration::hours(1),
+            now,
+            "UTC",
+        )
+        .await
+        .expect("Failed to get combined data");
+    let total: i64 = buckets
+        .iter()
+        .filter_map(|bucket| bucket.reading_count)
+        .sum();
+    assert_eq!(total, 2, "only group members are combined");
+    let sum: f64 = buckets
+        .iter()
+        .filter_map(|bucket| bucket.sum_temperature)
+        .sum();
+    assert!((sum - 44.0).abs() < 1e-9, "Expected 44, got {sum}");
+
+    let group = store
+        .remove_group_member("living-room", first)
+        .await
+        .expect("Failed to remove member")
+        .expect("Existing group");
+    assert_eq!(group.sensor_macs, vec![second]);
+
+    let groups = store
+        .list_sensor_groups()
+        .await
+        .expect("Failed to list groups");
+    assert_eq!(groups, vec![group]);
+
+    assert!(store
+        .delete_sensor_group("living-room")
+        .await
+        .expect("Failed to delete group"));
+    assert!(!store
+        .delete_sensor_group("living-room")
+        .await
+        .expect("Failed to delete group"));
+    assert!(store
+        .get_sensor_group("living-room")
+        .await
+        .expect("Failed to get group")
+        .is_none());
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_project_battery_life() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let declining = "AA:BB:CC:DD:EE:01";
+    let stable = "AA:BB:CC:DD:EE:02";
+    let now = Utc::now();
+
+    // Ten days of readings every six hours, losing 10 mV a day down to 2800 mV
+    for step in 0..40 {
+        let hours_ago = 6 * (40 - step);
+        let timestamp = now - Duration::hours(hours_ago);
+        let mut event = create_test_event(declining, timestamp);
+        event.battery = 2800 + 10 * hours_ago / 24;
+        test_db
+            .store
+            .insert_event(&event)
+            .await
+            .expect("Failed to insert event");
+        test_db
+            .store
+            .insert_event(&create_test_event(stable, timestamp))
+            .await
+            .expect("Failed to insert event");
+    }
+
+    let projection = test_db
+        .store
+        .project_battery_life(declining, 30)
+        .await
+        .expect("Failed to project battery life");
+
+    assert_eq!(projection.sample_count, 40);
+    let slope = projection.slope_mv_per_day.expect("slope");
+    assert!((slope + 10.0).abs() < 0.5, "unexpected slope {slope}");
+    let depletion = projection.depletion_date.expect("depletion date");
+    assert!(
+        depletion > now + Duration::days(70),
+        "too early: {depletion}"
+    );
+    assert!(
+        depletion < now + Duration::days(90),
+        "too late: {depletion}"
+    );
+
+    let projection = test_db
+        .store
+        .project_battery_life(stable, 30)
+        .await
+        .expect("Failed to project battery life");
+    assert_eq!(projection.depletion_date, None);
+
+    let projection = test_db
+        .store
+        .project_battery_life("AA:BB:CC:DD:EE:03", 30)
+        .await
+        .expect("Failed to project battery life");
+    assert_eq!(projection.sample_count, 0);
+    assert_eq!(projection.slope_mv_per_day, None);
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_time_bucketed_data_binds_interval() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let mac = "AA:BB:CC:DD:EE:01";
+    let start = Utc::now()
+        .duration_trunc(Duration::days(7))
+        .expect("Failed to truncate")
+        - Duration::days(14);
+
+    // One reading every 20 minutes for two days
+    for step in 0..144 {
+        test_db
+            .store
+            .insert_event(&create_test_event(
+                mac,
+                start + Duration::minutes(20 * step),
+            ))
+            .await
+            .expect("Failed to insert event");
+    }
+    let span = Duration::days(2);
+    let end = start + span - Duration::seconds(1);
+
+    for interval in [
+        TimeInterval::Minutes(20),
+        TimeInterval::Hours(1),
+        TimeInterval::Days(1),
+    ] {
+        let buckets = test_db
+            .store
+            .get_time_bucketed_data(mac, &interval, start, end, "UTC")
+            .await
+            .expect("Failed to get bucketed data");
+        let expected_buckets = span.num_seconds() / interval.as_seconds();
+        assert_eq!(buckets.len() as i64, expected_buckets, "{interval:?}");
+        let total: i64 = buckets.iter().filter_map(|b| b.reading_count).sum();
+        assert_eq!(total, 144, "{interval:?}");
+    }
+
+    // A crafted interval is only ever a value: the cast fails instead of the
+    // text being executed as SQL
+    let crafted = "1 hour', timestamp); DROP TABLE sensor_data; --";
+    let result = sqlx::query("SELECT time_bucket($1::interval, NOW())")
+        .bind(crafted)
+        .fetch_one(&test_db.store.pool)
+        .await;
+    assert!(result.is_err());
+    assert_eq!(
+        test_db
+            .store
+            .count_readings(mac, start, end)
+            .await
+            .expect("sensor_data still exists"),
+        144
+    );
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
+
+#[tokio::test]
+#[allow(clippy::expect_used)]
+async fn test_observed_storage_estimate() {
+    let test_db = TestDatabase::new()
+        .await
+        .expect("Failed to setup test database");
+    let now = Utc::now();
+
+    // Without data the observed estimate is the uniform one
+    let uniform = test_db
+        .store
+        .estimate_storage_requirements(3, 30, 2)
+        .await
+        .expect("Failed to estimate");
+    let observed = test_db
+        .store
+        .estimate_observed_storage_requirements(3, 30, 2)
+        .await
+        .expect("Failed to estimate");
+    assert_eq!(observed.total_readings, uniform.total_readings);
+
+    // One sensor every minute, one every two minutes, and one with too few
+    // readings to tell, which is assumed to follow the 30 second default
+    for (mac, interval_seconds, readings) in [
+        ("AA:BB:CC:DD:EE:01", 60, 20),
+        ("AA:BB:CC:DD:EE:02", 120, 20),
+        ("AA:BB:CC:DD:EE:03", 60, 3),
+    ] {
+        for step in 0..readings {
+            let timestamp = now - Duration::seconds(interval_seconds * (step + 1));
+            test_db
+                .store
+                .insert_event(&create_test_event(mac, timestamp))
+                .await
+                .expect("Failed to insert event");
+        }
+    }
+
+    let interval = test_db
+        .store
+        .get_reading_interval_seconds("AA:BB:CC:DD:EE:02")
+        .await
+        .expect("Failed to get interval");
+    assert!(interval.is_some_and(|seconds| (seconds - 120.0).abs() < 1e-6));
+    let interval = test_db
+        .store
+        .get_reading_interval_seconds("AA:BB:CC:DD:EE:03")
+        .await
+        .expect("Failed to get interval");
+    assert_eq!(interval, None);
+
+    let observed = test_db
+        .store
+        .estimate_observed_storage_requirements(3, 30, 2)
+        .await
+        .expect("Failed to estimate");
+    let seconds_per_year = 365 * 24 * 3600;
+    let expected = (seconds_per_year / 60 + seconds_per_year / 120 + seconds_per_year / 30) * 2;
+    assert_eq!(observed.total_readings, Some(expected));
+    assert!(observed.total_readings < uniform.total_readings);
+    assert!(observed.scenario.contains("2 with observed intervals"));
+
+    test_db
+        .cleanup()
+        .await
+        .expect("Failed to cleanup test database");
+}
@@ -50,6 +50,7 @@ impl std::error::Error for TestDatabaseError {}
 pub struct TestDatabase {
     pub store: PostgresStore,
     pub db_name: String,
+    pub database_url: String,
     admin_pool: PgPool,
 }
 
@@ -136,6 +137,7 @@ impl TestDatabase {
         Ok(Self {
             store,
             db_name,
+            database_url: test_db_url,
             admin_pool,
         })
     }
@@ -206,6 +208,81 @@ impl TestDatabase {
         )
         .await?;
 
+        pool.execute(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_sensor_data_unique_reading ON \
+             sensor_data(sensor_mac, measurement_sequence_number, timestamp)",
+        )
+        .await?;
+
+        pool.execute(
+            r"
+            CREATE OR REPLACE FUNCTION notify_sensor_data_insert() RETURNS trigger AS $$
+            BEGIN
+                PERFORM pg_notify('sensor_data_inserted', row_to_json(NEW)::text);
+                RETURN NULL;
+            END;
+            $$ LANGUAGE plpgsql;
+            CREATE TRIGGER sensor_data_notify_insert
+                AFTER INSERT ON sensor_data
+                FOR EACH ROW EXECUTE FUNCTION notify_sensor_data_insert();
+        ",
+        )
+        .await?;
+
+        pool.execute(
+            r"
+            CREATE TABLE IF NOT EXISTS sensor_thresholds (
+                id BIGSERIAL PRIMARY KEY,
+                sensor_mac VARCHAR(17) NOT NULL,
+                metric VARCHAR(32) NOT NULL,
+                min_value DOUBLE PRECISION,
+                max_value DOUBLE PRECISION,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+            )
+        ",
+        )
+        .await?;
+
+        pool.execute(
+            r"
+            CREATE TABLE IF NOT EXISTS sensor_groups (
+                name VARCHAR(64) PRIMARY KEY,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+            );
+            CREATE TABLE IF NOT EXISTS sensor_group_members (
+                group_name VARCHAR(64) NOT NULL REFERENCES sensor_groups(name) ON DELETE CASCADE,
+                sensor_mac VARCHAR(17) NOT NULL,
+                PRIMARY KEY (group_name, sensor_mac)
+            )
+        ",
+        )
+        .await?;
+
+        pool.execute(
+            r"
+            CREATE TABLE IF NOT EXISTS sensor_aliases (
+                sensor_mac VARCHAR(17) PRIMARY KEY,
+                alias VARCHAR(100) NOT NULL,
+                updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+            )
+        ",
+        )
+        .await?;
+
+        pool.execute(
+            r"
+            CREATE TABLE IF NOT EXISTS alerts (
+                id BIGSERIAL PRIMARY KEY,
+                sensor_mac VARCHAR(17) NOT NULL,
+                metric VARCHAR(32) NOT NULL,
+                comparator VARCHAR(8) NOT NULL,
+                threshold DOUBLE PRECISION NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+            )
+        ",
+        )
+        .await?;
+
         // Add constraints for reasonable sensor values
         let _ = pool
             .execute(
@@ -1,202 +1,289 @@
-use std::collections::HashMap;
+use std::collections::{
+    BTreeMap,
+    HashMap,
+};
+
 use anyhow::Result;
-use chrono::{DateTime, Utc};
-use redis::{AsyncCommands, Client, Connection};
-use serde::{Deserialize, Serialize};
+use async_trait::async_trait;
+use chrono::{
+    DateTime,
+    Utc,
+};
+use redis::{
+    aio::MultiplexedConnection,
+    streams::{
+        StreamId,
+        StreamTrimOptions,
+        StreamTrimmingMode,
+    },
+    AsyncCommands,
+    Client,
+    RedisError,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+pub use store_core::{
+    Event,
+    Store,
+    TimeBucketedData,
+    TimeInterval,
+};
 use tokio::sync::broadcast;
-use tracing::{error, info, warn};
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Event {
-    pub sensor_mac: String,
-    pub gateway_mac: String,
-    pub temperature: f64,
-    pub humidity: f64,
-    pub pressure: f64,
-    pub battery: i64,
-    pub tx_power: i64,
-    pub movement_counter: i64,
-    pub measurement_sequence_number: i64,
-    pub acceleration: f64,
-    pub acceleration_x: i64,
-    pub acceleration_y: i64,
-    pub acceleration_z: i64,
-    pub rssi: i64,
-    pub timestamp: DateTime<Utc>,
+use tracing::{
+    error,
+    info,
+    warn,
+};
+
+type RedisFields = Vec<(String, String)>;
+
+fn to_redis_fields(event: &Event) -> RedisFields {
+    vec![
+        ("sensor_mac".to_string(), event.sensor_mac.clone()),
+        ("gateway_mac".to_string(), event.gateway_mac.clone()),
+        ("temperature".to_string(), event.temperature.to_string()),
+        ("humidity".to_string(), event.humidity.to_string()),
+        ("pressure".to_string(), event.pressure.to_string()),
+        ("battery".to_string(), event.battery.to_string()),
+        ("tx_power".to_string(), event.tx_power.to_string()),
+        (
+            "movement_counter".to_string(),
+            event.movement_counter.to_string(),
+        ),
+        (
+            "measurement_sequence_number".to_string(),
+            event.measurement_sequence_number.to_string(),
+        ),
+        ("acceleration".to_string(), event.acceleration.to_string()),
+        (
+            "acceleration_x".to_string(),
+            event.acceleration_x.to_string(),
+        ),
+        (
+            "acceleration_y".to_string(),
+            event.acceleration_y.to_string(),
+        ),
+        (
+            "acceleration_z".to_string(),
+            event.acceleration_z.to_string(),
+        ),
+        ("rssi".to_string(), event.rssi.to_string()),
+        (
+            "timestamp".to_string(),
+            event.timestamp.timestamp_millis().to_string(),
+        ),
+    ]
 }
 
-impl Event {
-    pub fn new_with_current_time(
-        sensor_mac: String,
-        gateway_mac: String,
-        temperature: f64,
-        humidity: f64,
-        pressure: f64,
-        battery: i64,
-        tx_power: i64,
-        movement_counter: i64,
-        measurement_sequence_number: i64,
-        acceleration: f64,
-        acceleration_x: i64,
-        acceleration_y: i64,
-        acceleration_z: i64,
-        rssi: i64,
-    ) -> Self {
-        Self {
-            sensor_mac,
-            gateway_mac,
-            temperature,
-            humidity,
-            pressure,
-            battery,
-            tx_power,
-            movement_counter,
-            measurement_sequence_number,
-            acceleration,
-            acceleration_x,
-            acceleration_y,
-            acceleration_z,
-            rssi,
-            timestamp: Utc::now(),
-        }
+fn event_from_stream_entry(entry: &StreamId) -> Result<Event> {
+    let mut field_map: HashMap<String, String> = entry
+        .map
+        .iter()
+        .map(|(k, v)| Ok((k.clone(), redis::from_redis_value(v)?)))
+        .collect::<Result<_, redis::RedisError>>()?;
+
+    let sensor_mac = field_map
+        .remove("sensor_mac")
+        .ok_or_else(|| anyhow::anyhow!("Missing sensor_mac field"))?;
+    let gateway_mac = field_map
+        .remove("gateway_mac")
+        .ok_or_else(|| anyhow::anyhow!("Missing gateway_mac field"))?;
+
+    let temperature = field_map
+        .remove("temperature")
+        .ok_or_else(|| anyhow::anyhow!("Missing temperature field"))?
+        .parse::<f64>()?;
+    let humidity = field_map
+        .remove("humidity")
+        .ok_or_else(|| anyhow::anyhow!("Missing humidity field"))?
+        .parse::<f64>()?;
+    let pressure = field_map
+        .remove("pressure")
+        .ok_or_else(|| anyhow::anyhow!("Missing pressure field"))?
+        .parse::<f64>()?;
+    let battery = field_map
+        .remove("battery")
+        .ok_or_else(|| anyhow::anyhow!("Missing battery field"))?
+        .parse::<i64>()?;
+    let tx_power = field_map
+        .remove("tx_power")
+        .ok_or_else(|| anyhow::anyhow!("Missing tx_power field"))?
+        .parse::<i64>()?;
+    let movement_counter = field_map
+        .remove("movement_counter")
+        .ok_or_else(|| anyhow::anyhow!("Missing movement_counter field"))?
+        .parse::<i64>()?;
+    let measurement_sequence_number = field_map
+        .remove("measurement_sequence_number")
+        .ok_or_else(|| anyhow::anyhow!("Missing measurement_sequence_number field"))?
+        .parse::<i64>()?;
+    let acceleration = field_map
+        .remove("acceleration")
+        .ok_or_else(|| anyhow::anyhow!("Missing acceleration field"))?
+        .parse::<f64>()?;
+    let acceleration_x = field_map
+        .remove("acceleration_x")
+        .ok_or_else(|| anyhow::anyhow!("Missing acceleration_x field"))?
+        .parse::<i64>()?;
+    let acceleration_y = field_map
+        .remove("acceleration_y")
+        .ok_or_else(|| anyhow::anyhow!("Missing acceleration_y field"))?
+        .parse::<i64>()?;
+    let acceleration_z = field_map
+        .remove("acceleration_z")
+        .ok_or_else(|| anyhow::anyhow!("Missing acceleration_z field"))?
+        .parse::<i64>()?;
+    let rssi = field_map
+        .remove("rssi")
+        .ok_or_else(|| anyhow::anyhow!("Missing rssi field"))?
+        .parse::<i64>()?;
+
+    let timestamp_millis = field_map
+        .remove("timestamp")
+        .ok_or_else(|| anyhow::anyhow!("Missing timestamp field"))?
+        .parse::<i64>()?;
+    let timestamp = DateTime::from_timestamp_millis(timestamp_millis)
+        .ok_or_else(|| anyhow::anyhow!("Invalid timestamp"))?
+        .with_timezone(&Utc);
+
+    Ok(Event {
+        sensor_mac,
+        gateway_mac,
+        temperature,
+        humidity,
+        pressure,
+        battery,
+        tx_power,
+        movement_counter,
+        measurement_sequence_number,
+        acceleration,
+        acceleration_x,
+        acceleration_y,
+        acceleration_z,
+        rssi,
+        timestamp,
+    })
+}
+
+/// Connections a [`RedisStore`] keeps open unless told otherwise
+pub const DEFAULT_POOL_SIZE: u32 = 10;
+
+/// Opens and checks the pooled connections of a [`RedisStore`]
+#[derive(Debug, Clone)]
+struct RedisConnectionManager {
+    client: Client,
+}
+
+impl bb8::ManageConnection for RedisConnectionManager {
+    type Connection = MultiplexedConnection;
+    type Error = RedisError;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        self.client.get_multiplexed_async_connection().await
     }
 
-    fn to_redis_fields(&self) -> Vec<(String, String)> {
-        vec![
-            ("sensor_mac".to_string(), self.sensor_mac.clone()),
-            ("gateway_mac".to_string(), self.gateway_mac.clone()),
-            ("temperature".to_string(), self.temperature.to_string()),
-            ("humidity".to_string(), self.humidity.to_string()),
-            ("pressure".to_string(), self.pressure.to_string()),
-            ("battery".to_string(), self.battery.to_string()),
-            ("tx_power".to_string(), self.tx_power.to_string()),
-            ("movement_counter".to_string(), self.movement_counter.to_string()),
-            ("measurement_sequence_number".to_string(), self.measurement_sequence_number.to_string()),
-            ("acceleration".to_string(), self.acceleration.to_string()),
-            ("acceleration_x".to_string(), self.acceleration_x.to_string()),
-            ("acceleration_y".to_string(), self.acceleration_y.to_string()),
-            ("acceleration_z".to_string(), self.acceleration_z.to_string()),
-            ("rssi".to_string(), self.rssi.to_string()),
-            ("timestamp".to_string(), self.timestamp.timestamp_millis().to_string()),
-        ]
+    async fn is_valid(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        let _: String = redis::cmd("PING").query_async(conn).await?;
+        Ok(())
     }
 
-    fn from_redis_fields(fields: &[(String, String)]) -> Result<Self> {
-        let mut field_map: HashMap<String, String> = fields.iter()
-            .map(|(k, v)| (k.clone(), v.clone()))
-            .collect();
+    fn has_broken(&self, _conn: &mut Self::Connection) -> bool {
+        false
+    }
+}
 
-        let sensor_mac = field_map.remove("sensor_mac")
-            .ok_or_else(|| anyhow::anyhow!("Missing sensor_mac field"))?;
-        let gateway_mac = field_map.remove("gateway_mac")
-            .ok_or_else(|| anyhow::anyhow!("Missing gateway_mac field"))?;
-
-        let temperature = field_map.remove("temperature")
-            .ok_or_else(|| anyhow::anyhow!("Missing temperature field"))?
-            .parse::<f64>()?;
-        let humidity = field_map.remove("humidity")
-            .ok_or_else(|| anyhow::anyhow!("Missing humidity field"))?
-            .parse::<f64>()?;
-        let pressure = field_map.remove("pressure")
-            .ok_or_else(|| anyhow::anyhow!("Missing pressure field"))?
-            .parse::<f64>()?;
-        let battery = field_map.remove("battery")
-            .ok_or_else(|| anyhow::anyhow!("Missing battery field"))?
-            .parse::<i64>()?;
-        let tx_power = field_map.remove("tx_power")
-            .ok_or_else(|| anyhow::anyhow!("Missing tx_power field"))?
-            .parse::<i64>()?;
-        let movement_counter = field_map.remove("movement_counter")
-            .ok_or_else(|| anyhow::anyhow!("Missing movement_counter field"))?
-            .parse::<i64>()?;
-        let measurement_sequence_number = field_map.remove("measurement_sequence_number")
-            .ok_or_else(|| anyhow::anyhow!("Missing measurement_sequence_number field"))?
-            .parse::<i64>()?;
-        let acceleration = field_map.remove("acceleration")
-            .ok_or_else(|| anyhow::anyhow!("Missing acceleration field"))?
-            .parse::<f64>()?;
-        let acceleration_x = field_map.remove("acceleration_x")
-            .ok_or_else(|| anyhow::anyhow!("Missing acceleration_x field"))?
-            .parse::<i64>()?;
-        let acceleration_y = field_map.remove("acceleration_y")
-            .ok_or_else(|| anyhow::anyhow!("Missing acceleration_y field"))?
-            .parse::<i64>()?;
-        let acceleration_z = field_map.remove("acceleration_z")
-            .ok_or_else(|| anyhow::anyhow!("Missing acceleration_z field"))?
-            .parse::<i64>()?;
-        let rssi = field_map.remove("rssi")
-            .ok_or_else(|| anyhow::anyhow!("Missing rssi field"))?
-            .parse::<i64>()?;
-
-        let timestamp_millis = field_map.remove("timestamp")
-            .ok_or_else(|| anyhow::anyhow!("Missing timestamp field"))?
-            .parse::<i64>()?;
-        let timestamp = DateTime::from_timestamp_millis(timestamp_millis)
-            .ok_or_else(|| anyhow::anyhow!("Invalid timestamp"))?
-            .with_timezone(&Utc);
+/// How long the latest reading of a sensor is kept by default: a day
+pub const DEFAULT_LATEST_TTL_SECS: u64 = 86400;
 
-        Ok(Self {
-            sensor_mac,
-            gateway_mac,
-            temperature,
-            humidity,
-            pressure,
-            battery,
-            tx_power,
-            movement_counter,
-            measurement_sequence_number,
-            acceleration,
-            acceleration_x,
-            acceleration_y,
-            acceleration_z,
-            rssi,
-            timestamp,
-        })
+/// Tunables of a [`RedisStore`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RedisConfig {
+    /// Seconds the latest reading of a sensor outlives its last insert;
+    /// raise it for sensors that report less often than that
+    pub latest_ttl_secs: u64,
+}
+
+impl Default for RedisConfig {
+    fn default() -> Self {
+        Self {
+            latest_ttl_secs: DEFAULT_LATEST_TTL_SECS,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct RedisStore {
     client: Client,
+    pool: bb8::Pool<RedisConnectionManager>,
+    config: RedisConfig,
     event_sender: broadcast::Sender<Event>,
 }
 
 impl RedisStore {
     pub async fn new(redis_url: &str) -> Result<Self> {
+        Self::connect(redis_url, DEFAULT_POOL_SIZE, RedisConfig::default()).await
+    }
+
+    /// Connect with a pool of at most `pool_size` connections shared by all
+    /// operations of the store
+    pub async fn new_with_pool_size(redis_url: &str, pool_size: u32) -> Result<Self> {
+        Self::connect(redis_url, pool_size, RedisConfig::default()).await
+    }
+
+    /// Connect with the given tunables instead of the defaults
+    pub async fn new_with_config(redis_url: &str, config: RedisConfig) -> Result<Self> {
+        Self::connect(redis_url, DEFAULT_POOL_SIZE, config).await
+    }
+
+    async fn connect(redis_url: &str, pool_size: u32, config: RedisConfig) -> Result<Self> {
+        anyhow::ensure!(pool_size > 0, "Redis pool size must be positive");
         let client = Client::open(redis_url)?;
 
-        // Test connection
-        let mut conn = client.get_multiplexed_async_connection().await?;
-        let _: String = conn.ping().await?;
+        // Building the pool opens its first connection, failing on an
+        // unreachable server
+        let pool = bb8::Pool::builder()
+            .max_size(pool_size)
+            .build(RedisConnectionManager {
+                client: client.clone(),
+            })
+            .await?;
 
         let (event_sender, _) = broadcast::channel(1000);
 
-        info!("Connected to Redis at {}", redis_url);
+        info!(
+            "Connected to Redis at {} with a pool of {} connections",
+            redis_url, pool_size
+        );
 
         Ok(Self {
             client,
+            pool,
+            config,
             event_sender,
         })
     }
 
     pub async fn insert_event(&self, event: &Event) -> Result<()> {
-        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let mut conn = self.pool.get().await?;
 
         // Store in Redis Stream for time-series data
         let stream_key = format!("sensor_data:{}", event.sensor_mac);
-        let fields = event.to_redis_fields();
+        let fields = to_redis_fields(event);
 
         let _: String = conn.xadd(&stream_key, "*", &fields).await?;
 
         // Store latest reading for quick access
         let latest_key = format!("latest:{}", event.sensor_mac);
         let serialized = serde_json::to_string(event)?;
-        let _: () = conn.set(&latest_key, &serialized).await?;
-        let _: () = conn.expire(&latest_key, 86400).await?; // Expire after 24 hours
+        let _: () = conn
+            .set_ex(&latest_key, &serialized, self.config.latest_ttl_secs)
+            .await?;
 
         // Add to active sensors set
         let active_key = "active_sensors";
-        let _: () = conn.sadd(&active_key, &event.sensor_mac).await?;
+        let _: () = conn.sadd(active_key, &event.sensor_mac).await?;
 
         // Publish to pub/sub channel for real-time notifications
         let channel = "sensor_events";
@@ -210,33 +297,51 @@ impl RedisStore {
         Ok(())
     }
 
+    /// Latest reading of every sensor heard from in the last 24 hours
+    ///
+    /// All latest readings are fetched with one MGET on the same connection,
+    /// and sensors whose reading is older are dropped from the active set.
     pub async fn get_active_sensors(&self) -> Result<Vec<Event>> {
-        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let mut conn = self.pool.get().await?;
 
         // Get all active sensor MACs
         let active_key = "active_sensors";
-        let sensor_macs: Vec<String> = conn.smembers(&active_key).await?;
+        let sensor_macs: Vec<String> = conn.smembers(active_key).await?;
+        if sensor_macs.is_empty() {
+            return Ok(Vec::new());
+        }
 
-        let mut events = Vec::new();
+        let latest_keys: Vec<String> = sensor_macs
+            .iter()
+            .map(|sensor_mac| format!("latest:{}", sensor_mac))
+            .collect();
+        let readings: Vec<Option<String>> = conn.mget(&latest_keys).await?;
 
-        for sensor_mac in sensor_macs {
-            if let Ok(Some(event)) = self.get_latest_reading(&sensor_mac).await {
-                // Check if the reading is within the last 24 hours
-                let hours_ago_24 = Utc::now() - chrono::Duration::hours(24);
-                if event.timestamp >= hours_ago_24 {
-                    events.push(event);
-                } else {
-                    // Remove from active sensors if too old
-                    let _: () = conn.srem(&active_key, &sensor_mac).await?;
-                }
+        let hours_ago_24 = Utc::now() - chrono::Duration::hours(24);
+        let mut events = Vec::new();
+        let mut stale = Vec::new();
+        for (sensor_mac, serialized) in sensor_macs.iter().zip(readings) {
+            let Some(data) = serialized else {
+                continue;
+            };
+            let event: Event = serde_json::from_str(&data)?;
+            if event.timestamp >= hours_ago_24 {
+                events.push(event);
+            } else {
+                stale.push(sensor_mac);
             }
         }
 
+        // Remove from active sensors if too old
+        if !stale.is_empty() {
+            let _: () = conn.srem(active_key, stale).await?;
+        }
+
         Ok(events)
     }
 
     pub async fn get_latest_reading(&self, sensor_mac: &str) -> Result<Option<Event>> {
-        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let mut conn = self.pool.get().await?;
 
         let latest_key = format!("latest:{}", sensor_mac);
         let serialized: Option<String> = conn.get(&latest_key).await?;
@@ -245,11 +350,12 @@ impl RedisStore {
             Some(data) => {
                 let event: Event = serde_json::from_str(&data)?;
                 Ok(Some(event))
-            },
+            }
             None => Ok(None),
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn get_historical_data(
         &self,
         sensor_mac: &str,
@@ -257,7 +363,7 @@ impl RedisStore {
         end: Option<DateTime<Utc>>,
         limit: Option<i64>,
     ) -> Result<Vec<Event>> {
-        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let mut conn = self.pool.get().await?;
 
         let stream_key = format!("sensor_data:{}", sensor_mac);
         let limit = limit.unwrap_or(100);
@@ -282,7 +388,7 @@ impl RedisStore {
 
         for entry in stream_data {
             for stream_entry in entry.ids {
-                match Event::from_redis_fields(&stream_entry.map) {
+                match event_from_stream_entry(&stream_entry) {
                     Ok(event) => events.push(event),
                     Err(e) => warn!("Failed to parse event from Redis: {}", e),
                 }
@@ -298,52 +404,136 @@ impl RedisStore {
         start: DateTime<Utc>,
         end: DateTime<Utc>,
     ) -> Result<Vec<Event>> {
-        self.get_historical_data(sensor_mac, Some(start), Some(end), None).await
+        self.get_historical_data(sensor_mac, Some(start), Some(end), None)
+            .await
+    }
+
+    /// Average, minimum and maximum of the readings a sensor stored in the
+    /// last `hours`, computed from its stream
+    pub async fn get_sensor_statistics(&self, sensor_mac: &str, hours: i64) -> Result<SensorStats> {
+        let mut conn = self.pool.get().await?;
+
+        let stream_key = format!("sensor_data:{}", sensor_mac);
+        let start_id = (Utc::now() - chrono::Duration::hours(hours))
+            .timestamp_millis()
+            .to_string();
+
+        let stream_data: redis::streams::StreamRangeReply =
+            conn.xrange(&stream_key, &start_id, "+").await?;
+
+        let events: Vec<Event> = stream_data
+            .ids
+            .iter()
+            .filter_map(|entry| match event_from_stream_entry(entry) {
+                Ok(event) => Some(event),
+                Err(e) => {
+                    warn!("Failed to parse event from Redis: {}", e);
+                    None
+                }
+            })
+            .collect();
+
+        Ok(SensorStats::from_events(&events))
+    }
+
+    /// Buckets of `interval` over the readings of a sensor between `start`
+    /// and `end`, aligned like TimescaleDB's `time_bucket` in UTC.
+    ///
+    /// The stream is read a page at a time and only running sums are kept
+    /// per bucket, so long windows don't load every reading at once. The
+    /// percentiles need every value of a bucket and are left out.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn get_time_bucketed_data(
+        &self,
+        sensor_mac: &str,
+        interval: &TimeInterval,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<TimeBucketedData>> {
+        let mut conn = self.pool.get().await?;
+
+        let stream_key = format!("sensor_data:{}", sensor_mac);
+        let width = interval.as_seconds();
+        anyhow::ensure!(width > 0, "Bucket interval must be positive");
+
+        let mut buckets: BTreeMap<i64, BucketAccumulator> = BTreeMap::new();
+        let mut start_id = start.timestamp_millis().to_string();
+        let end_id = end.timestamp_millis().to_string();
+        loop {
+            let page: redis::streams::StreamRangeReply = conn
+                .xrange_count(&stream_key, &start_id, &end_id, STREAM_PAGE_SIZE)
+                .await?;
+            for entry in &page.ids {
+                match event_from_stream_entry(entry) {
+                    Ok(event) if (start..=end).contains(&event.timestamp) => buckets
+                        .entry(bucket_start(event.timestamp, width))
+                        .or_insert_with(|| BucketAccumulator::new(&event))
+                        .add(&event),
+                    Ok(_) => {}
+                    Err(e) => warn!("Failed to parse event from Redis: {}", e),
+                }
+            }
+            match page.ids.last() {
+                // An exclusive start resumes right after the last entry read
+                Some(last) if page.ids.len() == STREAM_PAGE_SIZE => {
+                    start_id = format!("({}", last.id);
+                }
+                _ => break,
+            }
+        }
+
+        Ok(buckets
+            .into_iter()
+            .filter_map(|(bucket, accumulator)| accumulator.finish(bucket))
+            .collect())
     }
 
     pub fn subscribe_to_events(&self) -> broadcast::Receiver<Event> {
         self.event_sender.subscribe()
     }
 
+    /// Delete the stream entries of a sensor older than `days_to_keep`,
+    /// returning how many were removed.
+    ///
+    /// Stream IDs start with their time in milliseconds, so `XTRIM MINID`
+    /// (Redis 6.2+) drops exactly the entries before the cutoff however many
+    /// there are.
     pub async fn cleanup_old_data(&self, sensor_mac: &str, days_to_keep: i32) -> Result<u64> {
-        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let mut conn = self.pool.get().await?;
 
         let stream_key = format!("sensor_data:{}", sensor_mac);
-        let cutoff_time = Utc::now() - chrono::Duration::days(days_to_keep as i64);
+        let cutoff_time = Utc::now() - chrono::Duration::days(i64::from(days_to_keep));
         let cutoff_id = cutoff_time.timestamp_millis().to_string();
 
-        // Count entries before deletion
-        let count_before: usize = conn.xlen(&stream_key).await.unwrap_or(0);
-
-        // Delete old entries using XTRIM
-        let _: () = conn.xtrim(&stream_key, redis::streams::StreamMaxlen::Approx(1000)).await?;
-
-        // Get count after deletion
-        let count_after: usize = conn.xlen(&stream_key).await.unwrap_or(0);
+        let deleted: u64 = conn
+            .xtrim_options(
+                &stream_key,
+                &StreamTrimOptions::minid(StreamTrimmingMode::Exact, cutoff_id),
+            )
+            .await?;
 
-        Ok((count_before - count_after) as u64)
+        Ok(deleted)
     }
 
     pub async fn subscribe_to_redis_pubsub(&self) -> Result<redis::aio::PubSub> {
-        let conn = self.client.get_async_connection().await?;
-        let mut pubsub = conn.into_pubsub();
+        let mut pubsub = self.client.get_async_pubsub().await?;
         pubsub.subscribe("sensor_events").await?;
         Ok(pubsub)
     }
 
     pub async fn get_sensor_count(&self) -> Result<usize> {
-        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let mut conn = self.pool.get().await?;
         let active_key = "active_sensors";
-        let count: usize = conn.scard(&active_key).await?;
+        let count: usize = conn.scard(active_key).await?;
         Ok(count)
     }
 
     pub async fn remove_sensor(&self, sensor_mac: &str) -> Result<()> {
-        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let mut conn = self.pool.get().await?;
 
         // Remove from active sensors
         let active_key = "active_sensors";
-        let _: () = conn.srem(&active_key, sensor_mac).await?;
+        let _: () = conn.srem(active_key, sensor_mac).await?;
 
         // Remove latest reading
         let latest_key = format!("latest:{}", sensor_mac);
@@ -353,7 +543,36 @@ impl RedisStore {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[async_trait]
+impl Store for RedisStore {
+    async fn insert_event(&self, event: &Event) -> Result<()> {
+        Self::insert_event(self, event).await
+    }
+
+    async fn get_latest_reading(&self, sensor_mac: &str) -> Result<Option<Event>> {
+        Self::get_latest_reading(self, sensor_mac).await
+    }
+
+    async fn get_historical_data(
+        &self,
+        sensor_mac: &str,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        limit: Option<i64>,
+    ) -> Result<Vec<Event>> {
+        Self::get_historical_data(self, sensor_mac, start, end, limit).await
+    }
+
+    async fn get_active_sensors(&self) -> Result<Vec<Event>> {
+        Self::get_active_sensors(self).await
+    }
+
+    fn subscribe_to_events(&self) -> broadcast::Receiver<Event> {
+        Self::subscribe_to_events(self)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct SensorStats {
     pub avg_temperature: f64,
     pub min_temperature: f64,
@@ -366,3 +585,142 @@ pub struct SensorStats {
     pub max_pressure: f64,
     pub reading_count: i64,
 }
+
+impl SensorStats {
+    /// Summarize readings; no readings give all zeros
+    fn from_events(events: &[Event]) -> Self {
+        if events.is_empty() {
+            return Self::default();
+        }
+        let (avg_temperature, min_temperature, max_temperature) =
+            summarize(events.iter().map(|event| event.temperature));
+        let (avg_humidity, min_humidity, max_humidity) =
+            summarize(events.iter().map(|event| event.humidity));
+        let (avg_pressure, min_pressure, max_pressure) =
+            summarize(events.iter().map(|event| event.pressure));
+        Self {
+            avg_temperature,
+            min_temperature,
+            max_temperature,
+            avg_humidity,
+            min_humidity,
+            max_humidity,
+            avg_pressure,
+            min_pressure,
+            max_pressure,
+            reading_count: i64::try_from(events.len()).unwrap_or(i64::MAX),
+        }
+    }
+}
+
+/// Average, minimum and maximum of a non-empty series
+fn summarize(values: impl Iterator<Item = f64>) -> (f64, f64, f64) {
+    let (sum, count, min, max) = values.fold(
+        (0.0, 0_u32, f64::INFINITY, f64::NEG_INFINITY),
+        |(sum, count, min, max), value| (sum + value, count + 1, min.min(value), max.max(value)),
+    );
+    (sum / f64::from(count), min, max)
+}
+
+/// Stream entries read per XRANGE call when scanning a long range
+const STREAM_PAGE_SIZE: usize = 1000;
+
+/// Origin `time_bucket` aligns buckets to by default, a Monday, so weekly
+/// buckets start on Mondays like in the Postgres store
+const BUCKET_ORIGIN_SECS: i64 = 946_857_600;
+
+/// Start in Unix seconds of the bucket of `width` seconds holding `timestamp`
+fn bucket_start(timestamp: DateTime<Utc>, width: i64) -> i64 {
+    let offset = timestamp.timestamp() - BUCKET_ORIGIN_SECS;
+    BUCKET_ORIGIN_SECS + offset.div_euclid(width) * width
+}
+
+/// Running sum, minimum and maximum of one metric
+#[derive(Debug, Clone, Copy)]
+struct MetricAccumulator {
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl MetricAccumulator {
+    const fn new(value: f64) -> Self {
+        Self {
+            sum: 0.0,
+            min: value,
+            max: value,
+        }
+    }
+
+    fn add(&mut self, value: f64) {
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+}
+
+/// Temperature of a reading with its time
+type TimedTemperature = (DateTime<Utc>, f64);
+
+/// Aggregates of one bucket, built up a reading at a time
+#[derive(Debug, Clone)]
+struct BucketAccumulator {
+    temperature: MetricAccumulator,
+    humidity: MetricAccumulator,
+    pressure: MetricAccumulator,
+    first: TimedTemperature,
+    last: TimedTemperature,
+    count: i64,
+}
+
+impl BucketAccumulator {
+    /// An empty bucket; `add` the reading to count it
+    const fn new(event: &Event) -> Self {
+        Self {
+            temperature: MetricAccumulator::new(event.temperature),
+            humidity: MetricAccumulator::new(event.humidity),
+            pressure: MetricAccumulator::new(event.pressure),
+            first: (event.timestamp, event.temperature),
+            last: (event.timestamp, event.temperature),
+            count: 0,
+        }
+    }
+
+    fn add(&mut self, event: &Event) {
+        self.temperature.add(event.temperature);
+        self.humidity.add(event.humidity);
+        self.pressure.add(event.pressure);
+        if event.timestamp < self.first.0 {
+            self.first = (event.timestamp, event.temperature);
+        }
+        if event.timestamp >= self.last.0 {
+            self.last = (event.timestamp, event.temperature);
+        }
+        self.count += 1;
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn finish(self, bucket: i64) -> Option<TimeBucketedData> {
+        let count = self.count as f64;
+        Some(TimeBucketedData {
+            bucket: DateTime::from_timestamp(bucket, 0)?,
+            avg_temperature: Some(self.temperature.sum / count),
+            min_temperature: Some(self.temperature.min),
+            max_temperature: Some(self.temperature.max),
+            sum_temperature: Some(self.temperature.sum),
+            p50_temperature: None,
+            p95_temperature: None,
+            first_temperature: Some(self.first.1),
+            last_temperature: Some(self.last.1),
+            avg_humidity: Some(self.humidity.sum / count),
+            min_humidity: Some(self.humidity.min),
+            max_humidity: Some(self.humidity.max),
+            sum_humidity: Some(self.humidity.sum),
+            avg_pressure: Some(self.pressure.sum / count),
+            min_pressure: Some(self.pressure.min),
+            max_pressure: Some(self.pressure.max),
+            sum_pressure: Some(self.pressure.sum),
+            reading_count: Some(self.count),
+        })
+    }
+}
@@ -0,0 +1,268 @@
+//! Redis integration tests for redis-store
+//!
+//! These tests run against a real Redis container and are ignored by default.
+
+use anyhow::Result;
+use chrono::{
+    Duration,
+    DurationRound,
+    Utc,
+};
+use redis::{
+    streams::StreamRangeReply,
+    AsyncCommands,
+};
+use redis_store::{
+    Event,
+    RedisConfig,
+    RedisStore,
+    TimeInterval,
+};
+use testcontainers_modules::{
+    redis::Redis,
+    testcontainers::{
+        runners::AsyncRunner,
+        ContainerAsync,
+    },
+};
+use tokio::task::JoinSet;
+
+/// Start a Redis container, returning it with its URL; the container stops
+/// when dropped
+#[allow(clippy::expect_used)]
+async fn start_redis() -> (ContainerAsync<Redis>, String) {
+    let container = Redis::default().start().await.expect("redis");
+    let redis_url = format!(
+        "redis://localhost:{}",
+        container
+            .get_host_port_ipv4(6379)
+            .await
+            .expect("Failed to get host port")
+    );
+    (container, redis_url)
+}
+
+fn reading(sensor_mac: &str, temperature: f64, humidity: f64, pressure: f64) -> Event {
+    Event::new_with_current_time(
+        sensor_mac.to_string(),
+        "FF:FF:FF:FF:FF:01".to_string(),
+        temperature,
+        humidity,
+        pressure,
+        3000,
+        4,
+        0,
+        1,
+        1000.0,
+        0,
+        0,
+        1000,
+        -60,
+    )
+}
+
+#[tokio::test]
+#[ignore = "Requires Docker for Redis"]
+#[allow(clippy::expect_used)]
+async fn test_cleanup_old_data_trims_by_time() -> Result<()> {
+    let (_container, redis_url) = start_redis().await;
+
+    let store = RedisStore::new(&redis_url).await?;
+    let client = redis::Client::open(redis_url.as_str())?;
+    let mut conn = client.get_multiplexed_async_connection().await?;
+
+    let sensor_mac = "AA:BB:CC:DD:EE:FF";
+    let stream_key = format!("sensor_data:{sensor_mac}");
+    let now = Utc::now();
+    let ids: Vec<String> = [10, 5, 3, 1, 0]
+        .iter()
+        .map(|days| format!("{}-0", (now - Duration::days(*days)).timestamp_millis()))
+        .collect();
+    for id in &ids {
+        let _: String = conn
+            .xadd(&stream_key, id, &[("sensor_mac", sensor_mac)])
+            .await?;
+    }
+
+    let deleted = store.cleanup_old_data(sensor_mac, 4).await?;
+    assert_eq!(deleted, 2);
+
+    let remaining: StreamRangeReply = conn.xrange_all(&stream_key).await?;
+    let remaining_ids: Vec<String> = remaining.ids.into_iter().map(|entry| entry.id).collect();
+    assert_eq!(remaining_ids, ids.get(2..).expect("three newer entries"));
+
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "Requires Docker for Redis"]
+async fn test_sensor_statistics_from_stream() -> Result<()> {
+    let (_container, redis_url) = start_redis().await;
+    let store = RedisStore::new(&redis_url).await?;
+    let sensor_mac = "AA:BB:CC:DD:EE:01";
+
+    let empty = store.get_sensor_statistics(sensor_mac, 24).await?;
+    assert_eq!(empty.reading_count, 0);
+    assert!(empty.avg_temperature.abs() < f64::EPSILON);
+    assert!(empty.max_pressure.abs() < f64::EPSILON);
+
+    for (temperature, humidity, pressure) in [
+        (20.0, 40.0, 1000.0),
+        (22.0, 50.0, 1010.0),
+        (27.0, 60.0, 1020.0),
+    ] {
+        store
+            .insert_event(&reading(sensor_mac, temperature, humidity, pressure))
+            .await?;
+    }
+
+    let stats = store.get_sensor_statistics(sensor_mac, 24).await?;
+    assert_eq!(stats.reading_count, 3);
+    for (actual, expected) in [
+        (stats.avg_temperature, 23.0),
+        (stats.min_temperature, 20.0),
+        (stats.max_temperature, 27.0),
+        (stats.avg_humidity, 50.0),
+        (stats.min_humidity, 40.0),
+        (stats.max_humidity, 60.0),
+        (stats.avg_pressure, 1010.0),
+        (stats.min_pressure, 1000.0),
+        (stats.max_pressure, 1020.0),
+    ] {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "Expected {expected}, got {actual}"
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "Requires Docker for Redis"]
+async fn test_concurrent_reads_share_small_pool() -> Result<()> {
+    let (_container, redis_url) = start_redis().await;
+    let store = RedisStore::new_with_pool_size(&redis_url, 2).await?;
+    let sensor_mac = "AA:BB:CC:DD:EE:01";
+    store
+        .insert_event(&reading(sensor_mac, 21.0, 45.0, 1013.0))
+        .await?;
+
+    let mut reads = JoinSet::new();
+    for _ in 0..64 {
+        let store = store.clone();
+        reads.spawn(async move { store.get_latest_reading(sensor_mac).await });
+    }
+    while let Some(read) = reads.join_next().await {
+        let latest = read??;
+        assert!(latest.is_some_and(|event| event.sensor_mac == sensor_mac));
+    }
+
+    assert!(RedisStore::new_with_pool_size(&redis_url, 0).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "Requires Docker for Redis"]
+async fn test_active_sensors_with_single_connection_pool() -> Result<()> {
+    let (_container, redis_url) = start_redis().await;
+    let store = RedisStore::new_with_pool_size(&redis_url, 1).await?;
+    for sensor_mac in ["AA:BB:CC:DD:EE:01", "AA:BB:CC:DD:EE:02"] {
+        store
+            .insert_event(&reading(sensor_mac, 21.0, 45.0, 1013.0))
+            .await?;
+    }
+
+    let active = tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        store.get_active_sensors(),
+    )
+    .await??;
+    assert_eq!(active.len(), 2);
+
+    let mut conn = redis::Client::open(redis_url.as_str())?
+        .get_multiplexed_async_connection()
+        .await?;
+    let _: () = conn.set("latest:AA:BB:CC:DD:EE:01", "not json").await?;
+    assert!(store.get_active_sensors().await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "Requires Docker for Redis"]
+async fn test_latest_ttl_follows_config() -> Result<()> {
+    let (_container, redis_url) = start_redis().await;
+    let client = redis::Client::open(redis_url.as_str())?;
+    let mut conn = client.get_multiplexed_async_connection().await?;
+
+    for (sensor_mac, config) in [
+        ("AA:BB:CC:DD:EE:01", RedisConfig::default()),
+        (
+            "AA:BB:CC:DD:EE:02",
+            RedisConfig {
+                latest_ttl_secs: 7 * 86400,
+            },
+        ),
+    ] {
+        let store = RedisStore::new_with_config(&redis_url, config).await?;
+        store
+            .insert_event(&reading(sensor_mac, 21.0, 45.0, 1013.0))
+            .await?;
+
+        let ttl: i64 = conn.ttl(format!("latest:{sensor_mac}")).await?;
+        let expected = i64::try_from(config.latest_ttl_secs)?;
+        assert!(
+            (expected - 5..=expected).contains(&ttl),
+            "Expected a TTL of about {expected}s, got {ttl}s"
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "Requires Docker for Redis"]
+#[allow(clippy::expect_used)]
+async fn test_hourly_buckets_match_hand_computed() -> Result<()> {
+    let (_container, redis_url) = start_redis().await;
+    let store = RedisStore::new(&redis_url).await?;
+    let sensor_mac = "AA:BB:CC:DD:EE:01";
+
+    // Two readings in one hour and one in the next, two hours back
+    let now = Utc::now();
+    let hour = now
+        .duration_trunc(Duration::hours(1))
+        .expect("truncated hour")
+        - Duration::hours(2);
+    for (minutes, temperature, humidity) in [(10, 20.0, 40.0), (40, 22.0, 50.0), (70, 25.0, 60.0)] {
+        let mut event = reading(sensor_mac, temperature, humidity, 1000.0);
+        event.timestamp = hour + Duration::minutes(minutes);
+        store.insert_event(&event).await?;
+    }
+
+    let buckets = store
+        .get_time_bucketed_data(sensor_mac, &TimeInterval::Hours(1), hour, now)
+        .await?;
+    assert_eq!(buckets.len(), 2);
+    let (first, second) = (&buckets[0], &buckets[1]);
+
+    assert_eq!(first.bucket, hour);
+    assert_eq!(first.reading_count, Some(2));
+    assert_eq!(first.avg_temperature, Some(21.0));
+    assert_eq!(first.min_temperature, Some(20.0));
+    assert_eq!(first.max_temperature, Some(22.0));
+    assert_eq!(first.sum_temperature, Some(42.0));
+    assert_eq!(first.first_temperature, Some(20.0));
+    assert_eq!(first.last_temperature, Some(22.0));
+    assert_eq!(first.avg_humidity, Some(45.0));
+    assert_eq!(first.avg_pressure, Some(1000.0));
+
+    assert_eq!(second.bucket, hour + Duration::hours(1));
+    assert_eq!(second.reading_count, Some(1));
+    assert_eq!(second.avg_temperature, Some(25.0));
+    assert_eq!(second.max_humidity, Some(60.0));
+
+    Ok(())
+}
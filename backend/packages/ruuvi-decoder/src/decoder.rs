@@ -4,10 +4,21 @@
 #![cfg_attr(not(test), deny(clippy::panic))]
 
 use std::{
+    collections::HashMap,
     error::Error,
+    fmt,
+    num::ParseIntError,
+    ops::RangeInclusive,
     str,
 };
 
+use aes::{
+    cipher::{
+        BlockDecrypt,
+        KeyInit,
+    },
+    Aes128,
+};
 use serde::Serialize;
 
 pub type Acceleration = (Option<i16>, Option<i16>, Option<i16>);
@@ -31,6 +42,26 @@ pub type ByteDataDf5 = (
     u8,
 );
 
+pub type ByteDataDf3 = (u8, u8, u8, u8, u16, i16, i16, i16, u16);
+
+/// Reading in data format 3 (RAWv1), broadcast by older RuuviTag firmware.
+///
+/// DF3 has no invalid-value markers and carries no transmit power, movement
+/// counter, sequence number or MAC address.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct SensorData3 {
+    pub data_format: u8,
+    pub humidity: Option<f32>,
+    pub temperature: f32,
+    pub pressure: Option<f32>,
+    pub acceleration: f32,
+    pub acceleration_x: i16,
+    pub acceleration_y: i16,
+    pub acceleration_z: i16,
+    pub battery: Option<u16>,
+    pub rssi: Option<i8>,
+}
+
 #[derive(Debug, PartialEq, Serialize)]
 pub struct SensorData5 {
     pub data_format: u8,
@@ -49,8 +80,44 @@ pub struct SensorData5 {
     pub rssi: Option<i8>,
 }
 
+/// Magnus formula coefficients (Sonntag 1990), valid from -45 °C to 60 °C
+const MAGNUS_A: f32 = 17.62;
+const MAGNUS_B: f32 = 243.12;
+
+/// 100 Pa/hPa over 100 %, times the molar mass of water over the gas
+/// constant, in g·K/J
+const WATER_VAPOUR_FACTOR: f32 = 2.1674;
+
+impl SensorData5 {
+    /// Acceleration magnitude in standard gravity units; `acceleration`
+    /// itself is in milli-g
+    pub fn acceleration_g(&self) -> f32 {
+        self.acceleration / 1000.0
+    }
+
+    /// Dew point in °C from temperature and relative humidity, using the
+    /// Magnus formula. `None` without a humidity reading, or at 0 % where the
+    /// dew point is undefined.
+    pub fn dew_point(&self) -> Option<f32> {
+        let humidity = self.humidity.filter(|humidity| *humidity > 0.0)?;
+        let gamma =
+            (humidity / 100.0).ln() + MAGNUS_A * self.temperature / (MAGNUS_B + self.temperature);
+        Some(MAGNUS_B * gamma / (MAGNUS_A - gamma))
+    }
+
+    /// Absolute humidity in grams of water vapour per cubic metre, from the
+    /// saturation vapour pressure at the current temperature. `None` without
+    /// a humidity reading.
+    pub fn absolute_humidity(&self) -> Option<f32> {
+        let humidity = self.humidity?;
+        let saturation_hpa = 6.112 * (17.67 * self.temperature / (self.temperature + 243.5)).exp();
+        Some(saturation_hpa * humidity * WATER_VAPOUR_FACTOR / (self.temperature + 273.15))
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum SensorData {
+    Df3(SensorData3),
     Df5(SensorData5),
 }
 
@@ -69,15 +136,179 @@ pub fn parse_mac(data_format: u8, payload_mac: &str) -> String {
     }
 }
 
+/// Canonical `AA:BB:CC:DD:EE:FF` form of a MAC address given with colons,
+/// dashes or no separators in any case, such as the bare `f797e36ed811` a
+/// DF5 payload carries. Anything that is not six hex bytes is returned as is.
+pub fn normalize_mac(mac: &str) -> String {
+    let digits: String = mac.chars().filter(|c| !matches!(c, ':' | '-')).collect();
+    if digits.len() != 12 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return mac.to_string();
+    }
+    parse_mac(5, &digits)
+}
+
 pub type DecoderResult = Result<SensorData, Box<dyn Error>>;
 
+/// Trailing RSSI byte, if any, or why its hex did not parse
+type RssiResult = Result<Option<i8>, ParseIntError>;
+
+/// Decoded DF5 measurements or why the payload was rejected
+type Df5Result = Result<SensorData5, Box<dyn Error>>;
+
 pub trait Decoder {
+    /// Decode a hex-encoded manufacturer payload
     fn decode_data(&self, data: &str) -> DecoderResult;
+
+    /// Decode a manufacturer payload given as raw bytes.
+    ///
+    /// By default the bytes are hex-encoded and passed to
+    /// [`Decoder::decode_data`]; decoders override this to skip the
+    /// round trip.
+    fn decode_bytes(&self, data: &[u8]) -> DecoderResult {
+        self.decode_data(&hex::encode(data))
+    }
+}
+
+/// Highest voltage a CR2477 coin cell can report; anything above is a bad
+/// reading
+pub const MAX_PLAUSIBLE_BATTERY_MV: u16 = 3600;
+
+/// Transmit powers the nRF52 radios in RuuviTags can be configured to
+pub const PLAUSIBLE_TX_POWER_DBM: RangeInclusive<i8> = -40..=8;
+
+/// Options controlling how strictly decoded values are checked
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecoderOptions {
+    /// Report battery voltages and transmit powers the hardware cannot
+    /// produce as `None` instead of passing them through
+    pub plausibility_checks: bool,
+    /// Read the signal strength from a byte appended after the 24-byte
+    /// payload, as some gateways do
+    pub trailing_rssi: bool,
+}
+
+impl DecoderOptions {
+    /// `battery` unless plausibility checks are on and it exceeds
+    /// [`MAX_PLAUSIBLE_BATTERY_MV`]
+    pub fn plausible_battery(self, battery: Option<u16>) -> Option<u16> {
+        battery.filter(|mv| !self.plausibility_checks || *mv <= MAX_PLAUSIBLE_BATTERY_MV)
+    }
+
+    /// `tx_power` unless plausibility checks are on and it is outside
+    /// [`PLAUSIBLE_TX_POWER_DBM`]
+    pub fn plausible_txpower(self, tx_power: Option<i8>) -> Option<i8> {
+        tx_power.filter(|dbm| !self.plausibility_checks || PLAUSIBLE_TX_POWER_DBM.contains(dbm))
+    }
+}
+
+/// Length of a DF3 payload in hex characters
+const DF3_PAYLOAD_LEN: usize = 28;
+
+/// Length of a DF5 payload in hex characters
+pub const DF5_PAYLOAD_LEN: usize = 48;
+
+/// Signed RSSI in dBm from the first trailing byte, if there is one
+fn get_rssi(trailing: &str) -> RssiResult {
+    let Some(rssi_byte) = trailing.get(..2) else {
+        return Ok(None);
+    };
+    let rssi = u8::from_str_radix(rssi_byte, 16)?;
+    Ok(Some(i8::from_be_bytes([rssi])))
+}
+
+/// Length of the acceleration vector in milli-g
+fn acceleration_magnitude(acc_x: i16, acc_y: i16, acc_z: i16) -> f32 {
+    #[allow(clippy::cast_precision_loss)]
+    (((i64::from(acc_x)).pow(2) + (i64::from(acc_y)).pow(2) + (i64::from(acc_z)).pow(2)) as f32)
+        .sqrt()
 }
 
-pub struct Df5Decoder;
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Df3Decoder {
+    options: DecoderOptions,
+}
+
+impl Df3Decoder {
+    pub const fn new(options: DecoderOptions) -> Self {
+        Self { options }
+    }
+
+    pub const fn options(&self) -> DecoderOptions {
+        self.options
+    }
+
+    fn get_humidity(data: ByteDataDf3) -> f32 {
+        f32::from(data.1) / 2.0
+    }
+
+    /// Temperature is a sign bit and 7-bit integer part followed by a byte of
+    /// hundredths
+    fn get_temperature(data: ByteDataDf3) -> f32 {
+        let magnitude = f32::from(data.2 & 0x7F) + f32::from(data.3) / 100.0;
+        if data.2 & 0x80 == 0 {
+            magnitude
+        } else {
+            -magnitude
+        }
+    }
+
+    fn get_pressure(data: ByteDataDf3) -> f32 {
+        #[allow(clippy::cast_precision_loss)]
+        let pressure = (u32::from(data.4) + 50000) as f32 / 100.0;
+        pressure
+    }
+}
+
+impl Decoder for Df3Decoder {
+    fn decode_data(&self, data: &str) -> DecoderResult {
+        let payload = data.get(..DF3_PAYLOAD_LEN).ok_or_else(|| {
+            format!(
+                "DF3 payload needs {DF3_PAYLOAD_LEN} hex characters, got {}",
+                data.len()
+            )
+        })?;
+        let byte_data = hex::decode(payload)?;
+        #[allow(clippy::too_many_arguments)] // Allow too many arguments for DF3 decoding
+        let data_structure = structure!(">BBBBHhhhH");
+        let byte_data: ByteDataDf3 = data_structure.unpack(&byte_data)?;
+        if byte_data.0 != 3 {
+            return Err(format!("Expected data format 3, got {}", byte_data.0).into());
+        }
+        let rssi = if self.options.trailing_rssi {
+            get_rssi(data.get(DF3_PAYLOAD_LEN..).unwrap_or_default())?
+        } else {
+            None
+        };
+        let (acc_x, acc_y, acc_z) = (byte_data.5, byte_data.6, byte_data.7);
+        Ok(SensorData::Df3(SensorData3 {
+            data_format: 3,
+            humidity: Some(Self::get_humidity(byte_data)),
+            temperature: Self::get_temperature(byte_data),
+            pressure: Some(Self::get_pressure(byte_data)),
+            acceleration: acceleration_magnitude(acc_x, acc_y, acc_z),
+            acceleration_x: acc_x,
+            acceleration_y: acc_y,
+            acceleration_z: acc_z,
+            battery: self.options.plausible_battery(Some(byte_data.8)),
+            rssi,
+        }))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Df5Decoder {
+    options: DecoderOptions,
+}
 
 impl Df5Decoder {
+    pub const fn new(options: DecoderOptions) -> Self {
+        Self { options }
+    }
+
+    pub const fn options(&self) -> DecoderOptions {
+        self.options
+    }
+
     fn get_temperature(data: ByteDataDf5) -> Option<f32> {
         if data.1 == -32768 {
             None
@@ -153,42 +384,60 @@ impl Df5Decoder {
             data.10, data.11, data.12, data.13, data.14, data.15
         )
     }
-
-    // fn get_rssi(&self, rssi_byte: &str) -> i8 {
-    //     if let Some(rssi) = u16::from_str_radix(rssi_byte, 16).ok() {
-    //         if rssi > 127 {
-    //             -((255 - rssi as u8) as i8)
-    //         } else {
-    //             rssi as i8
-    //         }
-    //     } else {
-    //         0
-    //     }
-    // }
 }
 
-impl Decoder for Df5Decoder {
-    fn decode_data(&self, data: &str) -> Result<SensorData, Box<dyn Error>> {
-        let byte_data = hex::decode(data.chars().take(48).collect::<String>())?;
+impl Df5Decoder {
+    /// Decode a DF5 payload and take the signal strength from a trailer the
+    /// gateway delivered separately, as two hex digits of a signed byte.
+    ///
+    /// Unlike [`DecoderOptions::trailing_rssi`], an empty or malformed trailer
+    /// leaves `rssi` as `None` instead of failing the whole reading.
+    pub fn decode_with_rssi(&self, data: &str, rssi_hex: &str) -> DecoderResult {
+        let sensor_data = self.decode_df5(data)?;
+        Ok(SensorData::Df5(SensorData5 {
+            rssi: get_rssi(rssi_hex).ok().flatten(),
+            ..sensor_data
+        }))
+    }
+
+    /// Decode a DF5 payload like [`Decoder::decode_data`], but fail with
+    /// [`DecodeError::OutOfRange`] when a value is outside what the sensor can
+    /// physically measure, as a corrupt payload can decode to.
+    pub fn decode_data_validated(&self, data: &str) -> DecoderResult {
+        let sensor_data = self.decode_df5(data)?;
+        check_physical_ranges(&sensor_data)?;
+        Ok(SensorData::Df5(sensor_data))
+    }
+
+    fn decode_df5(&self, data: &str) -> Df5Result {
+        let byte_data = hex::decode(data.chars().take(DF5_PAYLOAD_LEN).collect::<String>())?;
+        let rssi = if self.options.trailing_rssi {
+            get_rssi(data.get(DF5_PAYLOAD_LEN..).unwrap_or_default())?
+        } else {
+            None
+        };
+        self.unpack_df5(&byte_data, rssi)
+    }
+
+    /// Unpack the 24 bytes of a DF5 payload
+    fn unpack_df5(&self, byte_data: &[u8], rssi: Option<i8>) -> Df5Result {
         #[allow(clippy::too_many_arguments)] // Allow too many arguments for DF5 decoding
         let data_structure = structure!(">BhHHhhhHBH6B");
-        let byte_data = data_structure.unpack(&byte_data)?;
-        // let rssi = &data[48..];
+        let byte_data = data_structure.unpack(byte_data)?;
         let (acc_x, acc_y, acc_z) = Self::get_acceleration(byte_data);
         let acc = if let (Some(acc_x_val), Some(acc_y_val), Some(acc_z_val)) = (acc_x, acc_y, acc_z)
         {
-            println!("x: {acc_x_val}, y: {acc_y_val}, z: {acc_z_val}");
-            #[allow(clippy::cast_precision_loss)]
-            Some(
-                (((i64::from(acc_x_val)).pow(2)
-                    + (i64::from(acc_y_val)).pow(2)
-                    + (i64::from(acc_z_val)).pow(2)) as f32)
-                    .sqrt(),
-            )
+            tracing::trace!(
+                acc_x = acc_x_val,
+                acc_y = acc_y_val,
+                acc_z = acc_z_val,
+                "Decoded DF5 acceleration"
+            );
+            Some(acceleration_magnitude(acc_x_val, acc_y_val, acc_z_val))
         } else {
             None
         };
-        Ok(SensorData::Df5(SensorData5 {
+        Ok(SensorData5 {
             data_format: 5,
             humidity: Self::get_humidity(byte_data),
             temperature: Self::get_temperature(byte_data).unwrap_or(0.0),
@@ -197,17 +446,297 @@ impl Decoder for Df5Decoder {
             acceleration_x: acc_x.unwrap_or(0),
             acceleration_y: acc_y.unwrap_or(0),
             acceleration_z: acc_z.unwrap_or(0),
-            tx_power: Self::get_txpower(byte_data),
-            battery: Self::get_battery(byte_data),
+            tx_power: self.options.plausible_txpower(Self::get_txpower(byte_data)),
+            battery: self.options.plausible_battery(Self::get_battery(byte_data)),
             movement_counter: Self::get_movementcounter(byte_data),
             measurement_sequence_number: Self::get_measurementsequencenumber(byte_data),
             mac: Self::get_mac(byte_data),
-            rssi: None, /* rssi: if rssi.is_empty() {
-                         *     None
-                         * } else {
-                         *     Some(self.get_rssi(rssi))
-                         * }, */
-        }))
+            rssi,
+        })
+    }
+}
+
+impl Decoder for Df5Decoder {
+    fn decode_data(&self, data: &str) -> DecoderResult {
+        Ok(SensorData::Df5(self.decode_df5(data)?))
+    }
+
+    fn decode_bytes(&self, data: &[u8]) -> DecoderResult {
+        let (payload, trailing) = data.split_at(data.len().min(DF5_PAYLOAD_LEN / 2));
+        let rssi = if self.options.trailing_rssi {
+            trailing.first().map(|rssi| i8::from_be_bytes([*rssi]))
+        } else {
+            None
+        };
+        Ok(SensorData::Df5(self.unpack_df5(payload, rssi)?))
+    }
+}
+
+/// Temperatures the RuuviTag sensor is specified for, in °C
+pub const PHYSICAL_TEMPERATURE_RANGE: RangeInclusive<f32> = -60.0..=85.0;
+
+/// Relative humidity range in %
+pub const PHYSICAL_HUMIDITY_RANGE: RangeInclusive<f32> = 0.0..=100.0;
+
+/// Air pressure range in hPa, from high mountains to deep mines
+pub const PHYSICAL_PRESSURE_RANGE: RangeInclusive<f32> = 300.0..=1300.0;
+
+/// Why a decoded reading was rejected
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    /// A value lies outside the physically possible range for its field
+    OutOfRange { field: &'static str, value: f32 },
+    /// No decryption key is configured for the sensor with this MAC
+    MissingKey(String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfRange { field, value } => {
+                write!(formatter, "{field} {value} is outside the physical range")
+            }
+            Self::MissingKey(mac) => write!(formatter, "No decryption key for sensor {mac}"),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Check the measured values against the physical ranges; missing values
+/// pass
+fn check_physical_ranges(sensor_data: &SensorData5) -> Result<(), DecodeError> {
+    let checks = [
+        (
+            "temperature",
+            Some(sensor_data.temperature),
+            &PHYSICAL_TEMPERATURE_RANGE,
+        ),
+        ("humidity", sensor_data.humidity, &PHYSICAL_HUMIDITY_RANGE),
+        ("pressure", sensor_data.pressure, &PHYSICAL_PRESSURE_RANGE),
+    ];
+    for (field, value, range) in checks {
+        if let Some(value) = value.filter(|value| !range.contains(value)) {
+            return Err(DecodeError::OutOfRange { field, value });
+        }
+    }
+    Ok(())
+}
+
+/// Length of a DF8 payload in hex characters
+const DF8_PAYLOAD_LEN: usize = 48;
+
+/// 128-bit AES key that decrypts a sensor's DF8 payloads
+pub type AesKey = [u8; 16];
+
+/// Raw DF8 payload bytes, before decryption
+type Df8Payload = [u8; 24];
+
+/// Decoder for data format 8, whose measurements are encrypted with
+/// AES-128-ECB using a key per sensor.
+///
+/// The payload is the format byte, one encrypted 16-byte block, a CRC8 of
+/// that block and the 6-byte MAC. The decrypted block holds temperature,
+/// humidity, pressure, power info, movement counter and sequence number
+/// with the same scaling as DF5, followed by padding. DF8 carries no
+/// acceleration, which decodes as zero.
+#[derive(Debug, Clone, Default)]
+pub struct Df8Decoder {
+    options: DecoderOptions,
+    /// Keys by MAC address in `AA:BB:CC:DD:EE:FF` form
+    keys: HashMap<String, AesKey>,
+}
+
+impl Df8Decoder {
+    /// Create a decoder for the sensors in `keys`, with MACs in any form
+    /// [`normalize_mac`] accepts
+    pub fn new(options: DecoderOptions, keys: HashMap<String, AesKey>) -> Self {
+        Self {
+            options,
+            keys: keys
+                .into_iter()
+                .map(|(mac, key)| (normalize_mac(&mac), key))
+                .collect(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_key(mut self, sensor_mac: &str, key: AesKey) -> Self {
+        self.keys.insert(normalize_mac(sensor_mac), key);
+        self
+    }
+
+    pub const fn options(&self) -> DecoderOptions {
+        self.options
+    }
+
+    fn decode_df8(&self, data: &str) -> Df5Result {
+        let payload: Df8Payload =
+            hex::decode(data.chars().take(DF8_PAYLOAD_LEN).collect::<String>())?
+                .try_into()
+                .map_err(|bytes: Vec<u8>| {
+                    format!("DF8 payload needs 24 bytes, got {}", bytes.len())
+                })?;
+        let [format, encrypted @ .., checksum, m0, m1, m2, m3, m4, m5] = payload;
+        if format != 8 {
+            return Err(format!("Expected data format 8, got {format}").into());
+        }
+        let mut block = encrypted;
+        if crc8(&block) != checksum {
+            return Err("DF8 checksum does not match the encrypted data".into());
+        }
+
+        let mac = format!("{m0:02x}{m1:02x}{m2:02x}{m3:02x}{m4:02x}{m5:02x}");
+        let key = self
+            .keys
+            .get(&normalize_mac(&mac))
+            .ok_or_else(|| DecodeError::MissingKey(normalize_mac(&mac)))?;
+        Aes128::new(key.into()).decrypt_block((&mut block).into());
+
+        let rssi = if self.options.trailing_rssi {
+            get_rssi(data.get(DF8_PAYLOAD_LEN..).unwrap_or_default())?
+        } else {
+            None
+        };
+        // The movement counter is 16 bits; only its low byte is kept so the
+        // counter wraps like the DF5 one
+        let [t0, t1, h0, h1, p0, p1, w0, w1, _, movement_counter, s0, s1, ..] = block;
+        let temperature = i16::from_be_bytes([t0, t1]);
+        let humidity = u16::from_be_bytes([h0, h1]);
+        let pressure = u16::from_be_bytes([p0, p1]);
+        let power_info = u16::from_be_bytes([w0, w1]);
+        let (battery, tx_power) = (power_info >> 5, power_info & 0b1_1111);
+
+        Ok(SensorData5 {
+            data_format: 8,
+            humidity: (humidity != 0xFFFF).then(|| f32::from(humidity) / 400.0),
+            temperature: if temperature == i16::MIN {
+                0.0
+            } else {
+                f32::from(temperature) / 200.0
+            },
+            pressure: (pressure != 0xFFFF).then(|| (f32::from(pressure) + 50_000.0) / 100.0),
+            acceleration: 0.0,
+            acceleration_x: 0,
+            acceleration_y: 0,
+            acceleration_z: 0,
+            tx_power: self.options.plausible_txpower(
+                i8::try_from(tx_power)
+                    .ok()
+                    .filter(|steps| *steps != 0b1_1111)
+                    .map(|steps| steps * 2 - 40),
+            ),
+            battery: self
+                .options
+                .plausible_battery((battery != 0b111_1111_1111).then(|| battery + 1600)),
+            movement_counter,
+            measurement_sequence_number: u16::from_be_bytes([s0, s1]),
+            mac,
+            rssi,
+        })
+    }
+}
+
+impl Decoder for Df8Decoder {
+    fn decode_data(&self, data: &str) -> DecoderResult {
+        Ok(SensorData::Df5(self.decode_df8(data)?))
+    }
+}
+
+/// CRC-8 with polynomial 0x07 and no reflection, as DF8 uses over its
+/// encrypted block
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 == 0 {
+                crc << 1
+            } else {
+                (crc << 1) ^ 0x07
+            }
+        })
+    })
+}
+
+/// Why a payload could not be handed to a decoder for its data format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatError {
+    /// The payload does not start with a hex-encoded data format byte
+    MissingFormat,
+    /// No decoder exists for this data format
+    UnsupportedFormat(u8),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingFormat => write!(formatter, "Payload has no data format byte"),
+            Self::UnsupportedFormat(format) => {
+                write!(formatter, "Unsupported data format {format}")
+            }
+        }
+    }
+}
+
+impl Error for FormatError {}
+
+/// Manufacturer-specific data type and Ruuvi Innovations' company ID that
+/// start the Ruuvi payload within a BLE advertisement
+const RUUVI_MANUFACTURER_HEADER: &str = "FF9904";
+
+/// Ruuvi manufacturer payload of a BLE advertisement such as the
+/// `0201061BFF9904…` a gateway relays: everything after the manufacturer data
+/// header, including any bytes the gateway appended. Data that holds no
+/// Ruuvi manufacturer data, such as a bare payload, is returned as is.
+pub fn manufacturer_payload(data: &str) -> &str {
+    let mut offset = 0_usize;
+    // Walk the advertisement's length-prefixed structures
+    while let Some(length) = data
+        .get(offset..offset.saturating_add(2))
+        .and_then(|length| u8::from_str_radix(length, 16).ok())
+        .filter(|length| *length > 0)
+    {
+        let start = offset.saturating_add(2);
+        let header_end = start.saturating_add(RUUVI_MANUFACTURER_HEADER.len());
+        if data
+            .get(start..header_end)
+            .is_some_and(|header| header.eq_ignore_ascii_case(RUUVI_MANUFACTURER_HEADER))
+        {
+            return data.get(header_end..).unwrap_or(data);
+        }
+        offset = start.saturating_add(usize::from(length) * 2);
+    }
+    data
+}
+
+/// Data format of a manufacturer payload, read from its first byte
+pub fn data_format(data: &str) -> Result<u8, FormatError> {
+    data.get(..2)
+        .and_then(|format| u8::from_str_radix(format, 16).ok())
+        .ok_or(FormatError::MissingFormat)
+}
+
+/// Decode a manufacturer payload with the decoder for the data format it
+/// declares, so a fleet of tags on different firmware can share one
+/// pipeline. A full BLE advertisement is reduced to its payload with
+/// [`manufacturer_payload`] first.
+///
+/// Formats without a decoder fail with a [`FormatError`] instead of being
+/// decoded as something they are not. Encrypted DF8 payloads need keys, so
+/// here they fail with [`DecodeError::MissingKey`]; use
+/// [`detect_and_decode_with`] to supply them.
+pub fn detect_and_decode(data: &str) -> DecoderResult {
+    detect_and_decode_with(data, &Df8Decoder::default())
+}
+
+/// [`detect_and_decode`] with the decoder, and so the keys, used for DF8;
+/// its options apply to every data format
+pub fn detect_and_decode_with(data: &str, df8: &Df8Decoder) -> DecoderResult {
+    let data = manufacturer_payload(data);
+    match data_format(data)? {
+        3 => Df3Decoder::new(df8.options()).decode_data(data),
+        5 => Df5Decoder::new(df8.options()).decode_data(data),
+        8 => df8.decode_data(data),
+        format => Err(FormatError::UnsupportedFormat(format).into()),
     }
 }
 
@@ -255,7 +784,7 @@ mod test {
         let splitted = splitted.get(1).expect("Split");
         println!("{data:?}: {}, {splitted:?}: {}", data.len(), splitted.len());
 
-        let decoder = Df5Decoder {};
+        let decoder = Df5Decoder::default();
         #[allow(clippy::expect_used)]
         let result = decoder.decode_data(splitted).expect("Decode");
         assert_eq!(result, SensorData::Df5(expected));
@@ -263,20 +792,422 @@ mod test {
 
     #[test]
     fn test_df5_decoder_direct() {
-        let decoder = Df5Decoder {};
+        let decoder = Df5Decoder::default();
 
         // Test with known valid hex data
         let hex_data = "0201061BFF9904050F18FFFFFFFFFFF0FFEC0414AA96A8DE8EF797E36ED811";
-        let SensorData::Df5(data) = decoder.decode_data(hex_data).unwrap();
+        let SensorData::Df5(data) = decoder.decode_data(hex_data).unwrap() else {
+            panic!("Expected DF5 data");
+        };
 
         assert_eq!(data.data_format, 5);
         assert!(data.temperature > -50.0 && data.temperature < 100.0);
         assert!(data.acceleration >= 0.0);
     }
 
+    /// Set in the child process spawned by `test_decode_is_silent_on_stdout`
+    const SILENT_DECODE_CHILD: &str = "RUUVI_DECODER_SILENT_DECODE_CHILD";
+    const STDOUT_BEGIN: &str = "<<decode-begin>>";
+    const STDOUT_END: &str = "<<decode-end>>";
+
+    #[test]
+    fn test_decode_is_silent_on_stdout() {
+        let hex_data = "0201061BFF9904050F18FFFFFFFFFFF0FFEC0414AA96A8DE8EF797E36ED811";
+
+        if std::env::var_os(SILENT_DECODE_CHILD).is_some() {
+            println!("{STDOUT_BEGIN}");
+            let _ = Df5Decoder::default().decode_data(hex_data);
+            println!("{STDOUT_END}");
+            return;
+        }
+
+        // The test harness captures print! output in-process, so re-run this
+        // test in a child with capturing disabled and inspect its real stdout
+        #[allow(clippy::expect_used)]
+        let output = std::process::Command::new(std::env::current_exe().expect("Test binary"))
+            .args([
+                "--exact",
+                "decoder::test::test_decode_is_silent_on_stdout",
+                "--nocapture",
+            ])
+            .env(SILENT_DECODE_CHILD, "1")
+            .output()
+            .expect("Run child test");
+        assert!(output.status.success());
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let decode_output = stdout
+            .split_once(STDOUT_BEGIN)
+            .and_then(|(_, rest)| rest.split_once(STDOUT_END))
+            .map(|(between, _)| between.trim());
+        assert_eq!(
+            decode_output,
+            Some(""),
+            "Decoding wrote to stdout: {stdout}"
+        );
+    }
+
+    /// DF5 payload from the fixture with the power info field replaced
+    fn df5_with_power_info(power_info: &str) -> String {
+        format!("050F18FFFFFFFFFFF0FFEC0414{power_info}A8DE8EF797E36ED811")
+    }
+
+    // Battery millivolts and TX power dBm decoded from the power field
+    type PowerInfo = (Option<u16>, Option<i8>);
+
+    fn decode_power(decoder: Df5Decoder, power_info: &str) -> PowerInfo {
+        #[allow(clippy::expect_used)]
+        let SensorData::Df5(data) = decoder
+            .decode_data(&df5_with_power_info(power_info))
+            .expect("Decode")
+        else {
+            panic!("Expected DF5 data");
+        };
+        (data.battery, data.tx_power)
+    }
+
+    #[test]
+    fn test_plausibility_checks_keep_normal_values() {
+        let checked = Df5Decoder::new(DecoderOptions {
+            plausibility_checks: true,
+            ..DecoderOptions::default()
+        });
+
+        // 2964 mV, +4 dBm
+        assert_eq!(decode_power(checked, "AA96"), (Some(2964), Some(4)));
+        assert_eq!(
+            decode_power(Df5Decoder::default(), "AA96"),
+            (Some(2964), Some(4))
+        );
+    }
+
+    #[test]
+    fn test_plausibility_checks_all_ones_sentinel() {
+        let checked = Df5Decoder::new(DecoderOptions {
+            plausibility_checks: true,
+            ..DecoderOptions::default()
+        });
+
+        assert_eq!(decode_power(checked, "FFFF"), (None, None));
+        assert_eq!(decode_power(Df5Decoder::default(), "FFFF"), (None, None));
+    }
+
+    #[test]
+    fn test_plausibility_checks_reject_implausible_values() {
+        let checked = Df5Decoder::new(DecoderOptions {
+            plausibility_checks: true,
+            ..DecoderOptions::default()
+        });
+
+        // 3640 mV is above what a CR2477 can deliver
+        assert_eq!(decode_power(checked, "FF16"), (None, Some(4)));
+        assert_eq!(
+            decode_power(Df5Decoder::default(), "FF16"),
+            (Some(3640), Some(4))
+        );
+
+        // +20 dBm is beyond the radio's maximum output
+        assert_eq!(decode_power(checked, "AA9E"), (Some(2964), None));
+        assert_eq!(
+            decode_power(Df5Decoder::default(), "AA9E"),
+            (Some(2964), Some(20))
+        );
+    }
+
+    #[test]
+    fn test_decoder_options_plausibility_bounds() {
+        let checked = DecoderOptions {
+            plausibility_checks: true,
+            ..DecoderOptions::default()
+        };
+
+        assert_eq!(checked.plausible_battery(Some(3600)), Some(3600));
+        assert_eq!(checked.plausible_battery(Some(3601)), None);
+        assert_eq!(checked.plausible_txpower(Some(-40)), Some(-40));
+        assert_eq!(checked.plausible_txpower(Some(9)), None);
+        assert_eq!(
+            DecoderOptions::default().plausible_battery(Some(3601)),
+            Some(3601)
+        );
+        assert_eq!(
+            DecoderOptions::default().plausible_txpower(Some(9)),
+            Some(9)
+        );
+    }
+
+    fn decode_rssi(decoder: Df5Decoder, data: &str) -> Option<i8> {
+        #[allow(clippy::expect_used)]
+        let SensorData::Df5(decoded) = decoder.decode_data(data).expect("Decode") else {
+            panic!("Expected DF5 data");
+        };
+        decoded.rssi
+    }
+
+    #[rstest]
+    #[case("C5", Some(-59))]
+    #[case("7F", Some(127))]
+    #[case("80", Some(-128))]
+    #[case("FF", Some(-1))]
+    #[case("", None)]
+    fn test_trailing_rssi(#[case] trailing: &str, #[case] expected: Option<i8>) {
+        let decoder = Df5Decoder::new(DecoderOptions {
+            trailing_rssi: true,
+            ..DecoderOptions::default()
+        });
+        let data = format!("{}{trailing}", df5_with_power_info("AA96"));
+
+        assert_eq!(decode_rssi(decoder, &data), expected);
+    }
+
+    #[rstest]
+    #[case("3C", Some(60))]
+    #[case("7F", Some(127))]
+    #[case("80", Some(-128))]
+    #[case("C5", Some(-59))]
+    #[case("c5", Some(-59))]
+    #[case("", None)]
+    #[case("C", None)]
+    #[case("ZZ", None)]
+    #[case("-5", None)]
+    fn test_decode_with_rssi(#[case] rssi_hex: &str, #[case] expected: Option<i8>) {
+        let SensorData::Df5(decoded) = Df5Decoder::default()
+            .decode_with_rssi(&df5_with_power_info("AA96"), rssi_hex)
+            .expect("Decode")
+        else {
+            panic!("Expected DF5 data");
+        };
+
+        assert_eq!(decoded.rssi, expected);
+        assert_eq!(decoded.battery, Some(2964));
+    }
+
+    #[test]
+    fn test_decode_with_rssi_invalid_payload() {
+        assert!(Df5Decoder::default().decode_with_rssi("05", "C5").is_err());
+    }
+
+    #[rstest]
+    #[case(
+        DecoderOptions::default(),
+        "050F18FFFFFFFFFFF0FFEC0414AA96A8DE8EF797E36ED811"
+    )]
+    #[case(
+        DecoderOptions { trailing_rssi: true, ..DecoderOptions::default() },
+        "050F18FFFFFFFFFFF0FFEC0414AA96A8DE8EF797E36ED811C5"
+    )]
+    #[allow(clippy::expect_used)]
+    fn test_decode_bytes_matches_decode_data(#[case] options: DecoderOptions, #[case] data: &str) {
+        let decoder = Df5Decoder::new(options);
+        let bytes = hex::decode(data).expect("Hex payload");
+
+        assert_eq!(
+            decoder.decode_bytes(&bytes).expect("Decode bytes"),
+            decoder.decode_data(data).expect("Decode hex")
+        );
+        assert!(decoder.decode_bytes(&bytes[..10]).is_err());
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_default_decode_bytes_goes_through_hex() {
+        let data = "03291A1ECE1EFC18F94202CA0B53";
+        let bytes = hex::decode(data).expect("Hex payload");
+
+        assert_eq!(
+            Df3Decoder::default()
+                .decode_bytes(&bytes)
+                .expect("Decode bytes"),
+            Df3Decoder::default().decode_data(data).expect("Decode hex")
+        );
+    }
+
+    #[test]
+    fn test_trailing_rssi_disabled_by_default() {
+        let data = format!("{}C5", df5_with_power_info("AA96"));
+
+        assert_eq!(decode_rssi(Df5Decoder::default(), &data), None);
+    }
+
+    #[test]
+    fn test_trailing_rssi_invalid_hex() {
+        let decoder = Df5Decoder::new(DecoderOptions {
+            trailing_rssi: true,
+            ..DecoderOptions::default()
+        });
+        let data = format!("{}ZZ", df5_with_power_info("AA96"));
+
+        assert!(decoder.decode_data(&data).is_err());
+    }
+
+    // Test vectors from the Ruuvi data format 3 specification
+    #[rstest]
+    #[case("03291A1ECE1EFC18F94202CA0B53", SensorData3 {
+        data_format: 3,
+        humidity: Some(20.5),
+        temperature: 26.3,
+        pressure: Some(1027.66),
+        acceleration: 2_118.695_8,
+        acceleration_x: -1000,
+        acceleration_y: -1726,
+        acceleration_z: 714,
+        battery: Some(2899),
+        rssi: None
+    })]
+    #[case("03FF7F63FFFF7FFF7FFF7FFFFFFF", SensorData3 {
+        data_format: 3,
+        humidity: Some(127.5),
+        temperature: 127.99,
+        pressure: Some(1155.35),
+        acceleration: 56_754.11,
+        acceleration_x: 32767,
+        acceleration_y: 32767,
+        acceleration_z: 32767,
+        battery: Some(65535),
+        rssi: None
+    })]
+    #[case("0300FF6300008001800180010000", SensorData3 {
+        data_format: 3,
+        humidity: Some(0.0),
+        temperature: -127.99,
+        pressure: Some(500.0),
+        acceleration: 56_754.11,
+        acceleration_x: -32767,
+        acceleration_y: -32767,
+        acceleration_z: -32767,
+        battery: Some(0),
+        rssi: None
+    })]
+    fn test_df3_decoder(#[case] encoded: &str, #[case] expected: SensorData3) {
+        let result = Df3Decoder::default().decode_data(encoded).expect("Decode");
+        assert_eq!(result, SensorData::Df3(expected));
+    }
+
+    #[test]
+    fn test_df3_trailing_rssi_and_plausibility() {
+        let decoder = Df3Decoder::new(DecoderOptions {
+            plausibility_checks: true,
+            trailing_rssi: true,
+        });
+
+        let SensorData::Df3(data) = decoder
+            .decode_data("03291A1ECE1EFC18F94202CA0B53C5")
+            .expect("Decode")
+        else {
+            panic!("Expected DF3 data");
+        };
+        assert_eq!(data.rssi, Some(-59));
+        assert_eq!(data.battery, Some(2899));
+
+        let SensorData::Df3(data) = decoder
+            .decode_data("03FF7F63FFFF7FFF7FFF7FFFFFFF")
+            .expect("Decode")
+        else {
+            panic!("Expected DF3 data");
+        };
+        assert_eq!(data.battery, None);
+    }
+
+    #[test]
+    fn test_df3_decoder_error_cases() {
+        let decoder = Df3Decoder::default();
+
+        // Truncated payloads
+        assert!(decoder.decode_data("").is_err());
+        assert!(decoder.decode_data("03").is_err());
+        assert!(decoder.decode_data("03291A1ECE1EFC18F94202CA0B").is_err());
+
+        // Not hex
+        assert!(decoder.decode_data("03291A1ECE1EFC18F94202CA0BZZ").is_err());
+
+        // A DF5 payload is long enough but has the wrong format byte
+        assert!(decoder.decode_data(&df5_with_power_info("AA96")).is_err());
+    }
+
+    #[test]
+    fn test_detect_and_decode_dispatches_on_format() {
+        let df3 = detect_and_decode("03291A1ECE1EFC18F94202CA0B53").expect("Decode DF3");
+        assert!(matches!(df3, SensorData::Df3(ref data) if data.battery == Some(2899)));
+
+        let df5 = detect_and_decode(&df5_with_power_info("AA96")).expect("Decode DF5");
+        assert!(matches!(df5, SensorData::Df5(ref data) if data.battery == Some(2964)));
+    }
+
+    #[test]
+    fn test_manufacturer_payload_strips_advertisement() {
+        let payload = "050F18FFFFFFFFFFF0FFEC0414AA96A8DE8EF797E36ED811";
+
+        assert_eq!(
+            manufacturer_payload(&format!("0201061BFF9904{payload}")),
+            payload
+        );
+        assert_eq!(
+            manufacturer_payload(&format!("0201061bff9904{payload}C5")),
+            format!("{payload}C5")
+        );
+        // Bare payloads and other manufacturers' data are left alone
+        assert_eq!(manufacturer_payload(payload), payload);
+        assert_eq!(
+            manufacturer_payload("03291A1ECE1EFC18F94202CA0B53"),
+            "03291A1ECE1EFC18F94202CA0B53"
+        );
+        assert_eq!(
+            manufacturer_payload("0201061BFF4C00050F18"),
+            "0201061BFF4C00050F18"
+        );
+        assert_eq!(manufacturer_payload(""), "");
+    }
+
+    #[test]
+    fn test_detect_and_decode_gateway_advertisement() {
+        let decoded =
+            detect_and_decode("0201061BFF9904050F18FFFFFFFFFFF0FFEC0414AA96A8DE8EF797E36ED811")
+                .expect("Decode advertisement");
+
+        assert!(matches!(decoded, SensorData::Df5(ref data)
+            if data.mac == "f797e36ed811" && (data.temperature - 19.32).abs() < 1e-4));
+    }
+
+    #[test]
+    fn test_detect_and_decode_with_applies_options() {
+        let options = DecoderOptions {
+            plausibility_checks: true,
+            trailing_rssi: true,
+        };
+        let decoder = Df8Decoder::new(options, HashMap::new());
+
+        let decoded =
+            detect_and_decode_with("03291A1ECE1EFC18F94202CA0B53C5", &decoder).expect("Decode DF3");
+
+        assert!(matches!(decoded, SensorData::Df3(ref data) if data.rssi == Some(-59)));
+    }
+
+    fn format_error(data: &str) -> Option<FormatError> {
+        detect_and_decode(data)
+            .err()
+            .and_then(|error| error.downcast_ref::<FormatError>().cloned())
+    }
+
+    #[test]
+    fn test_detect_and_decode_rejects_unknown_formats() {
+        assert_eq!(
+            format_error("0400112233445566778899AABBCC"),
+            Some(FormatError::UnsupportedFormat(4))
+        );
+        assert_eq!(
+            format_error("C5FFFFFFFFFFFFFF"),
+            Some(FormatError::UnsupportedFormat(0xC5))
+        );
+        assert_eq!(format_error(""), Some(FormatError::MissingFormat));
+        assert_eq!(format_error("ZZ0102"), Some(FormatError::MissingFormat));
+
+        // A known format with a truncated body fails in its decoder instead
+        assert!(detect_and_decode("0329").is_err());
+        assert_eq!(format_error("0329"), None);
+    }
+
     #[test]
     fn test_df5_decoder_error_cases() {
-        let decoder = Df5Decoder {};
+        let decoder = Df5Decoder::default();
 
         // Test with invalid hex data
         let invalid_hex = "INVALID_HEX_DATA";
@@ -294,7 +1225,7 @@ mod test {
 
     #[test]
     fn test_df5_decoder_boundary_values() {
-        let decoder = Df5Decoder {};
+        let decoder = Df5Decoder::default();
 
         // Test with minimum length valid data (may still fail due to content)
         let min_data = "0500000000000000000000000000000000000000";
@@ -341,6 +1272,272 @@ mod test {
         assert_eq!(sensor_data.mac, "AA:BB:CC:DD:EE:FF");
     }
 
+    fn climate_reading(temperature: f32, humidity: Option<f32>) -> SensorData5 {
+        SensorData5 {
+            data_format: 5,
+            humidity,
+            temperature,
+            pressure: None,
+            acceleration: 1.0,
+            acceleration_x: 0,
+            acceleration_y: 0,
+            acceleration_z: 1000,
+            tx_power: None,
+            battery: None,
+            movement_counter: 0,
+            measurement_sequence_number: 1,
+            mac: "AA:BB:CC:DD:EE:FF".to_string(),
+            rssi: None,
+        }
+    }
+
+    #[rstest]
+    #[case(20.0, 50.0, 9.255)]
+    #[case(25.0, 60.0, 16.693)]
+    #[case(0.0, 100.0, 0.0)]
+    #[case(-10.0, 80.0, -12.797)]
+    #[case(30.0, 90.0, 28.178)]
+    fn test_dew_point(#[case] temperature: f32, #[case] humidity: f32, #[case] expected: f32) {
+        let Some(dew_point) = climate_reading(temperature, Some(humidity)).dew_point() else {
+            panic!("Expected a dew point for {temperature} °C at {humidity} %");
+        };
+        assert!(
+            (dew_point - expected).abs() < 0.01,
+            "Expected {expected}, got {dew_point}"
+        );
+    }
+
+    #[rstest]
+    #[case(20.0, 50.0, 8.639)]
+    #[case(0.0, 100.0, 4.850)]
+    #[case(30.0, 80.0, 24.283)]
+    #[case(-10.0, 60.0, 1.417)]
+    fn test_absolute_humidity(
+        #[case] temperature: f32,
+        #[case] humidity: f32,
+        #[case] expected: f32,
+    ) {
+        let Some(absolute) = climate_reading(temperature, Some(humidity)).absolute_humidity()
+        else {
+            panic!("Expected absolute humidity for {temperature} °C at {humidity} %");
+        };
+        assert!(
+            (absolute - expected).abs() < 0.01,
+            "Expected {expected}, got {absolute}"
+        );
+    }
+
+    #[test]
+    fn test_absolute_humidity_without_humidity() {
+        assert_eq!(climate_reading(20.0, None).absolute_humidity(), None);
+        assert_eq!(
+            climate_reading(20.0, Some(0.0)).absolute_humidity(),
+            Some(0.0)
+        );
+    }
+
+    #[test]
+    fn test_acceleration_g() {
+        let stationary = SensorData5 {
+            acceleration: acceleration_magnitude(-16, -20, 1044),
+            ..climate_reading(20.0, None)
+        };
+
+        assert!((stationary.acceleration_g() - 1.044).abs() < 0.001);
+        assert!((climate_reading(20.0, None).acceleration_g() - 0.001).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_dew_point_without_humidity() {
+        assert_eq!(climate_reading(20.0, None).dew_point(), None);
+        assert_eq!(climate_reading(20.0, Some(0.0)).dew_point(), None);
+    }
+
+    #[rstest]
+    #[case("f797e36ed811", "F7:97:E3:6E:D8:11")]
+    #[case("F7:97:E3:6E:D8:11", "F7:97:E3:6E:D8:11")]
+    #[case("f7:97:e3:6e:d8:11", "F7:97:E3:6E:D8:11")]
+    #[case("F7-97-E3-6E-D8-11", "F7:97:E3:6E:D8:11")]
+    #[case("not-a-mac", "not-a-mac")]
+    #[case("f797e36ed8", "f797e36ed8")]
+    fn test_normalize_mac(#[case] mac: &str, #[case] expected: &str) {
+        assert_eq!(normalize_mac(mac), expected);
+    }
+
+    fn out_of_range(data: &str) -> Option<DecodeError> {
+        Df5Decoder::default()
+            .decode_data_validated(data)
+            .err()
+            .and_then(|error| error.downcast_ref::<DecodeError>().cloned())
+    }
+
+    #[rstest]
+    // 160 °C
+    #[case(
+        "057D00FFFFFFFFFFF0FFEC0414AA96A8DE8EF797E36ED811",
+        "temperature",
+        160.0
+    )]
+    // -61 °C
+    #[case("05D058FFFFFFFFFFF0FFEC0414AA96A8DE8EF797E36ED811", "temperature", -61.0)]
+    // 120 %
+    #[case("050F18BB80FFFFFFF0FFEC0414AA96A8DE8EF797E36ED811", "humidity", 120.0)]
+    fn test_decode_data_validated_rejects_out_of_range(
+        #[case] data: &str,
+        #[case] field: &'static str,
+        #[case] value: f32,
+    ) {
+        assert_eq!(
+            out_of_range(data),
+            Some(DecodeError::OutOfRange { field, value })
+        );
+        // The lenient decoder still passes the value through
+        assert!(Df5Decoder::default().decode_data(data).is_ok());
+    }
+
+    #[test]
+    fn test_decode_data_validated_accepts_bounds() {
+        // 85 °C, 100 % and missing pressure
+        let data = "0542689C40FFFFFFF0FFEC0414AA96A8DE8EF797E36ED811";
+        let Ok(SensorData::Df5(sensor_data)) = Df5Decoder::default().decode_data_validated(data)
+        else {
+            panic!("Expected a valid reading");
+        };
+
+        assert!((sensor_data.temperature - 85.0).abs() < f32::EPSILON);
+        assert_eq!(sensor_data.humidity, Some(100.0));
+        assert!(Df5Decoder::default().decode_data_validated("05").is_err());
+    }
+
+    #[test]
+    fn test_pressure_range() {
+        // DF5 cannot encode pressures outside 500-1155 hPa, so the bound is
+        // only reachable through the check itself
+        let reading = |pressure| SensorData5 {
+            pressure: Some(pressure),
+            ..climate_reading(20.0, Some(50.0))
+        };
+
+        assert_eq!(check_physical_ranges(&reading(1013.25)), Ok(()));
+        assert_eq!(
+            check_physical_ranges(&reading(1400.0)),
+            Err(DecodeError::OutOfRange {
+                field: "pressure",
+                value: 1400.0
+            })
+        );
+        assert_eq!(
+            check_physical_ranges(&reading(250.0)),
+            Err(DecodeError::OutOfRange {
+                field: "pressure",
+                value: 250.0
+            })
+        );
+    }
+
+    const DF8_KEY: AesKey = *b"ruuvi-home-key16";
+
+    /// Encrypt a DF8 measurement block for tag F7:97:E3:6E:D8:11
+    fn df8_payload(plaintext: AesKey) -> String {
+        use aes::cipher::BlockEncrypt;
+
+        let mut block = plaintext;
+        Aes128::new(&DF8_KEY.into()).encrypt_block((&mut block).into());
+        format!(
+            "08{}{:02X}F797E36ED811",
+            hex::encode_upper(block),
+            crc8(&block)
+        )
+    }
+
+    fn df8_plaintext() -> AesKey {
+        [
+            0x10, 0xCC, // 21.5 °C
+            0x46, 0x50, // 45 %
+            0xC8, 0x7D, // 1013.25 hPa
+            0xA2, 0x96, // 2900 mV, 4 dBm
+            0x01, 0x02, // movement counter 258
+            0x03, 0x04, // sequence number 772
+            0x00, 0x00, 0x00, 0x00,
+        ]
+    }
+
+    #[test]
+    fn test_df8_round_trip() {
+        let decoder = Df8Decoder::default().with_key("f7:97:e3:6e:d8:11", DF8_KEY);
+
+        let Ok(SensorData::Df5(sensor_data)) = decoder.decode_data(&df8_payload(df8_plaintext()))
+        else {
+            panic!("Expected a decrypted reading");
+        };
+
+        assert_eq!(
+            sensor_data,
+            SensorData5 {
+                data_format: 8,
+                humidity: Some(45.0),
+                temperature: 21.5,
+                pressure: Some(1013.25),
+                acceleration: 0.0,
+                acceleration_x: 0,
+                acceleration_y: 0,
+                acceleration_z: 0,
+                tx_power: Some(4),
+                battery: Some(2900),
+                movement_counter: 2,
+                measurement_sequence_number: 772,
+                mac: "f797e36ed811".to_string(),
+                rssi: None,
+            }
+        );
+    }
+
+    fn decode_error(result: &DecoderResult) -> Option<DecodeError> {
+        result
+            .as_ref()
+            .err()
+            .and_then(|error| error.downcast_ref::<DecodeError>().cloned())
+    }
+
+    #[test]
+    fn test_df8_missing_key() {
+        let payload = df8_payload(df8_plaintext());
+        let other_tag = Df8Decoder::new(
+            DecoderOptions::default(),
+            HashMap::from([("AABBCCDDEEFF".to_string(), DF8_KEY)]),
+        );
+
+        let missing = Some(DecodeError::MissingKey("F7:97:E3:6E:D8:11".to_string()));
+        assert_eq!(decode_error(&other_tag.decode_data(&payload)), missing);
+        assert_eq!(decode_error(&detect_and_decode(&payload)), missing);
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_df8_rejects_corrupt_payloads() {
+        let decoder = Df8Decoder::default().with_key("F7:97:E3:6E:D8:11", DF8_KEY);
+        let payload = df8_payload(df8_plaintext());
+
+        // Flip a bit of the checksum
+        let checksum = u8::from_str_radix(&payload[34..36], 16).expect("Hex checksum");
+        let mut corrupt = payload.clone();
+        corrupt.replace_range(34..36, &format!("{:02X}", checksum ^ 0x01));
+        assert!(decoder.decode_data(&corrupt).is_err());
+        assert!(decoder.decode_data(&payload[..40]).is_err());
+        assert!(decoder
+            .decode_data(&payload.replacen("08", "05", 1))
+            .is_err());
+    }
+
+    #[test]
+    fn test_detect_and_decode_with_df8_keys() {
+        let decoder = Df8Decoder::default().with_key("F7:97:E3:6E:D8:11", DF8_KEY);
+
+        let decoded = detect_and_decode_with(&df8_payload(df8_plaintext()), &decoder);
+
+        assert!(matches!(decoded, Ok(SensorData::Df5(ref data)) if data.data_format == 8));
+    }
+
     #[test]
     fn test_sensor_data5_optional_fields() {
         let sensor_data = SensorData5 {
@@ -419,6 +1616,7 @@ mod test {
                 assert_eq!(data.data_format, 5);
                 assert!((data.temperature - 25.0).abs() < f32::EPSILON);
             }
+            SensorData::Df3(_) => panic!("Expected DF5 data"),
         }
     }
 
@@ -514,7 +1712,7 @@ mod test {
 
     #[test]
     fn test_df5_decoder_various_inputs() {
-        let decoder = Df5Decoder {};
+        let decoder = Df5Decoder::default();
 
         // Test various hex string formats
         let test_cases = [
@@ -542,7 +1740,7 @@ mod test {
 
     #[test]
     fn test_decoder_trait() {
-        let decoder = Df5Decoder {};
+        let decoder = Df5Decoder::default();
 
         // Test that Df5Decoder implements the Decoder trait
         let hex_data = "051B1A00FF00040301002100C9004001DE007F";
@@ -0,0 +1,417 @@
+//! Types and the storage interface shared by the sensor data stores
+
+use std::fmt::Debug;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{
+    DateTime,
+    Utc,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use thiserror::Error;
+use tokio::sync::broadcast;
+
+/// One reading of a sensor as relayed by a gateway
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct Event {
+    pub sensor_mac: String,
+    pub gateway_mac: String,
+    pub temperature: f64,
+    pub humidity: f64,
+    pub pressure: f64,
+    pub battery: i64,
+    pub tx_power: i64,
+    pub movement_counter: i64,
+    pub measurement_sequence_number: i64,
+    pub acceleration: f64,
+    pub acceleration_x: i64,
+    pub acceleration_y: i64,
+    pub acceleration_z: i64,
+    pub rssi: i64,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Event {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_current_time(
+        sensor_mac: String,
+        gateway_mac: String,
+        temperature: f64,
+        humidity: f64,
+        pressure: f64,
+        battery: i64,
+        tx_power: i64,
+        movement_counter: i64,
+        measurement_sequence_number: i64,
+        acceleration: f64,
+        acceleration_x: i64,
+        acceleration_y: i64,
+        acceleration_z: i64,
+        rssi: i64,
+    ) -> Self {
+        Self {
+            sensor_mac,
+            gateway_mac,
+            temperature,
+            humidity,
+            pressure,
+            battery,
+            tx_power,
+            movement_counter,
+            measurement_sequence_number,
+            acceleration,
+            acceleration_x,
+            acceleration_y,
+            acceleration_z,
+            rssi,
+            timestamp: Utc::now(),
+        }
+    }
+
+    /// Check the reading against the `sensor_data` CHECK constraints, so bad
+    /// rows can be reported before the database rejects them
+    pub fn validate(&self) -> Result<(), InvalidEvent> {
+        #[allow(clippy::cast_precision_loss)]
+        let checks = [
+            ("temperature", self.temperature, -100.0, 100.0),
+            ("humidity", self.humidity, 0.0, 100.0),
+            ("pressure", self.pressure, 300.0, 1300.0),
+            ("battery", self.battery as f64, 0.0, 4000.0),
+        ];
+
+        for (field, value, min, max) in checks {
+            if !(min..=max).contains(&value) {
+                return Err(InvalidEvent {
+                    field,
+                    value,
+                    min,
+                    max,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Acceleration magnitude in standard gravity units; `acceleration` is
+    /// stored in milli-g
+    pub fn acceleration_g(&self) -> f64 {
+        self.acceleration / 1000.0
+    }
+
+    /// Estimated battery charge in percent, or `None` for readings stored
+    /// without a battery voltage
+    pub fn battery_percentage(&self) -> Option<u8> {
+        (self.battery > 0).then(|| battery_percentage(self.battery))
+    }
+
+    /// Dew point in °C via the Magnus formula. Readings stored without a
+    /// humidity value have it as 0 %, where the dew point is undefined, so
+    /// those give `None`.
+    pub fn dew_point(&self) -> Option<f64> {
+        if self.humidity <= 0.0 {
+            return None;
+        }
+        let gamma = (self.humidity / 100.0).ln()
+            + MAGNUS_A * self.temperature / (MAGNUS_B + self.temperature);
+        Some(MAGNUS_B * gamma / (MAGNUS_A - gamma))
+    }
+
+    /// Absolute humidity in g/m³. Like [`Event::dew_point`], a stored
+    /// humidity of 0 % is taken as a missing reading.
+    pub fn absolute_humidity(&self) -> Option<f64> {
+        if self.humidity <= 0.0 {
+            return None;
+        }
+        let saturation_hpa =
+            MAGNUS_C * (MAGNUS_A * self.temperature / (MAGNUS_B + self.temperature)).exp();
+        Some(saturation_hpa * self.humidity * WATER_VAPOUR_FACTOR / (self.temperature + 273.15))
+    }
+}
+
+/// Magnus formula coefficients (Sonntag 1990)
+const MAGNUS_A: f64 = 17.62;
+const MAGNUS_B: f64 = 243.12;
+/// Saturation vapour pressure over water at 0 °C in hPa
+const MAGNUS_C: f64 = 6.112;
+
+/// Converts vapour pressure in hPa times relative humidity in % over
+/// temperature in K into g/m³
+const WATER_VAPOUR_FACTOR: f64 = 2.1674;
+
+/// A reading with a value outside the range the database accepts
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("{field} {value} is outside the allowed range {min}..={max}")]
+pub struct InvalidEvent {
+    pub field: &'static str,
+    pub value: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+/// Readings storage that the API and the MQTT reader can run against
+/// regardless of the backend
+///
+/// Only the operations every backend can serve are part of the trait;
+/// analytics stay on the concrete stores.
+#[async_trait]
+pub trait Store: Debug + Send + Sync {
+    /// Persist a reading and announce it to subscribers
+    async fn insert_event(&self, event: &Event) -> Result<()>;
+
+    /// Newest reading of a sensor, if it has any
+    async fn get_latest_reading(&self, sensor_mac: &str) -> Result<Option<Event>>;
+
+    /// Readings of a sensor between `start` and `end`, newest first and at
+    /// most `limit` of them; each bound falls back to a backend default
+    #[allow(clippy::too_many_arguments)]
+    async fn get_historical_data(
+        &self,
+        sensor_mac: &str,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        limit: Option<i64>,
+    ) -> Result<Vec<Event>>;
+
+    /// Latest reading of each of `sensor_macs` that has any, ordered by MAC
+    ///
+    /// Backends that can fetch them in one query override this.
+    async fn get_latest_for(&self, sensor_macs: &[String]) -> Result<Vec<Event>> {
+        let mut readings = Vec::with_capacity(sensor_macs.len());
+        for sensor_mac in sensor_macs {
+            readings.extend(self.get_latest_reading(sensor_mac).await?);
+        }
+        readings.sort_by(|a, b| a.sensor_mac.cmp(&b.sensor_mac));
+        Ok(readings)
+    }
+
+    /// Latest reading of every sensor heard in the last 24 hours
+    async fn get_active_sensors(&self) -> Result<Vec<Event>>;
+
+    /// Receive every reading inserted from now on
+    fn subscribe_to_events(&self) -> broadcast::Receiver<Event>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimeInterval {
+    Minutes(i32),
+    Hours(i32),
+    Days(i32),
+    Weeks(i32),
+}
+
+impl TimeInterval {
+    pub fn to_interval_string(&self) -> String {
+        match self {
+            TimeInterval::Minutes(minutes) => format!("{minutes} minutes"),
+            TimeInterval::Hours(hours) => format!("{hours} hours"),
+            TimeInterval::Days(days) => format!("{days} days"),
+            TimeInterval::Weeks(weeks) => format!("{weeks} weeks"),
+        }
+    }
+
+    /// Length of the interval in seconds, e.g. to work out how many buckets
+    /// cover a time range
+    pub fn as_seconds(&self) -> i64 {
+        match self {
+            TimeInterval::Minutes(minutes) => i64::from(*minutes) * 60,
+            TimeInterval::Hours(hours) => i64::from(*hours) * 3600,
+            TimeInterval::Days(days) => i64::from(*days) * 86_400,
+            TimeInterval::Weeks(weeks) => i64::from(*weeks) * 604_800,
+        }
+    }
+
+    /// Length of the interval
+    pub fn as_duration(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.as_seconds())
+    }
+}
+
+/// Aggregated readings for one time bucket.
+///
+/// `avg_*` weights every raw reading equally within the bucket, so averaging
+/// the averages of several buckets is wrong whenever their reading counts
+/// differ. To merge buckets, add up their `sum_*` and `reading_count` values
+/// and divide.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct TimeBucketedData {
+    pub bucket: DateTime<Utc>,
+    pub avg_temperature: Option<f64>,
+    pub min_temperature: Option<f64>,
+    pub max_temperature: Option<f64>,
+    pub sum_temperature: Option<f64>,
+    /// Median temperature of the bucket
+    pub p50_temperature: Option<f64>,
+    /// Temperature that 95 % of the bucket's readings stay at or below
+    pub p95_temperature: Option<f64>,
+    /// Temperature of the bucket's earliest reading
+    pub first_temperature: Option<f64>,
+    /// Temperature of the bucket's latest reading
+    pub last_temperature: Option<f64>,
+    pub avg_humidity: Option<f64>,
+    pub min_humidity: Option<f64>,
+    pub max_humidity: Option<f64>,
+    pub sum_humidity: Option<f64>,
+    pub avg_pressure: Option<f64>,
+    pub min_pressure: Option<f64>,
+    pub max_pressure: Option<f64>,
+    pub sum_pressure: Option<f64>,
+    pub reading_count: Option<i64>,
+}
+
+/// Battery voltage in millivolts and the charge percent left at it
+pub type CurvePoint = (i64, i64);
+
+/// CR2477 discharge curve as `(millivolts, percent)` points, from empty to
+/// full. The voltage stays high for most of the cell's life and drops fast
+/// near the end, so the points are denser there.
+pub const BATTERY_DISCHARGE_CURVE: [CurvePoint; 6] = [
+    (2000, 0),
+    (2400, 10),
+    (2600, 25),
+    (2800, 60),
+    (2900, 85),
+    (3000, 100),
+];
+
+/// Estimated charge left in percent for a battery voltage, interpolated
+/// linearly along [`BATTERY_DISCHARGE_CURVE`] and clamped to 0-100
+pub fn battery_percentage(mv: i64) -> u8 {
+    let percent = BATTERY_DISCHARGE_CURVE
+        .windows(2)
+        .find_map(|points| match points {
+            [(low_mv, low_pct), (high_mv, high_pct)] if mv <= *high_mv => Some(if mv <= *low_mv {
+                *low_pct
+            } else {
+                low_pct + (mv - low_mv) * (high_pct - low_pct) / (high_mv - low_mv)
+            }),
+            _ => None,
+        })
+        .unwrap_or(100);
+    u8::try_from(percent).unwrap_or(100)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(temperature: f64, humidity: f64) -> Event {
+        Event::new_with_current_time(
+            "AA:BB:CC:DD:EE:01".to_string(),
+            "FF:FF:FF:FF:FF:01".to_string(),
+            temperature,
+            humidity,
+            1013.25,
+            3000,
+            4,
+            0,
+            1,
+            1.0,
+            0,
+            0,
+            1000,
+            -40,
+        )
+    }
+
+    #[test]
+    fn test_event_acceleration_g() {
+        let mut stationary = reading(20.0, 50.0);
+        stationary.acceleration = 1_001.5;
+
+        assert!((stationary.acceleration_g() - 1.0015).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_battery_percentage() {
+        assert_eq!(battery_percentage(1800), 0);
+        assert_eq!(battery_percentage(2000), 0);
+        assert_eq!(battery_percentage(2500), 17);
+        assert_eq!(battery_percentage(2850), 72);
+        assert_eq!(battery_percentage(3000), 100);
+        assert_eq!(battery_percentage(3200), 100);
+    }
+
+    #[test]
+    fn test_battery_discharge_curve_is_monotonic() {
+        assert!(BATTERY_DISCHARGE_CURVE
+            .windows(2)
+            .all(|points| matches!(points, [low, high] if low.0 < high.0 && low.1 <= high.1)));
+    }
+
+    #[test]
+    fn test_event_battery_percentage() {
+        let mut event = reading(20.0, 50.0);
+        event.battery = 2600;
+        assert_eq!(event.battery_percentage(), Some(25));
+
+        event.battery = 0;
+        assert_eq!(event.battery_percentage(), None);
+    }
+
+    #[test]
+    fn test_event_dew_point() {
+        for (temperature, humidity, expected) in [
+            (20.0, 50.0, 9.255),
+            (25.0, 60.0, 16.693),
+            (0.0, 100.0, 0.0),
+            (-10.0, 80.0, -12.797),
+        ] {
+            let dew_point = reading(temperature, humidity).dew_point();
+            assert!(
+                dew_point.is_some_and(|dew_point| (dew_point - expected).abs() < 0.001),
+                "Expected {expected} at {temperature} °C / {humidity} %, got {dew_point:?}"
+            );
+        }
+        assert_eq!(reading(20.0, 0.0).dew_point(), None);
+    }
+
+    #[test]
+    fn test_event_absolute_humidity() {
+        for (temperature, humidity, expected) in [
+            (20.0, 50.0, 8.623),
+            (0.0, 100.0, 4.850),
+            (30.0, 80.0, 24.216),
+        ] {
+            let absolute = reading(temperature, humidity).absolute_humidity();
+            assert!(
+                absolute.is_some_and(|absolute| (absolute - expected).abs() < 0.001),
+                "Expected {expected} at {temperature} °C / {humidity} %, got {absolute:?}"
+            );
+        }
+        assert_eq!(reading(20.0, 0.0).absolute_humidity(), None);
+    }
+
+    #[test]
+    fn test_time_interval_as_seconds() {
+        assert_eq!(TimeInterval::Minutes(15).as_seconds(), 900);
+        assert_eq!(TimeInterval::Hours(1).as_seconds(), 3600);
+        assert_eq!(TimeInterval::Days(1).as_seconds(), 86_400);
+        assert_eq!(TimeInterval::Weeks(2).as_seconds(), 1_209_600);
+    }
+
+    #[test]
+    fn test_time_interval_as_duration() {
+        assert_eq!(
+            TimeInterval::Minutes(15).as_duration(),
+            chrono::Duration::minutes(15)
+        );
+        assert_eq!(
+            TimeInterval::Hours(6).as_duration(),
+            chrono::Duration::hours(6)
+        );
+        assert_eq!(
+            TimeInterval::Days(1).as_duration(),
+            chrono::Duration::days(1)
+        );
+        assert_eq!(
+            TimeInterval::Weeks(1).as_duration(),
+            chrono::Duration::weeks(1)
+        );
+    }
+}
@@ -0,0 +1,45 @@
+-- Migration: 20241210090000_add_sensor_thresholds.sql
+-- Description: Add per-sensor metric thresholds used for alerting
+
+BEGIN;
+
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM schema_migrations
+        WHERE version = '20241210090000'
+    ) THEN
+
+        -- Allowed range for one metric of a sensor; either bound may be open
+        CREATE TABLE IF NOT EXISTS sensor_thresholds (
+            id BIGSERIAL PRIMARY KEY,
+            sensor_mac VARCHAR(17) NOT NULL,
+            metric VARCHAR(32) NOT NULL,
+            min_value DOUBLE PRECISION,
+            max_value DOUBLE PRECISION,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        );
+
+        CREATE INDEX idx_sensor_thresholds_sensor_metric ON sensor_thresholds(sensor_mac, metric);
+
+        ALTER TABLE sensor_thresholds ADD CONSTRAINT chk_threshold_bounds
+            CHECK (min_value IS NOT NULL OR max_value IS NOT NULL);
+        ALTER TABLE sensor_thresholds ADD CONSTRAINT chk_threshold_order
+            CHECK (min_value IS NULL OR max_value IS NULL OR min_value <= max_value);
+
+        -- Grant permissions
+        GRANT ALL PRIVILEGES ON sensor_thresholds TO ruuvi;
+        GRANT USAGE, SELECT ON SEQUENCE sensor_thresholds_id_seq TO ruuvi;
+
+        -- Record migration
+        INSERT INTO schema_migrations (version, description, applied_at)
+        VALUES ('20241210090000', 'Add per-sensor metric thresholds', NOW());
+
+        RAISE NOTICE 'Migration 20241210090000 applied successfully';
+
+    ELSE
+        RAISE NOTICE 'Migration 20241210090000 already applied, skipping';
+    END IF;
+END $$;
+
+COMMIT;
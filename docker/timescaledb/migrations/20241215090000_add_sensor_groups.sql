@@ -0,0 +1,43 @@
+-- Migration: 20241215090000_add_sensor_groups.sql
+-- Description: Add named sensor groups for room-level views
+
+BEGIN;
+
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM schema_migrations
+        WHERE version = '20241215090000'
+    ) THEN
+
+        -- Named set of sensors, e.g. the ones in one room
+        CREATE TABLE IF NOT EXISTS sensor_groups (
+            name VARCHAR(64) PRIMARY KEY,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        );
+
+        -- A sensor may belong to any number of groups
+        CREATE TABLE IF NOT EXISTS sensor_group_members (
+            group_name VARCHAR(64) NOT NULL REFERENCES sensor_groups(name) ON DELETE CASCADE,
+            sensor_mac VARCHAR(17) NOT NULL,
+            PRIMARY KEY (group_name, sensor_mac)
+        );
+
+        CREATE INDEX idx_sensor_group_members_sensor ON sensor_group_members(sensor_mac);
+
+        -- Grant permissions
+        GRANT ALL PRIVILEGES ON sensor_groups TO ruuvi;
+        GRANT ALL PRIVILEGES ON sensor_group_members TO ruuvi;
+
+        -- Record migration
+        INSERT INTO schema_migrations (version, description, applied_at)
+        VALUES ('20241215090000', 'Add sensor groups', NOW());
+
+        RAISE NOTICE 'Migration 20241215090000 applied successfully';
+
+    ELSE
+        RAISE NOTICE 'Migration 20241215090000 already applied, skipping';
+    END IF;
+END $$;
+
+COMMIT;
@@ -0,0 +1,37 @@
+-- Migration: 20241220090000_add_sensor_aliases.sql
+-- Description: Add human-readable sensor aliases
+
+BEGIN;
+
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM schema_migrations
+        WHERE version = '20241220090000'
+    ) THEN
+
+        -- Name shown instead of the MAC address, at most one per sensor
+        CREATE TABLE IF NOT EXISTS sensor_aliases (
+            sensor_mac VARCHAR(17) PRIMARY KEY,
+            alias VARCHAR(100) NOT NULL,
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        );
+
+        ALTER TABLE sensor_aliases ADD CONSTRAINT chk_alias_not_blank
+            CHECK (btrim(alias) <> '');
+
+        -- Grant permissions
+        GRANT ALL PRIVILEGES ON sensor_aliases TO ruuvi;
+
+        -- Record migration
+        INSERT INTO schema_migrations (version, description, applied_at)
+        VALUES ('20241220090000', 'Add sensor aliases', NOW());
+
+        RAISE NOTICE 'Migration 20241220090000 applied successfully';
+
+    ELSE
+        RAISE NOTICE 'Migration 20241220090000 already applied, skipping';
+    END IF;
+END $$;
+
+COMMIT;
@@ -0,0 +1,45 @@
+-- Migration: 20241222090000_add_alerts.sql
+-- Description: Add alert rules evaluated against the latest readings
+
+BEGIN;
+
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM schema_migrations
+        WHERE version = '20241222090000'
+    ) THEN
+
+        -- Fires while the latest reading of a metric is above or below the threshold
+        CREATE TABLE IF NOT EXISTS alerts (
+            id BIGSERIAL PRIMARY KEY,
+            sensor_mac VARCHAR(17) NOT NULL,
+            metric VARCHAR(32) NOT NULL,
+            comparator VARCHAR(8) NOT NULL,
+            threshold DOUBLE PRECISION NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        );
+
+        CREATE INDEX idx_alerts_sensor ON alerts(sensor_mac);
+
+        ALTER TABLE alerts ADD CONSTRAINT chk_alert_metric
+            CHECK (metric IN ('temperature', 'humidity', 'pressure'));
+        ALTER TABLE alerts ADD CONSTRAINT chk_alert_comparator
+            CHECK (comparator IN ('above', 'below'));
+
+        -- Grant permissions
+        GRANT ALL PRIVILEGES ON alerts TO ruuvi;
+        GRANT USAGE, SELECT ON SEQUENCE alerts_id_seq TO ruuvi;
+
+        -- Record migration
+        INSERT INTO schema_migrations (version, description, applied_at)
+        VALUES ('20241222090000', 'Add alert rules', NOW());
+
+        RAISE NOTICE 'Migration 20241222090000 applied successfully';
+
+    ELSE
+        RAISE NOTICE 'Migration 20241222090000 already applied, skipping';
+    END IF;
+END $$;
+
+COMMIT;
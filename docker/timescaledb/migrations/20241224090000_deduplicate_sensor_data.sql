@@ -0,0 +1,39 @@
+-- Migration: 20241224090000_deduplicate_sensor_data.sql
+-- Description: Reject exact replays of a stored reading
+
+BEGIN;
+
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM schema_migrations
+        WHERE version = '20241224090000'
+    ) THEN
+
+        -- Drop the copies stored so far, keeping one row of each reading.
+        -- Copies share their timestamp and so always live in the same chunk.
+        DELETE FROM sensor_data a
+        USING sensor_data b
+        WHERE a.ctid > b.ctid
+          AND a.tableoid = b.tableoid
+          AND a.sensor_mac = b.sensor_mac
+          AND a.measurement_sequence_number = b.measurement_sequence_number
+          AND a.timestamp = b.timestamp;
+
+        -- Only exact replays are rejected: a copy forwarded by another
+        -- gateway is kept unless it carries the very same timestamp
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_sensor_data_unique_reading
+            ON sensor_data (sensor_mac, measurement_sequence_number, timestamp);
+
+        -- Record migration
+        INSERT INTO schema_migrations (version, description, applied_at)
+        VALUES ('20241224090000', 'Deduplicate sensor readings', NOW());
+
+        RAISE NOTICE 'Migration 20241224090000 applied successfully';
+
+    ELSE
+        RAISE NOTICE 'Migration 20241224090000 already applied, skipping';
+    END IF;
+END $$;
+
+COMMIT;
@@ -0,0 +1,38 @@
+-- Migration: 20241226090000_notify_sensor_data_inserts.sql
+-- Description: Notify listeners of every stored reading, so the API's live
+-- feed also carries readings written by the MQTT reader
+
+BEGIN;
+
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM schema_migrations
+        WHERE version = '20241226090000'
+    ) THEN
+
+        -- Duplicates skipped by ON CONFLICT DO NOTHING fire no trigger, and
+        -- notifications are only delivered once the insert commits
+        CREATE OR REPLACE FUNCTION notify_sensor_data_insert() RETURNS trigger AS $fn$
+        BEGIN
+            PERFORM pg_notify('sensor_data_inserted', row_to_json(NEW)::text);
+            RETURN NULL;
+        END;
+        $fn$ LANGUAGE plpgsql;
+
+        CREATE TRIGGER sensor_data_notify_insert
+            AFTER INSERT ON sensor_data
+            FOR EACH ROW EXECUTE FUNCTION notify_sensor_data_insert();
+
+        -- Record migration
+        INSERT INTO schema_migrations (version, description, applied_at)
+        VALUES ('20241226090000', 'Notify listeners of stored readings', NOW());
+
+        RAISE NOTICE 'Migration 20241226090000 applied successfully';
+
+    ELSE
+        RAISE NOTICE 'Migration 20241226090000 already applied, skipping';
+    END IF;
+END $$;
+
+COMMIT;
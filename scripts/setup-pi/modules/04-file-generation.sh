@@ -1191,7 +1191,7 @@ services:
       - DATABASE_URL=${DATABASE_URL}
       - API_PORT=8080
       - RUST_LOG=${RUST_LOG:-info}
-      - CORS_ALLOW_ORIGIN=${CORS_ALLOW_ORIGIN:-*}
+      - CORS_ORIGINS=${CORS_ALLOW_ORIGIN:-*}
     restart: unless-stopped
     healthcheck:
       test: ["CMD-SHELL", "curl -f http://localhost:8080/health || exit 1"]